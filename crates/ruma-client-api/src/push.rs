@@ -11,6 +11,7 @@ use ruma_common::{
 };
 use serde::{Deserialize, Serialize};
 
+pub use self::ruleset_diff::{diff_rulesets, PushRuleRequest, RulesetDiffError};
 use crate::PrivOwnedStr;
 
 pub mod delete_pushrule;
@@ -22,6 +23,7 @@ pub mod get_pushrule_enabled;
 pub mod get_pushrules_all;
 pub mod get_pushrules_global_scope;
 mod pusher_serde;
+mod ruleset_diff;
 pub mod set_pusher;
 pub mod set_pushrule;
 pub mod set_pushrule_actions;
@@ -0,0 +1,674 @@
+//! Compute the requests to change the push rules of a user.
+
+use std::{collections::BTreeSet, error::Error, fmt};
+
+use ruma_common::push::{
+    Action, AnyPushRuleRef, InsertPushRuleError, NewConditionalPushRule, NewPatternedPushRule,
+    NewPushRule, NewSimplePushRule, RemovePushRuleError, RuleKind, Ruleset,
+};
+use serde_json::{to_value as to_json_value, Value as JsonValue};
+
+use super::{delete_pushrule, set_pushrule, set_pushrule_actions, set_pushrule_enabled, RuleScope};
+
+/// A request to change a push rule of the user.
+#[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+pub enum PushRuleRequest {
+    /// Create or replace a user-defined push rule.
+    Set(set_pushrule::v3::Request),
+
+    /// Delete a user-defined push rule.
+    Delete(delete_pushrule::v3::Request),
+
+    /// Enable or disable a push rule.
+    SetEnabled(set_pushrule_enabled::v3::Request),
+
+    /// Change the actions of a push rule.
+    SetActions(set_pushrule_actions::v3::Request),
+}
+
+/// An error that happens when the push rules of a user can't be changed from one `Ruleset` to
+/// another with the push rules endpoints.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RulesetDiffError {
+    /// A server-default rule was added, removed, or its conditions or pattern were changed.
+    ServerDefaultRule {
+        /// The kind of the rule.
+        kind: RuleKind,
+
+        /// The ID of the rule.
+        rule_id: String,
+    },
+
+    /// A user-defined rule can't be created, because its ID is invalid.
+    InvalidRule {
+        /// The kind of the rule.
+        kind: RuleKind,
+
+        /// The ID of the rule.
+        rule_id: String,
+    },
+
+    /// A user-defined rule can't be created or moved.
+    Insert {
+        /// The kind of the rule.
+        kind: RuleKind,
+
+        /// The ID of the rule.
+        rule_id: String,
+
+        /// The error returned by `Ruleset::insert`.
+        source: InsertPushRuleError,
+    },
+
+    /// A user-defined rule can't be deleted.
+    Remove {
+        /// The kind of the rule.
+        kind: RuleKind,
+
+        /// The ID of the rule.
+        rule_id: String,
+
+        /// The error returned by `Ruleset::remove`.
+        source: RemovePushRuleError,
+    },
+
+    /// A rule to enable, disable or change the actions of was not found.
+    RuleNotFound {
+        /// The kind of the rule.
+        kind: RuleKind,
+
+        /// The ID of the rule.
+        rule_id: String,
+    },
+
+    /// The rules of a kind can't be put in the order of the target `Ruleset`.
+    ///
+    /// User-defined rules can only be placed relative to other user-defined rules.
+    Order {
+        /// The kind of the rules.
+        kind: RuleKind,
+    },
+}
+
+impl fmt::Display for RulesetDiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServerDefaultRule { kind, rule_id } => {
+                write!(f, "Server-default {kind} push rule `{rule_id}` cannot be changed.")
+            }
+            Self::InvalidRule { kind, rule_id } => {
+                write!(f, "{kind} push rule `{rule_id}` cannot be created.")
+            }
+            Self::Insert { kind, rule_id, .. } => {
+                write!(f, "{kind} push rule `{rule_id}` cannot be set.")
+            }
+            Self::Remove { kind, rule_id, .. } => {
+                write!(f, "{kind} push rule `{rule_id}` cannot be deleted.")
+            }
+            Self::RuleNotFound { kind, rule_id } => {
+                write!(f, "{kind} push rule `{rule_id}` was not found.")
+            }
+            Self::Order { kind } => write!(f, "{kind} push rules cannot be put in this order."),
+        }
+    }
+}
+
+impl Error for RulesetDiffError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Insert { source, .. } => Some(source),
+            Self::Remove { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Computes the requests to send to the homeserver to change the push rules of the user from
+/// `current` to `target`.
+///
+/// The requests must be sent in the returned order. Applying them to `current` with the
+/// mutation methods of `Ruleset` gives a `Ruleset` with the same rules as `target`, in the same
+/// order, apart from the `default` flag of the rules.
+///
+/// User-defined rules that keep their relative order and their conditions or pattern are not
+/// recreated, so only the rules that changed are sent.
+///
+/// Returns an error if `target` can't be reached with the push rules endpoints, for example if a
+/// server-default rule was removed.
+pub fn diff_rulesets(
+    current: &Ruleset,
+    target: &Ruleset,
+) -> Result<Vec<PushRuleRequest>, RulesetDiffError> {
+    let mut working = current.clone();
+    let mut requests = Vec::new();
+
+    for kind in [
+        RuleKind::Override,
+        RuleKind::Content,
+        RuleKind::Room,
+        RuleKind::Sender,
+        RuleKind::Underride,
+    ] {
+        diff_rules_of_kind(&mut working, target, kind, &mut requests)?;
+    }
+
+    Ok(requests)
+}
+
+/// Computes the requests to change the rules of the given kind of `working` to those of `target`,
+/// and applies them to `working`.
+fn diff_rules_of_kind(
+    working: &mut Ruleset,
+    target: &Ruleset,
+    kind: RuleKind,
+    requests: &mut Vec<PushRuleRequest>,
+) -> Result<(), RulesetDiffError> {
+    let target_rules = rules_of_kind(target, &kind);
+
+    // Server-default rules can't be added, removed or replaced.
+    for rule in &target_rules {
+        if !rule.is_server_default() {
+            continue;
+        }
+
+        let same_definition = working
+            .get(kind.clone(), rule.rule_id())
+            .map_or(false, |current| definition(current) == definition(*rule));
+        if !same_definition {
+            return Err(RulesetDiffError::ServerDefaultRule {
+                kind,
+                rule_id: rule.rule_id().to_owned(),
+            });
+        }
+    }
+    for rule in rules_of_kind(working, &kind) {
+        if rule.is_server_default() && target.get(kind.clone(), rule.rule_id()).is_none() {
+            return Err(RulesetDiffError::ServerDefaultRule {
+                kind,
+                rule_id: rule.rule_id().to_owned(),
+            });
+        }
+    }
+
+    // Delete the user-defined rules that are not in the target.
+    let deleted_rule_ids: Vec<_> = rules_of_kind(working, &kind)
+        .into_iter()
+        .filter(|rule| {
+            !rule.is_server_default() && target.get(kind.clone(), rule.rule_id()).is_none()
+        })
+        .map(|rule| rule.rule_id().to_owned())
+        .collect();
+    for rule_id in deleted_rule_ids {
+        let request =
+            delete_pushrule::v3::Request::new(RuleScope::Global, kind.clone(), rule_id.clone());
+        apply_request(working, PushRuleRequest::Delete(request), requests)?;
+    }
+
+    // Keep in place the longest sequence of unchanged user-defined rules in the right order, and
+    // set the other ones after the previous rule of the target.
+    let target_user_rules: Vec<_> =
+        target_rules.iter().copied().filter(|rule| !rule.is_server_default()).collect();
+    let unchanged_rule_ids = |rules: Vec<AnyPushRuleRef<'_>>, other: &Ruleset| {
+        rules
+            .into_iter()
+            .filter(|rule| {
+                !rule.is_server_default()
+                    && other
+                        .get(kind.clone(), rule.rule_id())
+                        .map_or(false, |other| definition(other) == definition(*rule))
+            })
+            .map(|rule| rule.rule_id().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let kept_rule_ids = longest_common_subsequence(
+        &unchanged_rule_ids(rules_of_kind(working, &kind), target),
+        &unchanged_rule_ids(target_user_rules.clone(), working),
+    );
+
+    for (i, rule) in target_user_rules.iter().enumerate() {
+        if kept_rule_ids.contains(rule.rule_id()) {
+            continue;
+        }
+
+        let mut request = set_pushrule::v3::Request::new(
+            RuleScope::Global,
+            new_push_rule(*rule).ok_or_else(|| RulesetDiffError::InvalidRule {
+                kind: kind.clone(),
+                rule_id: rule.rule_id().to_owned(),
+            })?,
+        );
+
+        if let Some(previous_rule) = i.checked_sub(1).map(|i| target_user_rules[i]) {
+            request.after = Some(previous_rule.rule_id().to_owned());
+        } else {
+            request.before = rules_of_kind(working, &kind)
+                .into_iter()
+                .find(|current| !current.is_server_default())
+                .map(|current| current.rule_id())
+                .filter(|rule_id| *rule_id != rule.rule_id())
+                .map(ToOwned::to_owned);
+        }
+
+        apply_request(working, PushRuleRequest::Set(request), requests)?;
+    }
+
+    // Update the `enabled` flag and the actions of the rules.
+    for rule in &target_rules {
+        let rule_id = rule.rule_id().to_owned();
+        let current = match working.get(kind.clone(), rule.rule_id()) {
+            Some(current) => current,
+            None => return Err(RulesetDiffError::RuleNotFound { kind, rule_id }),
+        };
+        let current_enabled = current.enabled();
+        let current_actions_match = same_actions(current.actions(), rule.actions());
+
+        if current_enabled != rule.enabled() {
+            let request = set_pushrule_enabled::v3::Request::new(
+                RuleScope::Global,
+                kind.clone(),
+                rule_id.clone(),
+                rule.enabled(),
+            );
+            apply_request(working, PushRuleRequest::SetEnabled(request), requests)?;
+        }
+
+        if !current_actions_match {
+            let request = set_pushrule_actions::v3::Request::new(
+                RuleScope::Global,
+                kind.clone(),
+                rule_id.clone(),
+                rule.actions().to_vec(),
+            );
+            apply_request(working, PushRuleRequest::SetActions(request), requests)?;
+        }
+    }
+
+    // User-defined rules can't be placed relative to server-default rules, so the target order
+    // might not be reachable.
+    let same_order = rules_of_kind(working, &kind)
+        .into_iter()
+        .map(|rule| rule.rule_id())
+        .eq(target_rules.iter().map(|rule| rule.rule_id()));
+    if !same_order {
+        return Err(RulesetDiffError::Order { kind });
+    }
+
+    Ok(())
+}
+
+/// Applies the request to the `Ruleset` with its mutation methods, and adds it to `requests`.
+fn apply_request(
+    ruleset: &mut Ruleset,
+    request: PushRuleRequest,
+    requests: &mut Vec<PushRuleRequest>,
+) -> Result<(), RulesetDiffError> {
+    apply(ruleset, &request)?;
+    requests.push(request);
+    Ok(())
+}
+
+/// Applies the request to the `Ruleset` with its mutation methods.
+fn apply(ruleset: &mut Ruleset, request: &PushRuleRequest) -> Result<(), RulesetDiffError> {
+    match request {
+        PushRuleRequest::Set(request) => ruleset
+            .insert(request.rule.clone(), request.after.as_deref(), request.before.as_deref())
+            .map_err(|source| RulesetDiffError::Insert {
+                kind: request.rule.kind(),
+                rule_id: request.rule.rule_id().to_owned(),
+                source,
+            }),
+        PushRuleRequest::Delete(request) => ruleset
+            .remove(request.kind.clone(), &request.rule_id)
+            .map_err(|source| RulesetDiffError::Remove {
+                kind: request.kind.clone(),
+                rule_id: request.rule_id.clone(),
+                source,
+            }),
+        PushRuleRequest::SetEnabled(request) => ruleset
+            .set_enabled(request.kind.clone(), &request.rule_id, request.enabled)
+            .map_err(|_| RulesetDiffError::RuleNotFound {
+                kind: request.kind.clone(),
+                rule_id: request.rule_id.clone(),
+            }),
+        PushRuleRequest::SetActions(request) => ruleset
+            .set_actions(request.kind.clone(), &request.rule_id, request.actions.clone())
+            .map_err(|_| RulesetDiffError::RuleNotFound {
+                kind: request.kind.clone(),
+                rule_id: request.rule_id.clone(),
+            }),
+    }
+}
+
+/// The rules of the given kind, in order of priority.
+fn rules_of_kind<'a>(ruleset: &'a Ruleset, kind: &RuleKind) -> Vec<AnyPushRuleRef<'a>> {
+    match kind {
+        RuleKind::Override => ruleset.override_.iter().map(AnyPushRuleRef::Override).collect(),
+        RuleKind::Content => ruleset.content.iter().map(AnyPushRuleRef::Content).collect(),
+        RuleKind::Room => ruleset.room.iter().map(AnyPushRuleRef::Room).collect(),
+        RuleKind::Sender => ruleset.sender.iter().map(AnyPushRuleRef::Sender).collect(),
+        RuleKind::Underride => ruleset.underride.iter().map(AnyPushRuleRef::Underride).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The parts of the rule that can only be changed by replacing the rule.
+fn definition(rule: AnyPushRuleRef<'_>) -> JsonValue {
+    match rule {
+        AnyPushRuleRef::Override(rule) | AnyPushRuleRef::Underride(rule) => {
+            to_json_value(&rule.conditions).unwrap_or_default()
+        }
+        AnyPushRuleRef::Content(rule) => rule.pattern.as_str().into(),
+        _ => JsonValue::Null,
+    }
+}
+
+/// Whether the two lists of actions are the same.
+fn same_actions(a: &[Action], b: &[Action]) -> bool {
+    to_json_value(a).ok() == to_json_value(b).ok()
+}
+
+/// Converts the rule to a `NewPushRule`.
+fn new_push_rule(rule: AnyPushRuleRef<'_>) -> Option<NewPushRule> {
+    Some(match rule {
+        AnyPushRuleRef::Override(rule) => NewPushRule::Override(NewConditionalPushRule::new(
+            rule.rule_id.clone(),
+            rule.conditions.clone(),
+            rule.actions.clone(),
+        )),
+        AnyPushRuleRef::Underride(rule) => NewPushRule::Underride(NewConditionalPushRule::new(
+            rule.rule_id.clone(),
+            rule.conditions.clone(),
+            rule.actions.clone(),
+        )),
+        AnyPushRuleRef::Content(rule) => NewPushRule::Content(NewPatternedPushRule::new(
+            rule.rule_id.clone(),
+            rule.pattern.clone(),
+            rule.actions.clone(),
+        )),
+        AnyPushRuleRef::Room(rule) => {
+            NewPushRule::Room(NewSimplePushRule::new(rule.rule_id.clone(), rule.actions.clone()))
+        }
+        AnyPushRuleRef::Sender(rule) => {
+            NewPushRule::Sender(NewSimplePushRule::new(rule.rule_id.clone(), rule.actions.clone()))
+        }
+        #[allow(unreachable_patterns)]
+        _ => return None,
+    })
+}
+
+/// The elements of the longest common subsequence of `a` and `b`.
+fn longest_common_subsequence(a: &[String], b: &[String]) -> BTreeSet<String> {
+    // `lengths[i][j]` is the length of the longest common subsequence of `a[i..]` and `b[j..]`.
+    let mut lengths = vec![vec![0_usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut subsequence = BTreeSet::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            subsequence.insert(a[i].clone());
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    subsequence
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use proptest::{collection::vec, prelude::*};
+    use ruma_common::{
+        push::{
+            Action, AnyPushRuleRef, NewConditionalPushRule, NewPatternedPushRule, NewPushRule,
+            NewSimplePushRule, PushCondition, RemovePushRuleError, RuleKind, Ruleset, Tweak,
+        },
+        user_id, OwnedRoomId, OwnedUserId,
+    };
+    use serde_json::{to_value as to_json_value, Value as JsonValue};
+
+    use super::{
+        apply, definition, diff_rulesets, rules_of_kind, PushRuleRequest, RulesetDiffError,
+    };
+    use crate::push::{delete_pushrule, set_pushrule_enabled, RuleScope};
+
+    const KINDS: [RuleKind; 5] = [
+        RuleKind::Override,
+        RuleKind::Content,
+        RuleKind::Room,
+        RuleKind::Sender,
+        RuleKind::Underride,
+    ];
+
+    fn server_default() -> Ruleset {
+        Ruleset::server_default(user_id!("@jolly_jumper:server.name"))
+    }
+
+    fn actions(variant: u8) -> Vec<Action> {
+        match variant % 3 {
+            0 => vec![Action::Notify],
+            1 => vec![],
+            _ => vec![Action::Notify, Action::SetTweak(Tweak::Sound("default".into()))],
+        }
+    }
+
+    fn new_rule(kind: &RuleKind, id: u8, definition: u8, actions: Vec<Action>) -> NewPushRule {
+        let conditions = vec![PushCondition::EventMatch {
+            key: "content.body".into(),
+            pattern: format!("pattern{definition}"),
+        }];
+
+        match kind {
+            RuleKind::Override => NewPushRule::Override(NewConditionalPushRule::new(
+                format!("override{id}"),
+                conditions,
+                actions,
+            )),
+            RuleKind::Content => NewPushRule::Content(NewPatternedPushRule::new(
+                format!("content{id}"),
+                format!("pattern{definition}"),
+                actions,
+            )),
+            RuleKind::Room => {
+                let room_id = OwnedRoomId::try_from(format!("!room{id}:server.name")).unwrap();
+                NewPushRule::Room(NewSimplePushRule::new(room_id, actions))
+            }
+            RuleKind::Sender => {
+                let user_id = OwnedUserId::try_from(format!("@user{id}:server.name")).unwrap();
+                NewPushRule::Sender(NewSimplePushRule::new(user_id, actions))
+            }
+            _ => NewPushRule::Underride(NewConditionalPushRule::new(
+                format!("underride{id}"),
+                conditions,
+                actions,
+            )),
+        }
+    }
+
+    /// The rules of the `Ruleset`, without the `default` flag.
+    fn rules(ruleset: &Ruleset) -> Vec<Vec<(String, bool, JsonValue, JsonValue)>> {
+        KINDS
+            .iter()
+            .map(|kind| {
+                rules_of_kind(ruleset, kind)
+                    .into_iter()
+                    .map(|rule: AnyPushRuleRef<'_>| {
+                        (
+                            rule.rule_id().to_owned(),
+                            rule.enabled(),
+                            to_json_value(rule.actions()).unwrap(),
+                            definition(rule),
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn apply_all(ruleset: &Ruleset, requests: &[PushRuleRequest]) -> Ruleset {
+        let mut ruleset = ruleset.clone();
+        for request in requests {
+            apply(&mut ruleset, request).unwrap();
+        }
+        ruleset
+    }
+
+    #[test]
+    fn diff_user_rules() {
+        let mut current = server_default();
+        for id in [3, 2, 1] {
+            current.insert(new_rule(&RuleKind::Override, id, 0, actions(0)), None, None).unwrap();
+        }
+
+        // Move `override1` after `override3`, change the pattern of `override2` and delete
+        // `override3`.
+        let mut target = current.clone();
+        target
+            .insert(new_rule(&RuleKind::Override, 1, 0, actions(0)), Some("override3"), None)
+            .unwrap();
+        target.insert(new_rule(&RuleKind::Override, 2, 1, actions(0)), None, None).unwrap();
+        target.remove(RuleKind::Override, "override3").unwrap();
+        target.set_enabled(RuleKind::Content, ".m.rule.contains_user_name", false).unwrap();
+        target.set_actions(RuleKind::Underride, ".m.rule.message", actions(1)).unwrap();
+
+        let requests = diff_rulesets(&current, &target).unwrap();
+        assert_eq!(requests.len(), 4);
+        assert_matches!(
+            &requests[0],
+            PushRuleRequest::Delete(request) if request.rule_id == "override3"
+        );
+        assert_matches!(
+            &requests[1],
+            PushRuleRequest::Set(request)
+                if request.rule.rule_id() == "override2"
+                    && request.before.as_deref() == Some("override1")
+                    && request.after.is_none()
+        );
+        assert_matches!(
+            &requests[2],
+            PushRuleRequest::SetEnabled(request)
+                if request.kind == RuleKind::Content
+                    && request.rule_id == ".m.rule.contains_user_name"
+                    && !request.enabled
+        );
+        assert_matches!(
+            &requests[3],
+            PushRuleRequest::SetActions(request)
+                if request.kind == RuleKind::Underride
+                    && request.rule_id == ".m.rule.message"
+                    && request.actions.is_empty()
+        );
+
+        assert_eq!(rules(&apply_all(&current, &requests)), rules(&target));
+        assert_matches!(diff_rulesets(&target, &target), Ok(requests) if requests.is_empty());
+    }
+
+    #[test]
+    fn diff_server_default_rules() {
+        let current = server_default();
+
+        let mut target = current.clone();
+        target.override_.shift_remove(".m.rule.master");
+        assert_matches!(
+            diff_rulesets(&current, &target),
+            Err(RulesetDiffError::ServerDefaultRule { kind: RuleKind::Override, rule_id })
+                if rule_id == ".m.rule.master"
+        );
+        assert_matches!(
+            diff_rulesets(&target, &current),
+            Err(RulesetDiffError::ServerDefaultRule { kind: RuleKind::Override, rule_id })
+                if rule_id == ".m.rule.master"
+        );
+    }
+
+    #[test]
+    fn apply_errors() {
+        let mut ruleset = server_default();
+
+        let request = delete_pushrule::v3::Request::new(
+            RuleScope::Global,
+            RuleKind::Override,
+            ".m.rule.master".to_owned(),
+        );
+        assert_matches!(
+            apply(&mut ruleset, &PushRuleRequest::Delete(request)),
+            Err(RulesetDiffError::Remove {
+                kind: RuleKind::Override,
+                source: RemovePushRuleError::ServerDefault,
+                ..
+            })
+        );
+
+        let request = set_pushrule_enabled::v3::Request::new(
+            RuleScope::Global,
+            RuleKind::Override,
+            "unknown".to_owned(),
+            false,
+        );
+        assert_matches!(
+            apply(&mut ruleset, &PushRuleRequest::SetEnabled(request)),
+            Err(RulesetDiffError::RuleNotFound { kind: RuleKind::Override, rule_id })
+                if rule_id == "unknown"
+        );
+    }
+
+    /// The user rules and the `enabled` flags of the server-default rules of a `Ruleset`.
+    type RulesetSpec = (Vec<(u8, u8, u8, bool, u8)>, Vec<bool>);
+
+    fn ruleset_spec() -> impl Strategy<Value = RulesetSpec> {
+        (vec((0..5_u8, 0..6_u8, 0..2_u8, any::<bool>(), 0..3_u8), 0..16), vec(any::<bool>(), 0..8))
+    }
+
+    fn ruleset((user_rules, default_enabled): RulesetSpec) -> Ruleset {
+        let mut ruleset = server_default();
+
+        for (kind, id, definition, enabled, actions_variant) in user_rules {
+            let kind = &KINDS[usize::from(kind)];
+            let rule = new_rule(kind, id, definition, actions(actions_variant));
+            let rule_id = rule.rule_id().to_owned();
+            ruleset.insert(rule, None, None).unwrap();
+            ruleset.set_enabled(kind.clone(), &rule_id, enabled).unwrap();
+        }
+
+        let default_rule_ids: Vec<_> = ruleset
+            .override_
+            .iter()
+            .map(|rule| rule.rule_id.clone())
+            .filter(|rule_id| rule_id.starts_with('.'))
+            .collect();
+        for (rule_id, enabled) in default_rule_ids.iter().zip(default_enabled) {
+            ruleset.set_enabled(RuleKind::Override, rule_id, enabled).unwrap();
+        }
+
+        ruleset
+    }
+
+    proptest! {
+        #[test]
+        fn diff_round_trip(current in ruleset_spec(), target in ruleset_spec()) {
+            let current = ruleset(current);
+            let target = ruleset(target);
+
+            let requests = diff_rulesets(&current, &target).unwrap();
+            prop_assert_eq!(rules(&apply_all(&current, &requests)), rules(&target));
+
+            prop_assert!(diff_rulesets(&target, &target).unwrap().is_empty());
+        }
+    }
+}
@@ -157,12 +157,21 @@ pub mod v3 {
 
         /// Given a `CreationContent` and the other fields that a homeserver has to fill, construct
         /// a `RoomCreateEventContent`.
+        ///
+        /// The `creator` is only set for room versions that don't use the sender of the event as
+        /// the creator of the room.
         pub fn into_event_content(
             self,
             creator: OwnedUserId,
             room_version: RoomVersionId,
         ) -> RoomCreateEventContent {
-            assign!(RoomCreateEventContent::new(creator), {
+            let creator = room_version
+                .rules()
+                .map_or(true, |rules| !rules.authorization.use_room_create_sender)
+                .then_some(creator);
+
+            assign!(RoomCreateEventContent::new_v11(), {
+                creator: creator,
                 federate: self.federate,
                 room_version: room_version,
                 predecessor: self.predecessor,
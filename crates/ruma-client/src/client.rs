@@ -1,4 +1,5 @@
 use std::{
+    any::type_name,
     sync::{Arc, Mutex},
     time::Duration,
 };
@@ -6,25 +7,56 @@ use std::{
 use assign::assign;
 use async_stream::try_stream;
 use futures_core::stream::Stream;
+use futures_timer::Delay;
+use futures_util::lock::Mutex as AsyncMutex;
+use http::{header::AUTHORIZATION, HeaderValue, StatusCode};
 use ruma_client_api::{
     account::register::{self, RegistrationKind},
-    session::login::{self, v3::LoginInfo},
+    error::{ErrorKind, StandardErrorBody},
+    filter::FilterDefinition,
+    session::{
+        login::{self, v3::LoginInfo},
+        refresh_token,
+    },
     sync::sync_events,
-    uiaa::UserIdentifier,
+    uiaa::{AuthData, UiaaResponse, UserIdentifier},
 };
 use ruma_common::{
-    api::{MatrixVersion, OutgoingRequest, SendAccessToken},
+    api::{
+        error::{FromHttpResponseError, IntoHttpError},
+        MatrixVersion, OutgoingRequest, SendAccessToken,
+    },
     presence::PresenceState,
     DeviceId, UserId,
 };
+use tracing::{debug, info_span, warn, Instrument};
 
 use crate::{
-    add_user_id_to_query, send_customized_request, Error, HttpClient, ResponseError, ResponseResult,
+    add_user_id_to_query, deserialize_response, serialize_request, Error, HttpClient,
+    HttpClientExt, ResponseError, ResponseResult,
 };
 
 mod builder;
+mod discovery;
+#[cfg(test)]
+mod mock;
+mod retry;
+mod session;
+#[cfg(feature = "unstable-msc3575")]
+mod sliding_sync;
+mod sync;
+mod uiaa;
 
-pub use self::builder::ClientBuilder;
+#[cfg(feature = "unstable-msc3575")]
+pub use self::sliding_sync::{RoomListEntry, SlidingSync, SlidingSyncUpdate};
+pub use self::{
+    builder::ClientBuilder,
+    discovery::DiscoveryError,
+    retry::RetryPolicy,
+    session::{Session, SessionStore},
+    sync::{EventHandlers, SyncSettings, SyncTokenStore},
+    uiaa::UiaaHandler,
+};
 
 /// A client for the Matrix client-server API.
 #[derive(Clone, Debug)]
@@ -42,8 +74,25 @@ struct ClientData<C> {
     /// The access token, if logged in.
     access_token: Mutex<Option<String>>,
 
+    /// The full session, if logged in through this client or restored from a previous login.
+    ///
+    /// If this is set, its access token is the same as `access_token`.
+    session: Mutex<Option<Session>>,
+
+    /// The store the session is persisted through, if any.
+    session_store: Option<Arc<dyn SessionStore>>,
+
+    /// Whether to request a refresh token when logging in or registering.
+    request_refresh_token: bool,
+
+    /// Held while the access token is refreshed, so that it is only refreshed once at a time.
+    refresh_lock: AsyncMutex<()>,
+
     /// The (known) Matrix versions the homeserver supports.
     supported_matrix_versions: Vec<MatrixVersion>,
+
+    /// The policy for retrying failed requests, if any.
+    retry_policy: Option<RetryPolicy>,
 }
 
 impl Client<()> {
@@ -54,12 +103,60 @@ impl Client<()> {
 }
 
 impl<C> Client<C> {
+    /// Get the URL of the homeserver this client sends requests to.
+    ///
+    /// This is useful to find out which homeserver was found through
+    /// [`ClientBuilder::server_name`] or [`ClientBuilder::user_id`].
+    pub fn homeserver_url(&self) -> &str {
+        &self.0.homeserver_url
+    }
+
     /// Get a copy of the current `access_token`, if any.
     ///
     /// Useful for serializing and persisting the session to be restored later.
     pub fn access_token(&self) -> Option<String> {
         self.0.access_token.lock().expect("session mutex was poisoned").clone()
     }
+
+    /// Get a copy of the current session, if any.
+    ///
+    /// A session is available after logging in or registering through this client, or if one was
+    /// restored with [`ClientBuilder::session`].
+    pub fn session(&self) -> Option<Session> {
+        self.0.session.lock().expect("session mutex was poisoned").clone()
+    }
+
+    /// Replace the current session and persist it through the session store, if any.
+    async fn set_session<F>(&self, session: Session) -> Result<(), Error<C::Error, F>>
+    where
+        C: HttpClient,
+    {
+        *self.0.access_token.lock().expect("session mutex was poisoned") =
+            Some(session.access_token.clone());
+        *self.0.session.lock().expect("session mutex was poisoned") = Some(session.clone());
+
+        if let Some(store) = &self.0.session_store {
+            store.save_session(&session).await.map_err(Error::Store)?;
+        }
+
+        Ok(())
+    }
+
+    /// Forget the current session, also in the session store if any, and use the given access
+    /// token instead.
+    async fn clear_session<F>(&self, access_token: Option<String>) -> Result<(), Error<C::Error, F>>
+    where
+        C: HttpClient,
+    {
+        *self.0.access_token.lock().expect("session mutex was poisoned") = access_token;
+        *self.0.session.lock().expect("session mutex was poisoned") = None;
+
+        if let Some(store) = &self.0.session_store {
+            store.delete_session().await.map_err(Error::Store)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl<C: HttpClient> Client<C> {
@@ -84,15 +181,216 @@ impl<C: HttpClient> Client<C> {
             None => SendAccessToken::None,
         };
 
-        send_customized_request(
-            &self.0.http_client,
+        let mut http_req = serialize_request::<C, R, F>(
             &self.0.homeserver_url,
             send_access_token,
             &self.0.supported_matrix_versions,
             request,
             customize,
-        )
-        .await
+        )?;
+
+        let mut attempt = 1;
+        let mut refreshed = false;
+        loop {
+            let send_span = info_span!(
+                "send_request",
+                request_type = type_name::<R>(),
+                http_client = type_name::<C>(),
+                homeserver_url = self.0.homeserver_url.as_str(),
+                attempt,
+            );
+
+            let result = self
+                .0
+                .http_client
+                .send_http_request(retry::clone_request(&http_req))
+                .instrument(send_span)
+                .await;
+
+            if let Ok(http_res) = &result {
+                if !refreshed
+                    && http_req.headers().contains_key(AUTHORIZATION)
+                    && is_soft_logout(http_res)
+                {
+                    refreshed = true;
+                    if let Some(new_access_token) =
+                        self.refresh_after_soft_logout(access_token.as_deref()).await
+                    {
+                        let header = HeaderValue::try_from(format!("Bearer {new_access_token}"))
+                            .map_err(IntoHttpError::from)?;
+                        http_req.headers_mut().insert(AUTHORIZATION, header);
+                        continue;
+                    }
+                }
+            }
+
+            let delay = self.0.retry_policy.as_ref().and_then(|retry_policy| match &result {
+                Ok(http_res) => retry_policy.delay_after_response(&R::METADATA, attempt, http_res),
+                Err(_) => retry_policy.delay_after_error(&R::METADATA, attempt),
+            });
+
+            match delay {
+                Some(delay) => {
+                    debug!(request_type = type_name::<R>(), attempt, ?delay, "Retrying request");
+                    Delay::new(delay).await;
+                    attempt += 1;
+                }
+                None => return deserialize_response::<C, R>(result.map_err(Error::Response)?),
+            }
+        }
+    }
+
+    /// Makes a request to an endpoint that uses [User-Interactive Authentication][uiaa], driving
+    /// the authentication with the given handler.
+    ///
+    /// `make_request` is called to create the request for every attempt, with the authentication
+    /// data for the next stage (`None` for the first attempt). It should usually put it in the
+    /// `auth` field of the request. Whenever the homeserver responds with a [`UiaaInfo`], the
+    /// handler is asked to complete the next stage of the chosen flow, until the request succeeds
+    /// or the handler aborts.
+    ///
+    /// The authentication also stops if the homeserver still requires authentication after a
+    /// limited number of attempts. In both cases, the last [`UiaaInfo`] is returned as an error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use ruma_client::UiaaHandler;
+    /// # struct Handler;
+    /// # impl UiaaHandler for Handler {}
+    /// # let homeserver_url = "https://example.com".parse().unwrap();
+    /// # async {
+    /// # let client = ruma_client::Client::builder()
+    /// #     .homeserver_url(homeserver_url)
+    /// #     .build::<ruma_client::http_client::Dummy>()
+    /// #     .await
+    /// #     .unwrap();
+    /// use ruma_client_api::device::delete_devices;
+    /// use ruma_common::device_id;
+    ///
+    /// let devices = vec![device_id!("ABCDEFG").to_owned()];
+    /// client
+    ///     .send_request_with_uiaa(
+    ///         |auth| {
+    ///             let mut request = delete_devices::v3::Request::new(devices.clone());
+    ///             request.auth = auth;
+    ///             request
+    ///         },
+    ///         &Handler,
+    ///     )
+    ///     .await?;
+    /// # Result::<(), ruma_client::Error<_, ruma_client_api::uiaa::UiaaResponse>>::Ok(())
+    /// # };
+    /// ```
+    ///
+    /// [uiaa]: https://spec.matrix.org/latest/client-server-api/#user-interactive-authentication-api
+    /// [`UiaaInfo`]: ruma_client_api::uiaa::UiaaInfo
+    pub async fn send_request_with_uiaa<R, F, H>(
+        &self,
+        mut make_request: F,
+        handler: &H,
+    ) -> ResponseResult<C, R>
+    where
+        R: OutgoingRequest<EndpointError = UiaaResponse>,
+        F: FnMut(Option<AuthData>) -> R,
+        H: UiaaHandler + ?Sized,
+    {
+        let mut auth = None;
+        let mut attempt = 1;
+        loop {
+            let info = match self.send_request(make_request(auth.take())).await {
+                Err(Error::FromHttpResponse(FromHttpResponseError::Server(
+                    UiaaResponse::AuthResponse(info),
+                ))) => info,
+                result => return result,
+            };
+
+            let auth_data = if attempt < uiaa::MAX_ATTEMPTS {
+                uiaa::next_auth_data(handler, &info).await
+            } else {
+                debug!(request_type = type_name::<R>(), attempt, "Too many UIAA attempts");
+                None
+            };
+            attempt += 1;
+
+            match auth_data {
+                Some(auth_data) => auth = Some(auth_data),
+                None => {
+                    return Err(Error::FromHttpResponse(FromHttpResponseError::Server(
+                        UiaaResponse::AuthResponse(info),
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Obtain a new access token using the refresh token of the current session.
+    ///
+    /// On success, the new tokens are stored in this client and persisted through the session
+    /// store, if any. Requests that fail with a soft logout (`M_UNKNOWN_TOKEN` with
+    /// `soft_logout: true`) call this automatically, so it should rarely be necessary to call it
+    /// directly.
+    ///
+    /// Returns [`Error::AuthenticationRequired`] if there is no session with a refresh token.
+    pub async fn refresh_access_token(
+        &self,
+    ) -> Result<refresh_token::v3::Response, Error<C::Error, ruma_client_api::Error>> {
+        let _guard = self.0.refresh_lock.lock().await;
+        self.send_refresh_request().await
+    }
+
+    /// Refresh the access token, `refresh_lock` must be held.
+    async fn send_refresh_request(
+        &self,
+    ) -> Result<refresh_token::v3::Response, Error<C::Error, ruma_client_api::Error>> {
+        let mut session = self.session().ok_or(Error::AuthenticationRequired)?;
+        let refresh_token = session.refresh_token.clone().ok_or(Error::AuthenticationRequired)?;
+
+        let response = self
+            .0
+            .http_client
+            .send_matrix_request(
+                &self.0.homeserver_url,
+                SendAccessToken::None,
+                &self.0.supported_matrix_versions,
+                refresh_token::v3::Request::new(refresh_token),
+            )
+            .await?;
+
+        session.update_from_refresh(response.clone());
+        self.set_session(session).await?;
+
+        Ok(response)
+    }
+
+    /// Get a new access token after a request sent with `failed_access_token` resulted in a soft
+    /// logout.
+    ///
+    /// If the access token was already refreshed in the meantime (e.g. by a concurrent request),
+    /// the current access token is returned without refreshing it again.
+    async fn refresh_after_soft_logout(&self, failed_access_token: Option<&str>) -> Option<String> {
+        // Check the access token only once the lock is acquired, so that concurrent requests that
+        // failed with the same access token wait for the first refresh instead of repeating it.
+        let _guard = self.0.refresh_lock.lock().await;
+
+        let current_access_token = self.access_token();
+        if current_access_token.is_some() && current_access_token.as_deref() != failed_access_token
+        {
+            return current_access_token;
+        }
+
+        match self.send_refresh_request().await {
+            Ok(response) => Some(response.access_token),
+            Err(Error::AuthenticationRequired) => None,
+            Err(Error::FromHttpResponse(error)) => {
+                warn!(%error, "Failed to refresh the access token after a soft logout");
+                None
+            }
+            Err(_) => {
+                warn!("Failed to refresh the access token after a soft logout");
+                None
+            }
+        }
     }
 
     /// Makes a request to a Matrix API endpoint as a virtual user.
@@ -109,8 +407,12 @@ impl<C: HttpClient> Client<C> {
 
     /// Log in with a username and password.
     ///
-    /// In contrast to [`send_request`][Self::send_request], this method stores the access token
-    /// returned by the endpoint in this client, in addition to returning it.
+    /// In contrast to [`send_request`][Self::send_request], this method stores the session
+    /// returned by the endpoint in this client and persists it through the session store, if any,
+    /// in addition to returning it.
+    ///
+    /// A refresh token is requested if it was enabled with
+    /// [`ClientBuilder::request_refresh_token`].
     pub async fn log_in(
         &self,
         user: &str,
@@ -126,34 +428,46 @@ impl<C: HttpClient> Client<C> {
             .send_request(assign!(login::v3::Request::new(login_info), {
                 device_id: device_id.map(ToOwned::to_owned),
                 initial_device_display_name: initial_device_display_name.map(ToOwned::to_owned),
+                refresh_token: self.0.request_refresh_token,
             }))
             .await?;
 
-        *self.0.access_token.lock().unwrap() = Some(response.access_token.clone());
+        self.set_session(response.clone().into()).await?;
 
         Ok(response)
     }
 
     /// Register as a guest.
     ///
-    /// In contrast to [`send_request`][Self::send_request], this method stores the access token
-    /// returned by the endpoint in this client, in addition to returning it.
+    /// In contrast to [`send_request`][Self::send_request], this method stores the session
+    /// returned by the endpoint in this client and persists it through the session store, if any,
+    /// in addition to returning it.
+    ///
+    /// A refresh token is requested if it was enabled with
+    /// [`ClientBuilder::request_refresh_token`].
     pub async fn register_guest(
         &self,
-    ) -> Result<register::v3::Response, Error<C::Error, ruma_client_api::uiaa::UiaaResponse>> {
+    ) -> Result<register::v3::Response, Error<C::Error, UiaaResponse>> {
         let response = self
-            .send_request(assign!(register::v3::Request::new(), { kind: RegistrationKind::Guest }))
+            .send_request(assign!(register::v3::Request::new(), {
+                kind: RegistrationKind::Guest,
+                refresh_token: self.0.request_refresh_token,
+            }))
             .await?;
 
-        *self.0.access_token.lock().unwrap() = response.access_token.clone();
+        self.store_registration(&response).await?;
 
         Ok(response)
     }
 
     /// Register as a new user on this server.
     ///
-    /// In contrast to [`send_request`][Self::send_request], this method stores the access token
-    /// returned by the endpoint in this client, in addition to returning it.
+    /// In contrast to [`send_request`][Self::send_request], this method stores the session
+    /// returned by the endpoint in this client and persists it through the session store, if any,
+    /// in addition to returning it.
+    ///
+    /// A refresh token is requested if it was enabled with
+    /// [`ClientBuilder::request_refresh_token`].
     ///
     /// The username is the local part of the returned user_id. If it is omitted from this request,
     /// the server will generate one.
@@ -161,19 +475,38 @@ impl<C: HttpClient> Client<C> {
         &self,
         username: Option<&str>,
         password: &str,
-    ) -> Result<register::v3::Response, Error<C::Error, ruma_client_api::uiaa::UiaaResponse>> {
+    ) -> Result<register::v3::Response, Error<C::Error, UiaaResponse>> {
         let response = self
             .send_request(assign!(register::v3::Request::new(), {
                 username: username.map(ToOwned::to_owned),
-                password: Some(password.to_owned())
+                password: Some(password.to_owned()),
+                refresh_token: self.0.request_refresh_token,
             }))
             .await?;
 
-        *self.0.access_token.lock().unwrap() = response.access_token.clone();
+        self.store_registration(&response).await?;
 
         Ok(response)
     }
 
+    /// Store the session or access token returned by the register endpoint.
+    ///
+    /// Without a device ID, there is no session to store, so the previous one is cleared.
+    async fn store_registration<F>(
+        &self,
+        response: &register::v3::Response,
+    ) -> Result<(), Error<C::Error, F>> {
+        match (&response.access_token, &response.device_id) {
+            (Some(access_token), Some(device_id)) => {
+                let mut session =
+                    Session::new(response.user_id.clone(), device_id.clone(), access_token.clone());
+                session.refresh_token = response.refresh_token.clone();
+                self.set_session(session).await
+            }
+            (access_token, _) => self.clear_session(access_token.clone()).await,
+        }
+    }
+
     /// Convenience method that represents repeated calls to the sync_events endpoint as a stream.
     ///
     /// # Example:
@@ -226,4 +559,106 @@ impl<C: HttpClient> Client<C> {
             }
         }
     }
+
+    /// Run a [sliding sync (MSC3575)][msc] loop that keeps the given `SlidingSync` up to date.
+    ///
+    /// Every request uses the current `pos` token and list configuration of `sliding_sync`, so
+    /// the ranges of its lists can be changed between iterations with
+    /// [`SlidingSync::set_ranges`]. Each response is applied to `sliding_sync` and the resulting
+    /// changes are yielded by the stream.
+    ///
+    /// [msc]: https://github.com/matrix-org/matrix-spec-proposals/pull/3575
+    #[cfg(feature = "unstable-msc3575")]
+    pub fn sliding_sync<'a>(
+        &'a self,
+        sliding_sync: &'a SlidingSync,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = Result<SlidingSyncUpdate, Error<C::Error, ruma_client_api::Error>>> + 'a
+    {
+        try_stream! {
+            loop {
+                let response = self.send_request(sliding_sync.request(timeout)).await?;
+                yield sliding_sync.update(response);
+            }
+        }
+    }
+
+    /// Sync with the homeserver in a loop and dispatch the events of every response to the given
+    /// handlers.
+    ///
+    /// After the events of a response have been handled, its `next_batch` token is persisted
+    /// through the token store of the settings, if any, so that the next call can resume where
+    /// this one stopped.
+    ///
+    /// This only returns when a request fails or the token store returns an error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ruma_client::{EventHandlers, SyncSettings};
+    /// use ruma_common::events::room::message::OriginalSyncRoomMessageEvent;
+    ///
+    /// # let homeserver_url = "https://example.com".parse().unwrap();
+    /// # async {
+    /// # let client = ruma_client::Client::builder()
+    /// #     .homeserver_url(homeserver_url)
+    /// #     .build::<ruma_client::http_client::Dummy>()
+    /// #     .await?;
+    /// let mut handlers = EventHandlers::new();
+    /// handlers.on_message_like(|event: OriginalSyncRoomMessageEvent, room_id| async move {
+    ///     println!("{} in {room_id}: {}", event.sender, event.content.body());
+    /// });
+    ///
+    /// client.sync_with_handlers(&handlers, SyncSettings::new().skip_initial_backlog(true)).await?;
+    /// # Result::<(), ruma_client::Error<_, _>>::Ok(())
+    /// # };
+    /// ```
+    pub async fn sync_with_handlers(
+        &self,
+        handlers: &EventHandlers,
+        settings: SyncSettings,
+    ) -> Result<(), Error<C::Error, ruma_client_api::Error>> {
+        let mut since = match (settings.since, &settings.token_store) {
+            (Some(since), _) => Some(since),
+            (None, Some(store)) => store.load_sync_token().await.map_err(Error::Store)?,
+            (None, None) => None,
+        };
+
+        if since.is_none() && settings.skip_initial_backlog {
+            let response = self
+                .send_request(assign!(sync_events::v3::Request::new(), {
+                    filter: Some(FilterDefinition::ignore_all().into()),
+                }))
+                .await?;
+
+            since = Some(response.next_batch);
+        }
+
+        loop {
+            let response = self
+                .send_request(assign!(sync_events::v3::Request::new(), {
+                    filter: settings.filter.clone(),
+                    since: since.clone(),
+                    set_presence: settings.set_presence.clone(),
+                    timeout: settings.timeout,
+                }))
+                .await?;
+
+            handlers.dispatch(&response).await;
+
+            if let Some(store) = &settings.token_store {
+                store.save_sync_token(&response.next_batch).await.map_err(Error::Store)?;
+            }
+            since = Some(response.next_batch);
+        }
+    }
+}
+
+/// Whether the given response is an `M_UNKNOWN_TOKEN` error with `soft_logout` set to `true`.
+fn is_soft_logout<T: AsRef<[u8]>>(response: &http::Response<T>) -> bool {
+    response.status() == StatusCode::UNAUTHORIZED
+        && matches!(
+            serde_json::from_slice(response.body().as_ref()),
+            Ok(StandardErrorBody { kind: ErrorKind::UnknownToken { soft_logout: true }, .. })
+        )
 }
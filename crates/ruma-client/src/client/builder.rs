@@ -1,9 +1,15 @@
 use std::sync::{Arc, Mutex};
 
+use futures_util::lock::Mutex as AsyncMutex;
 use ruma_client_api::discovery::get_supported_versions;
-use ruma_common::api::{MatrixVersion, SendAccessToken};
+use ruma_common::{
+    api::{MatrixVersion, SendAccessToken},
+    OwnedServerName, UserId,
+};
 
-use super::{Client, ClientData};
+use super::{
+    discovery::discover_homeserver, Client, ClientData, RetryPolicy, Session, SessionStore,
+};
 use crate::{DefaultConstructibleHttpClient, Error, HttpClient, HttpClientExt};
 
 /// A [`Client`] builder.
@@ -11,28 +17,83 @@ use crate::{DefaultConstructibleHttpClient, Error, HttpClient, HttpClientExt};
 /// This type can be used to construct a `Client` through a few method calls.
 pub struct ClientBuilder {
     homeserver_url: Option<String>,
+    server_name: Option<OwnedServerName>,
     access_token: Option<String>,
     supported_matrix_versions: Option<Vec<MatrixVersion>>,
+    retry_policy: Option<RetryPolicy>,
+    session: Option<Session>,
+    session_store: Option<Arc<dyn SessionStore>>,
+    request_refresh_token: bool,
 }
 
 impl ClientBuilder {
     pub(super) fn new() -> Self {
-        Self { homeserver_url: None, access_token: None, supported_matrix_versions: None }
+        Self {
+            homeserver_url: None,
+            server_name: None,
+            access_token: None,
+            supported_matrix_versions: None,
+            retry_policy: None,
+            session: None,
+            session_store: None,
+            request_refresh_token: false,
+        }
     }
 
     /// Set the homeserver URL.
     ///
-    /// The homeserver URL must be set before calling [`build()`][Self::build] or
-    /// [`http_client()`][Self::http_client].
+    /// Either the homeserver URL or the server name must be set before calling
+    /// [`build()`][Self::build] or [`http_client()`][Self::http_client].
     pub fn homeserver_url(self, url: String) -> Self {
         Self { homeserver_url: Some(url), ..self }
     }
 
+    /// Set the server name to discover the homeserver URL from.
+    ///
+    /// Unless a homeserver URL is set, the [`build()`][Self::build] and
+    /// [`http_client()`][Self::http_client] methods fetch the `/.well-known/matrix/client` file of
+    /// the server and validate the homeserver it advertises. If the server has no well-known file,
+    /// `https://{server_name}` is used as the homeserver URL. Failures are reported as
+    /// [`Error::Discovery`].
+    pub fn server_name(self, server_name: OwnedServerName) -> Self {
+        Self { server_name: Some(server_name), ..self }
+    }
+
+    /// Set the server name to discover the homeserver URL from to the one of the given user ID.
+    ///
+    /// See [`server_name()`][Self::server_name] for details.
+    pub fn user_id(self, user_id: &UserId) -> Self {
+        self.server_name(user_id.server_name().to_owned())
+    }
+
     /// Set the access token.
     pub fn access_token(self, access_token: Option<String>) -> Self {
         Self { access_token, ..self }
     }
 
+    /// Restore a previous session.
+    ///
+    /// This also sets the access token to the one of the session.
+    pub fn session(self, session: Session) -> Self {
+        Self { session: Some(session), ..self }
+    }
+
+    /// Set the store the session is persisted through.
+    ///
+    /// If no session was set with [`session()`][Self::session], the session is loaded from the
+    /// store when the client is built.
+    pub fn session_store(self, store: impl SessionStore + 'static) -> Self {
+        Self { session_store: Some(Arc::new(store)), ..self }
+    }
+
+    /// Set whether a refresh token is requested when logging in or registering through the client.
+    ///
+    /// With a refresh token, the client can obtain a new access token on its own when the current
+    /// one expires. By default, no refresh token is requested.
+    pub fn request_refresh_token(self, request_refresh_token: bool) -> Self {
+        Self { request_refresh_token, ..self }
+    }
+
     /// Set the supported Matrix versions.
     ///
     /// This method generally *shouldn't* be called. The [`build()`][Self::build] or
@@ -42,6 +103,13 @@ impl ClientBuilder {
         Self { supported_matrix_versions: Some(versions), ..self }
     }
 
+    /// Set the policy for retrying requests that failed due to rate limiting or network errors.
+    ///
+    /// By default, failed requests are not retried.
+    pub fn retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self { retry_policy: Some(retry_policy), ..self }
+    }
+
     /// Finish building the [`Client`].
     ///
     /// Uses [`DefaultConstructibleHttpClient::default()`] to create an HTTP client instance.
@@ -67,11 +135,22 @@ impl ClientBuilder {
     where
         C: HttpClient,
     {
-        let homeserver_url = self
-            .homeserver_url
-            .expect("homeserver URL has to be set prior to calling .build() or .http_client()");
+        let (homeserver_url, discovered_versions) = match (self.homeserver_url, self.server_name) {
+            (Some(homeserver_url), _) => (homeserver_url, None),
+            (None, Some(server_name)) => {
+                let discovered = discover_homeserver(&http_client, &server_name)
+                    .await
+                    .map_err(Error::Discovery)?;
+                (discovered.homeserver_url, discovered.supported_matrix_versions)
+            }
+            (None, None) => panic!(
+                "homeserver URL or server name has to be set prior to calling .build() or \
+                 .http_client()"
+            ),
+        };
 
-        let supported_matrix_versions = match self.supported_matrix_versions {
+        let supported_matrix_versions = match self.supported_matrix_versions.or(discovered_versions)
+        {
             Some(versions) => versions,
             None => http_client
                 .send_matrix_request(
@@ -85,11 +164,26 @@ impl ClientBuilder {
                 .collect(),
         };
 
+        let session = match (self.session, &self.session_store) {
+            (Some(session), _) => Some(session),
+            (None, Some(store)) => store.load_session().await.map_err(Error::Store)?,
+            (None, None) => None,
+        };
+        let access_token = match &session {
+            Some(session) => Some(session.access_token.clone()),
+            None => self.access_token,
+        };
+
         Ok(Client(Arc::new(ClientData {
             homeserver_url,
             http_client,
-            access_token: Mutex::new(self.access_token),
+            access_token: Mutex::new(access_token),
+            session: Mutex::new(session),
+            session_store: self.session_store,
+            request_refresh_token: self.request_refresh_token,
+            refresh_lock: AsyncMutex::new(()),
             supported_matrix_versions,
+            retry_policy: self.retry_policy,
         })))
     }
 }
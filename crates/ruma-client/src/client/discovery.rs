@@ -0,0 +1,183 @@
+use std::fmt::{self, Debug, Display};
+
+use http::StatusCode;
+use ruma_client_api::discovery::{discover_homeserver, get_supported_versions};
+use ruma_common::{
+    api::{error::FromHttpResponseError, MatrixVersion, SendAccessToken},
+    ServerName,
+};
+use tracing::debug;
+
+use crate::{Error, HttpClient, HttpClientExt};
+
+/// An error that can occur during [homeserver discovery][discovery].
+///
+/// The variants correspond to the `FAIL_PROMPT` and `FAIL_ERROR` outcomes of the discovery
+/// process as defined by the spec.
+///
+/// [discovery]: https://spec.matrix.org/latest/client-server-api/#well-known-uri
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DiscoveryError<E> {
+    /// `FAIL_PROMPT`: The `/.well-known/matrix/client` file could not be fetched or is malformed.
+    ///
+    /// The user should be prompted for the homeserver URL.
+    FailPrompt(Box<Error<E, ruma_client_api::Error>>),
+
+    /// `FAIL_ERROR`: The `m.homeserver.base_url` advertised by the well-known file doesn't point
+    /// to a valid homeserver.
+    ///
+    /// The user should be informed of the problem, and discovery should not continue.
+    FailError {
+        /// The advertised base URL.
+        base_url: String,
+
+        /// The error that occurred when validating the base URL.
+        source: Box<Error<E, ruma_client_api::Error>>,
+    },
+}
+
+impl<E: Display> Display for DiscoveryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FailPrompt(err) => write!(f, "Couldn't fetch the well-known file: {err}"),
+            Self::FailError { base_url, source } => {
+                write!(f, "Invalid homeserver base URL `{base_url}`: {source}")
+            }
+        }
+    }
+}
+
+/// The result of a successful homeserver discovery.
+pub(super) struct Discovered {
+    /// The base URL of the homeserver, without a trailing slash.
+    pub(super) homeserver_url: String,
+
+    /// The Matrix versions the homeserver supports, if they were queried to validate the base
+    /// URL.
+    pub(super) supported_matrix_versions: Option<Vec<MatrixVersion>>,
+}
+
+/// Discover the homeserver of the given server name through its `/.well-known/matrix/client`
+/// file.
+///
+/// If the server doesn't have a well-known file, `https://{server_name}` is used.
+pub(super) async fn discover_homeserver<C: HttpClient>(
+    http_client: &C,
+    server_name: &ServerName,
+) -> Result<Discovered, DiscoveryError<C::Error>> {
+    let server_url = format!("https://{server_name}");
+
+    let well_known = match http_client
+        .send_matrix_request(
+            &server_url,
+            SendAccessToken::None,
+            &[MatrixVersion::V1_0],
+            discover_homeserver::Request::new(),
+        )
+        .await
+    {
+        Ok(response) => response,
+        Err(Error::FromHttpResponse(FromHttpResponseError::Server(err)))
+            if err.status_code == StatusCode::NOT_FOUND =>
+        {
+            debug!(%server_name, "No well-known file, falling back to the server name");
+            return Ok(Discovered { homeserver_url: server_url, supported_matrix_versions: None });
+        }
+        Err(err) => return Err(DiscoveryError::FailPrompt(Box::new(err))),
+    };
+
+    let base_url = well_known.homeserver.base_url;
+    let homeserver_url = base_url.trim_end_matches('/').to_owned();
+
+    match http_client
+        .send_matrix_request(
+            &homeserver_url,
+            SendAccessToken::None,
+            &[MatrixVersion::V1_0],
+            get_supported_versions::Request::new(),
+        )
+        .await
+    {
+        Ok(response) => Ok(Discovered {
+            homeserver_url,
+            supported_matrix_versions: Some(response.known_versions().collect()),
+        }),
+        Err(err) => Err(DiscoveryError::FailError { base_url, source: Box::new(err) }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use ruma_common::{server_name, user_id};
+
+    use super::DiscoveryError;
+    use crate::{client::mock::MockClient, Client, Error};
+
+    const WELL_KNOWN: &str = r#"{ "m.homeserver": { "base_url": "https://matrix.example.com/" } }"#;
+    const VERSIONS: &str = r#"{ "versions": ["v1.1"] }"#;
+
+    #[tokio::test]
+    async fn use_well_known_base_url() {
+        let client = Client::builder()
+            .user_id(user_id!("@alice:example.com"))
+            .http_client(MockClient::new(vec![
+                Ok((StatusCode::OK, WELL_KNOWN)),
+                Ok((StatusCode::OK, VERSIONS)),
+            ]))
+            .await
+            .unwrap();
+
+        assert_eq!(client.homeserver_url(), "https://matrix.example.com");
+        // The versions from the validation are reused.
+        let sent = client.0.http_client.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].0, "/.well-known/matrix/client");
+        assert_eq!(sent[1].0, "/_matrix/client/versions");
+    }
+
+    #[tokio::test]
+    async fn fall_back_to_server_name() {
+        let client = Client::builder()
+            .server_name(server_name!("example.com").to_owned())
+            .http_client(MockClient::new(vec![
+                Ok((StatusCode::NOT_FOUND, r#"{ "errcode": "M_NOT_FOUND", "error": "" }"#)),
+                Ok((StatusCode::OK, VERSIONS)),
+            ]))
+            .await
+            .unwrap();
+
+        assert_eq!(client.homeserver_url(), "https://example.com");
+        assert_eq!(client.0.http_client.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn fail_prompt_on_invalid_well_known() {
+        let error = Client::builder()
+            .server_name(server_name!("example.com").to_owned())
+            .http_client(MockClient::new(vec![Ok((StatusCode::OK, r#"{ "m.homeserver": {} }"#))]))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, Error::Discovery(DiscoveryError::FailPrompt(_))));
+    }
+
+    #[tokio::test]
+    async fn fail_error_on_invalid_homeserver() {
+        let error = Client::builder()
+            .server_name(server_name!("example.com").to_owned())
+            .http_client(MockClient::new(vec![
+                Ok((StatusCode::OK, WELL_KNOWN)),
+                Ok((StatusCode::OK, "<html></html>")),
+            ]))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::Discovery(DiscoveryError::FailError { base_url, .. })
+                if base_url == "https://matrix.example.com/"
+        ));
+    }
+}
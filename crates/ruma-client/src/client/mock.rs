@@ -0,0 +1,83 @@
+//! An `HttpClient` for tests that returns canned responses.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use async_trait::async_trait;
+use http::StatusCode;
+
+use crate::HttpClient;
+
+/// An `HttpClient` that returns canned responses in order and records the requests it gets.
+///
+/// Every request yields to the runtime once before returning its response, so that concurrent
+/// requests are interleaved.
+#[derive(Debug)]
+pub(crate) struct MockClient {
+    responses: Mutex<VecDeque<Result<http::Response<Vec<u8>>, ()>>>,
+    requests: Mutex<Vec<http::Request<Vec<u8>>>>,
+}
+
+impl MockClient {
+    /// Creates a new `MockClient` with the given status codes and JSON bodies, or `Err(())` for a
+    /// transport error.
+    pub(crate) fn new(responses: Vec<Result<(StatusCode, &str), ()>>) -> Self {
+        let responses = responses
+            .into_iter()
+            .map(|res| {
+                res.map(|(status, body)| {
+                    http::Response::builder().status(status).body(body.into()).unwrap()
+                })
+            })
+            .collect();
+
+        Self { responses: Mutex::new(responses), requests: Mutex::new(Vec::new()) }
+    }
+
+    /// The number of requests sent so far.
+    pub(crate) fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// The paths and `Authorization` headers of the requests sent so far.
+    pub(crate) fn sent(&self) -> Vec<(String, Option<String>)> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|req| {
+                let auth = req
+                    .headers()
+                    .get(http::header::AUTHORIZATION)
+                    .map(|value| value.to_str().unwrap().to_owned());
+                (req.uri().path().to_owned(), auth)
+            })
+            .collect()
+    }
+
+    /// The JSON bodies of the requests sent so far, or `Value::Null` for empty bodies.
+    pub(crate) fn sent_bodies(&self) -> Vec<serde_json::Value> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|req| serde_json::from_slice(req.body()).unwrap_or_default())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl HttpClient for MockClient {
+    type RequestBody = Vec<u8>;
+    type ResponseBody = Vec<u8>;
+    type Error = ();
+
+    async fn send_http_request(
+        &self,
+        req: http::Request<Vec<u8>>,
+    ) -> Result<http::Response<Vec<u8>>, ()> {
+        self.requests.lock().unwrap().push(req);
+        let response = self.responses.lock().unwrap().pop_front().expect("unexpected request");
+        tokio::task::yield_now().await;
+        response
+    }
+}
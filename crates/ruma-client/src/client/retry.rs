@@ -0,0 +1,253 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use http::{Method, StatusCode};
+use ruma_client_api::error::{ErrorKind, StandardErrorBody};
+use ruma_common::api::Metadata;
+
+/// A policy that decides whether and when a [`Client`](super::Client) retries a failed request.
+///
+/// Two kinds of failures are retried:
+///
+/// * The homeserver rejected the request with `M_LIMIT_EXCEEDED`. The server did not process the
+///   request, so it is retried for every endpoint that is rate-limited or idempotent. If the server
+///   sent a `retry_after_ms`, the client waits exactly that long before the next attempt.
+/// * The HTTP client could not obtain a response at all (e.g. due to network issues). Since the
+///   request may or may not have reached the server, it is only retried if the endpoint's HTTP
+///   method is idempotent.
+///
+/// Unless the server dictates the delay, the client waits for an exponentially growing backoff
+/// between attempts, starting at [`initial_backoff`][Self::initial_backoff] and capped at
+/// [`max_backoff`][Self::max_backoff], optionally randomized through
+/// [`jitter`][Self::jitter].
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Creates a new `RetryPolicy` with the default settings.
+    ///
+    /// By default, a request is attempted at most 5 times, the backoff starts at 500ms and is
+    /// capped at 30s, and jitter is enabled.
+    pub fn new() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+
+    /// Set the maximum number of times a request is sent, including the first attempt.
+    ///
+    /// A value of `0` or `1` disables retries.
+    pub fn max_attempts(self, max_attempts: u32) -> Self {
+        Self { max_attempts, ..self }
+    }
+
+    /// Set the delay before the first retry.
+    ///
+    /// The delay doubles with every subsequent retry.
+    pub fn initial_backoff(self, initial_backoff: Duration) -> Self {
+        Self { initial_backoff, ..self }
+    }
+
+    /// Set the maximum delay between two attempts, unless the server asks for a longer one with
+    /// `retry_after_ms`.
+    pub fn max_backoff(self, max_backoff: Duration) -> Self {
+        Self { max_backoff, ..self }
+    }
+
+    /// Set whether the backoff should be randomized.
+    ///
+    /// With jitter enabled, the actual delay is picked uniformly between half the backoff and the
+    /// full backoff, which avoids many clients retrying in lockstep.
+    pub fn jitter(self, jitter: bool) -> Self {
+        Self { jitter, ..self }
+    }
+
+    /// Get the delay after which a request that got the given response should be sent again, or
+    /// `None` if the request shouldn't be retried.
+    ///
+    /// `attempt` is the number of times the request has been sent already.
+    pub(super) fn delay_after_response<T: AsRef<[u8]>>(
+        &self,
+        metadata: &Metadata,
+        attempt: u32,
+        response: &http::Response<T>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts
+            || response.status() != StatusCode::TOO_MANY_REQUESTS
+            || !(metadata.rate_limited || is_idempotent(&metadata.method))
+        {
+            return None;
+        }
+
+        match serde_json::from_slice(response.body().as_ref()) {
+            Ok(StandardErrorBody { kind: ErrorKind::LimitExceeded { retry_after_ms }, .. }) => {
+                Some(retry_after_ms.unwrap_or_else(|| self.backoff(attempt)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the delay after which a request for which no response could be obtained should be sent
+    /// again, or `None` if the request shouldn't be retried.
+    ///
+    /// `attempt` is the number of times the request has been sent already.
+    pub(super) fn delay_after_error(&self, metadata: &Metadata, attempt: u32) -> Option<Duration> {
+        (attempt < self.max_attempts && is_idempotent(&metadata.method))
+            .then(|| self.backoff(attempt))
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1_u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        let backoff = self.initial_backoff.saturating_mul(factor).min(self.max_backoff);
+
+        if self.jitter {
+            let half = backoff / 2;
+            let random = RandomState::new().build_hasher().finish();
+            let nanos = u64::try_from(half.as_nanos()).unwrap_or(u64::MAX);
+            half + Duration::from_nanos(random % nanos.saturating_add(1))
+        } else {
+            backoff
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether sending a request with the given method multiple times has the same effect as sending
+/// it once.
+///
+/// `PUT` endpoints in the client-server API use transaction IDs where necessary, which makes them
+/// idempotent.
+fn is_idempotent(method: &Method) -> bool {
+    [Method::GET, Method::HEAD, Method::PUT, Method::DELETE, Method::OPTIONS].contains(method)
+}
+
+/// Copy the parts of an `http::Request` that are set by `OutgoingRequest::try_into_http_request`.
+pub(super) fn clone_request<B: Clone>(request: &http::Request<B>) -> http::Request<B> {
+    let mut clone = http::Request::new(request.body().clone());
+    *clone.method_mut() = request.method().clone();
+    *clone.uri_mut() = request.uri().clone();
+    *clone.version_mut() = request.version();
+    *clone.headers_mut() = request.headers().clone();
+    clone
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::StatusCode;
+    use ruma_client_api::{discovery::get_supported_versions, session::login};
+    use ruma_common::api::MatrixVersion;
+
+    use super::RetryPolicy;
+    use crate::{client::mock::MockClient, Client, Error};
+
+    async fn client(responses: Vec<Result<(StatusCode, &str), ()>>) -> Client<MockClient> {
+        Client::builder()
+            .homeserver_url("https://example.com".to_owned())
+            .supported_matrix_versions(vec![MatrixVersion::V1_0])
+            .retry_policy(
+                RetryPolicy::new()
+                    .max_attempts(3)
+                    .initial_backoff(Duration::from_millis(1))
+                    .jitter(false),
+            )
+            .http_client(MockClient::new(responses))
+            .await
+            .unwrap()
+    }
+
+    const VERSIONS: &str = r#"{ "versions": ["v1.1"] }"#;
+    const LIMIT_EXCEEDED: &str = r#"{
+        "errcode": "M_LIMIT_EXCEEDED",
+        "error": "Too many requests",
+        "retry_after_ms": 1
+    }"#;
+
+    fn requests(client: &Client<MockClient>) -> usize {
+        client.0.http_client.request_count()
+    }
+
+    #[tokio::test]
+    async fn retry_after_limit_exceeded() {
+        let client = client(vec![
+            Ok((StatusCode::TOO_MANY_REQUESTS, LIMIT_EXCEEDED)),
+            Ok((StatusCode::OK, VERSIONS)),
+        ])
+        .await;
+
+        let response = client.send_request(get_supported_versions::Request::new()).await.unwrap();
+        assert_eq!(response.versions, ["v1.1"]);
+        assert_eq!(requests(&client), 2);
+    }
+
+    #[tokio::test]
+    async fn give_up_after_max_attempts() {
+        let client = client(vec![
+            Ok((StatusCode::TOO_MANY_REQUESTS, LIMIT_EXCEEDED)),
+            Ok((StatusCode::TOO_MANY_REQUESTS, LIMIT_EXCEEDED)),
+            Ok((StatusCode::TOO_MANY_REQUESTS, LIMIT_EXCEEDED)),
+        ])
+        .await;
+
+        let error = client.send_request(get_supported_versions::Request::new()).await.unwrap_err();
+        assert!(matches!(error, Error::FromHttpResponse(_)));
+        assert_eq!(requests(&client), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_idempotent_request_after_transport_error() {
+        let client = client(vec![Err(()), Ok((StatusCode::OK, VERSIONS))]).await;
+
+        client.send_request(get_supported_versions::Request::new()).await.unwrap();
+        assert_eq!(requests(&client), 2);
+    }
+
+    #[tokio::test]
+    async fn no_retry_of_non_idempotent_request_after_transport_error() {
+        let client = client(vec![Err(())]).await;
+
+        let request = login::v3::Request::new(login::v3::LoginInfo::Token(login::v3::Token::new(
+            "token".to_owned(),
+        )));
+        let error = client.send_request(request).await.unwrap_err();
+        assert!(matches!(error, Error::Response(())));
+        assert_eq!(requests(&client), 1);
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(5))
+            .jitter(false);
+
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+        assert_eq!(policy.backoff(40), Duration::from_secs(5));
+
+        let policy = policy.jitter(true);
+        for attempt in 1..5 {
+            let backoff = policy.backoff(attempt);
+            assert!(backoff >= Duration::from_millis(500) && backoff <= Duration::from_secs(5));
+        }
+    }
+}
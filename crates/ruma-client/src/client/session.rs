@@ -0,0 +1,287 @@
+use std::{
+    error::Error as StdError,
+    fmt::{self, Debug},
+};
+
+use async_trait::async_trait;
+use ruma_client_api::session::{login, refresh_token};
+use ruma_common::{OwnedDeviceId, OwnedUserId};
+use serde::{Deserialize, Serialize};
+
+/// A session with a homeserver, as obtained by logging in.
+///
+/// This can be serialized to persist it, and restored later with
+/// [`ClientBuilder::session`](super::ClientBuilder::session).
+#[derive(Clone, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Session {
+    /// The user the session belongs to.
+    pub user_id: OwnedUserId,
+
+    /// The ID of the device of the session.
+    pub device_id: OwnedDeviceId,
+
+    /// The access token used to authenticate requests.
+    pub access_token: String,
+
+    /// The token that can be used to obtain a new access token when the current one expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+impl Session {
+    /// Creates a new `Session` with the given user ID, device ID and access token.
+    pub fn new(user_id: OwnedUserId, device_id: OwnedDeviceId, access_token: String) -> Self {
+        Self { user_id, device_id, access_token, refresh_token: None }
+    }
+
+    /// Replaces the tokens of this session with the ones from the given refresh response.
+    ///
+    /// If the response doesn't contain a new refresh token, the old one is kept.
+    pub(super) fn update_from_refresh(&mut self, response: refresh_token::v3::Response) {
+        self.access_token = response.access_token;
+        if let Some(refresh_token) = response.refresh_token {
+            self.refresh_token = Some(refresh_token);
+        }
+    }
+}
+
+impl From<login::v3::Response> for Session {
+    fn from(response: login::v3::Response) -> Self {
+        Self {
+            user_id: response.user_id,
+            device_id: response.device_id,
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+        }
+    }
+}
+
+impl Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("user_id", &self.user_id)
+            .field("device_id", &self.device_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A storage backend that a [`Client`](super::Client) persists its [`Session`] through.
+///
+/// Whenever the session changes, e.g. because of a login or an access token refresh, the client
+/// calls [`save_session`](Self::save_session) so that the new tokens survive a restart.
+#[async_trait]
+pub trait SessionStore: Debug + Send + Sync {
+    /// Load the previously stored session, if any.
+    async fn load_session(&self) -> Result<Option<Session>, Box<dyn StdError + Send + Sync>>;
+
+    /// Store the given session, replacing any previously stored one.
+    async fn save_session(&self, session: &Session) -> Result<(), Box<dyn StdError + Send + Sync>>;
+
+    /// Delete the previously stored session, if any.
+    async fn delete_session(&self) -> Result<(), Box<dyn StdError + Send + Sync>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error as StdError,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use http::StatusCode;
+    use ruma_client_api::discovery::get_capabilities;
+    use ruma_common::{api::MatrixVersion, device_id, user_id};
+
+    use super::{Session, SessionStore};
+    use crate::{client::mock::MockClient, Client};
+
+    #[derive(Debug, Default)]
+    struct MemoryStore(Arc<Mutex<Option<Session>>>);
+
+    #[async_trait]
+    impl SessionStore for MemoryStore {
+        async fn load_session(&self) -> Result<Option<Session>, Box<dyn StdError + Send + Sync>> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        async fn save_session(
+            &self,
+            session: &Session,
+        ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+            *self.0.lock().unwrap() = Some(session.clone());
+            Ok(())
+        }
+
+        async fn delete_session(&self) -> Result<(), Box<dyn StdError + Send + Sync>> {
+            *self.0.lock().unwrap() = None;
+            Ok(())
+        }
+    }
+
+    const SOFT_LOGOUT: &str = r#"{
+        "errcode": "M_UNKNOWN_TOKEN",
+        "error": "Access token has expired",
+        "soft_logout": true
+    }"#;
+    const REFRESH: &str = r#"{ "access_token": "new_access", "refresh_token": "new_refresh" }"#;
+    const CAPABILITIES: &str = r#"{ "capabilities": {} }"#;
+
+    async fn client(
+        responses: Vec<Result<(StatusCode, &str), ()>>,
+        store: MemoryStore,
+    ) -> Client<MockClient> {
+        Client::builder()
+            .homeserver_url("https://example.com".to_owned())
+            .supported_matrix_versions(vec![MatrixVersion::V1_3])
+            .session_store(store)
+            .http_client(MockClient::new(responses))
+            .await
+            .unwrap()
+    }
+
+    fn session() -> Session {
+        let mut session = Session::new(
+            user_id!("@alice:example.com").to_owned(),
+            device_id!("ABCDEF").to_owned(),
+            "old_access".to_owned(),
+        );
+        session.refresh_token = Some("old_refresh".to_owned());
+        session
+    }
+
+    #[tokio::test]
+    async fn refresh_and_replay_after_soft_logout() {
+        let stored = Arc::new(Mutex::new(Some(session())));
+        let client = client(
+            vec![
+                Ok((StatusCode::UNAUTHORIZED, SOFT_LOGOUT)),
+                Ok((StatusCode::OK, REFRESH)),
+                Ok((StatusCode::OK, CAPABILITIES)),
+            ],
+            MemoryStore(stored.clone()),
+        )
+        .await;
+
+        client.send_request(get_capabilities::v3::Request::new()).await.unwrap();
+
+        let sent = client.0.http_client.sent();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0].1.as_deref(), Some("Bearer old_access"));
+        assert_eq!(sent[1].0, "/_matrix/client/v3/refresh");
+        assert_eq!(sent[2].1.as_deref(), Some("Bearer new_access"));
+
+        assert_eq!(client.access_token().as_deref(), Some("new_access"));
+        let stored = stored.lock().unwrap().clone().unwrap();
+        assert_eq!(stored.access_token, "new_access");
+        assert_eq!(stored.refresh_token.as_deref(), Some("new_refresh"));
+    }
+
+    #[tokio::test]
+    async fn no_refresh_without_refresh_token() {
+        let mut session = session();
+        session.refresh_token = None;
+        let client = client(
+            vec![Ok((StatusCode::UNAUTHORIZED, SOFT_LOGOUT))],
+            MemoryStore(Arc::new(Mutex::new(Some(session)))),
+        )
+        .await;
+
+        client.send_request(get_capabilities::v3::Request::new()).await.unwrap_err();
+        assert_eq!(client.0.http_client.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn log_in_persists_session() {
+        let store = MemoryStore::default();
+        let stored = store.0.clone();
+        let client = client(
+            vec![Ok((
+                StatusCode::OK,
+                r#"{
+                    "user_id": "@alice:example.com",
+                    "access_token": "access",
+                    "refresh_token": "refresh",
+                    "device_id": "ABCDEF"
+                }"#,
+            ))],
+            store,
+        )
+        .await;
+
+        client.log_in("alice", "secret", None, None).await.unwrap();
+
+        assert!(client.0.http_client.sent_bodies()[0].get("refresh_token").is_none());
+        let session = client.session().unwrap();
+        assert_eq!(session.user_id, "@alice:example.com");
+        assert_eq!(session.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(client.access_token().as_deref(), Some("access"));
+        assert_eq!(stored.lock().unwrap().as_ref().unwrap().device_id, "ABCDEF");
+    }
+
+    #[tokio::test]
+    async fn log_in_requests_refresh_token_if_enabled() {
+        let client = Client::builder()
+            .homeserver_url("https://example.com".to_owned())
+            .supported_matrix_versions(vec![MatrixVersion::V1_3])
+            .request_refresh_token(true)
+            .http_client(MockClient::new(vec![Ok((
+                StatusCode::OK,
+                r#"{
+                    "user_id": "@alice:example.com",
+                    "access_token": "access",
+                    "device_id": "ABCDEF"
+                }"#,
+            ))]))
+            .await
+            .unwrap();
+
+        client.log_in("alice", "secret", None, None).await.unwrap();
+        assert_eq!(client.0.http_client.sent_bodies()[0]["refresh_token"], true);
+    }
+
+    #[tokio::test]
+    async fn concurrent_soft_logouts_refresh_once() {
+        let client = client(
+            vec![
+                Ok((StatusCode::UNAUTHORIZED, SOFT_LOGOUT)),
+                Ok((StatusCode::UNAUTHORIZED, SOFT_LOGOUT)),
+                Ok((StatusCode::OK, REFRESH)),
+                Ok((StatusCode::OK, CAPABILITIES)),
+                Ok((StatusCode::OK, CAPABILITIES)),
+            ],
+            MemoryStore(Arc::new(Mutex::new(Some(session())))),
+        )
+        .await;
+
+        let (first, second) = tokio::join!(
+            client.send_request(get_capabilities::v3::Request::new()),
+            client.send_request(get_capabilities::v3::Request::new()),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        let sent = client.0.http_client.sent();
+        assert_eq!(sent.iter().filter(|(path, _)| path == "/_matrix/client/v3/refresh").count(), 1);
+        assert_eq!(sent[3].1.as_deref(), Some("Bearer new_access"));
+        assert_eq!(sent[4].1.as_deref(), Some("Bearer new_access"));
+    }
+
+    #[tokio::test]
+    async fn registration_without_device_clears_session() {
+        let stored = Arc::new(Mutex::new(Some(session())));
+        let client = client(
+            vec![Ok((StatusCode::OK, r#"{ "user_id": "@bob:example.com" }"#))],
+            MemoryStore(stored.clone()),
+        )
+        .await;
+        assert!(client.session().is_some());
+
+        client.register_user(Some("bob"), "secret").await.unwrap();
+
+        assert!(client.session().is_none());
+        assert_eq!(client.access_token(), None);
+        assert!(stored.lock().unwrap().is_none());
+    }
+}
@@ -0,0 +1,491 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use js_int::UInt;
+use ruma_client_api::sync::sync_events::v4::{
+    self, SlidingOp, SlidingSyncRoom, SyncList, SyncOp, SyncRequestList,
+};
+use ruma_common::{OwnedRoomId, RoomId};
+use tracing::warn;
+
+/// An entry of a sliding sync room list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum RoomListEntry {
+    /// The room at this position is not known, because it is outside of the requested ranges.
+    Empty,
+
+    /// The room at this position was known, but the server invalidated it.
+    ///
+    /// The room ID is kept as a hint until the position is synced again.
+    Invalidated(OwnedRoomId),
+
+    /// The room at this position is up to date.
+    Filled(OwnedRoomId),
+}
+
+impl RoomListEntry {
+    /// The ID of the room at this position, if any.
+    pub fn room_id(&self) -> Option<&RoomId> {
+        match self {
+            Self::Empty => None,
+            Self::Invalidated(room_id) | Self::Filled(room_id) => Some(room_id.as_ref()),
+        }
+    }
+}
+
+/// The changes applied by a sliding sync response.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct SlidingSyncUpdate {
+    /// The new state of the room lists that changed, by list name.
+    ///
+    /// See [`SlidingSync::list`] for the positions that are kept.
+    pub lists: BTreeMap<String, Vec<RoomListEntry>>,
+
+    /// The rooms whose data changed, with their updated data.
+    pub rooms: BTreeMap<OwnedRoomId, SlidingSyncRoom>,
+}
+
+/// The local state of a [sliding sync (MSC3575)][msc] session.
+///
+/// It keeps the `pos` token and a local copy of every configured room list, to which the list
+/// operations (`SYNC`, `INSERT`, `DELETE`, `INVALIDATE`) of each response are applied, as well as
+/// the accumulated data of the rooms the server sent.
+///
+/// This type is a cheaply clonable handle, so the ranges of the lists can be changed (e.g. as the
+/// user scrolls) while [`Client::sliding_sync`](super::Client::sliding_sync) is running. The new
+/// ranges are used from the next request on.
+///
+/// [msc]: https://github.com/matrix-org/matrix-spec-proposals/pull/3575
+#[derive(Clone, Debug, Default)]
+pub struct SlidingSync {
+    inner: Arc<Mutex<SlidingSyncState>>,
+}
+
+#[derive(Debug, Default)]
+struct SlidingSyncState {
+    pos: Option<String>,
+    lists: BTreeMap<String, RoomList>,
+    rooms: BTreeMap<OwnedRoomId, SlidingSyncRoom>,
+}
+
+#[derive(Debug)]
+struct RoomList {
+    config: SyncRequestList,
+    count: UInt,
+    entries: Vec<RoomListEntry>,
+}
+
+impl SlidingSync {
+    /// Creates a new `SlidingSync` without any lists.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a room list with the given name and configuration, or replace the configuration of an
+    /// existing one.
+    pub fn add_list(&self, name: impl Into<String>, config: SyncRequestList) {
+        let mut state = self.lock();
+        match state.lists.entry(name.into()) {
+            Entry::Occupied(mut entry) => entry.get_mut().config = config,
+            Entry::Vacant(entry) => {
+                entry.insert(RoomList { config, count: UInt::MIN, entries: Vec::new() });
+            }
+        }
+    }
+
+    /// Remove the room list with the given name.
+    ///
+    /// Returns `false` if there is no list with that name.
+    pub fn remove_list(&self, name: &str) -> bool {
+        self.lock().lists.remove(name).is_some()
+    }
+
+    /// Change the ranges of the room list with the given name.
+    ///
+    /// The ranges are inclusive on both ends. Returns `false` if there is no list with that name.
+    pub fn set_ranges(&self, name: &str, ranges: Vec<(UInt, UInt)>) -> bool {
+        match self.lock().lists.get_mut(name) {
+            Some(list) => {
+                list.config.ranges = ranges;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The current `pos` token, if a response was received already.
+    pub fn pos(&self) -> Option<String> {
+        self.lock().pos.clone()
+    }
+
+    /// The current entries of the room list with the given name.
+    ///
+    /// Only the positions up to the end of the highest requested range are kept, the total number
+    /// of rooms in the list is available with [`SlidingSync::list_count`].
+    pub fn list(&self, name: &str) -> Option<Vec<RoomListEntry>> {
+        self.lock().lists.get(name).map(|list| list.entries.clone())
+    }
+
+    /// The number of rooms in the room list with the given name, as announced by the server.
+    pub fn list_count(&self, name: &str) -> Option<UInt> {
+        self.lock().lists.get(name).map(|list| list.count)
+    }
+
+    /// The accumulated data of the room with the given ID.
+    pub fn room(&self, room_id: &RoomId) -> Option<SlidingSyncRoom> {
+        self.lock().rooms.get(room_id).cloned()
+    }
+
+    /// Build the request for the next sliding sync iteration with the given long-polling timeout.
+    pub fn request(&self, timeout: Option<Duration>) -> v4::Request {
+        let state = self.lock();
+
+        let mut request = v4::Request::new();
+        request.pos = state.pos.clone();
+        request.timeout = timeout;
+        request.lists =
+            state.lists.iter().map(|(name, list)| (name.clone(), list.config.clone())).collect();
+
+        request
+    }
+
+    /// Apply the given response to the local state.
+    ///
+    /// Returns the lists and rooms that changed.
+    pub fn update(&self, response: v4::Response) -> SlidingSyncUpdate {
+        let mut state = self.lock();
+        let mut update = SlidingSyncUpdate::default();
+
+        for (name, sync_list) in response.lists {
+            let list = match state.lists.get_mut(&name) {
+                Some(list) => list,
+                None => {
+                    warn!(list = name, "Received operations for an unknown sliding sync list");
+                    continue;
+                }
+            };
+
+            apply_sync_list(list, sync_list);
+            update.lists.insert(name, list.entries.clone());
+        }
+
+        for (room_id, room) in response.rooms {
+            let entry = state.rooms.entry(room_id.clone()).or_default();
+            merge_room(entry, room.clone());
+            update.rooms.insert(room_id, room);
+        }
+
+        state.pos = Some(response.pos);
+
+        update
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SlidingSyncState> {
+        self.inner.lock().expect("sliding sync mutex was poisoned")
+    }
+}
+
+/// Apply the operations of a list in a response to the local entries of the list.
+fn apply_sync_list(list: &mut RoomList, sync_list: SyncList) {
+    // The count comes from the server, so it is not used as is to allocate the entries.
+    let len = list
+        .config
+        .ranges
+        .iter()
+        .map(|(_, end)| to_index(*end).saturating_add(1))
+        .max()
+        .unwrap_or(0)
+        .min(to_index(sync_list.count));
+
+    list.count = sync_list.count;
+    list.entries.resize(len, RoomListEntry::Empty);
+
+    // The operations don't change the length of the entries.
+    for op in sync_list.ops {
+        apply_op(&mut list.entries, op);
+    }
+}
+
+/// Apply a single list operation to the local entries of a list.
+///
+/// Malformed operations are ignored with a warning.
+fn apply_op(entries: &mut Vec<RoomListEntry>, op: SyncOp) {
+    let range = op.range.map(|(start, end)| (to_index(start), to_index(end)));
+    let index = op.index.map(to_index);
+
+    match (op.op, range, index, op.room_id) {
+        (SlidingOp::Sync, Some((start, end)), _, _) => {
+            let window = entries.iter_mut().take(end.saturating_add(1)).skip(start);
+            for (entry, room_id) in window.zip(op.room_ids) {
+                *entry = RoomListEntry::Filled(room_id);
+            }
+        }
+        (SlidingOp::Invalidate, Some((start, end)), _, _) => {
+            for entry in entries.iter_mut().take(end.saturating_add(1)).skip(start) {
+                if let RoomListEntry::Filled(room_id) = entry {
+                    *entry = RoomListEntry::Invalidated(room_id.clone());
+                }
+            }
+        }
+        (SlidingOp::Delete, _, Some(index), _) => {
+            if index < entries.len() {
+                entries.remove(index);
+                entries.push(RoomListEntry::Empty);
+            }
+        }
+        (SlidingOp::Insert, _, Some(index), Some(room_id)) if index <= entries.len() => {
+            entries.insert(index, RoomListEntry::Filled(room_id));
+            entries.pop();
+        }
+        (op, ..) => warn!(?op, "Ignoring malformed sliding sync list operation"),
+    }
+}
+
+fn to_index(value: UInt) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
+
+/// Merge the data of a room from a response into the accumulated data of that room.
+fn merge_room(room: &mut SlidingSyncRoom, update: SlidingSyncRoom) {
+    if update.initial == Some(true) {
+        *room = update;
+        return;
+    }
+
+    let SlidingSyncRoom {
+        name,
+        initial: _,
+        is_dm,
+        invite_state,
+        unread_notifications,
+        timeline,
+        required_state,
+        prev_batch,
+        limited,
+        joined_count,
+        invited_count,
+        num_live,
+        ..
+    } = update;
+
+    if name.is_some() {
+        room.name = name;
+    }
+    if is_dm.is_some() {
+        room.is_dm = is_dm;
+    }
+    if !invite_state.is_empty() {
+        room.invite_state = invite_state;
+    }
+    if !unread_notifications.is_empty() {
+        room.unread_notifications = unread_notifications;
+    }
+    if limited {
+        room.timeline = timeline;
+        room.prev_batch = prev_batch;
+    } else {
+        room.timeline.extend(timeline);
+        if prev_batch.is_some() {
+            room.prev_batch = prev_batch;
+        }
+    }
+    room.limited = limited;
+    room.required_state.extend(required_state);
+    if joined_count.is_some() {
+        room.joined_count = joined_count;
+    }
+    if invited_count.is_some() {
+        room.invited_count = invited_count;
+    }
+    room.num_live = num_live;
+}
+
+#[cfg(test)]
+mod tests {
+    use assign::assign;
+    use http::StatusCode;
+    use js_int::{uint, UInt};
+    use ruma_client_api::sync::sync_events::v4::{self, SyncRequestList};
+    use ruma_common::{
+        api::{IncomingResponse, MatrixVersion},
+        room_id, OwnedRoomId,
+    };
+    use tokio_stream::StreamExt as _;
+
+    use super::{RoomListEntry, SlidingSync};
+    use crate::{client::mock::MockClient, Client, Error};
+
+    fn response(json: &str) -> v4::Response {
+        v4::Response::try_from_http_response(http::Response::new(json)).unwrap()
+    }
+
+    fn filled(room_id: &str) -> RoomListEntry {
+        RoomListEntry::Filled(OwnedRoomId::try_from(room_id).unwrap())
+    }
+
+    fn sliding_sync() -> SlidingSync {
+        let sliding_sync = SlidingSync::new();
+        sliding_sync.add_list(
+            "all",
+            assign!(SyncRequestList::default(), { ranges: vec![(uint!(0), uint!(2))] }),
+        );
+        sliding_sync
+    }
+
+    const INITIAL: &str = r#"{
+        "pos": "1",
+        "lists": {
+            "all": {
+                "count": 5,
+                "ops": [{
+                    "op": "SYNC",
+                    "range": [0, 2],
+                    "room_ids": ["!a:example.com", "!b:example.com", "!c:example.com"]
+                }]
+            }
+        },
+        "rooms": {
+            "!a:example.com": {
+                "name": "A",
+                "initial": true,
+                "timeline": [{ "type": "m.room.message", "content": {} }]
+            }
+        }
+    }"#;
+
+    #[test]
+    fn apply_list_operations() {
+        let sliding_sync = sliding_sync();
+
+        let update = sliding_sync.update(response(INITIAL));
+        let expected =
+            [filled("!a:example.com"), filled("!b:example.com"), filled("!c:example.com")];
+        assert_eq!(update.lists["all"], expected);
+        assert_eq!(sliding_sync.list_count("all"), Some(uint!(5)));
+        assert_eq!(sliding_sync.pos().as_deref(), Some("1"));
+
+        // !c moves to the top.
+        sliding_sync.update(response(
+            r#"{
+                "pos": "2",
+                "lists": {
+                    "all": {
+                        "count": 5,
+                        "ops": [
+                            { "op": "DELETE", "index": 2 },
+                            { "op": "INSERT", "index": 0, "room_id": "!c:example.com" }
+                        ]
+                    }
+                }
+            }"#,
+        ));
+        let expected =
+            [filled("!c:example.com"), filled("!a:example.com"), filled("!b:example.com")];
+        assert_eq!(sliding_sync.list("all").unwrap(), expected);
+
+        // Part of the window is invalidated and the list shrinks.
+        sliding_sync.update(response(
+            r#"{
+                "pos": "3",
+                "lists": {
+                    "all": {
+                        "count": 2,
+                        "ops": [
+                            { "op": "INVALIDATE", "range": [0, 1] },
+                            { "op": "SYNC", "range": [1, 1], "room_ids": ["!d:example.com"] }
+                        ]
+                    }
+                }
+            }"#,
+        ));
+        let expected = [
+            RoomListEntry::Invalidated(room_id!("!c:example.com").to_owned()),
+            filled("!d:example.com"),
+        ];
+        assert_eq!(sliding_sync.list("all").unwrap(), expected);
+        assert_eq!(expected[0].room_id(), Some(room_id!("!c:example.com")));
+    }
+
+    #[test]
+    fn huge_count_is_capped_to_the_ranges() {
+        let sliding_sync = sliding_sync();
+
+        let update = sliding_sync.update(response(
+            r#"{
+                "pos": "1",
+                "lists": {
+                    "all": {
+                        "count": 9007199254740991,
+                        "ops": [{
+                            "op": "SYNC",
+                            "range": [0, 9007199254740991],
+                            "room_ids": ["!a:example.com"]
+                        }]
+                    }
+                }
+            }"#,
+        ));
+        let expected = [filled("!a:example.com"), RoomListEntry::Empty, RoomListEntry::Empty];
+        assert_eq!(update.lists["all"], expected);
+        assert_eq!(sliding_sync.list_count("all"), Some(UInt::MAX));
+    }
+
+    #[test]
+    fn merge_room_data() {
+        let sliding_sync = sliding_sync();
+        sliding_sync.update(response(INITIAL));
+
+        let update = sliding_sync.update(response(
+            r#"{
+                "pos": "2",
+                "rooms": {
+                    "!a:example.com": {
+                        "timeline": [{ "type": "m.room.message", "content": {} }],
+                        "joined_count": 2
+                    }
+                }
+            }"#,
+        ));
+        assert!(update.lists.is_empty());
+        assert_eq!(update.rooms[room_id!("!a:example.com")].timeline.len(), 1);
+
+        let room = sliding_sync.room(room_id!("!a:example.com")).unwrap();
+        assert_eq!(room.name.as_deref(), Some("A"));
+        assert_eq!(room.timeline.len(), 2);
+        assert_eq!(room.joined_count, Some(uint!(2)));
+    }
+
+    #[tokio::test]
+    async fn stream_uses_current_ranges() {
+        let client = Client::builder()
+            .homeserver_url("https://example.com".to_owned())
+            .supported_matrix_versions(vec![MatrixVersion::V1_0])
+            .access_token(Some("access".to_owned()))
+            .http_client(MockClient::new(vec![
+                Ok((StatusCode::OK, INITIAL)),
+                Ok((StatusCode::OK, r#"{ "pos": "2" }"#)),
+                Err(()),
+            ]))
+            .await
+            .unwrap();
+        let sliding_sync = sliding_sync();
+
+        let mut stream = Box::pin(client.sliding_sync(&sliding_sync, None));
+        let update = stream.next().await.unwrap().unwrap();
+        assert_eq!(update.rooms.len(), 1);
+
+        sliding_sync.set_ranges("all", vec![(uint!(3), uint!(4))]);
+        stream.next().await.unwrap().unwrap();
+        assert!(matches!(stream.next().await.unwrap(), Err(Error::Response(()))));
+
+        let bodies = client.0.http_client.sent_bodies();
+        assert_eq!(bodies[0]["lists"]["all"]["ranges"], serde_json::json!([[0, 2]]));
+        assert_eq!(bodies[1]["lists"]["all"]["ranges"], serde_json::json!([[3, 4]]));
+        assert_eq!(sliding_sync.request(None).pos.as_deref(), Some("2"));
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    collections::BTreeMap,
+    error::Error as StdError,
+    fmt::{self, Debug},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use ruma_client_api::sync::sync_events;
+use ruma_common::{
+    events::{
+        EventContentFromType, MessageLikeEventContent, OriginalSyncMessageLikeEvent,
+        PossiblyRedactedStateEventContent, RedactContent, RedactedMessageLikeEventContent,
+        RedactedStateEventContent, StaticEventContent, StaticStateEventContent, StrippedStateEvent,
+        SyncMessageLikeEvent, SyncStateEvent,
+    },
+    presence::PresenceState,
+    serde::Raw,
+    OwnedRoomId, RoomId,
+};
+use serde_json::value::RawValue as RawJsonValue;
+
+type HandlerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type ErasedHandler = Box<dyn Fn(&RawJsonValue, &RoomId) -> Option<HandlerFuture> + Send + Sync>;
+
+/// A registry of handlers for room events, dispatched by event type.
+///
+/// Handlers are registered for a specific event content type and receive the deserialized event
+/// along with the ID of the room it was sent in, or the room the user is invited to for stripped
+/// state events. Events that can't be deserialized to the
+/// handler's event type are skipped.
+///
+/// Use it with [`Client::sync_with_handlers`](super::Client::sync_with_handlers), or call
+/// [`dispatch`](Self::dispatch) for every response of [`Client::sync`](super::Client::sync).
+///
+/// # Example
+///
+/// ```
+/// use ruma_client::EventHandlers;
+/// use ruma_common::events::room::{
+///     member::SyncRoomMemberEvent, message::OriginalSyncRoomMessageEvent,
+/// };
+///
+/// let mut handlers = EventHandlers::new();
+/// handlers
+///     .on_message_like(|event: OriginalSyncRoomMessageEvent, room_id| async move {
+///         println!("{} in {room_id}: {}", event.sender, event.content.body());
+///     })
+///     .on_state(|event: SyncRoomMemberEvent, room_id| async move {
+///         println!("The membership of {} in {room_id} changed", event.state_key());
+///     });
+/// ```
+#[derive(Default)]
+pub struct EventHandlers {
+    handlers: BTreeMap<&'static str, Vec<ErasedHandler>>,
+    stripped_state_handlers: BTreeMap<&'static str, Vec<ErasedHandler>>,
+}
+
+impl EventHandlers {
+    /// Creates an empty `EventHandlers`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a handler for message-like events with the content type `C`.
+    ///
+    /// Redacted events are not passed to the handler.
+    pub fn on_message_like<C, F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        C: MessageLikeEventContent + StaticEventContent + EventContentFromType + RedactContent,
+        C::Redacted: RedactedMessageLikeEventContent + EventContentFromType,
+        F: Fn(OriginalSyncMessageLikeEvent<C>, OwnedRoomId) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.add(C::TYPE, move |json, room_id| {
+            match serde_json::from_str::<SyncMessageLikeEvent<C>>(json.get()).ok()? {
+                SyncMessageLikeEvent::Original(event) => {
+                    Some(Box::pin(handler(event, room_id.to_owned())))
+                }
+                SyncMessageLikeEvent::Redacted(_) => None,
+            }
+        })
+    }
+
+    /// Register a handler for state events with the content type `C`.
+    ///
+    /// The handler is called for state events from the `state` as well as the `timeline` of the
+    /// sync response.
+    pub fn on_state<C, F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        C: StaticStateEventContent + StaticEventContent + EventContentFromType + RedactContent,
+        C::Redacted: RedactedStateEventContent<StateKey = C::StateKey> + EventContentFromType,
+        F: Fn(SyncStateEvent<C>, OwnedRoomId) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.add(C::TYPE, move |json, room_id| {
+            let event = serde_json::from_str::<SyncStateEvent<C>>(json.get()).ok()?;
+            Some(Box::pin(handler(event, room_id.to_owned())))
+        })
+    }
+
+    /// Register a handler for the stripped state events with the content type `C` of the rooms
+    /// the user is invited to.
+    ///
+    /// This can be used to react to invites, with a handler for `StrippedRoomMemberEvent`.
+    pub fn on_stripped_state<C, F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        C: PossiblyRedactedStateEventContent + StaticEventContent + EventContentFromType,
+        F: Fn(StrippedStateEvent<C>, OwnedRoomId) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler: ErasedHandler = Box::new(move |json, room_id| {
+            let event = serde_json::from_str::<StrippedStateEvent<C>>(json.get()).ok()?;
+            Some(Box::pin(handler(event, room_id.to_owned())))
+        });
+        self.stripped_state_handlers.entry(C::TYPE).or_default().push(handler);
+        self
+    }
+
+    fn add(
+        &mut self,
+        event_type: &'static str,
+        handler: impl Fn(&RawJsonValue, &RoomId) -> Option<HandlerFuture> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers.entry(event_type).or_default().push(Box::new(handler));
+        self
+    }
+
+    /// Call the registered handlers for the events of the joined rooms and the stripped state
+    /// events of the invited rooms in the given sync response.
+    ///
+    /// The events are handled in order, the state events of a room before its timeline events.
+    /// Each handler is awaited before the next one is called.
+    pub async fn dispatch(&self, response: &sync_events::v3::Response) {
+        for (room_id, room) in &response.rooms.join {
+            for event in &room.state.events {
+                dispatch_event(&self.handlers, event.cast_ref(), room_id).await;
+            }
+
+            for event in &room.timeline.events {
+                dispatch_event(&self.handlers, event.cast_ref(), room_id).await;
+            }
+        }
+
+        for (room_id, room) in &response.rooms.invite {
+            for event in &room.invite_state.events {
+                dispatch_event(&self.stripped_state_handlers, event.cast_ref(), room_id).await;
+            }
+        }
+    }
+}
+
+/// Call the handlers of the given map that are registered for the type of the event.
+async fn dispatch_event(
+    handlers: &BTreeMap<&'static str, Vec<ErasedHandler>>,
+    event: &Raw<()>,
+    room_id: &RoomId,
+) {
+    let event_type = match event.get_field::<String>("type") {
+        Ok(Some(event_type)) => event_type,
+        _ => return,
+    };
+
+    let handlers = match handlers.get(event_type.as_str()) {
+        Some(handlers) => handlers,
+        None => return,
+    };
+
+    for handler in handlers {
+        if let Some(future) = handler(event.json(), room_id) {
+            future.await;
+        }
+    }
+}
+
+impl Debug for EventHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventHandlers")
+            .field("event_types", &self.handlers.keys().collect::<Vec<_>>())
+            .field(
+                "stripped_state_event_types",
+                &self.stripped_state_handlers.keys().collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// A storage backend for the `next_batch` token of the sync loop.
+///
+/// Persisting the token allows to resume syncing where it stopped after a restart.
+#[async_trait]
+pub trait SyncTokenStore: Debug + Send + Sync {
+    /// Load the previously stored sync token, if any.
+    async fn load_sync_token(&self) -> Result<Option<String>, Box<dyn StdError + Send + Sync>>;
+
+    /// Store the given sync token, replacing any previously stored one.
+    async fn save_sync_token(&self, token: &str) -> Result<(), Box<dyn StdError + Send + Sync>>;
+}
+
+/// Settings for [`Client::sync_with_handlers`](super::Client::sync_with_handlers).
+#[derive(Clone, Debug)]
+pub struct SyncSettings {
+    pub(super) filter: Option<sync_events::v3::Filter>,
+    pub(super) since: Option<String>,
+    pub(super) set_presence: PresenceState,
+    pub(super) timeout: Option<Duration>,
+    pub(super) skip_initial_backlog: bool,
+    pub(super) token_store: Option<Arc<dyn SyncTokenStore>>,
+}
+
+impl SyncSettings {
+    /// Creates a new `SyncSettings` with the default settings.
+    ///
+    /// By default, no filter is used, the presence is set to online, the long-polling timeout is
+    /// 30 seconds and the initial backlog is not skipped.
+    pub fn new() -> Self {
+        Self {
+            filter: None,
+            since: None,
+            set_presence: PresenceState::Online,
+            timeout: Some(Duration::from_secs(30)),
+            skip_initial_backlog: false,
+            token_store: None,
+        }
+    }
+
+    /// Set the filter to use for the sync requests.
+    pub fn filter(self, filter: sync_events::v3::Filter) -> Self {
+        Self { filter: Some(filter), ..self }
+    }
+
+    /// Set the token to start syncing from.
+    ///
+    /// This takes precedence over the token stored in the token store.
+    pub fn since(self, since: String) -> Self {
+        Self { since: Some(since), ..self }
+    }
+
+    /// Set the presence to set for the user while syncing.
+    pub fn set_presence(self, set_presence: PresenceState) -> Self {
+        Self { set_presence, ..self }
+    }
+
+    /// Set the long-polling timeout of the sync requests.
+    pub fn timeout(self, timeout: Option<Duration>) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Set whether the events that happened before the sync loop started should be skipped when
+    /// there is no token to start syncing from.
+    pub fn skip_initial_backlog(self, skip_initial_backlog: bool) -> Self {
+        Self { skip_initial_backlog, ..self }
+    }
+
+    /// Set the store the `next_batch` token is persisted through.
+    pub fn token_store(self, token_store: impl SyncTokenStore + 'static) -> Self {
+        Self { token_store: Some(Arc::new(token_store)), ..self }
+    }
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error as StdError,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use http::StatusCode;
+    use ruma_client_api::sync::sync_events;
+    use ruma_common::{
+        api::{IncomingResponse, MatrixVersion},
+        events::room::{
+            member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
+            message::OriginalSyncRoomMessageEvent,
+        },
+    };
+
+    use super::{EventHandlers, SyncSettings, SyncTokenStore};
+    use crate::{client::mock::MockClient, Client, Error};
+
+    #[derive(Debug, Default)]
+    struct MemoryStore(Arc<Mutex<Option<String>>>);
+
+    #[async_trait]
+    impl SyncTokenStore for MemoryStore {
+        async fn load_sync_token(&self) -> Result<Option<String>, Box<dyn StdError + Send + Sync>> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        async fn save_sync_token(
+            &self,
+            token: &str,
+        ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+            *self.0.lock().unwrap() = Some(token.to_owned());
+            Ok(())
+        }
+    }
+
+    const SYNC: &str = r#"{
+        "next_batch": "s2",
+        "rooms": {
+            "join": {
+                "!room:example.com": {
+                    "state": {
+                        "events": [{
+                            "type": "m.room.member",
+                            "event_id": "$member",
+                            "sender": "@alice:example.com",
+                            "state_key": "@alice:example.com",
+                            "origin_server_ts": 1,
+                            "content": { "membership": "join" }
+                        }]
+                    },
+                    "timeline": {
+                        "events": [
+                            {
+                                "type": "m.room.message",
+                                "event_id": "$message",
+                                "sender": "@alice:example.com",
+                                "origin_server_ts": 2,
+                                "content": { "msgtype": "m.text", "body": "Hello" }
+                            },
+                            {
+                                "type": "m.room.message",
+                                "event_id": "$redacted",
+                                "sender": "@alice:example.com",
+                                "origin_server_ts": 3,
+                                "content": {},
+                                "unsigned": {
+                                    "redacted_because": {
+                                        "type": "m.room.redaction",
+                                        "event_id": "$redaction",
+                                        "sender": "@alice:example.com",
+                                        "origin_server_ts": 4,
+                                        "redacts": "$redacted",
+                                        "content": {}
+                                    }
+                                }
+                            },
+                            {
+                                "type": "m.room.message",
+                                "event_id": "$invalid",
+                                "sender": "@alice:example.com",
+                                "origin_server_ts": 5,
+                                "content": { "body": "no msgtype" }
+                            }
+                        ]
+                    }
+                }
+            },
+            "invite": {
+                "!invite:example.com": {
+                    "invite_state": {
+                        "events": [{
+                            "type": "m.room.member",
+                            "sender": "@alice:example.com",
+                            "state_key": "@bob:example.com",
+                            "content": { "membership": "invite" }
+                        }]
+                    }
+                }
+            }
+        }
+    }"#;
+
+    fn handlers(calls: &Arc<Mutex<Vec<String>>>) -> EventHandlers {
+        let mut handlers = EventHandlers::new();
+        let message_calls = calls.clone();
+        let member_calls = calls.clone();
+        let invite_calls = calls.clone();
+        handlers
+            .on_message_like(move |event: OriginalSyncRoomMessageEvent, room_id| {
+                let calls = message_calls.clone();
+                async move {
+                    calls.lock().unwrap().push(format!("{room_id} {}", event.content.body()));
+                }
+            })
+            .on_state(move |event: SyncRoomMemberEvent, room_id| {
+                let calls = member_calls.clone();
+                async move {
+                    calls.lock().unwrap().push(format!("{room_id} {}", event.state_key()));
+                }
+            })
+            .on_stripped_state(move |event: StrippedRoomMemberEvent, room_id| {
+                let calls = invite_calls.clone();
+                async move {
+                    calls.lock().unwrap().push(format!("{room_id} invites {}", event.state_key));
+                }
+            });
+        handlers
+    }
+
+    #[tokio::test]
+    async fn dispatch() {
+        let calls = Arc::default();
+        let handlers = handlers(&calls);
+
+        let response =
+            sync_events::v3::Response::try_from_http_response(http::Response::new(SYNC)).unwrap();
+        handlers.dispatch(&response).await;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                "!room:example.com @alice:example.com",
+                "!room:example.com Hello",
+                "!invite:example.com invites @bob:example.com"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_loop_skips_backlog_and_persists_token() {
+        let client = Client::builder()
+            .homeserver_url("https://example.com".to_owned())
+            .supported_matrix_versions(vec![MatrixVersion::V1_0])
+            .access_token(Some("access".to_owned()))
+            .http_client(MockClient::new(vec![
+                Ok((StatusCode::OK, r#"{ "next_batch": "s1" }"#)),
+                Ok((StatusCode::OK, SYNC)),
+                Err(()),
+            ]))
+            .await
+            .unwrap();
+        let calls = Arc::default();
+        let store = MemoryStore::default();
+        let stored = store.0.clone();
+
+        let error = client
+            .sync_with_handlers(
+                &handlers(&calls),
+                SyncSettings::new().skip_initial_backlog(true).token_store(store),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, Error::Response(())));
+        assert_eq!(calls.lock().unwrap().len(), 3);
+        assert_eq!(stored.lock().unwrap().as_deref(), Some("s2"));
+    }
+}
@@ -0,0 +1,319 @@
+use async_trait::async_trait;
+use ruma_client_api::uiaa::{
+    AuthData, AuthFlow, AuthType, Dummy, EmailIdentity, Msisdn, Password, ReCaptcha,
+    RegistrationToken, UiaaInfo,
+};
+use serde::Deserialize;
+
+/// The maximum number of requests sent by
+/// [`Client::send_request_with_uiaa`](super::Client::send_request_with_uiaa).
+///
+/// It stops the authentication if the homeserver keeps rejecting the stages, for example the
+/// `m.login.dummy` stages that are completed automatically.
+pub(super) const MAX_ATTEMPTS: usize = 16;
+
+/// A handler that completes the stages of [User-Interactive Authentication][uiaa].
+///
+/// It is used by [`Client::send_request_with_uiaa`](super::Client::send_request_with_uiaa),
+/// which asks the handler for the authentication data of each stage of the chosen
+/// [`AuthFlow`] until the request succeeds.
+///
+/// The methods for the individual stages are called again if the homeserver didn't accept the
+/// previous attempt, in which case [`UiaaInfo::auth_error`] is usually set. Returning `None` from
+/// any of them aborts the authentication, and the last `UiaaInfo` is returned as an error.
+///
+/// `m.login.dummy` stages are completed automatically.
+///
+/// [uiaa]: https://spec.matrix.org/latest/client-server-api/#user-interactive-authentication-api
+#[async_trait]
+pub trait UiaaHandler: Send + Sync {
+    /// Whether this handler can complete stages of the given type.
+    ///
+    /// By default, only `m.login.dummy` is supported.
+    fn supports_stage(&self, stage: &AuthType) -> bool {
+        *stage == AuthType::Dummy
+    }
+
+    /// Choose the flow to complete among the given ones.
+    ///
+    /// Only flows that are compatible with the stages completed so far are passed to this method.
+    ///
+    /// By default, the first flow whose stages are all supported according to
+    /// [`supports_stage`](Self::supports_stage) is chosen.
+    fn select_flow<'a>(&self, flows: &[&'a AuthFlow]) -> Option<&'a AuthFlow> {
+        flows
+            .iter()
+            .copied()
+            .find(|flow| flow.stages.iter().all(|stage| self.supports_stage(stage)))
+    }
+
+    /// Get the data to complete an `m.login.password` stage.
+    async fn password(&self, _info: &UiaaInfo) -> Option<Password> {
+        None
+    }
+
+    /// Get the data to complete an `m.login.recaptcha` stage.
+    ///
+    /// `public_key` is the reCAPTCHA public key the homeserver advertised, if any.
+    async fn recaptcha(&self, _info: &UiaaInfo, _public_key: Option<&str>) -> Option<ReCaptcha> {
+        None
+    }
+
+    /// Get the data to complete an `m.login.email.identity` stage.
+    ///
+    /// The email address must have been validated with one of the `requestToken` endpoints
+    /// beforehand.
+    async fn email_identity(&self, _info: &UiaaInfo) -> Option<EmailIdentity> {
+        None
+    }
+
+    /// Get the data to complete an `m.login.msisdn` stage.
+    ///
+    /// The phone number must have been validated with one of the `requestToken` endpoints
+    /// beforehand.
+    async fn msisdn(&self, _info: &UiaaInfo) -> Option<Msisdn> {
+        None
+    }
+
+    /// Get the data to complete an `m.login.registration_token` stage.
+    async fn registration_token(&self, _info: &UiaaInfo) -> Option<RegistrationToken> {
+        None
+    }
+
+    /// Complete a stage that has no dedicated method, like `m.login.sso`, through the
+    /// [fallback] web page.
+    ///
+    /// Should return `true` once the user completed the stage in the fallback page.
+    ///
+    /// [fallback]: https://spec.matrix.org/latest/client-server-api/#fallback
+    async fn fallback(&self, _info: &UiaaInfo, _stage: &AuthType) -> bool {
+        false
+    }
+}
+
+/// Get the authentication data for the next stage of the UIAA session described by `info`, or
+/// `None` if the authentication can't be completed.
+pub(super) async fn next_auth_data<H>(handler: &H, info: &UiaaInfo) -> Option<AuthData>
+where
+    H: UiaaHandler + ?Sized,
+{
+    let compatible_flows: Vec<_> =
+        info.flows.iter().filter(|flow| flow.stages.starts_with(&info.completed)).collect();
+    let flow = handler.select_flow(&compatible_flows)?;
+    let stage = flow.stages.get(info.completed.len())?;
+    let session = info.session.clone();
+
+    let auth_data = match stage {
+        AuthType::Password => {
+            let mut password = handler.password(info).await?;
+            password.session = session;
+            AuthData::Password(password)
+        }
+        AuthType::ReCaptcha => {
+            let public_key = recaptcha_public_key(info);
+            let mut recaptcha = handler.recaptcha(info, public_key.as_deref()).await?;
+            recaptcha.session = session;
+            AuthData::ReCaptcha(recaptcha)
+        }
+        AuthType::EmailIdentity => {
+            let mut email_identity = handler.email_identity(info).await?;
+            email_identity.session = session;
+            AuthData::EmailIdentity(email_identity)
+        }
+        AuthType::Msisdn => {
+            let mut msisdn = handler.msisdn(info).await?;
+            msisdn.session = session;
+            AuthData::Msisdn(msisdn)
+        }
+        AuthType::Dummy => {
+            let mut dummy = Dummy::new();
+            dummy.session = session;
+            AuthData::Dummy(dummy)
+        }
+        AuthType::RegistrationToken => {
+            let mut token = handler.registration_token(info).await?;
+            token.session = session;
+            AuthData::RegistrationToken(token)
+        }
+        _ => {
+            if !handler.fallback(info, stage).await {
+                return None;
+            }
+            AuthData::fallback_acknowledgement(session?)
+        }
+    };
+
+    Some(auth_data)
+}
+
+/// Get the reCAPTCHA public key from the parameters of the given `UiaaInfo`.
+fn recaptcha_public_key(info: &UiaaInfo) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Params {
+        #[serde(rename = "m.login.recaptcha")]
+        recaptcha: Option<RecaptchaParams>,
+    }
+
+    #[derive(Deserialize)]
+    struct RecaptchaParams {
+        public_key: String,
+    }
+
+    let params: Params = serde_json::from_str(info.params.get()).ok()?;
+    Some(params.recaptcha?.public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use http::StatusCode;
+    use ruma_client_api::{
+        account::register,
+        uiaa::{AuthType, Password, UiaaInfo, UiaaResponse, UserIdentifier},
+    };
+    use ruma_common::api::{error::FromHttpResponseError, MatrixVersion};
+
+    use super::{UiaaHandler, MAX_ATTEMPTS};
+    use crate::{client::mock::MockClient, Client, Error};
+
+    struct PasswordHandler {
+        passwords: Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait]
+    impl UiaaHandler for PasswordHandler {
+        fn supports_stage(&self, stage: &AuthType) -> bool {
+            matches!(stage, AuthType::Dummy | AuthType::Password)
+        }
+
+        async fn password(&self, _info: &UiaaInfo) -> Option<Password> {
+            let password = self.passwords.lock().unwrap().pop()?;
+            Some(Password::new(
+                UserIdentifier::UserIdOrLocalpart("alice".to_owned()),
+                password.to_owned(),
+            ))
+        }
+    }
+
+    async fn client(responses: Vec<Result<(StatusCode, &str), ()>>) -> Client<MockClient> {
+        Client::builder()
+            .homeserver_url("https://example.com".to_owned())
+            .supported_matrix_versions(vec![MatrixVersion::V1_0])
+            .http_client(MockClient::new(responses))
+            .await
+            .unwrap()
+    }
+
+    fn register(auth: Option<ruma_client_api::uiaa::AuthData>) -> register::v3::Request {
+        let mut request = register::v3::Request::new();
+        request.auth = auth;
+        request
+    }
+
+    const FLOWS: &str = r#"{
+        "flows": [
+            { "stages": ["m.login.recaptcha"] },
+            { "stages": ["m.login.password", "m.login.dummy"] }
+        ],
+        "params": {},
+        "session": "xyz"
+    }"#;
+    const PASSWORD_COMPLETED: &str = r#"{
+        "flows": [
+            { "stages": ["m.login.recaptcha"] },
+            { "stages": ["m.login.password", "m.login.dummy"] }
+        ],
+        "completed": ["m.login.password"],
+        "params": {},
+        "session": "xyz"
+    }"#;
+    const WRONG_PASSWORD: &str = r#"{
+        "errcode": "M_FORBIDDEN",
+        "error": "Invalid password",
+        "flows": [{ "stages": ["m.login.password"] }],
+        "params": {},
+        "session": "xyz"
+    }"#;
+    const REGISTERED: &str = r#"{ "user_id": "@alice:example.com" }"#;
+
+    #[tokio::test]
+    async fn complete_flow() {
+        let client = client(vec![
+            Ok((StatusCode::UNAUTHORIZED, FLOWS)),
+            Ok((StatusCode::UNAUTHORIZED, PASSWORD_COMPLETED)),
+            Ok((StatusCode::OK, REGISTERED)),
+        ])
+        .await;
+        let handler = PasswordHandler { passwords: Mutex::new(vec!["secret"]) };
+
+        let mut auth_types = Vec::new();
+        let response = client
+            .send_request_with_uiaa(
+                |auth| {
+                    auth_types.push(auth.as_ref().and_then(|auth| auth.auth_type()));
+                    assert!(auth.as_ref().map_or(true, |auth| auth.session() == Some("xyz")));
+                    register(auth)
+                },
+                &handler,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.user_id, "@alice:example.com");
+        assert_eq!(auth_types, [None, Some(AuthType::Password), Some(AuthType::Dummy)]);
+    }
+
+    #[tokio::test]
+    async fn retry_stage_then_abort() {
+        let client = client(vec![
+            Ok((StatusCode::UNAUTHORIZED, WRONG_PASSWORD)),
+            Ok((StatusCode::UNAUTHORIZED, WRONG_PASSWORD)),
+        ])
+        .await;
+        let handler = PasswordHandler { passwords: Mutex::new(vec!["wrong"]) };
+
+        let error = client.send_request_with_uiaa(register, &handler).await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::FromHttpResponse(FromHttpResponseError::Server(UiaaResponse::AuthResponse(
+                UiaaInfo { auth_error: Some(_), .. }
+            )))
+        ));
+        assert_eq!(client.0.http_client.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn stop_after_max_attempts() {
+        const DUMMY: &str = r#"{
+            "flows": [{ "stages": ["m.login.dummy"] }],
+            "params": {},
+            "session": "xyz"
+        }"#;
+        let client = client(vec![Ok((StatusCode::UNAUTHORIZED, DUMMY)); MAX_ATTEMPTS]).await;
+        let handler = PasswordHandler { passwords: Mutex::new(vec![]) };
+
+        let error = client.send_request_with_uiaa(register, &handler).await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::FromHttpResponse(FromHttpResponseError::Server(UiaaResponse::AuthResponse(_)))
+        ));
+        assert_eq!(client.0.http_client.request_count(), MAX_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn no_supported_flow() {
+        let client = client(vec![Ok((
+            StatusCode::UNAUTHORIZED,
+            r#"{ "flows": [{ "stages": ["m.login.sso"] }], "params": {} }"#,
+        ))])
+        .await;
+        let handler = PasswordHandler { passwords: Mutex::new(vec![]) };
+
+        client.send_request_with_uiaa(register, &handler).await.unwrap_err();
+        assert_eq!(client.0.http_client.request_count(), 1);
+    }
+}
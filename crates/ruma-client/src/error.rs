@@ -1,6 +1,9 @@
 //! Error conditions.
 
-use std::fmt::{self, Debug, Display, Formatter};
+use std::{
+    error::Error as StdError,
+    fmt::{self, Debug, Display, Formatter},
+};
 
 use ruma_common::api::error::{FromHttpResponseError, IntoHttpError};
 
@@ -22,6 +25,13 @@ pub enum Error<E, F> {
 
     /// Converting the HTTP response to one of ruma's types failed.
     FromHttpResponse(FromHttpResponseError<F>),
+
+    /// Loading or saving data through a store, like the `SessionStore`, failed.
+    Store(Box<dyn StdError + Send + Sync>),
+
+    /// Discovering the homeserver from a server name failed.
+    #[cfg(feature = "client-api")]
+    Discovery(crate::DiscoveryError<E>),
 }
 
 impl<E: Display, F: Display> Display for Error<E, F> {
@@ -34,6 +44,9 @@ impl<E: Display, F: Display> Display for Error<E, F> {
             Self::Url(err) => write!(f, "Invalid URL: {err}"),
             Self::Response(err) => write!(f, "Couldn't obtain a response: {err}"),
             Self::FromHttpResponse(err) => write!(f, "HTTP response conversion failed: {err}"),
+            Self::Store(err) => write!(f, "Store operation failed: {err}"),
+            #[cfg(feature = "client-api")]
+            Self::Discovery(err) => write!(f, "Homeserver discovery failed: {err}"),
         }
     }
 }
@@ -64,4 +77,4 @@ impl<E, F> From<FromHttpResponseError<F>> for Error<E, F> {
     }
 }
 
-impl<E: Debug + Display, F: Debug + Display> std::error::Error for Error<E, F> {}
+impl<E: Debug + Display, F: Debug + Display> StdError for Error<E, F> {}
@@ -34,7 +34,9 @@ pub use self::reqwest::Reqwest;
 #[async_trait]
 pub trait HttpClient: Sync {
     /// The type to use for `try_into_http_request`.
-    type RequestBody: Default + BufMut + Send;
+    ///
+    /// It must be `Clone` so that requests can be retried.
+    type RequestBody: Clone + Default + BufMut + Send;
 
     /// The type to use for `try_from_http_response`.
     type ResponseBody: AsRef<[u8]>;
@@ -116,7 +116,12 @@ mod error;
 pub mod http_client;
 
 #[cfg(feature = "client-api")]
-pub use self::client::{Client, ClientBuilder};
+pub use self::client::{
+    Client, ClientBuilder, DiscoveryError, EventHandlers, RetryPolicy, Session, SessionStore,
+    SyncSettings, SyncTokenStore, UiaaHandler,
+};
+#[cfg(feature = "unstable-msc3575")]
+pub use self::client::{RoomListEntry, SlidingSync, SlidingSyncUpdate};
 pub use self::{
     error::Error,
     http_client::{DefaultConstructibleHttpClient, HttpClient, HttpClientExt},
@@ -143,16 +148,13 @@ where
     R: OutgoingRequest,
     F: FnOnce(&mut http::Request<C::RequestBody>) -> Result<(), ResponseError<C, R>>,
 {
-    let http_req =
-        info_span!("serialize_request", request_type = type_name::<R>()).in_scope(move || {
-            request
-                .try_into_http_request(homeserver_url, send_access_token, for_versions)
-                .map_err(ResponseError::<C, R>::from)
-                .and_then(|mut req| {
-                    customize(&mut req)?;
-                    Ok(req)
-                })
-        });
+    let http_req = serialize_request::<C, R, F>(
+        homeserver_url,
+        send_access_token,
+        for_versions,
+        request,
+        customize,
+    );
 
     let send_span = info_span!(
         "send_request",
@@ -168,16 +170,47 @@ where
             .await
             .map_err(Error::Response)?;
 
-        let res =
-            info_span!("deserialize_response", response_type = type_name::<R::IncomingResponse>())
-                .in_scope(move || {
-                    ruma_common::api::IncomingResponse::try_from_http_response(http_res)
-                })?;
-
-        Ok(res)
+        deserialize_response::<C, R>(http_res)
     }
 }
 
+fn serialize_request<C, R, F>(
+    homeserver_url: &str,
+    send_access_token: SendAccessToken<'_>,
+    for_versions: &[MatrixVersion],
+    request: R,
+    customize: F,
+) -> Result<http::Request<C::RequestBody>, ResponseError<C, R>>
+where
+    C: HttpClient + ?Sized,
+    R: OutgoingRequest,
+    F: FnOnce(&mut http::Request<C::RequestBody>) -> Result<(), ResponseError<C, R>>,
+{
+    info_span!("serialize_request", request_type = type_name::<R>()).in_scope(move || {
+        request
+            .try_into_http_request(homeserver_url, send_access_token, for_versions)
+            .map_err(ResponseError::<C, R>::from)
+            .and_then(|mut req| {
+                customize(&mut req)?;
+                Ok(req)
+            })
+    })
+}
+
+fn deserialize_response<C, R>(http_res: http::Response<C::ResponseBody>) -> ResponseResult<C, R>
+where
+    C: HttpClient + ?Sized,
+    R: OutgoingRequest,
+{
+    let res =
+        info_span!("deserialize_response", response_type = type_name::<R::IncomingResponse>())
+            .in_scope(move || {
+                ruma_common::api::IncomingResponse::try_from_http_response(http_res)
+            })?;
+
+    Ok(res)
+}
+
 fn add_user_id_to_query<C: HttpClient + ?Sized, R: OutgoingRequest>(
     user_id: &UserId,
 ) -> impl FnOnce(&mut http::Request<C::RequestBody>) -> Result<(), ResponseError<C, R>> + '_ {
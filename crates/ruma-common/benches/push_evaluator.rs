@@ -0,0 +1,109 @@
+// `cargo bench` works, but if you use `cargo bench -- --save-baseline <name>`
+// or pass any other args to it, it fails with the error
+// `cargo bench unknown option --save-baseline`.
+// To pass args to criterion, use this form
+// `cargo bench --features criterion --bench <name of the bench> -- --save-baseline <name>`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use js_int::{int, uint};
+use ruma_common::{
+    power_levels::NotificationPowerLevels,
+    push::{
+        Action, FlattenedJson, NewPatternedPushRule, NewPushRule, PushConditionRoomCtx,
+        PushEvaluator, Ruleset,
+    },
+    room_id,
+    serde::Raw,
+    OwnedUserId,
+};
+use serde_json::json;
+
+/// The number of local members of the room the event is sent to.
+const RECIPIENTS: usize = 100;
+
+fn message() -> Raw<serde_json::Value> {
+    Raw::new(&json!({
+        "content": {
+            "body": "Has anyone seen the latest release notes? The new push rules look great",
+            "msgtype": "m.text"
+        },
+        "event_id": "$15139375512JaHAW:localhost",
+        "origin_server_ts": 45,
+        "room_id": "!room:localhost",
+        "sender": "@example:localhost",
+        "type": "m.room.message",
+        "unsigned": {
+            "age": 45
+        }
+    }))
+    .unwrap()
+}
+
+fn recipients() -> Vec<(Ruleset, PushConditionRoomCtx)> {
+    (0..RECIPIENTS)
+        .map(|i| {
+            let user_id: OwnedUserId = format!("@user{i}:localhost").try_into().unwrap();
+            let mut ruleset = Ruleset::server_default(&user_id);
+            ruleset
+                .insert(
+                    NewPushRule::Content(NewPatternedPushRule::new(
+                        "release".to_owned(),
+                        "releas*".to_owned(),
+                        vec![Action::Notify],
+                    )),
+                    None,
+                    None,
+                )
+                .unwrap();
+
+            let context = PushConditionRoomCtx {
+                room_id: room_id!("!room:localhost").to_owned(),
+                member_count: uint!(100),
+                user_display_name: format!("User {i}"),
+                user_id,
+                users_power_levels: Default::default(),
+                default_power_level: int!(0),
+                notification_power_levels: NotificationPowerLevels::new(),
+                #[cfg(feature = "unstable-msc3931")]
+                supported_features: Default::default(),
+            };
+
+            (ruleset, context)
+        })
+        .collect()
+}
+
+fn ruleset_get_actions(c: &mut Criterion) {
+    let event = message();
+    let recipients = recipients();
+
+    c.bench_function("get actions for all recipients with `Ruleset`", |b| {
+        b.iter(|| {
+            for (ruleset, context) in &recipients {
+                let _ = ruleset.get_actions(&event, context);
+            }
+        });
+    });
+}
+
+fn push_evaluator_get_actions(c: &mut Criterion) {
+    let event = message();
+    let recipients: Vec<_> = recipients()
+        .into_iter()
+        .map(|(ruleset, context)| (PushEvaluator::new(ruleset), context))
+        .collect();
+
+    c.bench_function("get actions for all recipients with `PushEvaluator`", |b| {
+        b.iter(|| {
+            let event = FlattenedJson::from_raw(&event);
+
+            for (evaluator, context) in &recipients {
+                let _ = evaluator.get_actions(&event, context);
+            }
+        });
+    });
+}
+
+criterion_group!(benches, ruleset_get_actions, push_evaluator_get_actions);
+
+criterion_main!(benches);
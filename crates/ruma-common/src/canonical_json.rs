@@ -7,12 +7,12 @@ use serde_json::Value as JsonValue;
 
 mod value;
 
-use crate::RoomVersionId;
 #[cfg(feature = "events")]
 use crate::{
     events::room::redaction::{OriginalRoomRedactionEvent, OriginalSyncRoomRedactionEvent},
     serde::Raw,
 };
+use crate::{room_version_rules::RedactionRules, RoomVersionId};
 
 pub use self::value::{CanonicalJsonObject, CanonicalJsonValue};
 
@@ -57,6 +57,12 @@ pub enum RedactionError {
 
     /// The given required field is missing from a JSON object.
     JsonFieldMissingFromObject(String),
+
+    /// The rules of the room version are unknown.
+    ///
+    /// The rules of custom room versions can be registered with
+    /// [`RoomVersionRules::register()`](crate::room_version_rules::RoomVersionRules::register).
+    UnsupportedRoomVersion(RoomVersionId),
 }
 
 impl fmt::Display for RedactionError {
@@ -68,6 +74,9 @@ impl fmt::Display for RedactionError {
             RedactionError::JsonFieldMissingFromObject(field) => {
                 write!(f, "JSON object must contain the field {field:?}")
             }
+            RedactionError::UnsupportedRoomVersion(version) => {
+                write!(f, "room version {version} is not supported")
+            }
         }
     }
 }
@@ -175,6 +184,7 @@ impl TryFrom<&Raw<OriginalSyncRoomRedactionEvent>> for RedactedBecause {
 /// * `object` contains a field called `hashes` that is not a JSON object.
 /// * `object` contains a field called `signatures` that is not a JSON object.
 /// * `object` is missing the `type` field or the field is not a JSON string.
+/// * The rules of `version` are unknown.
 pub fn redact(
     mut object: CanonicalJsonObject,
     version: &RoomVersionId,
@@ -194,12 +204,12 @@ pub fn redact_in_place(
     version: &RoomVersionId,
     redacted_because: Option<RedactedBecause>,
 ) -> Result<(), RedactionError> {
-    // Get the content keys here even if they're only needed inside the branch below, because we
-    // can't teach rust that this is a disjoint borrow with `get_mut("content")`.
-    let allowed_content_keys: &[&str] = match event.get("type") {
-        Some(CanonicalJsonValue::String(event_type)) => {
-            allowed_content_keys_for(event_type, version)
-        }
+    let rules = redaction_rules(version)?;
+
+    // Clone the event type because we can't teach rust that this is a disjoint borrow with
+    // `get_mut("content")`.
+    let event_type = match event.get("type") {
+        Some(CanonicalJsonValue::String(event_type)) => event_type.clone(),
         Some(_) => return Err(RedactionError::not_of_type("type", JsonType::String)),
         None => return Err(RedactionError::field_missing_from_object("type")),
     };
@@ -210,12 +220,18 @@ pub fn redact_in_place(
             _ => return Err(RedactionError::not_of_type("content", JsonType::Object)),
         };
 
-        object_retain_keys(content, allowed_content_keys);
+        redact_content(content, &event_type, &rules);
     }
 
     let mut old_event = mem::take(event);
 
     for &key in ALLOWED_KEYS {
+        if !rules.keep_origin_membership_prev_state
+            && matches!(key, "origin" | "membership" | "prev_state")
+        {
+            continue;
+        }
+
         if let Some(value) = old_event.remove(key) {
             event.insert(key.to_owned(), value);
         }
@@ -235,12 +251,44 @@ pub fn redact_in_place(
 /// Redacts event content using the rules specified in the Matrix client-server specification.
 ///
 /// Edits the `object` in-place.
+///
+/// Returns an error if the rules of `version` are unknown.
 pub fn redact_content_in_place(
     object: &mut CanonicalJsonObject,
     version: &RoomVersionId,
     event_type: impl AsRef<str>,
-) {
-    object_retain_keys(object, allowed_content_keys_for(event_type.as_ref(), version));
+) -> Result<(), RedactionError> {
+    let rules = redaction_rules(version)?;
+    redact_content(object, event_type.as_ref(), &rules);
+    Ok(())
+}
+
+fn redact_content(content: &mut CanonicalJsonObject, event_type: &str, rules: &RedactionRules) {
+    if event_type == "m.room.create" && rules.keep_room_create_content {
+        return;
+    }
+
+    object_retain_keys(content, allowed_content_keys_for(event_type, rules));
+
+    // Only the `signed` field of `third_party_invite` is kept.
+    if event_type == "m.room.member" {
+        match content.get_mut("third_party_invite") {
+            Some(CanonicalJsonValue::Object(third_party_invite)) => {
+                object_retain_keys(third_party_invite, &["signed"]);
+            }
+            Some(_) => {
+                content.remove("third_party_invite");
+            }
+            None => {}
+        }
+    }
+}
+
+fn redaction_rules(version: &RoomVersionId) -> Result<RedactionRules, RedactionError> {
+    version
+        .rules()
+        .map(|rules| rules.redaction)
+        .ok_or_else(|| RedactionError::UnsupportedRoomVersion(version.clone()))
 }
 
 fn object_retain_keys(object: &mut CanonicalJsonObject, keys: &[&str]) {
@@ -272,19 +320,33 @@ static ALLOWED_KEYS: &[&str] = &[
     "membership",
 ];
 
-fn allowed_content_keys_for(event_type: &str, version: &RoomVersionId) -> &'static [&'static str] {
+fn allowed_content_keys_for(event_type: &str, rules: &RedactionRules) -> &'static [&'static str] {
     match event_type {
-        "m.room.member" => match version {
-            RoomVersionId::V9 | RoomVersionId::V10 => {
-                &["membership", "join_authorised_via_users_server"]
+        "m.room.member" => match (
+            rules.keep_room_member_join_authorised_via_users_server,
+            rules.keep_room_member_third_party_invite_signed,
+        ) {
+            (false, false) => &["membership"],
+            (true, false) => &["membership", "join_authorised_via_users_server"],
+            (false, true) => &["membership", "third_party_invite"],
+            (true, true) => {
+                &["membership", "join_authorised_via_users_server", "third_party_invite"]
             }
-            _ => &["membership"],
         },
         "m.room.create" => &["creator"],
-        "m.room.join_rules" => match version {
-            RoomVersionId::V8 | RoomVersionId::V9 | RoomVersionId::V10 => &["join_rule", "allow"],
-            _ => &["join_rule"],
-        },
+        "m.room.join_rules" if rules.keep_room_join_rules_allow => &["join_rule", "allow"],
+        "m.room.join_rules" => &["join_rule"],
+        "m.room.power_levels" if rules.keep_room_power_levels_invite => &[
+            "ban",
+            "events",
+            "events_default",
+            "invite",
+            "kick",
+            "redact",
+            "state_default",
+            "users",
+            "users_default",
+        ],
         "m.room.power_levels" => &[
             "ban",
             "events",
@@ -295,21 +357,12 @@ fn allowed_content_keys_for(event_type: &str, version: &RoomVersionId) -> &'stat
             "users",
             "users_default",
         ],
-        "m.room.aliases" => match version {
-            RoomVersionId::V1
-            | RoomVersionId::V2
-            | RoomVersionId::V3
-            | RoomVersionId::V4
-            | RoomVersionId::V5 => &["aliases"],
-            // All other room versions, including custom ones, are treated by version 6 rules.
-            // TODO: Should we return an error for unknown versions instead?
-            _ => &[],
-        },
-        #[cfg(feature = "unstable-msc2870")]
-        "m.room.server_acl" if version.as_str() == "org.matrix.msc2870" => {
+        "m.room.aliases" if rules.keep_room_aliases_aliases => &["aliases"],
+        "m.room.server_acl" if rules.keep_room_server_acl_allow_deny_allow_ip_literals => {
             &["allow", "deny", "allow_ip_literals"]
         }
         "m.room.history_visibility" => &["history_visibility"],
+        "m.room.redaction" if rules.keep_room_redaction_redacts => &["redacts"],
         _ => &[],
     }
 }
@@ -318,10 +371,15 @@ fn allowed_content_keys_for(event_type: &str, version: &RoomVersionId) -> &'stat
 mod tests {
     use std::collections::BTreeMap;
 
+    use assert_matches::assert_matches;
     use js_int::int;
     use serde_json::{from_str as from_json_str, json, to_string as to_json_string};
 
-    use super::{to_canonical_value, try_from_json_map, value::CanonicalJsonValue};
+    use super::{
+        redact, to_canonical_value, try_from_json_map, value::CanonicalJsonValue,
+        CanonicalJsonObject, RedactionError,
+    };
+    use crate::{room_version_rules::RoomVersionRules, RoomVersionId};
 
     #[test]
     fn serialize_canon() {
@@ -411,4 +469,83 @@ mod tests {
 
         assert_eq!(to_canonical_value(t).unwrap(), CanonicalJsonValue::Object(expected));
     }
+
+    #[test]
+    fn redact_with_custom_room_version_rules() {
+        let room_version = RoomVersionId::try_from("org.example.redaction").unwrap();
+        let event = || {
+            try_from_json_map(
+                json!({
+                    "type": "m.room.join_rules",
+                    "content": {
+                        "join_rule": "restricted",
+                        "allow": [],
+                    },
+                })
+                .as_object()
+                .unwrap()
+                .clone(),
+            )
+            .unwrap()
+        };
+
+        assert_matches!(
+            redact(event(), &room_version, None),
+            Err(RedactionError::UnsupportedRoomVersion(_))
+        );
+
+        RoomVersionRules::register(room_version.clone(), RoomVersionRules::V8);
+        let redacted = redact(event(), &room_version, None).unwrap();
+        let content = redacted.get("content").unwrap().as_object().unwrap();
+        assert!(content.contains_key("allow"));
+    }
+
+    #[test]
+    fn redact_v11() {
+        let member = |version: &RoomVersionId| {
+            let event = try_from_json_map(
+                json!({
+                    "type": "m.room.member",
+                    "origin": "example.org",
+                    "membership": "invite",
+                    "content": {
+                        "membership": "invite",
+                        "displayname": "Alice",
+                        "third_party_invite": {
+                            "display_name": "alice",
+                            "signed": { "mxid": "@alice:example.org", "token": "abc" },
+                        },
+                    },
+                })
+                .as_object()
+                .unwrap()
+                .clone(),
+            )
+            .unwrap();
+            redact(event, version, None).unwrap()
+        };
+
+        let redacted = member(&RoomVersionId::V10);
+        assert!(redacted.contains_key("origin"));
+        assert!(redacted.contains_key("membership"));
+        let content = redacted.get("content").unwrap().as_object().unwrap();
+        assert_eq!(content.keys().collect::<Vec<_>>(), ["membership"]);
+
+        let redacted = member(&RoomVersionId::V11);
+        assert!(!redacted.contains_key("origin"));
+        assert!(!redacted.contains_key("membership"));
+        let content = redacted.get("content").unwrap().as_object().unwrap();
+        assert_eq!(content.keys().collect::<Vec<_>>(), ["membership", "third_party_invite"]);
+        let third_party_invite = content.get("third_party_invite").unwrap().as_object().unwrap();
+        assert_eq!(third_party_invite.keys().collect::<Vec<_>>(), ["signed"]);
+
+        let mut create = CanonicalJsonObject::new();
+        create.insert("type".to_owned(), "m.room.create".to_owned().into());
+        let mut create_content = CanonicalJsonObject::new();
+        create_content.insert("room_version".to_owned(), "11".to_owned().into());
+        create.insert("content".to_owned(), create_content.clone().into());
+
+        let redacted = redact(create, &RoomVersionId::V11, None).unwrap();
+        assert_eq!(redacted.get("content").unwrap().as_object().unwrap(), &create_content);
+    }
 }
@@ -102,9 +102,11 @@
 //! );
 //! ```
 
-use serde::{de::IgnoredAny, Deserialize, Serializer};
+use std::collections::BTreeSet;
 
-use crate::{EventEncryptionAlgorithm, RoomVersionId};
+use serde::{de::IgnoredAny, Deserialize, Serialize, Serializer};
+
+use crate::{EventEncryptionAlgorithm, OwnedUserId, RoomVersionId};
 
 // Needs to be public for trybuild tests
 #[doc(hidden)]
@@ -188,6 +190,51 @@ pub trait RedactContent {
     fn redact(self, version: &RoomVersionId) -> Self::Redacted;
 }
 
+/// Describes whether the event mentions other users or the room.
+///
+/// This is the content of the [`m.mentions`] field of an event, used by the push rules to notify
+/// the users that were intentionally mentioned.
+///
+/// [`m.mentions`]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
+pub struct Mentions {
+    /// The user IDs mentioned in the event.
+    ///
+    /// Defaults to an empty `BTreeSet`.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub user_ids: BTreeSet<OwnedUserId>,
+
+    /// Whether the whole room is mentioned.
+    ///
+    /// Defaults to `false`.
+    #[serde(default, skip_serializing_if = "crate::serde::is_default")]
+    pub room: bool,
+}
+
+impl Mentions {
+    /// Create a `Mentions` with the default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a `Mentions` for the given user IDs.
+    pub fn with_user_ids(user_ids: impl IntoIterator<Item = OwnedUserId>) -> Self {
+        Self { user_ids: user_ids.into_iter().collect(), ..Default::default() }
+    }
+
+    /// Create a `Mentions` for a room mention.
+    pub fn with_room_mention() -> Self {
+        Self { room: true, ..Default::default() }
+    }
+
+    /// Add the user IDs and the room mention of `other` to `self`.
+    pub fn add(&mut self, other: Self) {
+        self.user_ids.extend(other.user_ids);
+        self.room |= other.room;
+    }
+}
+
 /// Helper struct to determine the event kind from a `serde_json::value::RawValue`.
 #[doc(hidden)]
 #[derive(Deserialize)]
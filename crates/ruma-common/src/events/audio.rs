@@ -13,7 +13,7 @@ mod waveform_serde;
 
 use waveform_serde::WaveformSerDeHelper;
 
-use super::{file::FileContent, message::MessageContent, room::message::Relation};
+use super::{file::FileContent, message::MessageContent, room::message::Relation, Mentions};
 
 /// The payload for an extensible audio message.
 ///
@@ -45,6 +45,12 @@ pub struct AudioEventContent {
         deserialize_with = "crate::events::room::message::relation_serde::deserialize_relation"
     )]
     pub relates_to: Option<Relation<AudioEventContentWithoutRelation>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl AudioEventContent {
@@ -55,12 +61,13 @@ impl AudioEventContent {
             file,
             audio: Default::default(),
             relates_to: None,
+            mentions: None,
         }
     }
 
     /// Creates a new `AudioEventContent` with the given message and file.
     pub fn with_message(message: MessageContent, file: FileContent) -> Self {
-        Self { message, file, audio: Default::default(), relates_to: None }
+        Self { message, file, audio: Default::default(), relates_to: None, mentions: None }
     }
 }
 
@@ -11,6 +11,7 @@ use serde::{Deserialize, Serialize};
 use super::{
     message::MessageContent,
     room::{message::Relation, EncryptedFile, JsonWebKey},
+    Mentions,
 };
 use crate::{serde::Base64, OwnedMxcUri};
 
@@ -40,6 +41,12 @@ pub struct FileEventContent {
         deserialize_with = "crate::events::room::message::relation_serde::deserialize_relation"
     )]
     pub relates_to: Option<Relation<FileEventContentWithoutRelation>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl FileEventContent {
@@ -54,6 +61,7 @@ impl FileEventContent {
             message: MessageContent::plain(message),
             file: FileContent::plain(url, info),
             relates_to: None,
+            mentions: None,
         }
     }
 
@@ -64,7 +72,7 @@ impl FileEventContent {
         url: OwnedMxcUri,
         info: Option<Box<FileContentInfo>>,
     ) -> Self {
-        Self { message, file: FileContent::plain(url, info), relates_to: None }
+        Self { message, file: FileContent::plain(url, info), relates_to: None, mentions: None }
     }
 
     /// Creates a new encrypted `FileEventContent` with the given plain text message, url,
@@ -79,6 +87,7 @@ impl FileEventContent {
             message: MessageContent::plain(message),
             file: FileContent::encrypted(url, encryption_info, info),
             relates_to: None,
+            mentions: None,
         }
     }
 
@@ -90,7 +99,12 @@ impl FileEventContent {
         encryption_info: EncryptedContent,
         info: Option<Box<FileContentInfo>>,
     ) -> Self {
-        Self { message, file: FileContent::encrypted(url, encryption_info, info), relates_to: None }
+        Self {
+            message,
+            file: FileContent::encrypted(url, encryption_info, info),
+            relates_to: None,
+            mentions: None,
+        }
     }
 }
 
@@ -10,6 +10,7 @@ use super::{
     file::{EncryptedContent, FileContent},
     message::MessageContent,
     room::message::Relation,
+    Mentions,
 };
 use crate::OwnedMxcUri;
 
@@ -56,6 +57,12 @@ pub struct ImageEventContent {
         deserialize_with = "crate::events::room::message::relation_serde::deserialize_relation"
     )]
     pub relates_to: Option<Relation<ImageEventContentWithoutRelation>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl ImageEventContent {
@@ -68,6 +75,7 @@ impl ImageEventContent {
             thumbnail: Default::default(),
             caption: Default::default(),
             relates_to: None,
+            mentions: None,
         }
     }
 
@@ -80,6 +88,7 @@ impl ImageEventContent {
             thumbnail: Default::default(),
             caption: Default::default(),
             relates_to: None,
+            mentions: None,
         }
     }
 }
@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 
 mod zoomlevel_serde;
 
-use super::{message::MessageContent, room::message::Relation};
+use super::{message::MessageContent, room::message::Relation, Mentions};
 use crate::{MilliSecondsSinceUnixEpoch, PrivOwnedStr};
 
 /// The payload for an extensible location message.
@@ -45,6 +45,12 @@ pub struct LocationEventContent {
         deserialize_with = "crate::events::room::message::relation_serde::deserialize_relation"
     )]
     pub relates_to: Option<Relation<LocationEventContentWithoutRelation>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl LocationEventContent {
@@ -56,12 +62,20 @@ impl LocationEventContent {
             asset: Default::default(),
             ts: None,
             relates_to: None,
+            mentions: None,
         }
     }
 
     /// Creates a new `LocationEventContent` with the given text representation and location.
     pub fn with_message(message: MessageContent, location: LocationContent) -> Self {
-        Self { message, location, asset: Default::default(), ts: None, relates_to: None }
+        Self {
+            message,
+            location,
+            asset: Default::default(),
+            ts: None,
+            relates_to: None,
+            mentions: None,
+        }
     }
 }
 
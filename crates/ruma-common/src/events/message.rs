@@ -51,7 +51,7 @@ pub(crate) mod content_serde;
 
 use content_serde::MessageContentSerDeHelper;
 
-use super::room::message::Relation;
+use super::{room::message::Relation, Mentions};
 
 /// The payload for an extensible text message.
 ///
@@ -78,17 +78,23 @@ pub struct MessageEventContent {
         deserialize_with = "crate::events::room::message::relation_serde::deserialize_relation"
     )]
     pub relates_to: Option<Relation<MessageEventContentWithoutRelation>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl MessageEventContent {
     /// A convenience constructor to create a plain text message.
     pub fn plain(body: impl Into<String>) -> Self {
-        Self { message: MessageContent::plain(body), relates_to: None }
+        Self { message: MessageContent::plain(body), relates_to: None, mentions: None }
     }
 
     /// A convenience constructor to create an HTML message.
     pub fn html(body: impl Into<String>, html_body: impl Into<String>) -> Self {
-        Self { message: MessageContent::html(body, html_body), relates_to: None }
+        Self { message: MessageContent::html(body, html_body), relates_to: None, mentions: None }
     }
 
     /// A convenience constructor to create a Markdown message.
@@ -97,13 +103,13 @@ impl MessageEventContent {
     /// text message.
     #[cfg(feature = "markdown")]
     pub fn markdown(body: impl AsRef<str> + Into<String>) -> Self {
-        Self { message: MessageContent::markdown(body), relates_to: None }
+        Self { message: MessageContent::markdown(body), relates_to: None, mentions: None }
     }
 }
 
 impl From<MessageContent> for MessageEventContent {
     fn from(message: MessageContent) -> Self {
-        Self { message, relates_to: None }
+        Self { message, relates_to: None, mentions: None }
     }
 }
 
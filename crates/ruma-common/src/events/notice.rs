@@ -5,7 +5,7 @@
 use ruma_macros::EventContent;
 use serde::{Deserialize, Serialize};
 
-use super::{message::MessageContent, room::message::Relation};
+use super::{message::MessageContent, room::message::Relation, Mentions};
 
 /// The payload for an extensible notice message.
 ///
@@ -29,17 +29,23 @@ pub struct NoticeEventContent {
         deserialize_with = "crate::events::room::message::relation_serde::deserialize_relation"
     )]
     pub relates_to: Option<Relation<NoticeEventContentWithoutRelation>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl NoticeEventContent {
     /// A convenience constructor to create a plain text notice.
     pub fn plain(body: impl Into<String>) -> Self {
-        Self { message: MessageContent::plain(body), relates_to: None }
+        Self { message: MessageContent::plain(body), relates_to: None, mentions: None }
     }
 
     /// A convenience constructor to create an HTML notice.
     pub fn html(body: impl Into<String>, html_body: impl Into<String>) -> Self {
-        Self { message: MessageContent::html(body, html_body), relates_to: None }
+        Self { message: MessageContent::html(body, html_body), relates_to: None, mentions: None }
     }
 
     /// A convenience constructor to create a Markdown notice.
@@ -48,6 +54,6 @@ impl NoticeEventContent {
     /// text notice.
     #[cfg(feature = "markdown")]
     pub fn markdown(body: impl AsRef<str> + Into<String>) -> Self {
-        Self { message: MessageContent::markdown(body), relates_to: None }
+        Self { message: MessageContent::markdown(body), relates_to: None, mentions: None }
     }
 }
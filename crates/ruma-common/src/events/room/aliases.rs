@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     events::{EventContent, RedactContent, RedactedStateEventContent, StateEventType},
+    room_version_rules::RedactionRules,
     OwnedRoomAliasId, OwnedServerName, RoomVersionId,
 };
 
@@ -30,16 +31,8 @@ impl RedactContent for RoomAliasesEventContent {
     type Redacted = RedactedRoomAliasesEventContent;
 
     fn redact(self, version: &RoomVersionId) -> RedactedRoomAliasesEventContent {
-        // We compare the long way to avoid pre version 6 behavior if/when
-        // a new room version is introduced.
-        let aliases = match version {
-            RoomVersionId::V1
-            | RoomVersionId::V2
-            | RoomVersionId::V3
-            | RoomVersionId::V4
-            | RoomVersionId::V5 => Some(self.aliases),
-            _ => None,
-        };
+        let rules = version.rules().map_or(RedactionRules::STRICTEST, |rules| rules.redaction);
+        let aliases = rules.keep_room_aliases_aliases.then_some(self.aliases);
 
         RedactedRoomAliasesEventContent { aliases }
     }
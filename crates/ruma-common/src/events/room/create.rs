@@ -6,7 +6,10 @@ use ruma_macros::EventContent;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    events::EmptyStateKey, room::RoomType, OwnedEventId, OwnedRoomId, OwnedUserId, RoomVersionId,
+    events::{EmptyStateKey, RedactContent, RedactedStateEventContent},
+    room::RoomType,
+    room_version_rules::RedactionRules,
+    OwnedEventId, OwnedRoomId, OwnedUserId, RoomVersionId,
 };
 
 /// The content of an `m.room.create` event.
@@ -16,13 +19,16 @@ use crate::{
 /// It acts as the root of all other events.
 #[derive(Clone, Debug, Deserialize, Serialize, EventContent)]
 #[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
-#[ruma_event(type = "m.room.create", kind = State, state_key_type = EmptyStateKey)]
+#[ruma_event(type = "m.room.create", kind = State, state_key_type = EmptyStateKey, custom_redacted)]
 pub struct RoomCreateEventContent {
     /// The `user_id` of the room creator.
     ///
     /// This is set by the homeserver.
-    #[ruma_event(skip_redaction)]
-    pub creator: OwnedUserId,
+    ///
+    /// This field was removed in room version 11, the creator of the room is the `sender` of the
+    /// event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creator: Option<OwnedUserId>,
 
     /// Whether or not this room's data should be transferred to other homeservers.
     #[serde(
@@ -51,15 +57,62 @@ pub struct RoomCreateEventContent {
 
 impl RoomCreateEventContent {
     /// Creates a new `RoomCreateEventContent` with the given creator.
-    pub fn new(creator: OwnedUserId) -> Self {
+    ///
+    /// This is only valid for room versions 1 to 10, the room version defaults to
+    /// `RoomVersionId::V1`.
+    pub fn new_v1(creator: OwnedUserId) -> Self {
         Self {
-            creator,
+            creator: Some(creator),
             federate: true,
             room_version: default_room_version_id(),
             predecessor: None,
             room_type: None,
         }
     }
+
+    /// Creates a new `RoomCreateEventContent` without a creator.
+    ///
+    /// This is only valid for room version 11 and above, the room version defaults to
+    /// `RoomVersionId::V11`.
+    pub fn new_v11() -> Self {
+        Self {
+            creator: None,
+            federate: true,
+            room_version: RoomVersionId::V11,
+            predecessor: None,
+            room_type: None,
+        }
+    }
+}
+
+impl RedactContent for RoomCreateEventContent {
+    type Redacted = RedactedRoomCreateEventContent;
+
+    fn redact(self, version: &RoomVersionId) -> RedactedRoomCreateEventContent {
+        let rules = version.rules().map_or(RedactionRules::STRICTEST, |rules| rules.redaction);
+
+        if rules.keep_room_create_content {
+            self
+        } else {
+            Self {
+                creator: self.creator,
+                federate: true,
+                room_version: default_room_version_id(),
+                predecessor: None,
+                room_type: None,
+            }
+        }
+    }
+}
+
+/// A redacted `m.room.create` event content.
+///
+/// Since room version 11, the whole content is kept during redaction. In previous versions, only
+/// the `creator` field is kept, and the other fields have their default value.
+pub type RedactedRoomCreateEventContent = RoomCreateEventContent;
+
+impl RedactedStateEventContent for RoomCreateEventContent {
+    type StateKey = EmptyStateKey;
 }
 
 /// A reference to an old room replaced during a room version upgrade.
@@ -91,12 +144,12 @@ mod tests {
     use serde_json::{from_value as from_json_value, json, to_value as to_json_value};
 
     use super::{RoomCreateEventContent, RoomType};
-    use crate::{user_id, RoomVersionId};
+    use crate::{events::RedactContent, user_id, RoomVersionId};
 
     #[test]
     fn serialization() {
         let content = RoomCreateEventContent {
-            creator: user_id!("@carl:example.com").to_owned(),
+            creator: Some(user_id!("@carl:example.com").to_owned()),
             federate: false,
             room_version: RoomVersionId::V4,
             predecessor: None,
@@ -115,7 +168,7 @@ mod tests {
     #[test]
     fn space_serialization() {
         let content = RoomCreateEventContent {
-            creator: user_id!("@carl:example.com").to_owned(),
+            creator: Some(user_id!("@carl:example.com").to_owned()),
             federate: false,
             room_version: RoomVersionId::V4,
             predecessor: None,
@@ -141,7 +194,7 @@ mod tests {
         });
 
         let content = from_json_value::<RoomCreateEventContent>(json).unwrap();
-        assert_eq!(content.creator, "@carl:example.com");
+        assert_eq!(content.creator.unwrap(), "@carl:example.com");
         assert!(content.federate);
         assert_eq!(content.room_version, RoomVersionId::V4);
         assert_matches!(content.predecessor, None);
@@ -158,10 +211,35 @@ mod tests {
         });
 
         let content = from_json_value::<RoomCreateEventContent>(json).unwrap();
-        assert_eq!(content.creator, "@carl:example.com");
+        assert_eq!(content.creator.unwrap(), "@carl:example.com");
         assert!(content.federate);
         assert_eq!(content.room_version, RoomVersionId::V4);
         assert_matches!(content.predecessor, None);
         assert_eq!(content.room_type, Some(RoomType::Space));
     }
+
+    #[test]
+    fn deserialization_v11() {
+        let json = json!({
+            "room_version": "11"
+        });
+
+        let content = from_json_value::<RoomCreateEventContent>(json).unwrap();
+        assert_eq!(content.creator, None);
+        assert!(content.federate);
+        assert_eq!(content.room_version, RoomVersionId::V11);
+    }
+
+    #[test]
+    fn redaction() {
+        let mut content = RoomCreateEventContent::new_v1(user_id!("@carl:example.com").to_owned());
+        content.room_type = Some(RoomType::Space);
+
+        let redacted = content.clone().redact(&RoomVersionId::V10);
+        assert_eq!(redacted.creator.unwrap(), "@carl:example.com");
+        assert_eq!(redacted.room_type, None);
+
+        let redacted = content.redact(&RoomVersionId::V11);
+        assert_eq!(redacted.room_type, Some(RoomType::Space));
+    }
 }
@@ -13,6 +13,7 @@ use crate::{
         AnyStrippedStateEvent, BundledRelations, EventContent, PossiblyRedactedStateEventContent,
         RedactContent, RedactedStateEventContent, StateEventType,
     },
+    room_version_rules::RedactionRules,
     serde::{CanBeEmpty, Raw, StringEnum},
     OwnedMxcUri, OwnedServerName, OwnedServerSigningKeyId, OwnedTransactionId, OwnedUserId,
     PrivOwnedStr, RoomVersionId, UserId,
@@ -167,13 +168,14 @@ impl RoomMemberEventContent {
 impl RedactContent for RoomMemberEventContent {
     type Redacted = RedactedRoomMemberEventContent;
 
-    fn redact(self, _version: &RoomVersionId) -> RedactedRoomMemberEventContent {
+    fn redact(self, version: &RoomVersionId) -> RedactedRoomMemberEventContent {
+        let rules = version.rules().map_or(RedactionRules::STRICTEST, |rules| rules.redaction);
+
         RedactedRoomMemberEventContent {
             membership: self.membership,
-            join_authorized_via_users_server: match _version {
-                RoomVersionId::V9 | RoomVersionId::V10 => self.join_authorized_via_users_server,
-                _ => None,
-            },
+            join_authorized_via_users_server: self
+                .join_authorized_via_users_server
+                .filter(|_| rules.keep_room_member_join_authorised_via_users_server),
         }
     }
 }
@@ -2,14 +2,17 @@
 //!
 //! [`m.room.message`]: https://spec.matrix.org/latest/client-server-api/#mroommessage
 
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeSet};
 
 use ruma_macros::EventContent;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
 use crate::{
-    events::relation::{InReplyTo, Replacement, Thread},
+    events::{
+        relation::{InReplyTo, Replacement, Thread},
+        Mentions,
+    },
     serde::{JsonObject, StringEnum},
     OwnedEventId, PrivOwnedStr,
 };
@@ -65,12 +68,18 @@ pub struct RoomMessageEventContent {
     /// [rich replies]: https://spec.matrix.org/latest/client-server-api/#rich-replies
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub relates_to: Option<Relation<MessageType>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl RoomMessageEventContent {
     /// Create a `RoomMessageEventContent` with the given `MessageType`.
     pub fn new(msgtype: MessageType) -> Self {
-        Self { msgtype, relates_to: None }
+        Self { msgtype, relates_to: None, mentions: None }
     }
 
     /// A constructor to create a plain text message.
@@ -111,6 +120,9 @@ impl RoomMessageEventContent {
     /// quoted version of `original_message`. Also sets the `in_reply_to` field inside `relates_to`,
     /// and optionally the `rel_type` to `m.thread` if the `original_message is in a thread and
     /// thread forwarding is enabled.
+    ///
+    /// The sender of `original_message` and the users it mentions are added to the `mentions` of
+    /// `self`.
     #[doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/doc/rich_reply.md"))]
     ///
     /// # Panics
@@ -176,7 +188,11 @@ impl RoomMessageEventContent {
         };
         self.relates_to = Some(relates_to);
 
-        self
+        let mut user_ids = BTreeSet::from([original_message.sender.clone()]);
+        if let Some(mentions) = &original_message.content.mentions {
+            user_ids.extend(mentions.user_ids.iter().cloned());
+        }
+        self.add_mentions(Mentions::with_user_ids(user_ids))
     }
 
     /// Turns `self` into a new message for a thread, that is optionally a reply.
@@ -285,9 +301,11 @@ impl RoomMessageEventContent {
             f.body = format!("* {}", f.body);
         }
 
-        // Add reply fallback if needed.
+        // Add reply fallback if needed, without mentioning the users of the reply again.
         if let Some(original_message) = replied_to_message {
+            let mentions = self.mentions.take();
             self = self.make_reply_to(original_message, ForwardThread::No);
+            self.mentions = mentions;
         }
 
         self.relates_to = Some(relates_to);
@@ -295,6 +313,17 @@ impl RoomMessageEventContent {
         self
     }
 
+    /// Adds the given [mentions] to this event.
+    ///
+    /// If no [`Mentions`] was set on this event, it is set to `mentions`. Otherwise, the user IDs
+    /// and the room mention of `mentions` are added to the existing ones.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    pub fn add_mentions(mut self, mentions: Mentions) -> Self {
+        self.mentions.get_or_insert_with(Mentions::new).add(mentions);
+        self
+    }
+
     /// Returns a reference to the `msgtype` string.
     ///
     /// If you want to access the message type-specific data rather than the message type itself,
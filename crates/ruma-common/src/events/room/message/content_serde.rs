@@ -4,7 +4,7 @@ use serde::{de, Deserialize};
 use serde_json::value::RawValue as RawJsonValue;
 
 use super::{relation_serde::deserialize_relation, MessageType, RoomMessageEventContent};
-use crate::serde::from_raw_json_value;
+use crate::{events::Mentions, serde::from_raw_json_value};
 
 impl<'de> Deserialize<'de> for RoomMessageEventContent {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -14,11 +14,19 @@ impl<'de> Deserialize<'de> for RoomMessageEventContent {
         let json = Box::<RawJsonValue>::deserialize(deserializer)?;
         let mut deserializer = serde_json::Deserializer::from_str(json.get());
         let relates_to = deserialize_relation(&mut deserializer).map_err(de::Error::custom)?;
+        let MentionsDeHelper { mentions } = from_raw_json_value(&json)?;
 
-        Ok(Self { msgtype: from_raw_json_value(&json)?, relates_to })
+        Ok(Self { msgtype: from_raw_json_value(&json)?, relates_to, mentions })
     }
 }
 
+/// Helper struct to deserialize the `m.mentions` field of a `RoomMessageEventContent`.
+#[derive(Debug, Deserialize)]
+struct MentionsDeHelper {
+    #[serde(rename = "m.mentions")]
+    mentions: Option<Mentions>,
+}
+
 /// Helper struct to determine the msgtype from a `serde_json::value::RawValue`
 #[derive(Debug, Deserialize)]
 struct MessageTypeDeHelper {
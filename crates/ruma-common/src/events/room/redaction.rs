@@ -8,11 +8,13 @@ use serde_json::value::RawValue as RawJsonValue;
 
 use crate::{
     events::{
-        EventContent, MessageLikeEventType, MessageLikeUnsigned, RedactedUnsigned,
-        RedactionDeHelper,
+        EventContent, MessageLikeEventType, MessageLikeUnsigned, RedactContent,
+        RedactedMessageLikeEventContent, RedactedUnsigned, RedactionDeHelper,
     },
+    room_version_rules::RedactionRules,
     serde::from_raw_json_value,
-    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId,
+    RoomVersionId, UserId,
 };
 
 /// A possibly-redacted redaction event.
@@ -45,7 +47,9 @@ pub struct OriginalRoomRedactionEvent {
     pub content: RoomRedactionEventContent,
 
     /// The ID of the event that was redacted.
-    pub redacts: OwnedEventId,
+    ///
+    /// This field is required in room versions prior to 11.
+    pub redacts: Option<OwnedEventId>,
 
     /// The globally unique event identifier for the user who sent the event.
     pub event_id: OwnedEventId,
@@ -94,7 +98,9 @@ pub struct OriginalSyncRoomRedactionEvent {
     pub content: RoomRedactionEventContent,
 
     /// The ID of the event that was redacted.
-    pub redacts: OwnedEventId,
+    ///
+    /// This field is required in room versions prior to 11.
+    pub redacts: Option<OwnedEventId>,
 
     /// The globally unique event identifier for the user who sent the event.
     pub event_id: OwnedEventId,
@@ -132,8 +138,14 @@ pub struct RedactedSyncRoomRedactionEvent {
 /// A redaction of an event.
 #[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
 #[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
-#[ruma_event(type = "m.room.redaction", kind = MessageLike)]
+#[ruma_event(type = "m.room.redaction", kind = MessageLike, custom_redacted)]
 pub struct RoomRedactionEventContent {
+    /// The ID of the event that was redacted.
+    ///
+    /// This field is required starting from room version 11.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redacts: Option<OwnedEventId>,
+
     /// The reason for the redaction, if any.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
@@ -141,13 +153,97 @@ pub struct RoomRedactionEventContent {
 
 impl RoomRedactionEventContent {
     /// Creates an empty `RoomRedactionEventContent`.
+    ///
+    /// This is only valid for room versions 1 to 10, the ID of the redacted event must be in the
+    /// top-level `redacts` field of the event.
     pub fn new() -> Self {
         Self::default()
     }
 
     /// Creates a new `RoomRedactionEventContent` with the given reason.
+    ///
+    /// This is only valid for room versions 1 to 10, the ID of the redacted event must be in the
+    /// top-level `redacts` field of the event.
     pub fn with_reason(reason: String) -> Self {
-        Self { reason: Some(reason) }
+        Self { reason: Some(reason), ..Default::default() }
+    }
+
+    /// Creates a new `RoomRedactionEventContent` for the given redacted event.
+    ///
+    /// This is only valid for room version 11 and above.
+    pub fn new_v11(redacts: OwnedEventId) -> Self {
+        Self { redacts: Some(redacts), ..Default::default() }
+    }
+}
+
+impl RedactContent for RoomRedactionEventContent {
+    type Redacted = RedactedRoomRedactionEventContent;
+
+    fn redact(self, version: &RoomVersionId) -> RedactedRoomRedactionEventContent {
+        let rules = version.rules().map_or(RedactionRules::STRICTEST, |rules| rules.redaction);
+        let redacts = self.redacts.filter(|_| rules.keep_room_redaction_redacts);
+
+        RedactedRoomRedactionEventContent { redacts }
+    }
+}
+
+/// A redacted redaction event content.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
+pub struct RedactedRoomRedactionEventContent {
+    /// The ID of the event that was redacted.
+    ///
+    /// This field is kept during redaction starting from room version 11.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redacts: Option<OwnedEventId>,
+}
+
+impl RedactedRoomRedactionEventContent {
+    /// Creates an empty `RedactedRoomRedactionEventContent`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EventContent for RedactedRoomRedactionEventContent {
+    type EventType = MessageLikeEventType;
+
+    fn event_type(&self) -> MessageLikeEventType {
+        MessageLikeEventType::RoomRedaction
+    }
+}
+
+impl RedactedMessageLikeEventContent for RedactedRoomRedactionEventContent {}
+
+impl OriginalRoomRedactionEvent {
+    /// Get the ID of the event that this event redacts, according to the rules of the given room
+    /// version.
+    pub fn redacts(&self, room_version: &RoomVersionId) -> Option<&EventId> {
+        redacts(room_version, self.redacts.as_deref(), self.content.redacts.as_deref())
+    }
+}
+
+impl OriginalSyncRoomRedactionEvent {
+    /// Get the ID of the event that this event redacts, according to the rules of the given room
+    /// version.
+    pub fn redacts(&self, room_version: &RoomVersionId) -> Option<&EventId> {
+        redacts(room_version, self.redacts.as_deref(), self.content.redacts.as_deref())
+    }
+}
+
+/// Get the ID of the redacted event from the top-level `redacts` field or from the one in the
+/// content, according to the rules of the room version.
+///
+/// If the rules of the room version are unknown, the field in the content takes precedence.
+fn redacts<'a>(
+    room_version: &RoomVersionId,
+    top_level: Option<&'a EventId>,
+    content: Option<&'a EventId>,
+) -> Option<&'a EventId> {
+    match room_version.rules().map(|rules| rules.redaction.content_field_redacts) {
+        Some(true) => content,
+        Some(false) => top_level,
+        None => content.or(top_level),
     }
 }
 
@@ -10,6 +10,7 @@ use serde::{Deserialize, Serialize};
 
 use super::{
     file::FileContent, image::ThumbnailContent, message::MessageContent, room::message::Relation,
+    Mentions,
 };
 
 /// The payload for an extensible video message.
@@ -55,6 +56,12 @@ pub struct VideoEventContent {
         deserialize_with = "crate::events::room::message::relation_serde::deserialize_relation"
     )]
     pub relates_to: Option<Relation<VideoEventContentWithoutRelation>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl VideoEventContent {
@@ -67,6 +74,7 @@ impl VideoEventContent {
             thumbnail: Default::default(),
             caption: Default::default(),
             relates_to: None,
+            mentions: None,
         }
     }
 
@@ -79,6 +87,7 @@ impl VideoEventContent {
             thumbnail: Default::default(),
             caption: Default::default(),
             relates_to: None,
+            mentions: None,
         }
     }
 }
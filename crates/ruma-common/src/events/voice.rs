@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 
 use super::{
     audio::AudioContent, file::FileContent, message::MessageContent, room::message::Relation,
+    Mentions,
 };
 
 /// The payload for an extensible voice message.
@@ -43,6 +44,12 @@ pub struct VoiceEventContent {
         deserialize_with = "crate::events::room::message::relation_serde::deserialize_relation"
     )]
     pub relates_to: Option<Relation<VoiceEventContentWithoutRelation>>,
+
+    /// The [mentions] of this event.
+    ///
+    /// [mentions]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Mentions>,
 }
 
 impl VoiceEventContent {
@@ -54,6 +61,7 @@ impl VoiceEventContent {
             audio: Default::default(),
             voice: Default::default(),
             relates_to: None,
+            mentions: None,
         }
     }
 
@@ -65,6 +73,7 @@ impl VoiceEventContent {
             audio: Default::default(),
             voice: Default::default(),
             relates_to: None,
+            mentions: None,
         }
     }
 }
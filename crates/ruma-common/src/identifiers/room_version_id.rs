@@ -6,6 +6,7 @@ use ruma_macros::DisplayAsRefStr;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::IdParseError;
+use crate::room_version_rules::{self, RoomVersionRules};
 
 /// A Matrix [room version] ID.
 ///
@@ -56,6 +57,9 @@ pub enum RoomVersionId {
     /// A version 10 room.
     V10,
 
+    /// A version 11 room.
+    V11,
+
     #[doc(hidden)]
     _Custom(CustomRoomVersion),
 }
@@ -76,6 +80,7 @@ impl RoomVersionId {
             Self::V8 => "8",
             Self::V9 => "9",
             Self::V10 => "10",
+            Self::V11 => "11",
             Self::_Custom(version) => version.as_str(),
         }
     }
@@ -84,6 +89,14 @@ impl RoomVersionId {
     pub fn as_bytes(&self) -> &[u8] {
         self.as_str().as_bytes()
     }
+
+    /// Get the rules of this room version.
+    ///
+    /// Returns `None` if this is a custom room version whose rules were not registered with
+    /// [`RoomVersionRules::register()`].
+    pub fn rules(&self) -> Option<RoomVersionRules> {
+        room_version_rules::rules_for(self)
+    }
 }
 
 impl From<RoomVersionId> for String {
@@ -99,6 +112,7 @@ impl From<RoomVersionId> for String {
             RoomVersionId::V8 => "8".to_owned(),
             RoomVersionId::V9 => "9".to_owned(),
             RoomVersionId::V10 => "10".to_owned(),
+            RoomVersionId::V11 => "11".to_owned(),
             RoomVersionId::_Custom(version) => version.into(),
         }
     }
@@ -166,6 +180,7 @@ where
         "8" => RoomVersionId::V8,
         "9" => RoomVersionId::V9,
         "10" => RoomVersionId::V10,
+        "11" => RoomVersionId::V11,
         custom => {
             ruma_identifiers_validation::room_version_id::validate(custom)?;
             RoomVersionId::_Custom(CustomRoomVersion(room_version_id.into()))
@@ -30,17 +30,19 @@ use crate::{
 
 mod action;
 mod condition;
+mod evaluator;
 mod iter;
 mod predefined;
 
-#[cfg(feature = "unstable-msc3932")]
+#[cfg(feature = "unstable-msc3931")]
 pub use self::condition::RoomVersionFeature;
 pub use self::{
     action::{Action, Tweak},
     condition::{
-        ComparisonOperator, FlattenedJson, PushCondition, PushConditionRoomCtx, RoomMemberCountIs,
-        _CustomPushCondition,
+        _CustomPushCondition, ComparisonOperator, FlattenedJson, FlattenedJsonValue, PushCondition,
+        PushConditionRoomCtx, RoomMemberCountIs, ScalarJsonValue,
     },
+    evaluator::PushEvaluator,
     iter::{AnyPushRule, AnyPushRuleRef, RulesetIntoIter, RulesetIter},
     predefined::{
         PredefinedContentRuleId, PredefinedOverrideRuleId, PredefinedRuleId,
@@ -277,6 +279,44 @@ impl Ruleset {
         Ok(())
     }
 
+    /// Remove a user-defined rule in the rule set.
+    ///
+    /// Returns an error if the parameters are invalid.
+    pub fn remove(
+        &mut self,
+        kind: RuleKind,
+        rule_id: impl AsRef<str>,
+    ) -> Result<(), RemovePushRuleError> {
+        let rule_id = rule_id.as_ref();
+
+        let rule = self.get(kind.clone(), rule_id).ok_or(RemovePushRuleError::NotFound)?;
+        if rule.is_server_default() {
+            return Err(RemovePushRuleError::ServerDefault);
+        }
+
+        match kind {
+            RuleKind::Override => {
+                self.override_.shift_remove(rule_id);
+            }
+            RuleKind::Underride => {
+                self.underride.shift_remove(rule_id);
+            }
+            RuleKind::Sender => {
+                self.sender.shift_remove(rule_id);
+            }
+            RuleKind::Room => {
+                self.room.shift_remove(rule_id);
+            }
+            RuleKind::Content => {
+                self.content.shift_remove(rule_id);
+            }
+            // This has been handled in the `self.get` call earlier.
+            RuleKind::_Custom(_) => unreachable!(),
+        }
+
+        Ok(())
+    }
+
     /// Get the first push rule that applies to this event, if any.
     ///
     /// # Arguments
@@ -303,6 +343,8 @@ impl Ruleset {
     ///
     /// Returns an empty slice if no push rule applies.
     ///
+    /// To evaluate the rules for many events, use a [`PushEvaluator`] instead.
+    ///
     /// # Arguments
     ///
     /// * `event` - The raw JSON of a room message event.
@@ -440,12 +482,22 @@ impl ConditionalPushRule {
             return false;
         }
 
+        // The legacy mention rules don't apply to events with intentional mentions.
+        if event.contains_mentions()
+            && (self.rule_id == PredefinedOverrideRuleId::ContainsDisplayName.as_ref()
+                || self.rule_id == PredefinedOverrideRuleId::RoomNotif.as_ref())
+        {
+            return false;
+        }
+
         #[cfg(feature = "unstable-msc3932")]
         {
-            // These 3 rules always apply.
+            // These rules always apply.
             if self.rule_id != PredefinedOverrideRuleId::Master.as_ref()
                 && self.rule_id != PredefinedOverrideRuleId::RoomNotif.as_ref()
                 && self.rule_id != PredefinedOverrideRuleId::ContainsDisplayName.as_ref()
+                && self.rule_id != PredefinedOverrideRuleId::IsUserMention.as_ref()
+                && self.rule_id != PredefinedOverrideRuleId::IsRoomMention.as_ref()
             {
                 // Push rules which don't specify a `room_version_supports` condition are assumed
                 // to not support extensible events and are therefore expected to be treated as
@@ -564,6 +616,13 @@ impl PatternedPushRule {
             return false;
         }
 
+        // The legacy mention rule doesn't apply to events with intentional mentions.
+        if event.contains_mentions()
+            && self.rule_id == PredefinedContentRuleId::ContainsUserName.as_ref()
+        {
+            return false;
+        }
+
         self.enabled && condition::check_event_match(event, key, &self.pattern, context)
     }
 }
@@ -857,6 +916,19 @@ pub enum InsertPushRuleError {
     BeforeHigherThanAfter,
 }
 
+/// The possible errors in `Ruleset::remove`.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RemovePushRuleError {
+    /// The rule is a server-default rule and it can't be removed.
+    #[error("server-default rules cannot be removed")]
+    ServerDefault,
+
+    /// The rule was not found.
+    #[error("rule not found")]
+    NotFound,
+}
+
 /// The error type returned when trying modify a push rule that could not be found in a `Ruleset`.
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -895,6 +967,12 @@ where
 
     // Only move the item if it's new or if it was positioned.
     if replaced.is_none() || after.is_some() || before.is_some() {
+        // The rules between `from` and `to` are shifted when the rule is moved to a lower
+        // priority.
+        if from < to {
+            to -= 1;
+        }
+
         set.move_index(from, to);
     }
 
@@ -915,7 +993,9 @@ mod tests {
     use super::{
         action::{Action, Tweak},
         condition::{PushCondition, PushConditionRoomCtx, RoomMemberCountIs},
-        AnyPushRule, ConditionalPushRule, PatternedPushRule, Ruleset, SimplePushRule,
+        AnyPushRule, ConditionalPushRule, NewConditionalPushRule, NewPatternedPushRule,
+        NewPushRule, NewSimplePushRule, PatternedPushRule, PredefinedContentRuleId,
+        PredefinedOverrideRuleId, RemovePushRuleError, RuleKind, Ruleset, SimplePushRule,
     };
     use crate::{power_levels::NotificationPowerLevels, room_id, serde::Raw, user_id};
 
@@ -1461,6 +1541,299 @@ mod tests {
         assert_matches!(set.get_actions(&empty, context_one_to_one), []);
     }
 
+    #[test]
+    fn server_default_override_rules_order() {
+        let set = Ruleset::server_default(user_id!("@jolly_jumper:server.name"));
+        let rule_ids: Vec<_> = set.override_.iter().map(|rule| rule.rule_id.as_str()).collect();
+
+        assert_eq!(
+            rule_ids[..10],
+            [
+                ".m.rule.master",
+                ".m.rule.suppress_notices",
+                ".m.rule.invite_for_me",
+                ".m.rule.member_event",
+                ".m.rule.is_user_mention",
+                ".m.rule.contains_display_name",
+                ".m.rule.is_room_mention",
+                ".m.rule.tombstone",
+                ".m.rule.room.server_acl",
+                ".m.rule.roomnotif",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_ruleset_applies_with_mentions() {
+        let set = Ruleset::server_default(user_id!("@jolly_jumper:server.name"));
+
+        let context = &PushConditionRoomCtx {
+            room_id: room_id!("!far_west:server.name").to_owned(),
+            member_count: uint!(100),
+            user_id: user_id!("@jj:server.name").to_owned(),
+            user_display_name: "Jolly Jumper".into(),
+            users_power_levels: BTreeMap::new(),
+            default_power_level: int!(50),
+            notification_power_levels: NotificationPowerLevels { room: int!(50) },
+            #[cfg(feature = "unstable-msc3931")]
+            supported_features: Default::default(),
+        };
+
+        // Legacy mention rules are ignored.
+        let no_mentions = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": {
+                    "body": "@room Hi jolly_jumper, also known as Jolly Jumper!",
+                    "msgtype": "m.text",
+                    "m.mentions": {}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_matches!(
+            set.get_actions(&no_mentions, context),
+            [Action::Notify, Action::SetTweak(Tweak::Highlight(false))]
+        );
+
+        let user_mention = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": {
+                    "body": "Hi!",
+                    "msgtype": "m.text",
+                    "m.mentions": {
+                        "user_ids": ["@jolly_jumper:server.name"]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let rule = set.get_match(&user_mention, context).unwrap();
+        assert_eq!(rule.rule_id(), PredefinedOverrideRuleId::IsUserMention.as_ref());
+        assert_matches!(
+            rule.actions(),
+            [
+                Action::Notify,
+                Action::SetTweak(Tweak::Sound(_)),
+                Action::SetTweak(Tweak::Highlight(true)),
+            ]
+        );
+
+        let room_mention = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": {
+                    "body": "Attention please!",
+                    "msgtype": "m.text",
+                    "m.mentions": {
+                        "room": true
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let rule = set.get_match(&room_mention, context).unwrap();
+        assert_eq!(rule.rule_id(), PredefinedOverrideRuleId::IsRoomMention.as_ref());
+        assert_matches!(rule.actions(), [Action::Notify, Action::SetTweak(Tweak::Highlight(true))]);
+    }
+
+    #[test]
+    fn update_with_server_default() {
+        let user_id = user_id!("@jolly_jumper:server.name");
+        let mut set = Ruleset::server_default(user_id);
+
+        // A predefined rule that was added after the ruleset was stored.
+        set.override_.shift_remove(PredefinedOverrideRuleId::IsUserMention.as_ref());
+        // A predefined rule with an outdated definition.
+        let mut suppress_notices = ConditionalPushRule::suppress_notices();
+        suppress_notices.conditions = vec![];
+        set.override_.replace(suppress_notices);
+        // A predefined rule that doesn't exist anymore.
+        set.override_.insert(ConditionalPushRule {
+            actions: vec![],
+            default: true,
+            enabled: true,
+            rule_id: ".org.matrix.msc3786.rule.room.server_acl".into(),
+            conditions: vec![],
+        });
+        // User settings.
+        set.set_enabled(RuleKind::Override, PredefinedOverrideRuleId::Master, true).unwrap();
+        set.set_actions(RuleKind::Override, PredefinedOverrideRuleId::ContainsDisplayName, vec![])
+            .unwrap();
+        set.set_enabled(RuleKind::Content, PredefinedContentRuleId::ContainsUserName, false)
+            .unwrap();
+        // User-defined rules.
+        set.insert(
+            NewPushRule::Override(NewConditionalPushRule::new(
+                "user.override".into(),
+                vec![],
+                vec![Action::Notify],
+            )),
+            None,
+            None,
+        )
+        .unwrap();
+        set.insert(
+            NewPushRule::Content(NewPatternedPushRule::new(
+                "user.content".into(),
+                "lunch".into(),
+                vec![Action::Notify],
+            )),
+            None,
+            None,
+        )
+        .unwrap();
+        set.insert(
+            NewPushRule::Room(NewSimplePushRule::new(
+                room_id!("!room:server.name").to_owned(),
+                vec![Action::DontNotify],
+            )),
+            None,
+            None,
+        )
+        .unwrap();
+
+        set.update_with_server_default(user_id);
+
+        let server_default = Ruleset::server_default(user_id);
+        let expected_override_ids = server_default
+            .override_
+            .iter()
+            .take(1)
+            .map(|rule| rule.rule_id.as_str())
+            .chain(["user.override"])
+            .chain(server_default.override_.iter().skip(1).map(|rule| rule.rule_id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            set.override_.iter().map(|rule| rule.rule_id.as_str()).collect::<Vec<_>>(),
+            expected_override_ids
+        );
+        assert_eq!(
+            set.content.iter().map(|rule| rule.rule_id.as_str()).collect::<Vec<_>>(),
+            ["user.content", PredefinedContentRuleId::ContainsUserName.as_ref()]
+        );
+        assert_eq!(
+            set.underride.iter().map(|rule| rule.rule_id.as_str()).collect::<Vec<_>>(),
+            server_default.underride.iter().map(|rule| rule.rule_id.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(set.room.len(), 1);
+
+        let master = set.override_.get(PredefinedOverrideRuleId::Master.as_ref()).unwrap();
+        assert!(master.enabled);
+        let suppress_notices =
+            set.override_.get(PredefinedOverrideRuleId::SuppressNotices.as_ref()).unwrap();
+        assert_eq!(suppress_notices.conditions.len(), 1);
+        let contains_display_name =
+            set.override_.get(PredefinedOverrideRuleId::ContainsDisplayName.as_ref()).unwrap();
+        assert_eq!(contains_display_name.actions.len(), 0);
+        let contains_user_name =
+            set.content.get(PredefinedContentRuleId::ContainsUserName.as_ref()).unwrap();
+        assert!(!contains_user_name.enabled);
+    }
+
+    #[test]
+    fn update_with_server_default_keeps_unknown_server_rules() {
+        let user_id = user_id!("@jolly_jumper:server.name");
+        let mut set = Ruleset::server_default(user_id);
+
+        // Server-default rules of Synapse that Ruma doesn't know about, disabled by the user.
+        for rule_id in [".org.matrix.msc3914.rule.room.call", ".im.vector.jitsi"] {
+            set.underride.insert(ConditionalPushRule {
+                actions: vec![Action::Notify],
+                default: true,
+                enabled: false,
+                rule_id: rule_id.into(),
+                conditions: vec![],
+            });
+        }
+
+        set.update_with_server_default(user_id);
+
+        let server_default = Ruleset::server_default(user_id);
+        let expected_underride_ids = [".org.matrix.msc3914.rule.room.call", ".im.vector.jitsi"]
+            .into_iter()
+            .chain(server_default.underride.iter().map(|rule| rule.rule_id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            set.underride.iter().map(|rule| rule.rule_id.as_str()).collect::<Vec<_>>(),
+            expected_underride_ids
+        );
+        let jitsi = set.underride.get(".im.vector.jitsi").unwrap();
+        assert!(!jitsi.enabled);
+        assert!(jitsi.default);
+    }
+
+    #[test]
+    fn insert_and_move_user_rules() {
+        let mut set = Ruleset::new();
+        for rule_id in ["a", "b", "c", "d"] {
+            set.insert(
+                NewPushRule::Content(NewPatternedPushRule::new(
+                    rule_id.into(),
+                    rule_id.into(),
+                    vec![],
+                )),
+                None,
+                None,
+            )
+            .unwrap();
+        }
+        let content_ids =
+            |set: &Ruleset| set.content.iter().map(|r| r.rule_id.clone()).collect::<Vec<_>>();
+        assert_eq!(content_ids(&set), ["d", "c", "b", "a"]);
+
+        // Move to a lower priority.
+        let rule = NewPushRule::Content(NewPatternedPushRule::new("d".into(), "d".into(), vec![]));
+        set.insert(rule, Some("b"), None).unwrap();
+        assert_eq!(content_ids(&set), ["c", "b", "d", "a"]);
+
+        let rule = NewPushRule::Content(NewPatternedPushRule::new("c".into(), "c".into(), vec![]));
+        set.insert(rule, None, Some("a")).unwrap();
+        assert_eq!(content_ids(&set), ["b", "d", "c", "a"]);
+
+        // Move to a higher priority.
+        let rule = NewPushRule::Content(NewPatternedPushRule::new("a".into(), "a".into(), vec![]));
+        set.insert(rule, Some("b"), None).unwrap();
+        assert_eq!(content_ids(&set), ["b", "a", "d", "c"]);
+
+        let rule = NewPushRule::Content(NewPatternedPushRule::new("c".into(), "c".into(), vec![]));
+        set.insert(rule, None, Some("b")).unwrap();
+        assert_eq!(content_ids(&set), ["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn remove_rule() {
+        let mut set = Ruleset::server_default(user_id!("@jolly_jumper:server.name"));
+        set.insert(
+            NewPushRule::Sender(NewSimplePushRule::new(
+                user_id!("@rantanplan:server.name").to_owned(),
+                vec![],
+            )),
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_matches!(
+            set.remove(RuleKind::Override, PredefinedOverrideRuleId::Master),
+            Err(RemovePushRuleError::ServerDefault)
+        );
+        assert_matches!(
+            set.remove(RuleKind::Sender, "@dalton:server.name"),
+            Err(RemovePushRuleError::NotFound)
+        );
+
+        set.remove(RuleKind::Sender, "@rantanplan:server.name").unwrap();
+        assert!(set.sender.is_empty());
+    }
+
     #[test]
     fn custom_ruleset_applies() {
         let context_one_to_one = &PushConditionRoomCtx {
@@ -5,18 +5,23 @@ use regex::Regex;
 #[cfg(feature = "unstable-msc3931")]
 use ruma_macros::StringEnum;
 use serde::{Deserialize, Serialize};
-use serde_json::{to_value as to_json_value, value::Value as JsonValue};
-use tracing::{instrument, warn};
+use serde_json::value::Value as JsonValue;
 use wildmatch::WildMatch;
 
-use crate::{power_levels::NotificationPowerLevels, serde::Raw, OwnedRoomId, OwnedUserId, UserId};
+use crate::{power_levels::NotificationPowerLevels, OwnedRoomId, OwnedUserId, UserId};
 #[cfg(feature = "unstable-msc3931")]
 use crate::{PrivOwnedStr, RoomVersionId};
 
+mod flattened_json;
 mod push_condition_serde;
+#[cfg(feature = "events")]
+mod room_ctx;
 mod room_member_count_is;
 
-pub use room_member_count_is::{ComparisonOperator, RoomMemberCountIs};
+pub use self::{
+    flattened_json::{FlattenedJson, FlattenedJsonValue, ScalarJsonValue},
+    room_member_count_is::{ComparisonOperator, RoomMemberCountIs},
+};
 
 /// Features supported by room versions.
 #[cfg(feature = "unstable-msc3931")]
@@ -40,20 +45,13 @@ pub enum RoomVersionFeature {
 #[cfg(feature = "unstable-msc3931")]
 impl RoomVersionFeature {
     /// Get the default features for the given room version.
+    ///
+    /// These are the [`RoomVersionRules::push_features`] of the room version, or an empty list if
+    /// the rules of the room version are unknown.
+    ///
+    /// [`RoomVersionRules::push_features`]: crate::room_version_rules::RoomVersionRules::push_features
     pub fn list_for_room_version(version: &RoomVersionId) -> Vec<Self> {
-        match version {
-            RoomVersionId::V1
-            | RoomVersionId::V2
-            | RoomVersionId::V3
-            | RoomVersionId::V4
-            | RoomVersionId::V5
-            | RoomVersionId::V6
-            | RoomVersionId::V7
-            | RoomVersionId::V8
-            | RoomVersionId::V9
-            | RoomVersionId::V10
-            | RoomVersionId::_Custom(_) => vec![],
-        }
+        version.rules().map(|rules| rules.push_features.to_vec()).unwrap_or_default()
     }
 }
 
@@ -100,6 +98,28 @@ pub enum PushCondition {
         feature: RoomVersionFeature,
     },
 
+    /// Exact value match on a property of the event.
+    EventPropertyIs {
+        /// The dot-separated path of the property of the event to match.
+        ///
+        /// Dots and backslashes in the names of the properties must be escaped with a backslash.
+        key: String,
+
+        /// The value to match against.
+        value: ScalarJsonValue,
+    },
+
+    /// Exact value match on a value in an array property of the event.
+    EventPropertyContains {
+        /// The dot-separated path of the property of the event to match.
+        ///
+        /// Dots and backslashes in the names of the properties must be escaped with a backslash.
+        key: String,
+
+        /// The value to match against.
+        value: ScalarJsonValue,
+    },
+
     #[doc(hidden)]
     _Custom(_CustomPushCondition),
 }
@@ -170,6 +190,13 @@ impl PushCondition {
                 }
                 RoomVersionFeature::_Custom(_) => false,
             },
+            Self::EventPropertyIs { key, value } => {
+                event.get_value(key).and_then(FlattenedJsonValue::as_scalar) == Some(value)
+            }
+            Self::EventPropertyContains { key, value } => event
+                .get_value(key)
+                .and_then(FlattenedJsonValue::as_array)
+                .map_or(false, |a| a.contains(value)),
             Self::_Custom(_) => false,
         }
     }
@@ -300,14 +327,7 @@ impl StrExt for str {
     }
 
     fn matches_pattern(&self, pattern: &str, match_words: bool) -> bool {
-        let value = &self.to_lowercase();
-        let pattern = &pattern.to_lowercase();
-
-        if match_words {
-            value.matches_word(pattern)
-        } else {
-            WildMatch::new(pattern).matches(value)
-        }
+        PatternMatcher::new(pattern, match_words).matches(self)
     }
 
     fn matches_word(&self, pattern: &str) -> bool {
@@ -318,42 +338,10 @@ impl StrExt for str {
             return false;
         }
 
-        let has_wildcards = pattern.contains(|c| matches!(c, '?' | '*'));
+        let has_wildcards = pattern.contains(['?', '*']);
 
         if has_wildcards {
-            let mut chunks: Vec<String> = vec![];
-            let mut prev_wildcard = false;
-            let mut chunk_start = 0;
-
-            for (i, c) in pattern.char_indices() {
-                if matches!(c, '?' | '*') && !prev_wildcard {
-                    if i != 0 {
-                        chunks.push(regex::escape(&pattern[chunk_start..i]));
-                        chunk_start = i;
-                    }
-
-                    prev_wildcard = true;
-                } else if prev_wildcard {
-                    let chunk = &pattern[chunk_start..i];
-                    chunks.push(chunk.wildcards_to_regex());
-
-                    chunk_start = i;
-                    prev_wildcard = false;
-                }
-            }
-
-            let len = pattern.len();
-            if !prev_wildcard {
-                chunks.push(regex::escape(&pattern[chunk_start..len]));
-            } else if prev_wildcard {
-                let chunk = &pattern[chunk_start..len];
-                chunks.push(chunk.wildcards_to_regex());
-            }
-
-            // The word characters in ASCII compatible mode (with the `-u` flag) match the
-            // definition in the spec: any character not in the set `[A-Za-z0-9_]`.
-            let regex = format!(r"(?-u:^|\W|\b){}(?-u:\b|\W|$)", chunks.concat());
-            Regex::new(&regex).ok().filter(|re| re.is_match(self)).is_some()
+            word_glob_regex(pattern).filter(|re| re.is_match(self)).is_some()
         } else {
             match self.find(pattern) {
                 Some(start) => {
@@ -407,44 +395,97 @@ impl StrExt for str {
     }
 }
 
-/// The flattened representation of a JSON object.
+/// Build the regex matching the glob `pattern` with word boundaries.
+///
+/// Returns `None` if the regex could not be compiled.
+fn word_glob_regex(pattern: &str) -> Option<Regex> {
+    let mut chunks: Vec<String> = vec![];
+    let mut prev_wildcard = false;
+    let mut chunk_start = 0;
+
+    for (i, c) in pattern.char_indices() {
+        if matches!(c, '?' | '*') && !prev_wildcard {
+            if i != 0 {
+                chunks.push(regex::escape(&pattern[chunk_start..i]));
+                chunk_start = i;
+            }
+
+            prev_wildcard = true;
+        } else if prev_wildcard {
+            let chunk = &pattern[chunk_start..i];
+            chunks.push(chunk.wildcards_to_regex());
+
+            chunk_start = i;
+            prev_wildcard = false;
+        }
+    }
+
+    let len = pattern.len();
+    if !prev_wildcard {
+        chunks.push(regex::escape(&pattern[chunk_start..len]));
+    } else if prev_wildcard {
+        let chunk = &pattern[chunk_start..len];
+        chunks.push(chunk.wildcards_to_regex());
+    }
+
+    // The word characters in ASCII compatible mode (with the `-u` flag) match the
+    // definition in the spec: any character not in the set `[A-Za-z0-9_]`.
+    let regex = format!(r"(?-u:^|\W|\b){}(?-u:\b|\W|$)", chunks.concat());
+    Regex::new(&regex).ok()
+}
+
+/// A pattern of a push rule, compiled to be matched against many values.
+///
+/// The match is case insensitive.
 #[derive(Clone, Debug)]
-pub struct FlattenedJson {
-    /// The internal map containing the flattened JSON as a pair path, value.
-    map: BTreeMap<String, String>,
+pub(super) enum PatternMatcher {
+    /// A glob matching the whole value.
+    Glob(WildMatch),
+
+    /// A pattern without wildcards matching words of the value.
+    Word(String),
+
+    /// A glob matching words of the value.
+    WordGlob {
+        /// The lowercase pattern.
+        pattern: String,
+
+        /// The regex of the pattern, or `None` if it could not be compiled.
+        regex: Option<Regex>,
+    },
 }
 
-impl FlattenedJson {
-    /// Create a `FlattenedJson` from `Raw`.
-    pub fn from_raw<T>(raw: &Raw<T>) -> Self {
-        let mut s = Self { map: BTreeMap::new() };
-        s.flatten_value(to_json_value(raw).unwrap(), "".into());
-        s
+impl PatternMatcher {
+    /// Compile the given pattern.
+    ///
+    /// The pattern can be a glob with wildcards `*` and `?`.
+    ///
+    /// If `match_words` is `true`, checks that the pattern is separated from other words.
+    pub(super) fn new(pattern: &str, match_words: bool) -> Self {
+        let pattern = pattern.to_lowercase();
+
+        if !match_words {
+            Self::Glob(WildMatch::new(&pattern))
+        } else if !pattern.is_empty() && pattern.contains(['?', '*']) {
+            let regex = word_glob_regex(&pattern);
+            Self::WordGlob { pattern, regex }
+        } else {
+            Self::Word(pattern)
+        }
     }
 
-    /// Flatten and insert the `value` at `path`.
-    #[instrument(skip(self, value))]
-    fn flatten_value(&mut self, value: JsonValue, path: String) {
-        match value {
-            JsonValue::Object(fields) => {
-                for (key, value) in fields {
-                    let path = if path.is_empty() { key } else { format!("{path}.{key}") };
-                    self.flatten_value(value, path);
-                }
-            }
-            JsonValue::String(s) => {
-                if self.map.insert(path.clone(), s).is_some() {
-                    warn!("Duplicate path in flattened JSON: {path}");
-                }
+    /// Whether the given value matches this pattern.
+    pub(super) fn matches(&self, value: &str) -> bool {
+        let value = value.to_lowercase();
+
+        match self {
+            Self::Glob(glob) => glob.matches(&value),
+            Self::Word(pattern) => value.matches_word(pattern),
+            Self::WordGlob { pattern, regex } => {
+                value == *pattern || regex.as_ref().map_or(false, |re| re.is_match(&value))
             }
-            JsonValue::Number(_) | JsonValue::Bool(_) | JsonValue::Array(_) | JsonValue::Null => {}
         }
     }
-
-    /// Value associated with the given `path`.
-    pub fn get(&self, path: &str) -> Option<&str> {
-        self.map.get(path).map(|s| s.as_str())
-    }
 }
 
 #[cfg(test)]
@@ -453,12 +494,14 @@ mod tests {
 
     use assert_matches::assert_matches;
     use js_int::{int, uint};
-    use maplit::btreemap;
     use serde_json::{
         from_value as from_json_value, json, to_value as to_json_value, Value as JsonValue,
     };
 
-    use super::{FlattenedJson, PushCondition, PushConditionRoomCtx, RoomMemberCountIs, StrExt};
+    use super::{
+        FlattenedJson, PushCondition, PushConditionRoomCtx, RoomMemberCountIs, ScalarJsonValue,
+        StrExt,
+    };
     use crate::{power_levels::NotificationPowerLevels, room_id, serde::Raw, user_id};
 
     #[test]
@@ -561,6 +604,38 @@ mod tests {
         assert_eq!(key, "room");
     }
 
+    #[test]
+    fn serialize_event_property_is_condition() {
+        let json_data = json!({
+            "key": "content.m\\.mentions.room",
+            "kind": "event_property_is",
+            "value": true
+        });
+        assert_eq!(
+            json_data,
+            to_json_value(PushCondition::EventPropertyIs {
+                key: r"content.m\.mentions.room".into(),
+                value: true.into(),
+            })
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn deserialize_event_property_contains_condition() {
+        let json_data = json!({
+            "key": "content.m\\.mentions.user_ids",
+            "kind": "event_property_contains",
+            "value": "@alice:example.org"
+        });
+        let (key, value) = assert_matches!(
+            from_json_value::<PushCondition>(json_data).unwrap(),
+            PushCondition::EventPropertyContains { key, value } => (key, value)
+        );
+        assert_eq!(key, r"content.m\.mentions.user_ids");
+        assert_eq!(value, ScalarJsonValue::String("@alice:example.org".into()));
+    }
+
     #[test]
     fn words_match() {
         assert!("foo bar".matches_word("foo"));
@@ -734,6 +809,53 @@ mod tests {
 
         assert!(!sender_notification_permission.applies(&first_event, &context));
         assert!(sender_notification_permission.applies(&second_event, &context));
+
+        let mentions_event_raw = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "sender": "@worthy_whale:server.name",
+                "content": {
+                    "msgtype": "m.text",
+                    "body": "Hi everyone!",
+                    "m.mentions": {
+                        "user_ids": ["@gorilla:server.name", "@party_bot:server.name"],
+                        "room": true
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let mentions_event = FlattenedJson::from_raw(&mentions_event_raw);
+
+        let room_mention = PushCondition::EventPropertyIs {
+            key: r"content.m\.mentions.room".into(),
+            value: true.into(),
+        };
+
+        assert!(room_mention.applies(&mentions_event, &context));
+        assert!(!room_mention.applies(&first_event, &context));
+
+        let msgtype_is = PushCondition::EventPropertyIs {
+            key: "content.msgtype".into(),
+            value: "m.text".into(),
+        };
+
+        assert!(msgtype_is.applies(&first_event, &context));
+        assert!(!msgtype_is.applies(&second_event, &context));
+
+        let user_mention = PushCondition::EventPropertyContains {
+            key: r"content.m\.mentions.user_ids".into(),
+            value: "@gorilla:server.name".into(),
+        };
+
+        assert!(user_mention.applies(&mentions_event, &context));
+        assert!(!user_mention.applies(&first_event, &context));
+
+        let other_user_mention = PushCondition::EventPropertyContains {
+            key: r"content.m\.mentions.user_ids".into(),
+            value: "@other:server.name".into(),
+        };
+
+        assert!(!other_user_mention.applies(&mentions_event, &context));
     }
 
     #[cfg(feature = "unstable-msc3932")]
@@ -785,47 +907,4 @@ mod tests {
         assert!(room_version_condition.applies(&simple_event, &context_matching));
         assert!(!room_version_condition.applies(&simple_event, &context_not_matching));
     }
-
-    #[test]
-    fn flattened_json_values() {
-        let raw = serde_json::from_str::<Raw<JsonValue>>(
-            r#"{
-                "string": "Hello World",
-                "number": 10,
-                "array": [1, 2],
-                "boolean": true,
-                "null": null
-            }"#,
-        )
-        .unwrap();
-
-        let flattened = FlattenedJson::from_raw(&raw);
-        assert_eq!(flattened.map, btreemap! { "string".into() => "Hello World".into() });
-    }
-
-    #[test]
-    fn flattened_json_nested() {
-        let raw = serde_json::from_str::<Raw<JsonValue>>(
-            r#"{
-                "desc": "Level 0",
-                "up": {
-                    "desc": "Level 1",
-                    "up": {
-                        "desc": "Level 2"
-                    }
-                }
-            }"#,
-        )
-        .unwrap();
-
-        let flattened = FlattenedJson::from_raw(&raw);
-        assert_eq!(
-            flattened.map,
-            btreemap! {
-                "desc".into() => "Level 0".into(),
-                "up.desc".into() => "Level 1".into(),
-                "up.up.desc".into() => "Level 2".into(),
-            },
-        );
-    }
 }
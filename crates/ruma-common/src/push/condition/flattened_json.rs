@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+
+use js_int::Int;
+use serde::{de, Deserialize, Serialize, Serializer};
+use serde_json::{to_value as to_json_value, value::Value as JsonValue};
+use tracing::{instrument, warn};
+
+use crate::serde::Raw;
+
+/// The flattened representation of a JSON object.
+#[derive(Clone, Debug)]
+pub struct FlattenedJson {
+    /// The internal map containing the flattened JSON as a pair path, value.
+    map: BTreeMap<String, FlattenedJsonValue>,
+
+    /// Whether the content of the event has an `m.mentions` field.
+    has_mentions: bool,
+}
+
+impl FlattenedJson {
+    /// Create a `FlattenedJson` from `Raw`.
+    pub fn from_raw<T>(raw: &Raw<T>) -> Self {
+        let mut s = Self { map: BTreeMap::new(), has_mentions: false };
+        s.flatten_value(to_json_value(raw).unwrap(), "".into());
+        s
+    }
+
+    /// Flatten and insert the `value` at `path`.
+    #[instrument(skip(self, value))]
+    fn flatten_value(&mut self, value: JsonValue, path: String) {
+        if let JsonValue::Object(fields) = value {
+            if path == r"content.m\.mentions" {
+                self.has_mentions = true;
+            }
+
+            for (key, value) in fields {
+                let key = escape_key(&key);
+                let path = if path.is_empty() { key } else { format!("{path}.{key}") };
+                self.flatten_value(value, path);
+            }
+        } else if let Some(value) = FlattenedJsonValue::from_json_value(value) {
+            if self.map.insert(path.clone(), value).is_some() {
+                warn!("Duplicate path in flattened JSON: {path}");
+            }
+        }
+    }
+
+    /// String value associated with the given `path`.
+    ///
+    /// Returns `None` if there is no value at `path` or if it is not a string.
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.map.get(path).and_then(FlattenedJsonValue::as_str)
+    }
+
+    /// Value associated with the given `path`.
+    pub fn get_value(&self, path: &str) -> Option<&FlattenedJsonValue> {
+        self.map.get(path)
+    }
+
+    /// Whether the content of the event has an [`m.mentions`] field.
+    ///
+    /// The legacy mention push rules don't apply to events with an `m.mentions` field.
+    ///
+    /// [`m.mentions`]: https://spec.matrix.org/latest/client-server-api/#user-and-room-mentions
+    pub fn contains_mentions(&self) -> bool {
+        self.has_mentions
+    }
+}
+
+/// Escape a key of a JSON object for its path in a `FlattenedJson`.
+///
+/// Dots and backslashes are escaped with a backslash, so `m.mentions` becomes `m\.mentions`.
+fn escape_key(key: &str) -> String {
+    key.replace('\\', r"\\").replace('.', r"\.")
+}
+
+/// A value of a flattened JSON object.
+///
+/// Nested objects are flattened, and only arrays of scalar values are kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum FlattenedJsonValue {
+    /// A scalar value.
+    Scalar(ScalarJsonValue),
+
+    /// An array of scalar values.
+    ///
+    /// Values of the array that are not scalar are ignored.
+    Array(Vec<ScalarJsonValue>),
+}
+
+impl FlattenedJsonValue {
+    fn from_json_value(value: JsonValue) -> Option<Self> {
+        match value {
+            JsonValue::Array(values) => Some(Self::Array(
+                values.into_iter().filter_map(ScalarJsonValue::from_json_value).collect(),
+            )),
+            value => ScalarJsonValue::from_json_value(value).map(Self::Scalar),
+        }
+    }
+
+    /// If this is a string, get a reference to it.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Scalar(value) => value.as_str(),
+            Self::Array(_) => None,
+        }
+    }
+
+    /// If this is a scalar value, get a reference to it.
+    pub fn as_scalar(&self) -> Option<&ScalarJsonValue> {
+        match self {
+            Self::Scalar(value) => Some(value),
+            Self::Array(_) => None,
+        }
+    }
+
+    /// If this is an array, get a reference to its values.
+    pub fn as_array(&self) -> Option<&[ScalarJsonValue]> {
+        match self {
+            Self::Scalar(_) => None,
+            Self::Array(values) => Some(values),
+        }
+    }
+}
+
+/// A scalar JSON value, that can be compared in push conditions.
+///
+/// Floating point numbers are not allowed in canonical JSON, so they are not supported.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum ScalarJsonValue {
+    /// Represents a `null` value.
+    #[default]
+    Null,
+
+    /// Represents a boolean.
+    Bool(bool),
+
+    /// Represents an integer.
+    Integer(Int),
+
+    /// Represents a string.
+    String(String),
+}
+
+impl ScalarJsonValue {
+    fn from_json_value(value: JsonValue) -> Option<Self> {
+        Some(match value {
+            JsonValue::Null => Self::Null,
+            JsonValue::Bool(b) => Self::Bool(b),
+            JsonValue::Number(n) => Self::Integer(n.as_i64()?.try_into().ok()?),
+            JsonValue::String(s) => Self::String(s),
+            JsonValue::Array(_) | JsonValue::Object(_) => return None,
+        })
+    }
+
+    /// If this is a string, get a reference to it.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for ScalarJsonValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Null => serializer.serialize_unit(),
+            Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::Integer(n) => n.serialize(serializer),
+            Self::String(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ScalarJsonValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let value = JsonValue::deserialize(deserializer)?;
+        Self::from_json_value(value)
+            .ok_or_else(|| de::Error::custom("expected a string, an integer, a boolean or null"))
+    }
+}
+
+impl From<bool> for ScalarJsonValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<Int> for ScalarJsonValue {
+    fn from(value: Int) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<String> for ScalarJsonValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for ScalarJsonValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use js_int::int;
+    use maplit::btreemap;
+    use serde_json::{from_value as from_json_value, json, Value as JsonValue};
+
+    use super::{FlattenedJson, FlattenedJsonValue, ScalarJsonValue};
+    use crate::serde::Raw;
+
+    #[test]
+    fn flattened_json_values() {
+        let raw = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "string": "Hello World",
+                "number": 10,
+                "array": [1, "two", 3.5, {}],
+                "boolean": true,
+                "null": null,
+                "float": 1.5
+            }"#,
+        )
+        .unwrap();
+
+        let flattened = FlattenedJson::from_raw(&raw);
+        assert_eq!(
+            flattened.map,
+            btreemap! {
+                "string".into() => FlattenedJsonValue::Scalar("Hello World".into()),
+                "number".into() => FlattenedJsonValue::Scalar(int!(10).into()),
+                "array".into() => FlattenedJsonValue::Array(vec![int!(1).into(), "two".into()]),
+                "boolean".into() => FlattenedJsonValue::Scalar(true.into()),
+                "null".into() => FlattenedJsonValue::Scalar(ScalarJsonValue::Null),
+            }
+        );
+        assert_eq!(flattened.get("string"), Some("Hello World"));
+        assert_eq!(flattened.get("number"), None);
+    }
+
+    #[test]
+    fn flattened_json_nested() {
+        let raw = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "desc": "Level 0",
+                "up": {
+                    "desc": "Level 1",
+                    "up": {
+                        "desc": "Level 2"
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let flattened = FlattenedJson::from_raw(&raw);
+        assert_eq!(
+            flattened.map,
+            btreemap! {
+                "desc".into() => FlattenedJsonValue::Scalar("Level 0".into()),
+                "up.desc".into() => FlattenedJsonValue::Scalar("Level 1".into()),
+                "up.up.desc".into() => FlattenedJsonValue::Scalar("Level 2".into()),
+            },
+        );
+    }
+
+    #[test]
+    fn flattened_json_escaped_keys() {
+        let raw = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "content": {
+                    "m.mentions": {},
+                    "back\\slash": "Backslash"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let flattened = FlattenedJson::from_raw(&raw);
+        assert_eq!(
+            flattened.map,
+            btreemap! {
+                r"content.back\\slash".into() => FlattenedJsonValue::Scalar("Backslash".into()),
+            },
+        );
+        assert!(flattened.contains_mentions());
+    }
+
+    #[test]
+    fn scalar_json_value_serde() {
+        assert_eq!(
+            from_json_value::<ScalarJsonValue>(json!("string")).unwrap(),
+            ScalarJsonValue::String("string".into())
+        );
+        assert_eq!(
+            from_json_value::<ScalarJsonValue>(json!(-5)).unwrap(),
+            ScalarJsonValue::Integer(int!(-5))
+        );
+        assert_eq!(from_json_value::<ScalarJsonValue>(json!(null)).unwrap(), ScalarJsonValue::Null);
+        from_json_value::<ScalarJsonValue>(json!(1.5)).unwrap_err();
+        from_json_value::<ScalarJsonValue>(json!([true])).unwrap_err();
+
+        assert_eq!(serde_json::to_value(ScalarJsonValue::Bool(true)).unwrap(), json!(true));
+        assert_eq!(serde_json::to_value(ScalarJsonValue::Null).unwrap(), json!(null));
+    }
+}
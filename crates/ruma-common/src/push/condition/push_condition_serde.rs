@@ -5,7 +5,7 @@ use crate::serde::from_raw_json_value;
 
 #[cfg(feature = "unstable-msc3931")]
 use super::RoomVersionFeature;
-use super::{PushCondition, RoomMemberCountIs};
+use super::{PushCondition, RoomMemberCountIs, ScalarJsonValue};
 
 impl Serialize for PushCondition {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -31,7 +31,9 @@ impl<'de> Deserialize<'de> for PushCondition {
             "event_match"
             | "contains_display_name"
             | "room_member_count"
-            | "sender_notification_permission" => {
+            | "sender_notification_permission"
+            | "event_property_is"
+            | "event_property_contains" => {
                 let helper: PushConditionSerDeHelper = from_raw_json_value(&json)?;
                 Ok(helper.into())
             }
@@ -93,6 +95,24 @@ enum PushConditionSerDeHelper {
         /// The feature the room must support for the push rule to apply.
         feature: RoomVersionFeature,
     },
+
+    /// Exact value match on a property of the event.
+    EventPropertyIs {
+        /// The dot-separated path of the property of the event to match.
+        key: String,
+
+        /// The value to match against.
+        value: ScalarJsonValue,
+    },
+
+    /// Exact value match on a value in an array property of the event.
+    EventPropertyContains {
+        /// The dot-separated path of the property of the event to match.
+        key: String,
+
+        /// The value to match against.
+        value: ScalarJsonValue,
+    },
 }
 
 impl From<PushConditionSerDeHelper> for PushCondition {
@@ -110,6 +130,12 @@ impl From<PushConditionSerDeHelper> for PushCondition {
             PushConditionSerDeHelper::RoomVersionSupports { feature } => {
                 Self::RoomVersionSupports { feature }
             }
+            PushConditionSerDeHelper::EventPropertyIs { key, value } => {
+                Self::EventPropertyIs { key, value }
+            }
+            PushConditionSerDeHelper::EventPropertyContains { key, value } => {
+                Self::EventPropertyContains { key, value }
+            }
         }
     }
 }
@@ -125,6 +151,10 @@ impl From<PushCondition> for PushConditionSerDeHelper {
             }
             #[cfg(feature = "unstable-msc3931")]
             PushCondition::RoomVersionSupports { feature } => Self::RoomVersionSupports { feature },
+            PushCondition::EventPropertyIs { key, value } => Self::EventPropertyIs { key, value },
+            PushCondition::EventPropertyContains { key, value } => {
+                Self::EventPropertyContains { key, value }
+            }
             PushCondition::_Custom(_) => unimplemented!(),
         }
     }
@@ -0,0 +1,292 @@
+use js_int::{int, uint};
+
+use super::PushConditionRoomCtx;
+#[cfg(feature = "unstable-msc3931")]
+use super::RoomVersionFeature;
+use crate::{
+    events::{
+        room::{
+            create::SyncRoomCreateEvent,
+            member::{MembershipState, SyncRoomMemberEvent},
+            power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent},
+        },
+        AnySyncStateEvent,
+    },
+    OwnedRoomId, OwnedUserId,
+};
+
+impl PushConditionRoomCtx {
+    /// Creates a `PushConditionRoomCtx` for the given user from the current state of the room.
+    ///
+    /// The following events of `state` are used, the other events are ignored:
+    ///
+    /// - `m.room.create`, to get the room version and the creator of the room,
+    /// - `m.room.power_levels`, to get the power levels of the users and the notification power
+    ///   levels. If it is missing, the default power levels of the Matrix specification are used,
+    ///   with a power level of 100 for the creator of the room.
+    /// - `m.room.member`, to get the display name of the user and the number of joined members.
+    ///
+    /// If the `m.room.member` event of the user has no display name, `user_display_name` is empty.
+    ///
+    /// `state` should contain all the `m.room.member` events of the room to compute the right
+    /// `member_count`. If the members of the room are lazy-loaded, the field can be overridden
+    /// with the number of joined members provided by the homeserver.
+    pub fn from_room_state<'a>(
+        room_id: OwnedRoomId,
+        user_id: OwnedUserId,
+        state: impl IntoIterator<Item = &'a AnySyncStateEvent>,
+    ) -> Self {
+        let mut create = None;
+        let mut power_levels = None;
+        let mut user_display_name = None;
+        let mut member_count = uint!(0);
+
+        for event in state {
+            match event {
+                AnySyncStateEvent::RoomCreate(ev) => create = Some(ev),
+                AnySyncStateEvent::RoomPowerLevels(ev) => power_levels = Some(ev.power_levels()),
+                AnySyncStateEvent::RoomMember(ev) => {
+                    if *ev.membership() == MembershipState::Join {
+                        member_count += uint!(1);
+                    }
+
+                    if *ev.state_key() == user_id {
+                        user_display_name = match ev {
+                            SyncRoomMemberEvent::Original(ev) => ev.content.displayname.clone(),
+                            SyncRoomMemberEvent::Redacted(_) => None,
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let power_levels = power_levels.unwrap_or_else(|| {
+            let mut power_levels = RoomPowerLevels::from(RoomPowerLevelsEventContent::new());
+
+            if let Some(creator) = create.map(room_creator) {
+                power_levels.users.insert(creator, int!(100));
+            }
+
+            power_levels
+        });
+
+        Self {
+            room_id,
+            member_count,
+            user_id,
+            user_display_name: user_display_name.unwrap_or_default(),
+            users_power_levels: power_levels.users,
+            default_power_level: power_levels.users_default,
+            notification_power_levels: power_levels.notifications,
+            #[cfg(feature = "unstable-msc3931")]
+            supported_features: create
+                .map(|ev| {
+                    let room_version = match ev {
+                        SyncRoomCreateEvent::Original(ev) => &ev.content.room_version,
+                        SyncRoomCreateEvent::Redacted(ev) => &ev.content.room_version,
+                    };
+                    RoomVersionFeature::list_for_room_version(room_version)
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The creator of the room of the given `m.room.create` event.
+///
+/// The `creator` field of the content is ignored in room versions that use the sender of the event
+/// as the creator of the room.
+fn room_creator(event: &SyncRoomCreateEvent) -> OwnedUserId {
+    let (room_version, creator) = match event {
+        SyncRoomCreateEvent::Original(ev) => {
+            (&ev.content.room_version, ev.content.creator.as_ref())
+        }
+        SyncRoomCreateEvent::Redacted(ev) => {
+            (&ev.content.room_version, ev.content.creator.as_ref())
+        }
+    };
+    let use_sender =
+        room_version.rules().map_or(false, |rules| rules.authorization.use_room_create_sender);
+
+    match creator {
+        Some(creator) if !use_sender => creator.clone(),
+        _ => event.sender().to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use js_int::{int, uint};
+    use serde_json::{from_value as from_json_value, json, Value as JsonValue};
+
+    use super::PushConditionRoomCtx;
+    use crate::{events::AnySyncStateEvent, room_id, user_id};
+
+    fn state_event(json: JsonValue) -> AnySyncStateEvent {
+        from_json_value(json).unwrap()
+    }
+
+    fn create_event(room_version: &str) -> AnySyncStateEvent {
+        create_event_with_content(json!({ "room_version": room_version }))
+    }
+
+    fn create_event_with_content(content: JsonValue) -> AnySyncStateEvent {
+        state_event(json!({
+            "type": "m.room.create",
+            "state_key": "",
+            "sender": "@creator:server.name",
+            "event_id": "$create",
+            "origin_server_ts": 1,
+            "content": content,
+        }))
+    }
+
+    fn member_event(
+        user_id: &str,
+        membership: &str,
+        displayname: Option<&str>,
+    ) -> AnySyncStateEvent {
+        state_event(json!({
+            "type": "m.room.member",
+            "state_key": user_id,
+            "sender": user_id,
+            "event_id": format!("$member_{user_id}"),
+            "origin_server_ts": 1,
+            "content": { "membership": membership, "displayname": displayname },
+        }))
+    }
+
+    #[test]
+    fn from_room_state() {
+        let state = [
+            create_event("10"),
+            state_event(json!({
+                "type": "m.room.power_levels",
+                "state_key": "",
+                "sender": "@creator:server.name",
+                "event_id": "$power_levels",
+                "origin_server_ts": 1,
+                "content": {
+                    "users": { "@creator:server.name": 100, "@mod:server.name": 50 },
+                    "users_default": 10,
+                    "notifications": { "room": 40 },
+                },
+            })),
+            member_event("@creator:server.name", "join", None),
+            member_event("@mod:server.name", "join", Some("Moderator")),
+            member_event("@jj:server.name", "join", Some("Jolly Jumper")),
+            member_event("@left:server.name", "leave", None),
+            member_event("@invited:server.name", "invite", None),
+        ];
+
+        let ctx = PushConditionRoomCtx::from_room_state(
+            room_id!("!room:server.name").to_owned(),
+            user_id!("@jj:server.name").to_owned(),
+            &state,
+        );
+
+        assert_eq!(ctx.room_id, "!room:server.name");
+        assert_eq!(ctx.member_count, uint!(3));
+        assert_eq!(ctx.user_id, "@jj:server.name");
+        assert_eq!(ctx.user_display_name, "Jolly Jumper");
+        assert_eq!(
+            ctx.users_power_levels,
+            [
+                (user_id!("@creator:server.name").to_owned(), int!(100)),
+                (user_id!("@mod:server.name").to_owned(), int!(50)),
+            ]
+            .into()
+        );
+        assert_eq!(ctx.default_power_level, int!(10));
+        assert_eq!(ctx.notification_power_levels.room, int!(40));
+    }
+
+    #[test]
+    fn from_room_state_without_power_levels() {
+        let state = [
+            create_event("1"),
+            member_event("@creator:server.name", "join", Some("Creator")),
+            member_event("@jj:server.name", "join", None),
+        ];
+
+        let ctx = PushConditionRoomCtx::from_room_state(
+            room_id!("!room:server.name").to_owned(),
+            user_id!("@jj:server.name").to_owned(),
+            &state,
+        );
+
+        assert_eq!(ctx.member_count, uint!(2));
+        assert_eq!(ctx.user_display_name, "");
+        assert_eq!(
+            ctx.users_power_levels,
+            [(user_id!("@creator:server.name").to_owned(), int!(100))].into()
+        );
+        assert_eq!(ctx.default_power_level, int!(0));
+        assert_eq!(ctx.notification_power_levels.room, int!(50));
+    }
+
+    #[test]
+    fn from_room_state_ignores_creator_field_in_v11() {
+        let state = [create_event_with_content(json!({
+            "room_version": "11",
+            "creator": "@stale:server.name",
+        }))];
+
+        let ctx = PushConditionRoomCtx::from_room_state(
+            room_id!("!room:server.name").to_owned(),
+            user_id!("@jj:server.name").to_owned(),
+            &state,
+        );
+
+        assert_eq!(
+            ctx.users_power_levels,
+            [(user_id!("@creator:server.name").to_owned(), int!(100))].into()
+        );
+
+        // The `creator` field is used in older room versions.
+        let state = [create_event_with_content(json!({
+            "room_version": "10",
+            "creator": "@stale:server.name",
+        }))];
+
+        let ctx = PushConditionRoomCtx::from_room_state(
+            room_id!("!room:server.name").to_owned(),
+            user_id!("@jj:server.name").to_owned(),
+            &state,
+        );
+
+        assert_eq!(
+            ctx.users_power_levels,
+            [(user_id!("@stale:server.name").to_owned(), int!(100))].into()
+        );
+    }
+
+    #[cfg(feature = "unstable-msc3932")]
+    #[test]
+    fn from_room_state_supported_features() {
+        use super::RoomVersionFeature;
+        use crate::{room_version_rules::RoomVersionRules, RoomVersionId};
+
+        let mut rules = RoomVersionRules::V10;
+        rules.push_features = &[RoomVersionFeature::ExtensibleEvents];
+        RoomVersionRules::register(
+            RoomVersionId::try_from("com.example.extensible").unwrap(),
+            rules,
+        );
+
+        let ctx = PushConditionRoomCtx::from_room_state(
+            room_id!("!room:server.name").to_owned(),
+            user_id!("@jj:server.name").to_owned(),
+            &[create_event("com.example.extensible")],
+        );
+        assert_eq!(ctx.supported_features, [RoomVersionFeature::ExtensibleEvents]);
+
+        let ctx = PushConditionRoomCtx::from_room_state(
+            room_id!("!room:server.name").to_owned(),
+            user_id!("@jj:server.name").to_owned(),
+            &[create_event("10")],
+        );
+        assert_eq!(ctx.supported_features, []);
+    }
+}
@@ -0,0 +1,434 @@
+//! A push rule evaluator compiled from a `Ruleset`.
+
+use super::{
+    condition::PatternMatcher, Action, AnyPushRuleRef, FlattenedJson, PredefinedContentRuleId,
+    PredefinedOverrideRuleId, PushCondition, PushConditionRoomCtx, Ruleset,
+};
+#[cfg(feature = "unstable-msc3932")]
+use super::{ConditionalPushRule, RoomVersionFeature};
+
+/// A push rule evaluator compiled from the `Ruleset` of a user.
+///
+/// The glob patterns of the rules are compiled once when the evaluator is created, so it is
+/// much faster than [`Ruleset::get_actions`] to evaluate the rules of a user for many events.
+///
+/// The events are passed as [`FlattenedJson`], so an event can be flattened once and evaluated
+/// for all the recipients.
+///
+/// # Example
+///
+/// ```
+/// # use ruma_common::push::{FlattenedJson, PushConditionRoomCtx, PushEvaluator, Ruleset};
+/// # use ruma_common::serde::Raw;
+/// # fn recipients() -> Vec<(PushEvaluator, PushConditionRoomCtx)> { Vec::new() }
+/// # let raw_event = Raw::new(&serde_json::json!({})).unwrap();
+/// let event = FlattenedJson::from_raw(&raw_event);
+///
+/// for (evaluator, context) in recipients() {
+///     let actions = evaluator.get_actions(&event, &context);
+///     // Send the notification…
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PushEvaluator {
+    /// The rules that were compiled.
+    ruleset: Ruleset,
+
+    /// The compiled rules, in the order of `ruleset.iter()`.
+    rules: Vec<CompiledRule>,
+}
+
+impl PushEvaluator {
+    /// Compiles the given `Ruleset`.
+    pub fn new(ruleset: Ruleset) -> Self {
+        let rules = ruleset.iter().map(CompiledRule::new).collect();
+        Self { ruleset, rules }
+    }
+
+    /// The `Ruleset` that was compiled.
+    pub fn ruleset(&self) -> &Ruleset {
+        &self.ruleset
+    }
+
+    /// Get the first push rule that applies to this event, if any.
+    ///
+    /// Returns the same rule as [`Ruleset::get_match`].
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the message and room at the time of the event.
+    pub fn get_match(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> Option<AnyPushRuleRef<'_>> {
+        if event.get("sender").map_or(false, |sender| sender == context.user_id) {
+            // no need to look at the rules if the event was by the user themselves
+            return None;
+        }
+
+        self.ruleset
+            .iter()
+            .zip(&self.rules)
+            .find(|(_, compiled)| compiled.applies(event, context))
+            .map(|(rule, _)| rule)
+    }
+
+    /// Get the push actions that apply to this event.
+    ///
+    /// Returns an empty slice if no push rule applies, like [`Ruleset::get_actions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the message and room at the time of the event.
+    pub fn get_actions(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> &[Action] {
+        self.get_match(event, context).map(|rule| rule.actions()).unwrap_or(&[])
+    }
+}
+
+impl From<Ruleset> for PushEvaluator {
+    fn from(ruleset: Ruleset) -> Self {
+        Self::new(ruleset)
+    }
+}
+
+/// A compiled push rule.
+#[derive(Clone, Debug)]
+enum CompiledRule {
+    /// A disabled rule, that never applies.
+    Disabled,
+
+    /// An override or underride rule.
+    Conditions {
+        /// The compiled conditions of the rule.
+        conditions: Vec<CompiledCondition>,
+
+        /// Whether the rule is disabled in rooms that support extensible events.
+        #[cfg(feature = "unstable-msc3932")]
+        disabled_with_extensible_events: bool,
+    },
+
+    /// A content, room or sender rule.
+    EventMatch(EventMatcher),
+
+    /// A legacy mention rule, that doesn't apply to events with intentional mentions.
+    LegacyMention(Box<CompiledRule>),
+}
+
+impl CompiledRule {
+    fn new(rule: AnyPushRuleRef<'_>) -> Self {
+        if !rule.enabled() {
+            return Self::Disabled;
+        }
+
+        let compiled = match rule {
+            AnyPushRuleRef::Override(rule) | AnyPushRuleRef::Underride(rule) => Self::Conditions {
+                conditions: rule.conditions.iter().map(CompiledCondition::new).collect(),
+                #[cfg(feature = "unstable-msc3932")]
+                disabled_with_extensible_events: disabled_with_extensible_events(rule),
+            },
+            AnyPushRuleRef::Content(rule) => {
+                Self::EventMatch(EventMatcher::new("content.body", &rule.pattern))
+            }
+            AnyPushRuleRef::Room(rule) => {
+                Self::EventMatch(EventMatcher::new("room_id", rule.rule_id.as_str()))
+            }
+            AnyPushRuleRef::Sender(rule) => {
+                Self::EventMatch(EventMatcher::new("sender", rule.rule_id.as_str()))
+            }
+        };
+
+        if is_legacy_mention(rule) {
+            Self::LegacyMention(Box::new(compiled))
+        } else {
+            compiled
+        }
+    }
+
+    fn applies(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        match self {
+            Self::Disabled => false,
+            Self::Conditions {
+                conditions,
+                #[cfg(feature = "unstable-msc3932")]
+                disabled_with_extensible_events,
+            } => {
+                #[cfg(feature = "unstable-msc3932")]
+                if *disabled_with_extensible_events
+                    && context.supported_features.contains(&RoomVersionFeature::ExtensibleEvents)
+                {
+                    return false;
+                }
+
+                conditions.iter().all(|condition| condition.applies(event, context))
+            }
+            Self::EventMatch(matcher) => matcher.applies(event, context),
+            Self::LegacyMention(rule) => !event.contains_mentions() && rule.applies(event, context),
+        }
+    }
+}
+
+/// Whether the given rule is a legacy mention rule.
+///
+/// See [`ConditionalPushRule::applies`](super::ConditionalPushRule::applies) and
+/// [`PatternedPushRule::applies_to`](super::PatternedPushRule::applies_to).
+fn is_legacy_mention(rule: AnyPushRuleRef<'_>) -> bool {
+    match rule {
+        AnyPushRuleRef::Override(rule) => {
+            rule.rule_id == PredefinedOverrideRuleId::ContainsDisplayName.as_ref()
+                || rule.rule_id == PredefinedOverrideRuleId::RoomNotif.as_ref()
+        }
+        AnyPushRuleRef::Content(rule) => {
+            rule.rule_id == PredefinedContentRuleId::ContainsUserName.as_ref()
+        }
+        _ => false,
+    }
+}
+
+/// Whether the given rule is treated as disabled in rooms that support extensible events.
+///
+/// See [`ConditionalPushRule::applies`](super::ConditionalPushRule::applies).
+#[cfg(feature = "unstable-msc3932")]
+fn disabled_with_extensible_events(rule: &ConditionalPushRule) -> bool {
+    // These rules always apply.
+    rule.rule_id != PredefinedOverrideRuleId::Master.as_ref()
+        && rule.rule_id != PredefinedOverrideRuleId::RoomNotif.as_ref()
+        && rule.rule_id != PredefinedOverrideRuleId::ContainsDisplayName.as_ref()
+        && rule.rule_id != PredefinedOverrideRuleId::IsUserMention.as_ref()
+        && rule.rule_id != PredefinedOverrideRuleId::IsRoomMention.as_ref()
+        && !rule
+            .conditions
+            .iter()
+            .any(|condition| matches!(condition, PushCondition::RoomVersionSupports { .. }))
+}
+
+/// A compiled push condition.
+#[derive(Clone, Debug)]
+enum CompiledCondition {
+    /// An `event_match` condition.
+    EventMatch(EventMatcher),
+
+    /// Another condition, that doesn't need to be compiled.
+    Other(PushCondition),
+}
+
+impl CompiledCondition {
+    fn new(condition: &PushCondition) -> Self {
+        match condition {
+            PushCondition::EventMatch { key, pattern } => {
+                Self::EventMatch(EventMatcher::new(key, pattern))
+            }
+            condition => Self::Other(condition.clone()),
+        }
+    }
+
+    fn applies(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        match self {
+            Self::EventMatch(matcher) => matcher.applies(event, context),
+            Self::Other(condition) => condition.applies(event, context),
+        }
+    }
+}
+
+/// A compiled match of a pattern on a field of the event.
+#[derive(Clone, Debug)]
+struct EventMatcher {
+    /// The dot-separated field of the event to match.
+    key: String,
+
+    /// The compiled pattern.
+    pattern: PatternMatcher,
+}
+
+impl EventMatcher {
+    fn new(key: &str, pattern: &str) -> Self {
+        Self { key: key.to_owned(), pattern: PatternMatcher::new(pattern, key == "content.body") }
+    }
+
+    fn applies(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        let value = match self.key.as_str() {
+            "room_id" => context.room_id.as_str(),
+            key => match event.get(key) {
+                Some(v) => v,
+                None => return false,
+            },
+        };
+
+        self.pattern.matches(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use js_int::{int, uint};
+    use serde_json::{json, to_value as to_json_value, Value as JsonValue};
+
+    use super::PushEvaluator;
+    use crate::{
+        power_levels::NotificationPowerLevels,
+        push::{
+            Action, ConditionalPushRule, FlattenedJson, NewPushRule, NewSimplePushRule,
+            PatternedPushRule, PushCondition, PushConditionRoomCtx, RuleKind, Ruleset, Tweak,
+        },
+        room_id,
+        serde::Raw,
+        user_id,
+    };
+
+    fn context() -> PushConditionRoomCtx {
+        PushConditionRoomCtx {
+            room_id: room_id!("!dm:server.name").to_owned(),
+            member_count: uint!(2),
+            user_id: user_id!("@jj:server.name").to_owned(),
+            user_display_name: "Jolly Jumper".into(),
+            users_power_levels: [(user_id!("@rantanplan:server.name").to_owned(), int!(100))]
+                .into(),
+            default_power_level: int!(50),
+            notification_power_levels: NotificationPowerLevels { room: int!(50) },
+            #[cfg(feature = "unstable-msc3931")]
+            supported_features: Default::default(),
+        }
+    }
+
+    fn ruleset() -> Ruleset {
+        let mut ruleset = Ruleset::server_default(user_id!("@jj:server.name"));
+        ruleset.content.insert(PatternedPushRule {
+            actions: vec![Action::Notify],
+            default: false,
+            enabled: true,
+            rule_id: "lucky".into(),
+            pattern: "luck*".into(),
+        });
+        ruleset.override_.insert(ConditionalPushRule {
+            actions: vec![Action::DontNotify],
+            default: false,
+            enabled: true,
+            rule_id: "no.notices".into(),
+            conditions: vec![PushCondition::EventMatch {
+                key: "content.msgtype".into(),
+                pattern: "m.not?ce".into(),
+            }],
+        });
+        ruleset
+            .insert(
+                NewPushRule::Sender(NewSimplePushRule::new(
+                    user_id!("@dalton:server.name").to_owned(),
+                    vec![Action::SetTweak(Tweak::Highlight(true))],
+                )),
+                None,
+                None,
+            )
+            .unwrap();
+        ruleset
+    }
+
+    fn event(json: JsonValue) -> (Raw<JsonValue>, FlattenedJson) {
+        let raw = Raw::new(&json).unwrap();
+        let flattened = FlattenedJson::from_raw(&raw);
+        (raw, flattened)
+    }
+
+    #[test]
+    fn same_actions_as_ruleset() {
+        let ruleset = ruleset();
+        let evaluator = PushEvaluator::new(ruleset.clone());
+        let context = context();
+
+        let events = [
+            json!({
+                "sender": "@rantanplan:server.name",
+                "type": "m.room.message",
+                "content": { "msgtype": "m.text", "body": "Good luck, Jolly Jumper!" },
+            }),
+            json!({
+                "sender": "@rantanplan:server.name",
+                "type": "m.room.message",
+                "content": { "msgtype": "m.text", "body": "Unlucky you" },
+            }),
+            json!({
+                "sender": "@rantanplan:server.name",
+                "type": "m.room.message",
+                "content": { "msgtype": "m.notice", "body": "Jolly Jumper" },
+            }),
+            json!({
+                "sender": "@dalton:server.name",
+                "type": "m.room.message",
+                "content": { "msgtype": "m.text", "body": "Hello" },
+            }),
+            json!({
+                "sender": "@jj:server.name",
+                "type": "m.room.message",
+                "content": { "msgtype": "m.text", "body": "Jolly Jumper" },
+            }),
+            json!({
+                "sender": "@rantanplan:server.name",
+                "type": "m.room.member",
+                "state_key": "@jj:server.name",
+                "content": { "membership": "invite" },
+            }),
+            json!({
+                "sender": "@rantanplan:server.name",
+                "type": "m.room.message",
+                "content": { "msgtype": "m.text", "body": "@room" },
+            }),
+            json!({
+                "sender": "@rantanplan:server.name",
+                "type": "m.room.message",
+                "content": {
+                    "msgtype": "m.text",
+                    "body": "@room Jolly Jumper",
+                    "m.mentions": {},
+                },
+            }),
+            json!({
+                "sender": "@rantanplan:server.name",
+                "type": "m.room.message",
+                "content": {
+                    "msgtype": "m.text",
+                    "body": "Hello",
+                    "m.mentions": { "user_ids": ["@jj:server.name"] },
+                },
+            }),
+            json!({
+                "sender": "@rantanplan:server.name",
+                "type": "m.room.message",
+                "content": {
+                    "msgtype": "m.text",
+                    "body": "Hello",
+                    "m.mentions": { "room": true },
+                },
+            }),
+        ];
+
+        for json in events {
+            let (raw, flattened) = event(json);
+            assert_eq!(
+                to_json_value(evaluator.get_actions(&flattened, &context)).unwrap(),
+                to_json_value(ruleset.get_actions(&raw, &context)).unwrap()
+            );
+            assert_eq!(
+                evaluator.get_match(&flattened, &context).map(|rule| rule.rule_id()),
+                ruleset.get_match(&raw, &context).map(|rule| rule.rule_id())
+            );
+        }
+    }
+
+    #[test]
+    fn disabled_rule_does_not_apply() {
+        let mut ruleset = ruleset();
+        ruleset.set_enabled(RuleKind::Content, "lucky", false).unwrap();
+        let evaluator = PushEvaluator::new(ruleset);
+
+        let (_, flattened) = event(json!({
+            "sender": "@rantanplan:server.name",
+            "type": "m.room.message",
+            "content": { "msgtype": "m.text", "body": "Good luck" },
+        }));
+        assert_ne!(
+            evaluator.get_match(&flattened, &context()).map(|rule| rule.rule_id()),
+            Some("lucky")
+        );
+    }
+}
@@ -157,6 +157,13 @@ impl<'a> AnyPushRuleRef<'a> {
         }
     }
 
+    /// Whether the push rule is a server-default rule.
+    ///
+    /// The IDs of server-default rules start with a dot (`.`).
+    pub fn is_server_default(self) -> bool {
+        self.rule_id().starts_with('.')
+    }
+
     /// Get the `rule_id` of the push rule.
     pub fn rule_id(self) -> &'a str {
         match self {
@@ -1,3 +1,6 @@
+use std::hash::Hash;
+
+use indexmap::{Equivalent, IndexSet};
 use ruma_macros::StringEnum;
 
 ///! Constructors for [predefined push rules].
@@ -26,7 +29,9 @@ impl Ruleset {
                 ConditionalPushRule::suppress_notices(),
                 ConditionalPushRule::invite_for_me(user_id),
                 ConditionalPushRule::member_event(),
+                ConditionalPushRule::is_user_mention(user_id),
                 ConditionalPushRule::contains_display_name(),
+                ConditionalPushRule::is_room_mention(),
                 ConditionalPushRule::tombstone(),
                 ConditionalPushRule::server_acl(),
                 ConditionalPushRule::roomnotif(),
@@ -53,6 +58,150 @@ impl Ruleset {
             ..Default::default()
         }
     }
+
+    /// Update this ruleset with the current [predefined push rules].
+    ///
+    /// The server-default rules that are missing are inserted at their position, and the
+    /// definitions of the existing ones are updated, but their `enabled` and `actions` fields are
+    /// kept. The rules that were server-default rules in a previous version of Ruma, but not
+    /// anymore, are removed.
+    ///
+    /// The other rules are not changed, they keep a higher priority than the server-default rules
+    /// of the same kind, except `.m.rule.master`. This includes the rules with an ID starting with
+    /// a dot (`.`) that are unknown to Ruma, like the server-default rules of some homeservers.
+    ///
+    /// [predefined push rules]: https://spec.matrix.org/latest/client-server-api/#predefined-rules
+    ///
+    /// # Parameters
+    ///
+    /// - `user_id`: the user for which to generate the default rules. Some rules depend on the
+    ///   user's ID (for instance those to send notifications when they are mentioned).
+    pub fn update_with_server_default(&mut self, user_id: &UserId) {
+        let server_default = Self::server_default(user_id);
+
+        // `.m.rule.master` has a higher priority than the user-defined rules.
+        update_server_default_rules(
+            &mut self.override_,
+            server_default.override_,
+            KNOWN_OVERRIDE_RULE_IDS,
+            1,
+        );
+        update_server_default_rules(
+            &mut self.underride,
+            server_default.underride,
+            KNOWN_UNDERRIDE_RULE_IDS,
+            0,
+        );
+        update_server_default_rules(
+            &mut self.content,
+            server_default.content,
+            KNOWN_CONTENT_RULE_IDS,
+            0,
+        );
+    }
+}
+
+/// The IDs of the override rules that are or were server-default rules in Ruma, including the ones
+/// behind cargo features.
+const KNOWN_OVERRIDE_RULE_IDS: &[&str] = &[
+    ".m.rule.master",
+    ".m.rule.suppress_notices",
+    ".m.rule.invite_for_me",
+    ".m.rule.member_event",
+    ".m.rule.is_user_mention",
+    ".m.rule.contains_display_name",
+    ".m.rule.is_room_mention",
+    ".m.rule.tombstone",
+    ".m.rule.room.server_acl",
+    ".m.rule.roomnotif",
+    ".m.rule.reaction",
+    // Unstable ID of `.m.rule.room.server_acl` (MSC3786).
+    ".org.matrix.msc3786.rule.room.server_acl",
+];
+
+/// The IDs of the underride rules that are or were server-default rules in Ruma, including the
+/// ones behind cargo features.
+const KNOWN_UNDERRIDE_RULE_IDS: &[&str] = &[
+    ".m.rule.call",
+    ".m.rule.encrypted_room_one_to_one",
+    ".m.rule.room_one_to_one",
+    ".m.rule.message",
+    ".m.rule.encrypted",
+    ".m.rule.poll_start_one_to_one",
+    ".m.rule.poll_start",
+    ".m.rule.poll_end_one_to_one",
+    ".m.rule.poll_end",
+];
+
+/// The IDs of the content rules that are or were server-default rules in Ruma.
+const KNOWN_CONTENT_RULE_IDS: &[&str] = &[".m.rule.contains_user_name"];
+
+/// Replace the server-default rules of `rules` with `server_default`.
+///
+/// The rules whose ID is in `known_rule_ids` are removed if they are not in `server_default`.
+///
+/// The first `high_priority` rules of `server_default` are placed before the other rules, and the
+/// other ones after them.
+fn update_server_default_rules<T>(
+    rules: &mut IndexSet<T>,
+    server_default: IndexSet<T>,
+    known_rule_ids: &[&str],
+    high_priority: usize,
+) where
+    T: ServerDefaultRule,
+    str: Equivalent<T>,
+{
+    let mut server_default = server_default
+        .into_iter()
+        .map(|mut rule| {
+            if let Some(old_rule) = rules.get(rule.rule_id()) {
+                rule.keep_user_settings(old_rule);
+            }
+            rule
+        })
+        .collect::<Vec<_>>();
+    let low_priority = server_default.split_off(high_priority.min(server_default.len()));
+
+    let is_server_default = |rule_id: &str| {
+        known_rule_ids.contains(&rule_id)
+            || server_default.iter().chain(&low_priority).any(|rule| rule.rule_id() == rule_id)
+    };
+    let user_defined =
+        rules.drain(..).filter(|rule| !is_server_default(rule.rule_id())).collect::<Vec<_>>();
+    let updated = server_default.into_iter().chain(user_defined).chain(low_priority).collect();
+
+    *rules = updated;
+}
+
+/// A kind of push rule that can be a server-default rule.
+trait ServerDefaultRule: Hash + Eq {
+    /// The ID of the rule.
+    fn rule_id(&self) -> &str;
+
+    /// Copy the fields of `old_rule` that can be changed by the user.
+    fn keep_user_settings(&mut self, old_rule: &Self);
+}
+
+impl ServerDefaultRule for ConditionalPushRule {
+    fn rule_id(&self) -> &str {
+        &self.rule_id
+    }
+
+    fn keep_user_settings(&mut self, old_rule: &Self) {
+        self.enabled = old_rule.enabled;
+        self.actions = old_rule.actions.clone();
+    }
+}
+
+impl ServerDefaultRule for PatternedPushRule {
+    fn rule_id(&self) -> &str {
+        &self.rule_id
+    }
+
+    fn keep_user_settings(&mut self, old_rule: &Self) {
+        self.enabled = old_rule.enabled;
+        self.actions = old_rule.actions.clone();
+    }
 }
 
 /// Default override push rules
@@ -113,8 +262,29 @@ impl ConditionalPushRule {
         }
     }
 
+    /// Matches any message which contains the user's Matrix ID in the list of `user_ids` under the
+    /// `m.mentions` property.
+    pub fn is_user_mention(user_id: &UserId) -> Self {
+        Self {
+            actions: vec![
+                Notify,
+                SetTweak(Tweak::Sound("default".into())),
+                SetTweak(Tweak::Highlight(true)),
+            ],
+            default: true,
+            enabled: true,
+            rule_id: PredefinedOverrideRuleId::IsUserMention.to_string(),
+            conditions: vec![EventPropertyContains {
+                key: r"content.m\.mentions.user_ids".into(),
+                value: user_id.as_str().into(),
+            }],
+        }
+    }
+
     /// Matches any message whose content is unencrypted and contains the user's current display
     /// name in the room in which it was sent.
+    ///
+    /// This rule is ignored for events with an `m.mentions` property.
     pub fn contains_display_name() -> Self {
         Self {
             actions: vec![
@@ -145,8 +315,25 @@ impl ConditionalPushRule {
         }
     }
 
+    /// Matches any message whose `room` property under the `m.mentions` property is `true`,
+    /// signifying the whole room should be notified of the event.
+    pub fn is_room_mention() -> Self {
+        Self {
+            actions: vec![Notify, SetTweak(Tweak::Highlight(true))],
+            default: true,
+            enabled: true,
+            rule_id: PredefinedOverrideRuleId::IsRoomMention.to_string(),
+            conditions: vec![
+                EventPropertyIs { key: r"content.m\.mentions.room".into(), value: true.into() },
+                SenderNotificationPermission { key: "room".into() },
+            ],
+        }
+    }
+
     /// Matches any message whose content is unencrypted and contains the text `@room`, signifying
     /// the whole room should be notified of the event.
+    ///
+    /// This rule is ignored for events with an `m.mentions` property.
     pub fn roomnotif() -> Self {
         Self {
             actions: vec![Notify, SetTweak(Tweak::Highlight(true))],
@@ -194,6 +381,8 @@ impl ConditionalPushRule {
 impl PatternedPushRule {
     /// Matches any message whose content is unencrypted and contains the local part of the user's
     /// Matrix ID, separated by word boundaries.
+    ///
+    /// This rule is ignored for events with an `m.mentions` property.
     pub fn contains_user_name(user_id: &UserId) -> Self {
         Self {
             rule_id: PredefinedContentRuleId::ContainsUserName.to_string(),
@@ -387,9 +576,15 @@ pub enum PredefinedOverrideRuleId {
     /// `.m.rule.member_event`
     MemberEvent,
 
+    /// `.m.rule.is_user_mention`
+    IsUserMention,
+
     /// `.m.rule.contains_display_name`
     ContainsDisplayName,
 
+    /// `.m.rule.is_room_mention`
+    IsRoomMention,
+
     /// `.m.rule.tombstone`
     Tombstone,
 
@@ -0,0 +1,495 @@
+//! The rules of room versions.
+//!
+//! The rules of the room versions known by Ruma are available with [`RoomVersionId::rules()`].
+//! The rules of custom or experimental room versions can be registered with
+//! [`RoomVersionRules::register()`], so that all the code paths depending on the room version
+//! behave as expected for these rooms.
+
+use std::sync::{PoisonError, RwLock};
+
+#[cfg(feature = "unstable-msc3931")]
+use crate::push::RoomVersionFeature;
+use crate::RoomVersionId;
+
+/// The rules of a room version.
+///
+/// To define the rules of a custom room version, start from the rules of an existing room version
+/// and change the fields that differ:
+///
+/// ```
+/// use ruma_common::{
+///     room_version_rules::{RoomDisposition, RoomVersionRules},
+///     RoomVersionId,
+/// };
+///
+/// let room_version = RoomVersionId::try_from("com.example.v10").unwrap();
+///
+/// let mut rules = RoomVersionRules::V10;
+/// rules.disposition = RoomDisposition::Unstable;
+/// rules.redaction.keep_room_server_acl_allow_deny_allow_ip_literals = true;
+///
+/// assert!(RoomVersionRules::register(room_version.clone(), rules));
+/// assert!(
+///     room_version.rules().unwrap().redaction.keep_room_server_acl_allow_deny_allow_ip_literals
+/// );
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
+pub struct RoomVersionRules {
+    /// The stability of this room version.
+    pub disposition: RoomDisposition,
+
+    /// The format of the event IDs.
+    pub event_format: EventFormatVersion,
+
+    /// The state resolution algorithm that is used.
+    pub state_res: StateResolutionVersion,
+
+    /// Whether the `valid_until_ts` of signing keys must be respected when verifying the
+    /// signatures of events.
+    pub enforce_key_validity: bool,
+
+    /// The authorization rules.
+    pub authorization: AuthorizationRules,
+
+    /// The redaction rules.
+    pub redaction: RedactionRules,
+
+    /// The rules for checking the signatures of events.
+    pub signatures: SignaturesRules,
+
+    /// The features supported by this room version, for the `room_version_supports` push
+    /// condition.
+    #[cfg(feature = "unstable-msc3931")]
+    pub push_features: &'static [RoomVersionFeature],
+}
+
+impl RoomVersionRules {
+    /// Rules for [room version 1](https://spec.matrix.org/latest/rooms/v1/).
+    pub const V1: Self = Self {
+        disposition: RoomDisposition::Stable,
+        event_format: EventFormatVersion::V1,
+        state_res: StateResolutionVersion::V1,
+        enforce_key_validity: false,
+        authorization: AuthorizationRules::V1,
+        redaction: RedactionRules::V1,
+        signatures: SignaturesRules::V1,
+        #[cfg(feature = "unstable-msc3931")]
+        push_features: &[],
+    };
+
+    /// Rules for [room version 2](https://spec.matrix.org/latest/rooms/v2/).
+    pub const V2: Self = Self { state_res: StateResolutionVersion::V2, ..Self::V1 };
+
+    /// Rules for [room version 3](https://spec.matrix.org/latest/rooms/v3/).
+    pub const V3: Self = Self {
+        event_format: EventFormatVersion::V2,
+        authorization: AuthorizationRules::V3,
+        ..Self::V2
+    };
+
+    /// Rules for [room version 4](https://spec.matrix.org/latest/rooms/v4/).
+    pub const V4: Self = Self { event_format: EventFormatVersion::V3, ..Self::V3 };
+
+    /// Rules for [room version 5](https://spec.matrix.org/latest/rooms/v5/).
+    pub const V5: Self = Self { enforce_key_validity: true, ..Self::V4 };
+
+    /// Rules for [room version 6](https://spec.matrix.org/latest/rooms/v6/).
+    pub const V6: Self =
+        Self { authorization: AuthorizationRules::V6, redaction: RedactionRules::V6, ..Self::V5 };
+
+    /// Rules for [room version 7](https://spec.matrix.org/latest/rooms/v7/).
+    pub const V7: Self = Self { authorization: AuthorizationRules::V7, ..Self::V6 };
+
+    /// Rules for [room version 8](https://spec.matrix.org/latest/rooms/v8/).
+    pub const V8: Self = Self {
+        authorization: AuthorizationRules::V8,
+        redaction: RedactionRules::V8,
+        signatures: SignaturesRules::V8,
+        ..Self::V7
+    };
+
+    /// Rules for [room version 9](https://spec.matrix.org/latest/rooms/v9/).
+    pub const V9: Self = Self { redaction: RedactionRules::V9, ..Self::V8 };
+
+    /// Rules for [room version 10](https://spec.matrix.org/latest/rooms/v10/).
+    pub const V10: Self = Self { authorization: AuthorizationRules::V10, ..Self::V9 };
+
+    /// Rules for [room version 11](https://spec.matrix.org/latest/rooms/v11/).
+    pub const V11: Self = Self {
+        authorization: AuthorizationRules::V11,
+        redaction: RedactionRules::V11,
+        ..Self::V10
+    };
+
+    /// Rules for the unstable room version `org.matrix.msc2870`, based on room version 10.
+    ///
+    /// See [MSC2870](https://github.com/matrix-org/matrix-spec-proposals/pull/2870) for more
+    /// information.
+    #[cfg(feature = "unstable-msc2870")]
+    pub const MSC2870: Self = Self {
+        disposition: RoomDisposition::Unstable,
+        redaction: RedactionRules::MSC2870,
+        ..Self::V10
+    };
+
+    /// Registers the rules of a custom room version.
+    ///
+    /// After this call, [`RoomVersionId::rules()`] returns `rules` for `room_version`, which
+    /// replaces the rules registered previously for the same room version, if any.
+    ///
+    /// Returns `false` and does nothing if the rules of `room_version` are built into Ruma, they
+    /// cannot be replaced.
+    pub fn register(room_version: RoomVersionId, rules: RoomVersionRules) -> bool {
+        if builtin_rules(&room_version).is_some() {
+            return false;
+        }
+
+        let mut custom_rules = CUSTOM_RULES.write().unwrap_or_else(PoisonError::into_inner);
+
+        match custom_rules.iter_mut().find(|(id, _)| *id == room_version) {
+            Some((_, old_rules)) => *old_rules = rules,
+            None => custom_rules.push((room_version, rules)),
+        }
+
+        true
+    }
+}
+
+/// The rules of the custom room versions registered with [`RoomVersionRules::register()`].
+static CUSTOM_RULES: RwLock<Vec<(RoomVersionId, RoomVersionRules)>> = RwLock::new(Vec::new());
+
+/// Get the rules of the given room version, if they are built into Ruma or were registered.
+pub(crate) fn rules_for(room_version: &RoomVersionId) -> Option<RoomVersionRules> {
+    builtin_rules(room_version).or_else(|| {
+        CUSTOM_RULES
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|(id, _)| id == room_version)
+            .map(|(_, rules)| rules.clone())
+    })
+}
+
+fn builtin_rules(room_version: &RoomVersionId) -> Option<RoomVersionRules> {
+    Some(match room_version {
+        RoomVersionId::V1 => RoomVersionRules::V1,
+        RoomVersionId::V2 => RoomVersionRules::V2,
+        RoomVersionId::V3 => RoomVersionRules::V3,
+        RoomVersionId::V4 => RoomVersionRules::V4,
+        RoomVersionId::V5 => RoomVersionRules::V5,
+        RoomVersionId::V6 => RoomVersionRules::V6,
+        RoomVersionId::V7 => RoomVersionRules::V7,
+        RoomVersionId::V8 => RoomVersionRules::V8,
+        RoomVersionId::V9 => RoomVersionRules::V9,
+        RoomVersionId::V10 => RoomVersionRules::V10,
+        RoomVersionId::V11 => RoomVersionRules::V11,
+        #[cfg(feature = "unstable-msc2870")]
+        RoomVersionId::_Custom(version) if version.as_str() == "org.matrix.msc2870" => {
+            RoomVersionRules::MSC2870
+        }
+        RoomVersionId::_Custom(_) => return None,
+    })
+}
+
+/// The stability of a room version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum RoomDisposition {
+    /// A room version that has a stable specification.
+    Stable,
+
+    /// A room version that is not yet fully specified.
+    Unstable,
+}
+
+/// The format of the event IDs of a room version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
+pub enum EventFormatVersion {
+    /// `$id:server` event ID format, introduced for room version 1.
+    V1,
+
+    /// MSC1659-style `$hash` event ID format, introduced for room version 3.
+    V2,
+
+    /// MSC1884-style `$hash` event ID format, introduced for room version 4.
+    V3,
+}
+
+/// The state resolution algorithm of a room version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
+pub enum StateResolutionVersion {
+    /// State resolution for rooms at version 1.
+    V1,
+
+    /// State resolution for rooms at version 2 or later.
+    V2,
+}
+
+/// The authorization rules of a room version.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
+pub struct AuthorizationRules {
+    /// `m.room.aliases` had special auth rules and redaction rules before room version 6.
+    ///
+    /// See [MSC2261](https://github.com/matrix-org/matrix-spec-proposals/pull/2261) and
+    /// [MSC2432](https://github.com/matrix-org/matrix-spec-proposals/pull/2432) for more
+    /// information.
+    pub special_case_aliases_auth: bool,
+
+    /// Strictly enforce canonical JSON, do not allow:
+    ///
+    /// * Integers outside the range of [-2 ^ 53 + 1, 2 ^ 53 - 1]
+    /// * Floats
+    /// * NaN, Infinity, -Infinity
+    pub strict_canonicaljson: bool,
+
+    /// Verify the `notifications` key while checking `m.room.power_levels`.
+    ///
+    /// See [MSC2209](https://github.com/matrix-org/matrix-spec-proposals/pull/2209) for more
+    /// information.
+    pub limit_notifications_power_levels: bool,
+
+    /// Extra rules when verifying redaction events.
+    pub extra_redaction_checks: bool,
+
+    /// Allow knocking in event authentication.
+    ///
+    /// See [room v7 specification](https://spec.matrix.org/latest/rooms/v7/) for more information.
+    pub allow_knocking: bool,
+
+    /// Adds support for the restricted join rule.
+    ///
+    /// See [MSC3289](https://github.com/matrix-org/matrix-spec-proposals/pull/3289) for more
+    /// information.
+    pub restricted_join_rules: bool,
+
+    /// Adds support for the knock_restricted join rule.
+    ///
+    /// See [MSC3787](https://github.com/matrix-org/matrix-spec-proposals/pull/3787) for more
+    /// information.
+    pub knock_restricted_join_rule: bool,
+
+    /// Enforces integer power levels.
+    ///
+    /// See [MSC3667](https://github.com/matrix-org/matrix-spec-proposals/pull/3667) for more
+    /// information.
+    pub integer_power_levels: bool,
+
+    /// Use the `sender` of the `m.room.create` event as the creator of the room, instead of the
+    /// `creator` field of its content.
+    ///
+    /// See [MSC2175](https://github.com/matrix-org/matrix-spec-proposals/pull/2175) for more
+    /// information.
+    pub use_room_create_sender: bool,
+}
+
+impl AuthorizationRules {
+    /// Authorization rules for room version 1.
+    pub const V1: Self = Self {
+        special_case_aliases_auth: true,
+        strict_canonicaljson: false,
+        limit_notifications_power_levels: false,
+        extra_redaction_checks: false,
+        allow_knocking: false,
+        restricted_join_rules: false,
+        knock_restricted_join_rule: false,
+        integer_power_levels: false,
+        use_room_create_sender: false,
+    };
+
+    /// Authorization rules for room version 3.
+    pub const V3: Self = Self { extra_redaction_checks: true, ..Self::V1 };
+
+    /// Authorization rules for room version 6.
+    pub const V6: Self = Self {
+        special_case_aliases_auth: false,
+        strict_canonicaljson: true,
+        limit_notifications_power_levels: true,
+        ..Self::V3
+    };
+
+    /// Authorization rules for room version 7.
+    pub const V7: Self = Self { allow_knocking: true, ..Self::V6 };
+
+    /// Authorization rules for room version 8.
+    pub const V8: Self = Self { restricted_join_rules: true, ..Self::V7 };
+
+    /// Authorization rules for room version 10.
+    pub const V10: Self =
+        Self { knock_restricted_join_rule: true, integer_power_levels: true, ..Self::V8 };
+
+    /// Authorization rules for room version 11.
+    pub const V11: Self = Self { use_room_create_sender: true, ..Self::V10 };
+}
+
+/// The redaction rules of a room version.
+///
+/// These are the content keys that are preserved by redaction in addition to the ones that are
+/// preserved in all room versions.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
+pub struct RedactionRules {
+    /// Whether to keep the top-level `origin`, `membership` and `prev_state` fields of events.
+    ///
+    /// See [MSC2176](https://github.com/matrix-org/matrix-spec-proposals/pull/2176) and
+    /// [MSC3989](https://github.com/matrix-org/matrix-spec-proposals/pull/3989) for more
+    /// information.
+    pub keep_origin_membership_prev_state: bool,
+
+    /// Whether to keep all the content of `m.room.create` events.
+    ///
+    /// See [MSC2176](https://github.com/matrix-org/matrix-spec-proposals/pull/2176) for more
+    /// information.
+    pub keep_room_create_content: bool,
+
+    /// Whether to keep the `redacts` field of the content of `m.room.redaction` events.
+    ///
+    /// See [MSC2176](https://github.com/matrix-org/matrix-spec-proposals/pull/2176) for more
+    /// information.
+    pub keep_room_redaction_redacts: bool,
+
+    /// Whether to keep the `invite` field of `m.room.power_levels` events.
+    ///
+    /// See [MSC2176](https://github.com/matrix-org/matrix-spec-proposals/pull/2176) for more
+    /// information.
+    pub keep_room_power_levels_invite: bool,
+
+    /// Whether to keep the `signed` field of the `third_party_invite` of `m.room.member` events.
+    ///
+    /// It is needed to check that the invited user matches the `state_key` of the event.
+    ///
+    /// See [MSC3821](https://github.com/matrix-org/matrix-spec-proposals/pull/3821) for more
+    /// information.
+    pub keep_room_member_third_party_invite_signed: bool,
+
+    /// Whether to keep the `aliases` field of `m.room.aliases` events.
+    pub keep_room_aliases_aliases: bool,
+
+    /// Whether to keep the `allow` field of `m.room.join_rules` events.
+    pub keep_room_join_rules_allow: bool,
+
+    /// Whether to keep the `join_authorised_via_users_server` field of `m.room.member` events.
+    pub keep_room_member_join_authorised_via_users_server: bool,
+
+    /// Whether to keep the `allow`, `deny` and `allow_ip_literals` fields of `m.room.server_acl`
+    /// events.
+    ///
+    /// See [MSC2870](https://github.com/matrix-org/matrix-spec-proposals/pull/2870) for more
+    /// information.
+    pub keep_room_server_acl_allow_deny_allow_ip_literals: bool,
+
+    /// Whether the ID of the event redacted by an `m.room.redaction` event is in the `redacts`
+    /// field of its content, instead of the top-level `redacts` field of the event.
+    ///
+    /// See [MSC2174](https://github.com/matrix-org/matrix-spec-proposals/pull/2174) for more
+    /// information.
+    pub content_field_redacts: bool,
+}
+
+impl RedactionRules {
+    /// Redaction rules for room version 1.
+    pub const V1: Self = Self {
+        keep_origin_membership_prev_state: true,
+        keep_room_create_content: false,
+        keep_room_redaction_redacts: false,
+        keep_room_power_levels_invite: false,
+        keep_room_member_third_party_invite_signed: false,
+        keep_room_aliases_aliases: true,
+        keep_room_join_rules_allow: false,
+        keep_room_member_join_authorised_via_users_server: false,
+        keep_room_server_acl_allow_deny_allow_ip_literals: false,
+        content_field_redacts: false,
+    };
+
+    /// Redaction rules for room version 6.
+    pub const V6: Self = Self { keep_room_aliases_aliases: false, ..Self::V1 };
+
+    /// Redaction rules for room version 8.
+    pub const V8: Self = Self { keep_room_join_rules_allow: true, ..Self::V6 };
+
+    /// Redaction rules for room version 9.
+    pub const V9: Self =
+        Self { keep_room_member_join_authorised_via_users_server: true, ..Self::V8 };
+
+    /// Redaction rules for room version 11.
+    pub const V11: Self = Self {
+        keep_origin_membership_prev_state: false,
+        keep_room_create_content: true,
+        keep_room_redaction_redacts: true,
+        keep_room_power_levels_invite: true,
+        keep_room_member_third_party_invite_signed: true,
+        content_field_redacts: true,
+        ..Self::V9
+    };
+
+    /// Redaction rules for the unstable room version `org.matrix.msc2870`.
+    #[cfg(feature = "unstable-msc2870")]
+    pub const MSC2870: Self =
+        Self { keep_room_server_acl_allow_deny_allow_ip_literals: true, ..Self::V9 };
+
+    /// The redaction rules that preserve the fewest content keys.
+    ///
+    /// They are used to redact events in room versions whose rules are unknown, when the
+    /// redaction cannot fail.
+    pub const STRICTEST: Self = Self { keep_origin_membership_prev_state: false, ..Self::V6 };
+}
+
+/// The rules for checking the signatures of events of a room version.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
+pub struct SignaturesRules {
+    /// Whether the server of the user in the `join_authorised_via_users_server` field of
+    /// `m.room.member` events must sign the event.
+    ///
+    /// See [MSC3083](https://github.com/matrix-org/matrix-spec-proposals/pull/3083) for more
+    /// information.
+    pub check_join_authorised_via_users_server: bool,
+}
+
+impl SignaturesRules {
+    /// Signatures rules for room version 1.
+    pub const V1: Self = Self { check_join_authorised_via_users_server: false };
+
+    /// Signatures rules for room version 8.
+    pub const V8: Self = Self { check_join_authorised_via_users_server: true };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoomVersionRules;
+    use crate::RoomVersionId;
+
+    #[test]
+    fn builtin_rules_cannot_be_replaced() {
+        assert!(!RoomVersionRules::register(RoomVersionId::V6, RoomVersionRules::V1));
+        assert!(!RoomVersionId::V6.rules().unwrap().authorization.special_case_aliases_auth);
+    }
+
+    #[test]
+    fn custom_rules() {
+        let room_version = RoomVersionId::try_from("org.example.custom").unwrap();
+        assert!(room_version.rules().is_none());
+
+        assert!(RoomVersionRules::register(room_version.clone(), RoomVersionRules::V8));
+        assert!(
+            !room_version
+                .rules()
+                .unwrap()
+                .redaction
+                .keep_room_member_join_authorised_via_users_server
+        );
+
+        // Registering again replaces the rules.
+        assert!(RoomVersionRules::register(room_version.clone(), RoomVersionRules::V9));
+        assert!(
+            room_version
+                .rules()
+                .unwrap()
+                .redaction
+                .keep_room_member_join_authorised_via_users_server
+        );
+    }
+}
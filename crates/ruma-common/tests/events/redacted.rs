@@ -133,7 +133,7 @@ fn deserialize_redacted_state_event() {
         ))) => redacted
     );
     assert_eq!(redacted.event_id, "$h29iv0s8:example.com");
-    assert_eq!(redacted.content.creator, "@carl:example.com");
+    assert_eq!(redacted.content.creator.unwrap(), "@carl:example.com");
 }
 
 #[test]
@@ -172,7 +172,7 @@ fn redact_method_properly_redacts() {
 
     let redaction = OriginalSyncRoomRedactionEvent {
         content: RoomRedactionEventContent::with_reason("redacted because".into()),
-        redacts: event_id!("$143273582443PhrSn:example.com").to_owned(),
+        redacts: Some(event_id!("$143273582443PhrSn:example.com").to_owned()),
         event_id: event_id!("$h29iv0s8:example.com").to_owned(),
         origin_server_ts: MilliSecondsSinceUnixEpoch(uint!(1)),
         sender: user_id!("@carl:example.com").to_owned(),
@@ -230,5 +230,5 @@ fn redact_state_content() {
             ..
         } => creator
     );
-    assert_eq!(creator, "@carl:example.com");
+    assert_eq!(creator.unwrap(), "@carl:example.com");
 }
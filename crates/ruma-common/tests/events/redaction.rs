@@ -6,7 +6,7 @@ use ruma_common::{
         AnyMessageLikeEvent,
     },
     serde::CanBeEmpty,
-    MilliSecondsSinceUnixEpoch,
+    MilliSecondsSinceUnixEpoch, RoomVersionId,
 };
 use serde_json::{from_value as from_json_value, json, to_value as to_json_value};
 
@@ -42,9 +42,33 @@ fn deserialize_redaction() {
     );
     assert_eq!(ev.content.reason.as_deref(), Some("being very unfriendly"));
     assert_eq!(ev.event_id, "$h29iv0s8:example.com");
-    assert_eq!(ev.redacts, "$nomore:example.com");
+    assert_eq!(ev.redacts.unwrap(), "$nomore:example.com");
     assert_eq!(ev.origin_server_ts, MilliSecondsSinceUnixEpoch(uint!(1)));
     assert_eq!(ev.room_id, "!roomid:room.com");
     assert_eq!(ev.sender, "@carl:example.com");
     assert!(ev.unsigned.is_empty());
 }
+
+#[test]
+fn deserialize_redaction_v11() {
+    let json_data = json!({
+        "content": {
+            "redacts": "$nomore:example.com",
+            "reason": "being very unfriendly"
+        },
+        "event_id": "$h29iv0s8:example.com",
+        "sender": "@carl:example.com",
+        "origin_server_ts": 1,
+        "room_id": "!roomid:room.com",
+        "type": "m.room.redaction"
+    });
+
+    let ev = assert_matches!(
+        from_json_value::<AnyMessageLikeEvent>(json_data),
+        Ok(AnyMessageLikeEvent::RoomRedaction(RoomRedactionEvent::Original(ev))) => ev
+    );
+    assert_eq!(ev.redacts, None);
+    assert_eq!(ev.content.redacts.as_deref().unwrap(), "$nomore:example.com");
+    assert_eq!(ev.redacts(&RoomVersionId::V11).unwrap(), "$nomore:example.com");
+    assert_eq!(ev.redacts(&RoomVersionId::V10), None);
+}
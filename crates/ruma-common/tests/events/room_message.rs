@@ -14,7 +14,7 @@ use ruma_common::{
             },
             MediaSource,
         },
-        MessageLikeUnsigned,
+        Mentions, MessageLikeUnsigned,
     },
     mxc_uri, room_id, user_id, MilliSecondsSinceUnixEpoch, OwnedDeviceId,
 };
@@ -529,3 +529,81 @@ fn make_replacement_with_reply() {
         "
     );
 }
+
+#[test]
+fn mentions_serialization() {
+    let content = RoomMessageEventContent::text_plain("Hello @room and Alice!")
+        .add_mentions(Mentions::with_user_ids([user_id!("@alice:example.org").to_owned()]));
+    let content = content.add_mentions(Mentions::with_room_mention());
+
+    assert_eq!(
+        to_json_value(&content).unwrap(),
+        json!({
+            "msgtype": "m.text",
+            "body": "Hello @room and Alice!",
+            "m.mentions": {
+                "user_ids": ["@alice:example.org"],
+                "room": true,
+            },
+        })
+    );
+
+    let content = RoomMessageEventContent::text_plain("Hello").add_mentions(Mentions::new());
+
+    assert_eq!(
+        to_json_value(&content).unwrap(),
+        json!({
+            "msgtype": "m.text",
+            "body": "Hello",
+            "m.mentions": {},
+        })
+    );
+}
+
+#[test]
+fn mentions_deserialization() {
+    let json_data = json!({
+        "msgtype": "m.text",
+        "body": "Hello Alice!",
+        "m.mentions": {
+            "user_ids": ["@alice:example.org"],
+        },
+    });
+
+    let content = from_json_value::<RoomMessageEventContent>(json_data).unwrap();
+    let mentions = content.mentions.unwrap();
+    assert_eq!(mentions.user_ids.len(), 1);
+    assert!(mentions.user_ids.contains(user_id!("@alice:example.org")));
+    assert!(!mentions.room);
+
+    let json_data = json!({
+        "msgtype": "m.text",
+        "body": "Hello Alice!",
+    });
+
+    let content = from_json_value::<RoomMessageEventContent>(json_data).unwrap();
+    assert_matches!(content.mentions, None);
+}
+
+#[test]
+fn make_reply_to_adds_mentions() {
+    let first_message = OriginalRoomMessageEvent {
+        content: RoomMessageEventContent::text_plain("Hello Bob!")
+            .add_mentions(Mentions::with_user_ids([user_id!("@bob:example.org").to_owned()])),
+        event_id: event_id!("$143273582443PhrSn:example.org").to_owned(),
+        origin_server_ts: MilliSecondsSinceUnixEpoch(uint!(10_000)),
+        room_id: room_id!("!testroomid:example.org").to_owned(),
+        sender: user_id!("@alice:example.org").to_owned(),
+        unsigned: MessageLikeUnsigned::default(),
+    };
+    let reply = RoomMessageEventContent::text_plain("Hello Carl!")
+        .add_mentions(Mentions::with_user_ids([user_id!("@carl:example.org").to_owned()]))
+        .make_reply_to(&first_message, ForwardThread::Yes);
+
+    let mentions = reply.mentions.unwrap();
+    assert_eq!(
+        mentions.user_ids.iter().map(|user_id| user_id.as_str()).collect::<Vec<_>>(),
+        ["@alice:example.org", "@bob:example.org", "@carl:example.org"]
+    );
+    assert!(!mentions.room);
+}
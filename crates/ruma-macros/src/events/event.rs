@@ -121,6 +121,10 @@ fn expand_deserialize_event(
                 quote! {
                     let unsigned = unsigned.unwrap_or_default();
                 }
+            } else if is_option(&field.ty) {
+                quote! {
+                    let #name = #name.flatten();
+                }
             } else if name == "state_key" && var == EventKindVariation::Initial {
                 let ty = &field.ty;
                 quote! {
@@ -319,3 +323,13 @@ fn expand_eq_ord_event(input: &DeriveInput) -> TokenStream {
         }
     }
 }
+
+/// Whether the given type is an `Option`.
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => {
+            type_path.path.segments.last().filter(|s| s.ident == "Option").is_some()
+        }
+        _ => false,
+    }
+}
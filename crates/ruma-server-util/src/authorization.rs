@@ -2,7 +2,13 @@
 
 use headers::{authorization::Credentials, HeaderValue};
 
-use ruma_common::{OwnedServerName, OwnedServerSigningKeyId};
+use http::header::AUTHORIZATION;
+use ruma_common::{
+    CanonicalJsonObject, CanonicalJsonValue, IdParseError, OwnedServerName,
+    OwnedServerSigningKeyId, ServerName,
+};
+use ruma_signatures::{canonical_json, verify_json, KeyPair, PublicKeyMap};
+use thiserror::Error;
 use tracing::debug;
 use yap::{IntoTokens, TokenLocation, Tokens};
 
@@ -12,6 +18,7 @@ use yap::{IntoTokens, TokenLocation, Tokens};
 /// when using a web framework that supports typed headers.
 ///
 /// [spec]: https://spec.matrix.org/latest/server-server-api/#request-authentication
+#[derive(Clone, Debug)]
 #[non_exhaustive]
 pub struct XMatrix {
     /// The server name of the sending server.
@@ -38,6 +45,151 @@ impl XMatrix {
     ) -> Self {
         Self { origin, destination, key, sig }
     }
+
+    /// Sign the given request as `origin` for `destination` with the given key pair.
+    ///
+    /// This builds the JSON object described in the [spec] from the method, URI and JSON body of
+    /// the request, and signs it. The returned value can be encoded with
+    /// [`Credentials::encode`] and used as the `Authorization` header of the request.
+    ///
+    /// [spec]: https://spec.matrix.org/latest/server-server-api/#request-authentication
+    pub fn sign_request<B, K>(
+        request: &http::Request<B>,
+        origin: &ServerName,
+        destination: &ServerName,
+        key_pair: &K,
+    ) -> Result<Self, XMatrixError>
+    where
+        B: AsRef<[u8]>,
+        K: KeyPair + ?Sized,
+    {
+        let object = request_json(request, origin, destination)?;
+        let signature = key_pair.sign(canonical_json(&object)?.as_bytes())?;
+
+        Ok(Self {
+            origin: origin.to_owned(),
+            destination: Some(destination.to_owned()),
+            key: signature.id().try_into().map_err(XMatrixError::InvalidKeyId)?,
+            sig: signature.base64(),
+        })
+    }
+
+    /// Verify the `X-Matrix` `Authorization` header of the given incoming request.
+    ///
+    /// `destination` is the name of the receiving server. The request is rejected if the header
+    /// contains a different destination. Headers without a destination, as sent by older
+    /// servers, are verified as if they were sent for `destination`.
+    ///
+    /// `public_key_map` must contain the public key of the origin that signed the request. If the
+    /// request has several `X-Matrix` headers, it is enough that one of them can be verified.
+    ///
+    /// Returns the verified header, whose `origin` is the server that sent the request.
+    pub fn verify_request<B>(
+        request: &http::Request<B>,
+        destination: &ServerName,
+        public_key_map: &PublicKeyMap,
+    ) -> Result<Self, XMatrixError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let mut result = Err(XMatrixError::MissingHeader);
+
+        let headers = request.headers().get_all(AUTHORIZATION).into_iter();
+        for x_matrix in headers.filter_map(<Self as Credentials>::decode) {
+            result = x_matrix.verify(request, destination, public_key_map).map(|_| x_matrix);
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        result
+    }
+
+    fn verify<B>(
+        &self,
+        request: &http::Request<B>,
+        destination: &ServerName,
+        public_key_map: &PublicKeyMap,
+    ) -> Result<(), XMatrixError>
+    where
+        B: AsRef<[u8]>,
+    {
+        if let Some(header_destination) = &self.destination {
+            if header_destination != destination {
+                return Err(XMatrixError::DestinationMismatch {
+                    expected: destination.to_owned(),
+                    found: header_destination.clone(),
+                });
+            }
+        }
+
+        let mut object = request_json(request, &self.origin, destination)?;
+        let signature_set = [(self.key.to_string(), CanonicalJsonValue::String(self.sig.clone()))];
+        let signatures = [(
+            self.origin.to_string(),
+            CanonicalJsonValue::Object(signature_set.into_iter().collect()),
+        )];
+        object.insert(
+            "signatures".to_owned(),
+            CanonicalJsonValue::Object(signatures.into_iter().collect()),
+        );
+
+        Ok(verify_json(public_key_map, &object)?)
+    }
+}
+
+/// Build the JSON object that is signed for the given request.
+fn request_json<B: AsRef<[u8]>>(
+    request: &http::Request<B>,
+    origin: &ServerName,
+    destination: &ServerName,
+) -> Result<CanonicalJsonObject, XMatrixError> {
+    let uri = request.uri().path_and_query().map_or("/", |path_and_query| path_and_query.as_str());
+
+    let mut object = CanonicalJsonObject::new();
+    object.insert("method".to_owned(), request.method().as_str().into());
+    object.insert("uri".to_owned(), uri.into());
+    object.insert("origin".to_owned(), origin.as_str().into());
+    object.insert("destination".to_owned(), destination.as_str().into());
+
+    let body = request.body().as_ref();
+    if !body.is_empty() {
+        let content = serde_json::from_slice(body).map_err(XMatrixError::InvalidBody)?;
+        object.insert("content".to_owned(), content);
+    }
+
+    Ok(object)
+}
+
+/// An error that can occur when signing or verifying a request with [`XMatrix`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum XMatrixError {
+    /// The request has no valid `X-Matrix` `Authorization` header.
+    #[error("missing or invalid X-Matrix Authorization header")]
+    MissingHeader,
+
+    /// The destination of the `Authorization` header is not the receiving server.
+    #[error("X-Matrix destination mismatch: expected {expected}, found {found}")]
+    DestinationMismatch {
+        /// The name of the receiving server.
+        expected: OwnedServerName,
+
+        /// The destination found in the header.
+        found: OwnedServerName,
+    },
+
+    /// The body of the request is not valid canonical JSON.
+    #[error("request body is not valid canonical JSON: {0}")]
+    InvalidBody(serde_json::Error),
+
+    /// The ID of the key used to sign the request is invalid.
+    #[error("invalid signing key ID: {0}")]
+    InvalidKeyId(IdParseError),
+
+    /// Signing the request or verifying its signature failed.
+    #[error(transparent)]
+    Signatures(#[from] ruma_signatures::Error),
 }
 
 fn parse_token<'a>(tokens: &mut impl Tokens<Item = &'a u8>) -> Option<Vec<u8>> {
@@ -240,10 +392,14 @@ impl Credentials for XMatrix {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use headers::{authorization::Credentials, HeaderValue};
-    use ruma_common::OwnedServerName;
+    use http::header::AUTHORIZATION;
+    use ruma_common::{serde::Base64, server_name, OwnedServerName};
+    use ruma_signatures::{Ed25519KeyPair, PublicKeyMap};
 
-    use super::XMatrix;
+    use super::{XMatrix, XMatrixError};
 
     #[test]
     fn xmatrix_auth_pre_1_3() {
@@ -281,4 +437,99 @@ mod tests {
 
         assert_eq!(credentials.encode(), header);
     }
+
+    fn key_pair() -> Ed25519KeyPair {
+        Ed25519KeyPair::from_der(&Ed25519KeyPair::generate().unwrap(), "1".to_owned()).unwrap()
+    }
+
+    fn public_key_map(key_pair: &Ed25519KeyPair) -> PublicKeyMap {
+        let key_set = [("ed25519:1".to_owned(), Base64::new(key_pair.public_key().to_owned()))];
+        [("origin.example.com".to_owned(), key_set.into_iter().collect())].into_iter().collect()
+    }
+
+    fn signed_request(
+        key_pair: &Ed25519KeyPair,
+        body: &'static str,
+    ) -> http::Request<&'static str> {
+        let mut request = http::Request::put(
+            "https://destination.example.com/_matrix/federation/v1/send/1?query=value",
+        )
+        .body(body)
+        .unwrap();
+
+        let x_matrix = XMatrix::sign_request(
+            &request,
+            server_name!("origin.example.com"),
+            server_name!("destination.example.com"),
+            key_pair,
+        )
+        .unwrap();
+        assert_eq!(x_matrix.key, "ed25519:1");
+        request.headers_mut().insert(AUTHORIZATION, x_matrix.encode());
+
+        request
+    }
+
+    #[test]
+    fn sign_and_verify_request() {
+        let key_pair = key_pair();
+        let request = signed_request(&key_pair, r#"{ "pdus": [] }"#);
+
+        let x_matrix = XMatrix::verify_request(
+            &request,
+            server_name!("destination.example.com"),
+            &public_key_map(&key_pair),
+        )
+        .unwrap();
+        assert_eq!(x_matrix.origin, "origin.example.com");
+    }
+
+    #[test]
+    fn verify_request_without_destination() {
+        let key_pair = key_pair();
+        let mut request = http::Request::get("/_matrix/federation/v1/version").body("").unwrap();
+
+        let mut x_matrix = XMatrix::sign_request(
+            &request,
+            server_name!("origin.example.com"),
+            server_name!("destination.example.com"),
+            &key_pair,
+        )
+        .unwrap();
+        x_matrix.destination = None;
+        request.headers_mut().insert(AUTHORIZATION, x_matrix.encode());
+
+        XMatrix::verify_request(
+            &request,
+            server_name!("destination.example.com"),
+            &public_key_map(&key_pair),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_requests() {
+        let key_pair = key_pair();
+        let public_key_map = public_key_map(&key_pair);
+        let destination = server_name!("destination.example.com");
+
+        let request = signed_request(&key_pair, r#"{ "pdus": [] }"#);
+        let error =
+            XMatrix::verify_request(&request, server_name!("other.example.com"), &public_key_map)
+                .unwrap_err();
+        assert!(matches!(error, XMatrixError::DestinationMismatch { .. }));
+
+        let mut tampered =
+            http::Request::put(request.uri().clone()).body(r#"{ "pdus": [{}] }"#).unwrap();
+        *tampered.headers_mut() = request.headers().clone();
+        let error = XMatrix::verify_request(&tampered, destination, &public_key_map).unwrap_err();
+        assert!(matches!(error, XMatrixError::Signatures(_)));
+
+        let error = XMatrix::verify_request(&request, destination, &BTreeMap::new()).unwrap_err();
+        assert!(matches!(error, XMatrixError::Signatures(_)));
+
+        let unsigned = http::Request::get("/_matrix/federation/v1/version").body("").unwrap();
+        let error = XMatrix::verify_request(&unsigned, destination, &public_key_map).unwrap_err();
+        assert!(matches!(error, XMatrixError::MissingHeader));
+    }
 }
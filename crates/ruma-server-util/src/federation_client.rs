@@ -0,0 +1,223 @@
+//! A client for sending authenticated requests to other homeservers.
+
+use std::{
+    error::Error as StdError,
+    fmt::{self, Display},
+};
+
+use async_trait::async_trait;
+use headers::authorization::Credentials;
+use http::{
+    header::{AUTHORIZATION, HOST},
+    uri::InvalidUri,
+    HeaderValue, Uri,
+};
+use ruma_client::HttpClient;
+use ruma_common::{OwnedServerName, ServerName};
+use ruma_signatures::KeyPair;
+
+use crate::{
+    authorization::{XMatrix, XMatrixError},
+    resolver::Resolver,
+};
+
+/// An [`HttpClient`] that sends requests to other homeservers over federation.
+///
+/// The destination of a request is the authority of its URI, e.g. requests for
+/// `https://example.com/_matrix/federation/v1/version` are sent to the server `example.com`. This
+/// means that the server name can be used as the homeserver URL with
+/// [`HttpClientExt`](ruma_client::HttpClientExt).
+///
+/// Every request is signed with an [`XMatrix`] `Authorization` header, then its URI is rewritten
+/// to the address the destination resolves to and its `Host` header is set accordingly.
+///
+/// The wrapped HTTP client must check that the TLS certificate of the destination is valid for
+/// [`ResolvedDestination::tls_name`](crate::resolver::ResolvedDestination::tls_name), which is
+/// not the host of the URI when the destination was resolved through an `SRV` record.
+#[derive(Debug)]
+pub struct FederationClient<C, K> {
+    resolver: Resolver<C>,
+    origin: OwnedServerName,
+    key_pair: K,
+}
+
+impl<C, K> FederationClient<C, K>
+where
+    C: HttpClient,
+    K: KeyPair,
+{
+    /// Creates a new `FederationClient` sending requests as `origin`, signed with `key_pair`.
+    pub fn new(resolver: Resolver<C>, origin: OwnedServerName, key_pair: K) -> Self {
+        Self { resolver, origin, key_pair }
+    }
+
+    /// The resolver used to find the address of destinations.
+    pub fn resolver(&self) -> &Resolver<C> {
+        &self.resolver
+    }
+
+    /// The name of the server sending the requests.
+    pub fn origin(&self) -> &ServerName {
+        &self.origin
+    }
+}
+
+#[async_trait]
+impl<C, K> HttpClient for FederationClient<C, K>
+where
+    C: HttpClient,
+    C::RequestBody: AsRef<[u8]>,
+    K: KeyPair + Sync,
+{
+    type RequestBody = C::RequestBody;
+    type ResponseBody = C::ResponseBody;
+    type Error = FederationClientError<C::Error>;
+
+    async fn send_http_request(
+        &self,
+        mut req: http::Request<C::RequestBody>,
+    ) -> Result<http::Response<C::ResponseBody>, Self::Error> {
+        let destination = req
+            .uri()
+            .authority()
+            .and_then(|authority| <&ServerName>::try_from(authority.as_str()).ok())
+            .ok_or(FederationClientError::InvalidDestination)?
+            .to_owned();
+
+        let x_matrix = XMatrix::sign_request(&req, &self.origin, &destination, &self.key_pair)
+            .map_err(FederationClientError::Sign)?;
+
+        let resolved = self.resolver.resolve(&destination).await;
+        let path_and_query = req.uri().path_and_query().map_or("/", |p| p.as_str());
+        let uri: Uri = format!("{}{path_and_query}", resolved.base_url())
+            .parse()
+            .map_err(FederationClientError::InvalidUri)?;
+        *req.uri_mut() = uri;
+
+        let host = HeaderValue::from_str(&resolved.host_header)
+            .expect("server names are valid header values");
+        req.headers_mut().insert(HOST, host);
+        req.headers_mut().insert(AUTHORIZATION, x_matrix.encode());
+
+        self.resolver
+            .http_client()
+            .send_http_request(req)
+            .await
+            .map_err(FederationClientError::Http)
+    }
+}
+
+/// An error that can occur when sending a request with a [`FederationClient`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FederationClientError<E> {
+    /// The URI of the request doesn't have an authority that is a valid server name.
+    InvalidDestination,
+
+    /// Signing the request failed.
+    Sign(XMatrixError),
+
+    /// The resolved address of the destination doesn't form a valid URI.
+    InvalidUri(InvalidUri),
+
+    /// The wrapped HTTP client returned an error.
+    Http(E),
+}
+
+impl<E: Display> Display for FederationClientError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidDestination => f.write_str("request URI doesn't contain a server name"),
+            Self::Sign(err) => write!(f, "Couldn't sign the request: {err}"),
+            Self::InvalidUri(err) => write!(f, "Invalid resolved URI: {err}"),
+            Self::Http(err) => write!(f, "HTTP error: {err}"),
+        }
+    }
+}
+
+impl<E: StdError> StdError for FederationClientError<E> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use http::header::{AUTHORIZATION, HOST};
+    use ruma_client::HttpClient;
+    use ruma_common::{serde::Base64, server_name};
+    use ruma_signatures::{Ed25519KeyPair, PublicKeyMap};
+
+    use super::FederationClient;
+    use crate::{
+        authorization::XMatrix,
+        resolver::{
+            tests::{FakeDns, FakeHttp},
+            Resolver, SrvRecord,
+        },
+    };
+
+    #[tokio::test]
+    async fn sign_and_rewrite_request() {
+        let dns = FakeDns(
+            [(
+                "_matrix-fed._tcp.remote.example",
+                vec![SrvRecord {
+                    priority: 0,
+                    weight: 0,
+                    port: 8000,
+                    target: "federation.remote.example".to_owned(),
+                }],
+            )]
+            .into(),
+        );
+        let http = FakeHttp::default();
+        let requests = http.requests.clone();
+
+        let key_pair =
+            Ed25519KeyPair::from_der(&Ed25519KeyPair::generate().unwrap(), "1".into()).unwrap();
+        let public_key = Base64::new(key_pair.public_key().to_owned());
+        let client = FederationClient::new(
+            Resolver::new(http, dns),
+            server_name!("origin.example").to_owned(),
+            key_pair,
+        );
+
+        let request = http::Request::put("https://remote.example/_matrix/federation/v1/send/1")
+            .body(br#"{ "pdus": [] }"#.to_vec())
+            .unwrap();
+        client.send_http_request(request).await.unwrap();
+
+        let mut requests = requests.lock().unwrap();
+        // The first request is the lookup of the well-known file.
+        let request = requests.pop().unwrap();
+        assert_eq!(
+            request.uri().to_string(),
+            "https://federation.remote.example:8000/_matrix/federation/v1/send/1"
+        );
+        assert_eq!(request.headers()[HOST], "remote.example");
+        assert!(request.headers().contains_key(AUTHORIZATION));
+
+        let mut public_key_map = PublicKeyMap::new();
+        public_key_map.insert(
+            "origin.example".to_owned(),
+            BTreeMap::from([("ed25519:1".to_owned(), public_key)]),
+        );
+        let x_matrix =
+            XMatrix::verify_request(&request, server_name!("remote.example"), &public_key_map)
+                .unwrap();
+        assert_eq!(x_matrix.origin, "origin.example");
+    }
+
+    #[tokio::test]
+    async fn invalid_destination() {
+        let key_pair =
+            Ed25519KeyPair::from_der(&Ed25519KeyPair::generate().unwrap(), "1".into()).unwrap();
+        let client = FederationClient::new(
+            Resolver::new(FakeHttp::default(), FakeDns::default()),
+            server_name!("origin.example").to_owned(),
+            key_pair,
+        );
+
+        let request = http::Request::get("/_matrix/federation/v1/version").body(vec![]).unwrap();
+        assert!(client.send_http_request(request).await.is_err());
+    }
+}
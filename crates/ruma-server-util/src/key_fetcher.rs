@@ -0,0 +1,737 @@
+//! Fetching and caching of the signing keys of other homeservers.
+//!
+//! [`verify_event`](ruma_signatures::verify_event) needs the public keys of the servers that
+//! signed an event. A [`KeyFetcher`] works out which keys are needed from the signatures of the
+//! event, and fetches the ones that are not in its [`KeyStore`] from the [origin server] or
+//! through [notary servers].
+//!
+//! [origin server]: https://spec.matrix.org/latest/server-server-api/#querying-keys-directly
+//! [notary servers]: https://spec.matrix.org/latest/server-server-api/#querying-keys-through-another-server
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error as StdError,
+    fmt::Debug,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use ruma_client::{HttpClient, HttpClientExt};
+use ruma_common::{
+    api::{MatrixVersion, SendAccessToken},
+    serde::{Base64, Raw},
+    CanonicalJsonObject, CanonicalJsonValue, MilliSecondsSinceUnixEpoch, OwnedServerName,
+    OwnedServerSigningKeyId, RoomVersionId, ServerName, ServerSigningKeyId,
+};
+use ruma_federation_api::discovery::{
+    get_remote_server_keys_batch::{self, v2::QueryCriteria},
+    get_server_keys, OldVerifyKey, ServerSigningKeys,
+};
+use ruma_signatures::{verify_json, PublicKeyMap, PublicKeySet};
+use thiserror::Error;
+use tracing::debug;
+
+/// A storage backend for the signing keys of other servers.
+#[async_trait]
+pub trait KeyStore: Debug + Send + Sync {
+    /// Load the stored keys of the given server, if any.
+    async fn load_server_keys(
+        &self,
+        server_name: &ServerName,
+    ) -> Result<Option<ServerSigningKeys>, Box<dyn StdError + Send + Sync>>;
+
+    /// Store the given keys, replacing any previously stored keys of the same server.
+    ///
+    /// The keys passed to this method already include the `old_verify_keys` of the previously
+    /// stored keys.
+    async fn save_server_keys(
+        &self,
+        keys: &ServerSigningKeys,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>>;
+}
+
+/// Fetches the signing keys needed to verify events and caches them in a [`KeyStore`].
+///
+/// Keys are requested from `https://{server_name}`, so the HTTP client should resolve server
+/// names, like a [`FederationClient`](crate::federation_client::FederationClient) does.
+#[derive(Debug)]
+pub struct KeyFetcher<C> {
+    http_client: C,
+    store: Arc<dyn KeyStore>,
+    notaries: Vec<OwnedServerName>,
+}
+
+impl<C: HttpClient> KeyFetcher<C> {
+    /// Creates a new `KeyFetcher` with the given HTTP client and key store.
+    ///
+    /// By default, keys are only requested from the servers that own them.
+    pub fn new(http_client: C, store: impl KeyStore + 'static) -> Self {
+        Self { http_client, store: Arc::new(store), notaries: Vec::new() }
+    }
+
+    /// Set the notary servers to query, in order, when keys can't be fetched from the servers
+    /// that own them.
+    pub fn notaries(self, notaries: Vec<OwnedServerName>) -> Self {
+        Self { notaries, ..self }
+    }
+
+    /// Get the public keys needed to verify the signatures of the given event.
+    ///
+    /// Keys that are missing from the store, or that are not valid at the `origin_server_ts` of
+    /// the event, are fetched first. Keys that can't be found are not included in the returned
+    /// map, so verifying the event with it fails.
+    pub async fn public_key_map(
+        &self,
+        event: &CanonicalJsonObject,
+        room_version: &RoomVersionId,
+    ) -> Result<PublicKeyMap, KeyFetchError> {
+        let valid_at = event
+            .get("origin_server_ts")
+            .and_then(|ts| serde_json::from_value(ts.clone().into()).ok())
+            .unwrap_or_else(MilliSecondsSinceUnixEpoch::now);
+        let validity =
+            KeyValidity { valid_at, enforce_valid_until: enforces_valid_until(room_version) };
+
+        let mut public_key_map = PublicKeyMap::new();
+        for (server_name, key_ids) in required_keys(event) {
+            let keys = self.server_keys(&server_name, &key_ids, validity).await?;
+
+            let public_key_set: PublicKeySet = key_ids
+                .iter()
+                .filter_map(|key_id| {
+                    let key = validity.usable_key(keys.as_ref()?, key_id)?;
+                    Some((key_id.to_string(), key.clone()))
+                })
+                .collect();
+
+            if !public_key_set.is_empty() {
+                public_key_map.insert(server_name.into(), public_key_set);
+            }
+        }
+
+        Ok(public_key_map)
+    }
+
+    /// Get the keys of the given server, fetching them if the given key IDs are not usable with
+    /// the stored keys.
+    async fn server_keys(
+        &self,
+        server_name: &ServerName,
+        key_ids: &BTreeSet<OwnedServerSigningKeyId>,
+        validity: KeyValidity,
+    ) -> Result<Option<ServerSigningKeys>, KeyFetchError> {
+        let missing_keys = |keys: &Option<ServerSigningKeys>| -> BTreeSet<OwnedServerSigningKeyId> {
+            key_ids
+                .iter()
+                .filter(|key_id| {
+                    keys.as_ref().and_then(|keys| validity.usable_key(keys, key_id)).is_none()
+                })
+                .cloned()
+                .collect()
+        };
+
+        let mut keys =
+            self.store.load_server_keys(server_name).await.map_err(KeyFetchError::Store)?;
+        if missing_keys(&keys).is_empty() {
+            return Ok(keys);
+        }
+
+        if let Some(fetched) = self.fetch_direct(server_name).await {
+            keys = Some(self.save(keys, fetched).await?);
+        }
+
+        for notary in &self.notaries {
+            let missing = missing_keys(&keys);
+            if missing.is_empty() {
+                break;
+            }
+
+            for fetched in self.fetch_from_notary(notary, server_name, missing, validity).await? {
+                keys = Some(self.save(keys, fetched).await?);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Merge the given fetched keys into the stored keys and save them.
+    async fn save(
+        &self,
+        stored: Option<ServerSigningKeys>,
+        fetched: ServerSigningKeys,
+    ) -> Result<ServerSigningKeys, KeyFetchError> {
+        let keys = match stored {
+            Some(stored) => merge_keys(stored, fetched),
+            None => fetched,
+        };
+        self.store.save_server_keys(&keys).await.map_err(KeyFetchError::Store)?;
+
+        Ok(keys)
+    }
+
+    /// Fetch the keys of the given server from the server itself.
+    ///
+    /// The server must return its current keys, so keys that already expired are rejected.
+    async fn fetch_direct(&self, server_name: &ServerName) -> Option<ServerSigningKeys> {
+        let response = match self
+            .http_client
+            .send_matrix_request(
+                &format!("https://{server_name}"),
+                SendAccessToken::None,
+                &[MatrixVersion::V1_0],
+                get_server_keys::v2::Request::new(),
+            )
+            .await
+        {
+            Ok(response) => response,
+            Err(_) => {
+                debug!(%server_name, "Failed to fetch server keys");
+                return None;
+            }
+        };
+
+        validate_keys(
+            &response.server_key,
+            server_name,
+            None,
+            Some(MilliSecondsSinceUnixEpoch::now()),
+        )
+    }
+
+    /// Fetch the given keys of the given server through the given notary server.
+    async fn fetch_from_notary(
+        &self,
+        notary: &ServerName,
+        server_name: &ServerName,
+        key_ids: BTreeSet<OwnedServerSigningKeyId>,
+        validity: KeyValidity,
+    ) -> Result<Vec<ServerSigningKeys>, KeyFetchError> {
+        // The keys of the notary are needed to check its signatures on the response.
+        let stored = self.store.load_server_keys(notary).await.map_err(KeyFetchError::Store)?;
+        let notary_keys = match stored {
+            Some(keys) if keys.valid_until_ts >= MilliSecondsSinceUnixEpoch::now() => keys,
+            stored => match self.fetch_direct(notary).await {
+                Some(fetched) => self.save(stored, fetched).await?,
+                None => return Ok(Vec::new()),
+            },
+        };
+        let notary_key_set = current_key_set(&notary_keys);
+
+        let mut criteria = QueryCriteria::new();
+        if validity.enforce_valid_until {
+            criteria.minimum_valid_until_ts = Some(validity.valid_at);
+        }
+        let query = key_ids.into_iter().map(|key_id| (key_id, criteria.clone())).collect();
+        let request = get_remote_server_keys_batch::v2::Request::new(
+            [(server_name.to_owned(), query)].into(),
+        );
+
+        let response = match self
+            .http_client
+            .send_matrix_request(
+                &format!("https://{notary}"),
+                SendAccessToken::None,
+                &[MatrixVersion::V1_0],
+                request,
+            )
+            .await
+        {
+            Ok(response) => response,
+            Err(_) => {
+                debug!(%notary, %server_name, "Failed to query server keys through notary");
+                return Ok(Vec::new());
+            }
+        };
+
+        // Notaries can return keys that expired since the event was sent.
+        let min_valid_until = criteria.minimum_valid_until_ts;
+        Ok(response
+            .server_keys
+            .iter()
+            .filter_map(|keys| {
+                validate_keys(keys, server_name, Some((notary, &notary_key_set)), min_valid_until)
+            })
+            .collect())
+    }
+}
+
+/// An error that can occur when fetching signing keys.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum KeyFetchError {
+    /// Loading or saving keys in the [`KeyStore`] failed.
+    #[error("key store error: {0}")]
+    Store(Box<dyn StdError + Send + Sync>),
+}
+
+/// Get the IDs of the keys of each server that signed the given object.
+///
+/// Signatures whose key ID is not a valid server signing key ID are ignored.
+pub fn required_keys(
+    object: &CanonicalJsonObject,
+) -> BTreeMap<OwnedServerName, BTreeSet<OwnedServerSigningKeyId>> {
+    let signatures = match object.get("signatures") {
+        Some(CanonicalJsonValue::Object(signatures)) => signatures,
+        _ => return BTreeMap::new(),
+    };
+
+    signatures
+        .iter()
+        .filter_map(|(server_name, signature_set)| {
+            let server_name = <&ServerName>::try_from(server_name.as_str()).ok()?;
+            let key_ids = signature_set
+                .as_object()?
+                .keys()
+                .filter_map(|key_id| <&ServerSigningKeyId>::try_from(key_id.as_str()).ok())
+                .map(ToOwned::to_owned)
+                .collect();
+
+            Some((server_name.to_owned(), key_ids))
+        })
+        .collect()
+}
+
+/// The time at which keys must be valid to be used.
+#[derive(Clone, Copy)]
+struct KeyValidity {
+    valid_at: MilliSecondsSinceUnixEpoch,
+    enforce_valid_until: bool,
+}
+
+impl KeyValidity {
+    /// Get the public key with the given ID, if it is valid.
+    fn usable_key<'a>(
+        &self,
+        keys: &'a ServerSigningKeys,
+        key_id: &ServerSigningKeyId,
+    ) -> Option<&'a Base64> {
+        if let Some(verify_key) = keys.verify_keys.get(key_id) {
+            if !self.enforce_valid_until || keys.valid_until_ts >= self.valid_at {
+                return Some(&verify_key.key);
+            }
+        }
+
+        keys.old_verify_keys
+            .get(key_id)
+            .filter(|old_key| !self.enforce_valid_until || old_key.expired_ts > self.valid_at)
+            .map(|old_key| &old_key.key)
+    }
+}
+
+/// Whether the `valid_until_ts` of keys must be respected in the given room version.
+///
+/// It is respected for room versions with unknown rules, because it is more secure.
+fn enforces_valid_until(room_version: &RoomVersionId) -> bool {
+    room_version.rules().map_or(true, |rules| rules.enforce_key_validity)
+}
+
+/// Deserialize the given keys and check that they belong to `server_name`, that they are valid
+/// until at least `min_valid_until`, if any, and that they are signed by the server and by the
+/// given notary, if any.
+fn validate_keys(
+    raw: &Raw<ServerSigningKeys>,
+    server_name: &ServerName,
+    notary: Option<(&ServerName, &PublicKeySet)>,
+    min_valid_until: Option<MilliSecondsSinceUnixEpoch>,
+) -> Option<ServerSigningKeys> {
+    let keys = match raw.deserialize() {
+        Ok(keys) => keys,
+        Err(error) => {
+            debug!(%server_name, %error, "Invalid server keys");
+            return None;
+        }
+    };
+    if keys.server_name != server_name {
+        debug!(%server_name, found = %keys.server_name, "Server keys for the wrong server");
+        return None;
+    }
+    if min_valid_until.map_or(false, |min| keys.valid_until_ts < min) {
+        debug!(%server_name, valid_until_ts = ?keys.valid_until_ts, "Expired server keys");
+        return None;
+    }
+
+    let object: CanonicalJsonObject = serde_json::from_str(raw.json().get()).ok()?;
+    if !is_signed_by(&object, server_name, &current_key_set(&keys)) {
+        debug!(%server_name, "Server keys are not signed by the server");
+        return None;
+    }
+    if let Some((notary, notary_key_set)) = notary {
+        if !is_signed_by(&object, notary, notary_key_set) {
+            debug!(%server_name, %notary, "Server keys are not signed by the notary");
+            return None;
+        }
+    }
+
+    Some(keys)
+}
+
+/// Whether the given object has a valid signature of `entity` with one of the given keys.
+fn is_signed_by(object: &CanonicalJsonObject, entity: &ServerName, keys: &PublicKeySet) -> bool {
+    let signature_set = match object
+        .get("signatures")
+        .and_then(CanonicalJsonValue::as_object)
+        .and_then(|signatures| signatures.get(entity.as_str()))
+        .and_then(CanonicalJsonValue::as_object)
+    {
+        Some(signature_set) => signature_set,
+        None => return false,
+    };
+
+    // `verify_json` checks all the signatures of the object, so only keep the ones we can check.
+    let signature_set: CanonicalJsonObject = signature_set
+        .iter()
+        .filter(|(key_id, _)| keys.contains_key(key_id.as_str()))
+        .map(|(key_id, signature)| (key_id.clone(), signature.clone()))
+        .collect();
+    if signature_set.is_empty() {
+        return false;
+    }
+
+    let mut object = object.clone();
+    let signatures = [(entity.to_string(), CanonicalJsonValue::Object(signature_set))];
+    object.insert(
+        "signatures".to_owned(),
+        CanonicalJsonValue::Object(signatures.into_iter().collect()),
+    );
+
+    let public_key_map = [(entity.to_string(), keys.clone())].into();
+    verify_json(&public_key_map, &object).is_ok()
+}
+
+/// The current public keys of the given server keys.
+fn current_key_set(keys: &ServerSigningKeys) -> PublicKeySet {
+    keys.verify_keys.iter().map(|(key_id, key)| (key_id.to_string(), key.key.clone())).collect()
+}
+
+/// Merge two versions of the keys of a server.
+///
+/// The most recent version is kept, and the current keys of the other version that are not
+/// current anymore become old keys that expired at the end of the validity of that version.
+fn merge_keys(a: ServerSigningKeys, b: ServerSigningKeys) -> ServerSigningKeys {
+    let (mut merged, other) = if a.valid_until_ts >= b.valid_until_ts { (a, b) } else { (b, a) };
+
+    for (key_id, verify_key) in other.verify_keys {
+        if !merged.verify_keys.contains_key(&key_id) {
+            merged
+                .old_verify_keys
+                .entry(key_id)
+                .or_insert_with(|| OldVerifyKey::new(other.valid_until_ts, verify_key.key));
+        }
+    }
+
+    for (key_id, old_key) in other.old_verify_keys {
+        if !merged.verify_keys.contains_key(&key_id) {
+            merged.old_verify_keys.entry(key_id).or_insert(old_key);
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        error::Error as StdError,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use ruma_client::HttpClient;
+    use ruma_common::{
+        serde::{base64::Standard, Base64},
+        server_name, CanonicalJsonObject, CanonicalJsonValue, OwnedServerName, RoomVersionId,
+        ServerName,
+    };
+    use ruma_federation_api::discovery::ServerSigningKeys;
+    use ruma_signatures::{hash_and_sign_event, sign_json, verify_event, Ed25519KeyPair, Verified};
+    use serde_json::json;
+
+    use super::{required_keys, KeyFetcher, KeyStore};
+
+    /// Event timestamp used in the tests.
+    const EVENT_TS: u64 = 1_000_000;
+
+    /// A `valid_until_ts` that is always in the future.
+    const FAR_FUTURE_TS: u64 = u64::MAX >> 12;
+
+    #[derive(Clone, Debug, Default)]
+    struct MemoryStore(Arc<Mutex<BTreeMap<OwnedServerName, ServerSigningKeys>>>);
+
+    #[async_trait]
+    impl KeyStore for MemoryStore {
+        async fn load_server_keys(
+            &self,
+            server_name: &ServerName,
+        ) -> Result<Option<ServerSigningKeys>, Box<dyn StdError + Send + Sync>> {
+            Ok(self.0.lock().unwrap().get(server_name).cloned())
+        }
+
+        async fn save_server_keys(
+            &self,
+            keys: &ServerSigningKeys,
+        ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+            self.0.lock().unwrap().insert(keys.server_name.clone(), keys.clone());
+            Ok(())
+        }
+    }
+
+    /// An HTTP client that answers with fixed responses by URI and counts requests.
+    #[derive(Debug, Default)]
+    struct FakeHttp {
+        responses: BTreeMap<String, String>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl FakeHttp {
+        fn respond(mut self, uri: &str, body: serde_json::Value) -> Self {
+            self.responses.insert(uri.to_owned(), body.to_string());
+            self
+        }
+
+        fn request_count(&self) -> usize {
+            self.requests.lock().unwrap().len()
+        }
+    }
+
+    #[async_trait]
+    impl HttpClient for FakeHttp {
+        type RequestBody = Vec<u8>;
+        type ResponseBody = Vec<u8>;
+        type Error = ();
+
+        async fn send_http_request(
+            &self,
+            req: http::Request<Vec<u8>>,
+        ) -> Result<http::Response<Vec<u8>>, ()> {
+            let uri = req.uri().to_string();
+            self.requests.lock().unwrap().push(uri.clone());
+
+            let response = match self.responses.get(&uri) {
+                Some(body) => http::Response::new(body.clone().into_bytes()),
+                None => http::Response::builder()
+                    .status(404)
+                    .body(br#"{ "errcode": "M_NOT_FOUND", "error": "" }"#.to_vec())
+                    .unwrap(),
+            };
+            Ok(response)
+        }
+    }
+
+    fn key_pair(version: &str) -> Ed25519KeyPair {
+        Ed25519KeyPair::from_der(&Ed25519KeyPair::generate().unwrap(), version.to_owned()).unwrap()
+    }
+
+    fn public_key(key_pair: &Ed25519KeyPair) -> String {
+        Base64::<Standard>::new(key_pair.public_key().to_owned()).encode()
+    }
+
+    /// The keys of `server_name`, signed by the server and the given notary.
+    fn server_keys(
+        server_name: &str,
+        key_pair: &Ed25519KeyPair,
+        old_keys: serde_json::Value,
+        valid_until_ts: u64,
+        notary: Option<(&str, &Ed25519KeyPair)>,
+    ) -> serde_json::Value {
+        let keys = json!({
+            "server_name": server_name,
+            "verify_keys": { format!("ed25519:{}", key_pair.version()): { "key": public_key(key_pair) } },
+            "old_verify_keys": old_keys,
+            "valid_until_ts": valid_until_ts,
+        });
+        let mut object: CanonicalJsonObject = serde_json::from_value(keys).unwrap();
+        sign_json(server_name, key_pair, &mut object).unwrap();
+        if let Some((notary, notary_key_pair)) = notary {
+            sign_json(notary, notary_key_pair, &mut object).unwrap();
+        }
+
+        serde_json::to_value(object).unwrap()
+    }
+
+    fn signed_event(key_pair: &Ed25519KeyPair) -> CanonicalJsonObject {
+        let mut event: CanonicalJsonObject = serde_json::from_value(json!({
+            "room_id": "!room:origin.example",
+            "sender": "@alice:origin.example",
+            "origin_server_ts": EVENT_TS,
+            "type": "m.room.message",
+            "content": { "body": "hello" },
+            "auth_events": [],
+            "prev_events": [],
+            "depth": 3,
+        }))
+        .unwrap();
+        hash_and_sign_event("origin.example", key_pair, &mut event, &RoomVersionId::V6).unwrap();
+
+        event
+    }
+
+    const KEYS_URI: &str = "https://origin.example/_matrix/key/v2/server";
+
+    #[test]
+    fn required_keys_from_signatures() {
+        let mut event = signed_event(&key_pair("1"));
+        let signatures = match event.get_mut("signatures") {
+            Some(CanonicalJsonValue::Object(signatures)) => signatures,
+            _ => unreachable!(),
+        };
+        signatures.insert(
+            "other.example".to_owned(),
+            CanonicalJsonValue::Object(
+                [("ed25519:a".to_owned(), "sig".into()), ("invalid".to_owned(), "sig".into())]
+                    .into(),
+            ),
+        );
+
+        let required = required_keys(&event);
+        assert_eq!(required.len(), 2);
+        assert_eq!(required[server_name!("origin.example")].len(), 1);
+        assert_eq!(required[server_name!("other.example")].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_and_cache_keys() {
+        let key_pair = key_pair("1");
+        let keys = server_keys("origin.example", &key_pair, json!({}), FAR_FUTURE_TS, None);
+        let http = FakeHttp::default().respond(KEYS_URI, keys);
+        let fetcher = KeyFetcher::new(http, MemoryStore::default());
+
+        let event = signed_event(&key_pair);
+        let public_key_map = fetcher.public_key_map(&event, &RoomVersionId::V6).await.unwrap();
+        assert_eq!(
+            verify_event(&public_key_map, &event, &RoomVersionId::V6).unwrap(),
+            Verified::All
+        );
+
+        // The keys are cached.
+        fetcher.public_key_map(&event, &RoomVersionId::V6).await.unwrap();
+        assert_eq!(fetcher.http_client.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn reject_keys_without_self_signature() {
+        let key_pair = key_pair("1");
+        let mut keys = server_keys("origin.example", &key_pair, json!({}), EVENT_TS, None);
+        keys["signatures"] = json!({});
+        let http = FakeHttp::default().respond(KEYS_URI, keys);
+        let fetcher = KeyFetcher::new(http, MemoryStore::default());
+
+        let event = signed_event(&key_pair);
+        let public_key_map = fetcher.public_key_map(&event, &RoomVersionId::V6).await.unwrap();
+        assert!(public_key_map.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_keys_through_notary() {
+        let key_pair = key_pair("1");
+        let notary_key_pair = self::key_pair("n");
+        let notary_keys =
+            server_keys("notary.example", &notary_key_pair, json!({}), FAR_FUTURE_TS, None);
+        let notarized_keys = server_keys(
+            "origin.example",
+            &key_pair,
+            json!({}),
+            EVENT_TS,
+            Some(("notary.example", &notary_key_pair)),
+        );
+        let http = FakeHttp::default()
+            .respond("https://notary.example/_matrix/key/v2/server", notary_keys)
+            .respond(
+                "https://notary.example/_matrix/key/v2/query",
+                json!({ "server_keys": [notarized_keys] }),
+            );
+        let fetcher = KeyFetcher::new(http, MemoryStore::default())
+            .notaries(vec![server_name!("notary.example").to_owned()]);
+
+        let event = signed_event(&key_pair);
+        let public_key_map = fetcher.public_key_map(&event, &RoomVersionId::V6).await.unwrap();
+        assert_eq!(
+            verify_event(&public_key_map, &event, &RoomVersionId::V6).unwrap(),
+            Verified::All
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_expired_keys_from_origin() {
+        let key_pair = key_pair("1");
+        let notary_key_pair = self::key_pair("n");
+
+        // The keys were valid when the event was sent, but the server should return its current
+        // keys.
+        let expired = server_keys("origin.example", &key_pair, json!({}), EVENT_TS, None);
+        let store = MemoryStore::default();
+        let fetcher =
+            KeyFetcher::new(FakeHttp::default().respond(KEYS_URI, expired.clone()), store.clone());
+
+        let event = signed_event(&key_pair);
+        let public_key_map = fetcher.public_key_map(&event, &RoomVersionId::V6).await.unwrap();
+        assert!(public_key_map.is_empty());
+        assert!(store.0.lock().unwrap().is_empty());
+
+        // Notaries can vouch for keys that were valid when the event was sent.
+        let notary_keys =
+            server_keys("notary.example", &notary_key_pair, json!({}), FAR_FUTURE_TS, None);
+        let notarized_keys = server_keys(
+            "origin.example",
+            &key_pair,
+            json!({}),
+            EVENT_TS,
+            Some(("notary.example", &notary_key_pair)),
+        );
+        let http = FakeHttp::default()
+            .respond(KEYS_URI, expired)
+            .respond("https://notary.example/_matrix/key/v2/server", notary_keys)
+            .respond(
+                "https://notary.example/_matrix/key/v2/query",
+                json!({ "server_keys": [notarized_keys] }),
+            );
+        let fetcher = KeyFetcher::new(http, MemoryStore::default())
+            .notaries(vec![server_name!("notary.example").to_owned()]);
+
+        let public_key_map = fetcher.public_key_map(&event, &RoomVersionId::V6).await.unwrap();
+        assert_eq!(
+            verify_event(&public_key_map, &event, &RoomVersionId::V6).unwrap(),
+            Verified::All
+        );
+    }
+
+    #[tokio::test]
+    async fn refetch_expired_keys_and_keep_old_keys() {
+        let old_key_pair = key_pair("1");
+        let new_key_pair = key_pair("2");
+
+        // The cached keys expired before the event was sent.
+        let store = MemoryStore::default();
+        let expired = server_keys("origin.example", &old_key_pair, json!({}), EVENT_TS - 1, None);
+        store.save_server_keys(&serde_json::from_value(expired).unwrap()).await.unwrap();
+
+        // The server rotated its key after the event was sent.
+        let old_keys = json!({
+            "ed25519:1": { "key": public_key(&old_key_pair), "expired_ts": EVENT_TS + 1 },
+        });
+        let keys = server_keys("origin.example", &new_key_pair, old_keys, FAR_FUTURE_TS, None);
+        let http = FakeHttp::default().respond(KEYS_URI, keys);
+        let fetcher = KeyFetcher::new(http, store.clone());
+
+        let event = signed_event(&old_key_pair);
+        let public_key_map = fetcher.public_key_map(&event, &RoomVersionId::V6).await.unwrap();
+        assert_eq!(
+            verify_event(&public_key_map, &event, &RoomVersionId::V6).unwrap(),
+            Verified::All
+        );
+
+        let stored = store.0.lock().unwrap()[server_name!("origin.example")].clone();
+        assert_eq!(stored.verify_keys.len(), 1);
+        assert_eq!(stored.old_verify_keys.len(), 1);
+
+        // The validity of keys is ignored in older room versions.
+        let mut event = signed_event(&old_key_pair);
+        event.insert(
+            "origin_server_ts".to_owned(),
+            serde_json::from_value(json!(EVENT_TS * 3)).unwrap(),
+        );
+        let public_key_map = fetcher.public_key_map(&event, &RoomVersionId::V4).await.unwrap();
+        assert_eq!(public_key_map["origin.example"].len(), 1);
+        assert_eq!(fetcher.http_client.request_count(), 1);
+    }
+}
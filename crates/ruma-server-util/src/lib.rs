@@ -4,3 +4,16 @@
 
 #![warn(missing_docs)]
 pub mod authorization;
+#[cfg(feature = "federation-client")]
+pub mod federation_client;
+#[cfg(feature = "federation-client")]
+pub mod key_fetcher;
+#[cfg(feature = "pdu-builder")]
+pub mod pdu_builder;
+#[cfg(any(feature = "pdu-validation", all(test, feature = "pdu-builder")))]
+mod pdu_event;
+#[cfg(feature = "pdu-validation")]
+pub mod pdu_validation;
+#[cfg(feature = "federation-client")]
+pub mod resolver;
+pub mod router;
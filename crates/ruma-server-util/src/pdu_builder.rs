@@ -0,0 +1,529 @@
+//! Creation of new PDUs.
+//!
+//! A [`PduBuilder`] takes the type, content and state key of a new event, and fills in the fields
+//! that depend on the room: the `auth_events` are selected from the current state of the room,
+//! the `depth` is computed from the `prev_events`, then the PDU is hashed and signed and its event
+//! ID is derived according to the [event format] of the room version.
+//!
+//! [event format]: ruma_common::room_version_rules::EventFormatVersion
+
+use std::{borrow::Borrow, collections::BTreeMap};
+
+use js_int::uint;
+use ruma_common::{
+    canonical_json::to_canonical_value,
+    events::{
+        pdu::{EventHash, Pdu, RoomV1Pdu, RoomV3Pdu},
+        StateEventType, TimelineEventType,
+    },
+    room_version_rules::EventFormatVersion,
+    CanonicalJsonError, CanonicalJsonValue, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId,
+    OwnedRoomId, OwnedUserId, RoomVersionId,
+};
+use ruma_signatures::{hash_and_sign_event, reference_hash, KeyPair};
+use ruma_state_res::{auth_types_for_event, Event};
+use serde_json::value::RawValue as RawJsonValue;
+use thiserror::Error;
+
+/// An event that can be referenced in the `prev_events` or `auth_events` of a new PDU.
+pub trait PduEvent: Event {
+    /// The reference hash of this event.
+    ///
+    /// It is only used to reference events in the PDUs of room versions that use the `$id:server`
+    /// event ID format, like room versions 1 and 2, and can be computed from the JSON of the event
+    /// with [`reference_hash()`]. Building a PDU referencing this event in those room versions
+    /// fails if this returns `None`.
+    ///
+    /// The default implementation returns `None`.
+    fn reference_hash(&self) -> Option<EventHash> {
+        None
+    }
+}
+
+impl<T: PduEvent> PduEvent for &T {
+    fn reference_hash(&self) -> Option<EventHash> {
+        (*self).reference_hash()
+    }
+}
+
+/// A builder for new PDUs.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ruma_common::{
+/// #     events::{room::message::RoomMessageEventContent, TimelineEventType},
+/// #     room_id, user_id, RoomVersionId,
+/// # };
+/// # use ruma_server_util::pdu_builder::{PduBuilder, PduEvent};
+/// # use ruma_signatures::Ed25519KeyPair;
+/// # fn example<E: PduEvent>(key_pair: Ed25519KeyPair, forward_extremities: Vec<E>) {
+/// # let fetch_state = |_: &_, _: &_| -> Option<E> { None };
+/// let content =
+///     serde_json::value::to_raw_value(&RoomMessageEventContent::text_plain("Hello!")).unwrap();
+///
+/// let (event_id, pdu) = PduBuilder::new(
+///     room_id!("!room:example.org").to_owned(),
+///     user_id!("@alice:example.org").to_owned(),
+///     TimelineEventType::RoomMessage,
+///     content,
+/// )
+/// .build(&RoomVersionId::V10, forward_extremities, fetch_state, &key_pair)
+/// .unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PduBuilder {
+    room_id: OwnedRoomId,
+    sender: OwnedUserId,
+    event_type: TimelineEventType,
+    content: Box<RawJsonValue>,
+    state_key: Option<String>,
+    redacts: Option<OwnedEventId>,
+    origin_server_ts: Option<MilliSecondsSinceUnixEpoch>,
+}
+
+impl PduBuilder {
+    /// Creates a new `PduBuilder` for an event sent by `sender` in the given room.
+    pub fn new(
+        room_id: OwnedRoomId,
+        sender: OwnedUserId,
+        event_type: TimelineEventType,
+        content: Box<RawJsonValue>,
+    ) -> Self {
+        Self {
+            room_id,
+            sender,
+            event_type,
+            content,
+            state_key: None,
+            redacts: None,
+            origin_server_ts: None,
+        }
+    }
+
+    /// Set the state key of the event, making it a state event.
+    pub fn state_key(self, state_key: String) -> Self {
+        Self { state_key: Some(state_key), ..self }
+    }
+
+    /// Set the ID of the event redacted by this event.
+    ///
+    /// It is added to the content of the event in room versions where the `redacts` key was moved
+    /// there, like room version 11.
+    pub fn redacts(self, redacts: OwnedEventId) -> Self {
+        Self { redacts: Some(redacts), ..self }
+    }
+
+    /// Set the timestamp of the event.
+    ///
+    /// Defaults to the time when the PDU is built.
+    pub fn origin_server_ts(self, origin_server_ts: MilliSecondsSinceUnixEpoch) -> Self {
+        Self { origin_server_ts: Some(origin_server_ts), ..self }
+    }
+
+    /// Builds the PDU, and returns it with its event ID.
+    ///
+    /// # Parameters
+    ///
+    /// * `room_version`: The version of the room.
+    /// * `prev_events`: The events that the new event follows, usually the forward extremities of
+    ///   the room.
+    /// * `fetch_state`: A function to get the event with the given type and state key in the
+    ///   current state of the room. It is used to select the `auth_events` of the new event.
+    /// * `key_pair`: The key pair of the server of the sender, used to sign the PDU.
+    pub fn build<E, K>(
+        self,
+        room_version: &RoomVersionId,
+        prev_events: impl IntoIterator<Item = E>,
+        fetch_state: impl Fn(&StateEventType, &str) -> Option<E>,
+        key_pair: &K,
+    ) -> Result<(OwnedEventId, Pdu), PduBuildError>
+    where
+        E: PduEvent,
+        K: KeyPair + ?Sized,
+    {
+        let rules = room_version
+            .rules()
+            .ok_or_else(|| PduBuildError::UnsupportedRoomVersion(room_version.clone()))?;
+
+        let auth_types = auth_types_for_event(
+            &self.event_type,
+            &self.sender,
+            self.state_key.as_deref(),
+            &self.content,
+        )
+        .map_err(PduBuildError::Content)?;
+        let auth_events: Vec<E> = auth_types
+            .iter()
+            .filter_map(|(event_type, state_key)| fetch_state(event_type, state_key))
+            .collect();
+
+        let prev_events: Vec<E> = prev_events.into_iter().collect();
+        let depth = prev_events
+            .iter()
+            .map(|event| event.depth())
+            .max()
+            .map_or(uint!(1), |depth| depth.saturating_add(uint!(1)));
+
+        let origin_server_ts =
+            self.origin_server_ts.unwrap_or_else(MilliSecondsSinceUnixEpoch::now);
+
+        // The `redacts` key is in the content in some room versions, it's added below.
+        let (redacts, content_redacts) = if rules.redaction.content_field_redacts {
+            (None, self.redacts)
+        } else {
+            (self.redacts, None)
+        };
+
+        // The hashes and signatures are computed below.
+        let hashes = EventHash::new(String::new());
+        let signatures = BTreeMap::new();
+
+        let pdu = match rules.event_format {
+            EventFormatVersion::V1 => Pdu::RoomV1Pdu(RoomV1Pdu {
+                event_id: EventId::new(self.sender.server_name()),
+                room_id: self.room_id,
+                sender: self.sender.clone(),
+                origin_server_ts,
+                kind: self.event_type,
+                content: self.content,
+                state_key: self.state_key,
+                prev_events: prev_events.iter().map(event_reference).collect::<Result<_, _>>()?,
+                depth,
+                auth_events: auth_events.iter().map(event_reference).collect::<Result<_, _>>()?,
+                redacts,
+                unsigned: BTreeMap::new(),
+                hashes,
+                signatures,
+            }),
+            _ => Pdu::RoomV3Pdu(RoomV3Pdu {
+                room_id: self.room_id,
+                sender: self.sender.clone(),
+                origin_server_ts,
+                kind: self.event_type,
+                content: self.content,
+                state_key: self.state_key,
+                prev_events: prev_events.iter().map(event_id).collect(),
+                depth,
+                auth_events: auth_events.iter().map(event_id).collect(),
+                redacts,
+                unsigned: BTreeMap::new(),
+                hashes,
+                signatures,
+            }),
+        };
+
+        let mut object = match to_canonical_value(&pdu).map_err(PduBuildError::CanonicalJson)? {
+            CanonicalJsonValue::Object(object) => object,
+            _ => unreachable!("PDUs are serialized as JSON objects"),
+        };
+
+        if let Some(redacts) = content_redacts {
+            if let Some(CanonicalJsonValue::Object(content)) = object.get_mut("content") {
+                content.insert("redacts".to_owned(), CanonicalJsonValue::String(redacts.into()));
+            }
+        }
+
+        hash_and_sign_event(
+            self.sender.server_name().as_str(),
+            key_pair,
+            &mut object,
+            room_version,
+        )?;
+
+        let event_id = match &pdu {
+            Pdu::RoomV1Pdu(pdu) => pdu.event_id.clone(),
+            _ => {
+                let hash = reference_hash(&object, room_version)?;
+                EventId::parse(format!("${hash}")).expect("reference hashes are valid event IDs")
+            }
+        };
+
+        let pdu = serde_json::from_value(CanonicalJsonValue::Object(object).into())
+            .map_err(PduBuildError::Json)?;
+
+        Ok((event_id, pdu))
+    }
+}
+
+/// An error that can occur when building a PDU.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PduBuildError {
+    /// The rules of the room version are unknown.
+    #[error("unsupported room version {0}")]
+    UnsupportedRoomVersion(RoomVersionId),
+
+    /// The content of the event is invalid.
+    #[error("invalid content: {0}")]
+    Content(serde_json::Error),
+
+    /// An event referenced by the PDU doesn't have a reference hash, although the room version
+    /// requires it.
+    #[error("missing reference hash of event {0}")]
+    MissingReferenceHash(OwnedEventId),
+
+    /// The PDU could not be converted to canonical JSON.
+    #[error("canonical JSON error: {0}")]
+    CanonicalJson(CanonicalJsonError),
+
+    /// Hashing or signing the PDU failed.
+    #[error("signatures error: {0}")]
+    Signatures(#[from] ruma_signatures::Error),
+
+    /// The signed PDU could not be deserialized.
+    #[error("JSON error: {0}")]
+    Json(serde_json::Error),
+}
+
+fn event_id<E: Event>(event: &E) -> OwnedEventId {
+    event.event_id().borrow().to_owned()
+}
+
+/// The reference to an event in the PDUs of room versions with the `$id:server` event ID format.
+fn event_reference<E: PduEvent>(event: &E) -> Result<(OwnedEventId, EventHash), PduBuildError> {
+    let event_id = event_id(event);
+    match event.reference_hash() {
+        Some(hash) => Ok((event_id, hash)),
+        None => Err(PduBuildError::MissingReferenceHash(event_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use js_int::uint;
+    use ruma_common::{
+        event_id,
+        events::{
+            pdu::{EventHash, Pdu},
+            StateEventType, TimelineEventType,
+        },
+        room_id,
+        serde::Base64,
+        user_id, CanonicalJsonObject, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId,
+        RoomVersionId,
+    };
+    use ruma_signatures::{reference_hash, verify_event, Ed25519KeyPair, PublicKeyMap, Verified};
+    use ruma_state_res::Event;
+    use serde_json::{json, value::to_raw_value as to_raw_json_value};
+
+    use super::{PduBuildError, PduBuilder, PduEvent};
+    use crate::pdu_event::impl_event_for_pdu;
+
+    /// A built PDU, with the data that is not part of the typed PDU.
+    struct TestEvent {
+        event_id: OwnedEventId,
+        pdu: Pdu,
+        reference_hash: Option<EventHash>,
+    }
+
+    impl TestEvent {
+        fn new(event_id: OwnedEventId, pdu: Pdu, room_version: &RoomVersionId) -> Self {
+            let object: CanonicalJsonObject =
+                serde_json::from_value(serde_json::to_value(&pdu).unwrap()).unwrap();
+            let reference_hash =
+                Some(EventHash::new(reference_hash(&object, room_version).unwrap()));
+            Self { event_id, pdu, reference_hash }
+        }
+
+        fn object(&self) -> CanonicalJsonObject {
+            serde_json::from_value(serde_json::to_value(&self.pdu).unwrap()).unwrap()
+        }
+    }
+
+    impl_event_for_pdu!(TestEvent);
+
+    impl PduEvent for TestEvent {
+        fn reference_hash(&self) -> Option<EventHash> {
+            self.reference_hash.clone()
+        }
+    }
+
+    fn key_pair() -> Ed25519KeyPair {
+        let document = Ed25519KeyPair::generate().unwrap();
+        Ed25519KeyPair::from_der(&document, "1".to_owned()).unwrap()
+    }
+
+    fn public_key_map(key_pair: &Ed25519KeyPair) -> PublicKeyMap {
+        BTreeMap::from([(
+            "example.org".to_owned(),
+            BTreeMap::from([(
+                "ed25519:1".to_owned(),
+                Base64::new(key_pair.public_key().to_owned()),
+            )]),
+        )])
+    }
+
+    /// Build the `m.room.create` event and the join of its creator.
+    fn create_room(room_version: &RoomVersionId, key_pair: &Ed25519KeyPair) -> Vec<TestEvent> {
+        let mut events = Vec::new();
+
+        let (event_id, pdu) = PduBuilder::new(
+            room_id!("!room:example.org").to_owned(),
+            user_id!("@alice:example.org").to_owned(),
+            TimelineEventType::RoomCreate,
+            to_raw_json_value(&json!({
+                "creator": "@alice:example.org",
+                "room_version": room_version,
+            }))
+            .unwrap(),
+        )
+        .state_key(String::new())
+        .origin_server_ts(MilliSecondsSinceUnixEpoch(uint!(1)))
+        .build(room_version, [] as [&TestEvent; 0], |_, _| None, key_pair)
+        .unwrap();
+        events.push(TestEvent::new(event_id, pdu, room_version));
+
+        let (event_id, pdu) = PduBuilder::new(
+            room_id!("!room:example.org").to_owned(),
+            user_id!("@alice:example.org").to_owned(),
+            TimelineEventType::RoomMember,
+            to_raw_json_value(&json!({ "membership": "join" })).unwrap(),
+        )
+        .state_key("@alice:example.org".to_owned())
+        .origin_server_ts(MilliSecondsSinceUnixEpoch(uint!(2)))
+        .build(room_version, &events, |event_type, _| lookup(&events, event_type), key_pair)
+        .unwrap();
+        events.push(TestEvent::new(event_id, pdu, room_version));
+
+        events
+    }
+
+    fn lookup<'a>(events: &'a [TestEvent], event_type: &StateEventType) -> Option<&'a TestEvent> {
+        events.iter().rev().find(|event| event.event_type().to_string() == event_type.to_string())
+    }
+
+    #[test]
+    fn build_v3_pdus() {
+        let room_version = RoomVersionId::V10;
+        let key_pair = key_pair();
+        let events = create_room(&room_version, &key_pair);
+
+        let create = &events[0];
+        assert!(matches!(create.pdu, Pdu::RoomV3Pdu(_)));
+        assert_eq!(create.depth(), uint!(1));
+        assert_eq!(create.prev_events().count(), 0);
+        assert_eq!(create.auth_events().count(), 0);
+
+        let object = create.object();
+        assert_eq!(
+            create.event_id.as_str(),
+            format!("${}", reference_hash(&object, &room_version).unwrap())
+        );
+        assert_eq!(
+            verify_event(&public_key_map(&key_pair), &object, &room_version).unwrap(),
+            Verified::All
+        );
+
+        let join = &events[1];
+        assert_eq!(join.depth(), uint!(2));
+        assert_eq!(join.prev_events().collect::<Vec<_>>(), [&create.event_id]);
+        assert_eq!(join.auth_events().collect::<Vec<_>>(), [&create.event_id]);
+
+        let (_, message) = PduBuilder::new(
+            room_id!("!room:example.org").to_owned(),
+            user_id!("@alice:example.org").to_owned(),
+            TimelineEventType::RoomMessage,
+            to_raw_json_value(&json!({ "msgtype": "m.text", "body": "Hello" })).unwrap(),
+        )
+        .build(&room_version, &events[1..], |event_type, _| lookup(&events, event_type), &key_pair)
+        .unwrap();
+        let message = TestEvent::new(event_id!("$message").to_owned(), message, &room_version);
+        assert_eq!(message.depth(), uint!(3));
+        assert_eq!(message.auth_events().collect::<Vec<_>>(), [&join.event_id, &create.event_id]);
+    }
+
+    #[test]
+    fn build_v1_pdus() {
+        let room_version = RoomVersionId::V1;
+        let key_pair = key_pair();
+        let events = create_room(&room_version, &key_pair);
+
+        let create = &events[0];
+        let join = &events[1];
+        assert_eq!(create.event_id.server_name(), Some("example.org".try_into().unwrap()));
+
+        match &join.pdu {
+            Pdu::RoomV1Pdu(pdu) => {
+                assert_eq!(pdu.event_id, join.event_id);
+                assert_eq!(pdu.prev_events.len(), 1);
+                assert_eq!(pdu.prev_events[0].0, create.event_id);
+                assert_eq!(
+                    Some(&pdu.prev_events[0].1.sha256),
+                    create.reference_hash.as_ref().map(|h| &h.sha256)
+                );
+            }
+            _ => panic!("expected a room v1 PDU"),
+        }
+        assert_eq!(
+            verify_event(&public_key_map(&key_pair), &join.object(), &room_version).unwrap(),
+            Verified::All
+        );
+    }
+
+    #[test]
+    fn build_v1_pdu_without_reference_hash() {
+        let room_version = RoomVersionId::V1;
+        let key_pair = key_pair();
+        let mut events = create_room(&room_version, &key_pair);
+        events[1].reference_hash = None;
+
+        let err = PduBuilder::new(
+            room_id!("!room:example.org").to_owned(),
+            user_id!("@alice:example.org").to_owned(),
+            TimelineEventType::RoomMessage,
+            to_raw_json_value(&json!({ "msgtype": "m.text", "body": "Hello" })).unwrap(),
+        )
+        .build(&room_version, &events[1..], |_, _| None, &key_pair)
+        .unwrap_err();
+
+        assert!(
+            matches!(err, PduBuildError::MissingReferenceHash(event_id) if event_id == events[1].event_id)
+        );
+    }
+
+    #[test]
+    fn build_v11_redaction() {
+        let room_version = RoomVersionId::V11;
+        let key_pair = key_pair();
+        let events = create_room(&room_version, &key_pair);
+        let redacted = <&EventId>::try_from("$redacted").unwrap();
+
+        let (_, pdu) = PduBuilder::new(
+            room_id!("!room:example.org").to_owned(),
+            user_id!("@alice:example.org").to_owned(),
+            TimelineEventType::RoomRedaction,
+            to_raw_json_value(&json!({})).unwrap(),
+        )
+        .redacts(redacted.to_owned())
+        .build(&room_version, &events[1..], |event_type, _| lookup(&events, event_type), &key_pair)
+        .unwrap();
+
+        match pdu {
+            Pdu::RoomV3Pdu(pdu) => {
+                assert_eq!(pdu.redacts, None);
+                assert_eq!(pdu.content.get(), r#"{"redacts":"$redacted"}"#);
+            }
+            _ => panic!("expected a room v3 PDU"),
+        }
+    }
+
+    #[test]
+    fn build_with_unsupported_room_version() {
+        let room_version = RoomVersionId::try_from("org.example.unknown").unwrap();
+
+        let err = PduBuilder::new(
+            room_id!("!room:example.org").to_owned(),
+            user_id!("@alice:example.org").to_owned(),
+            TimelineEventType::RoomMessage,
+            to_raw_json_value(&json!({})).unwrap(),
+        )
+        .build(&room_version, [] as [&TestEvent; 0], |_, _| None, &key_pair())
+        .unwrap_err();
+
+        assert!(matches!(err, PduBuildError::UnsupportedRoomVersion(_)));
+    }
+}
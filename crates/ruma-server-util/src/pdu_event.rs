@@ -0,0 +1,90 @@
+//! Implementation of `ruma_state_res::Event` for types wrapping a typed PDU.
+
+/// Implements `ruma_state_res::Event` for a type with an `event_id: OwnedEventId` field and a
+/// `pdu: Pdu` field.
+///
+/// The PDU must be a room v1 or v3 PDU.
+macro_rules! impl_event_for_pdu {
+    ($ty:ty) => {
+        impl ::ruma_state_res::Event for $ty {
+            type Id = ::ruma_common::OwnedEventId;
+
+            fn event_id(&self) -> &Self::Id {
+                &self.event_id
+            }
+
+            fn room_id(&self) -> &::ruma_common::RoomId {
+                $crate::pdu_event::pdu_field!(&self.pdu, pdu => &pdu.room_id)
+            }
+
+            fn sender(&self) -> &::ruma_common::UserId {
+                $crate::pdu_event::pdu_field!(&self.pdu, pdu => &pdu.sender)
+            }
+
+            fn origin_server_ts(&self) -> ::ruma_common::MilliSecondsSinceUnixEpoch {
+                $crate::pdu_event::pdu_field!(&self.pdu, pdu => pdu.origin_server_ts)
+            }
+
+            fn event_type(&self) -> &::ruma_common::events::TimelineEventType {
+                $crate::pdu_event::pdu_field!(&self.pdu, pdu => &pdu.kind)
+            }
+
+            fn content(&self) -> &::serde_json::value::RawValue {
+                $crate::pdu_event::pdu_field!(&self.pdu, pdu => &pdu.content)
+            }
+
+            fn state_key(&self) -> Option<&str> {
+                $crate::pdu_event::pdu_field!(&self.pdu, pdu => pdu.state_key.as_deref())
+            }
+
+            fn depth(&self) -> ::js_int::UInt {
+                $crate::pdu_event::pdu_field!(&self.pdu, pdu => pdu.depth)
+            }
+
+            fn prev_events(&self) -> Box<dyn DoubleEndedIterator<Item = &Self::Id> + '_> {
+                match &self.pdu {
+                    ::ruma_common::events::pdu::Pdu::RoomV1Pdu(pdu) => {
+                        Box::new(pdu.prev_events.iter().map(|(id, _)| id))
+                    }
+                    ::ruma_common::events::pdu::Pdu::RoomV3Pdu(pdu) => {
+                        Box::new(pdu.prev_events.iter())
+                    }
+                    #[allow(unreachable_patterns)]
+                    _ => unreachable!("PDUs are parsed as room v1 or v3 PDUs"),
+                }
+            }
+
+            fn auth_events(&self) -> Box<dyn DoubleEndedIterator<Item = &Self::Id> + '_> {
+                match &self.pdu {
+                    ::ruma_common::events::pdu::Pdu::RoomV1Pdu(pdu) => {
+                        Box::new(pdu.auth_events.iter().map(|(id, _)| id))
+                    }
+                    ::ruma_common::events::pdu::Pdu::RoomV3Pdu(pdu) => {
+                        Box::new(pdu.auth_events.iter())
+                    }
+                    #[allow(unreachable_patterns)]
+                    _ => unreachable!("PDUs are parsed as room v1 or v3 PDUs"),
+                }
+            }
+
+            fn redacts(&self) -> Option<&Self::Id> {
+                $crate::pdu_event::pdu_field!(&self.pdu, pdu => pdu.redacts.as_ref())
+            }
+        }
+    };
+}
+
+/// Accesses a field that is common to room v1 and v3 PDUs.
+macro_rules! pdu_field {
+    ($pdu:expr, $pat:pat => $expr:expr) => {
+        match $pdu {
+            ::ruma_common::events::pdu::Pdu::RoomV1Pdu($pat) => $expr,
+            ::ruma_common::events::pdu::Pdu::RoomV3Pdu($pat) => $expr,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("PDUs are parsed as room v1 or v3 PDUs"),
+        }
+    };
+}
+
+pub(crate) use impl_event_for_pdu;
+pub(crate) use pdu_field;
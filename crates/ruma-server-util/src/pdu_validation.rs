@@ -0,0 +1,720 @@
+//! Validation of the PDUs received over federation.
+//!
+//! [`check_incoming_pdu`] runs the [checks performed on receipt of a PDU] in the order of the
+//! specification:
+//!
+//! 1. The PDU must be a valid event, otherwise it is dropped.
+//! 2. It must pass the signature checks, otherwise it is dropped.
+//! 3. It must pass the hash checks, otherwise it is redacted before being processed further.
+//! 4. It must pass the authorization rules based on its `auth_events`, otherwise it is rejected.
+//! 5. It must pass the authorization rules based on the state before the event, otherwise it is
+//!    rejected.
+//! 6. It must pass the authorization rules based on the current state of the room, otherwise it is
+//!    soft failed.
+//!
+//! [checks performed on receipt of a PDU]: https://spec.matrix.org/latest/server-server-api/#checks-performed-on-receipt-of-a-pdu
+
+use std::collections::BTreeMap;
+
+use ruma_common::{
+    canonical_json::{redact, RedactionError},
+    events::{
+        pdu::{Pdu, RoomV1Pdu, RoomV3Pdu},
+        StateEventType,
+    },
+    room_version_rules::{EventFormatVersion, RoomVersionRules},
+    CanonicalJsonObject, CanonicalJsonValue, EventId, OwnedEventId, RoomVersionId,
+};
+use ruma_signatures::{reference_hash, verify_event, PublicKeyMap, Verified};
+use ruma_state_res::{auth_check, auth_types_for_event, Event, RoomVersion};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::pdu_event::impl_event_for_pdu;
+
+/// The maximum size of a PDU, in bytes.
+const MAX_PDU_BYTES: usize = 65_536;
+
+/// The maximum size of the identifiers, event type and state key of a PDU, in bytes.
+const MAX_FIELD_BYTES: usize = 255;
+
+/// Checks a PDU received over federation.
+///
+/// # Parameters
+///
+/// * `pdu`: The JSON of the PDU, as received in a transaction.
+/// * `room_version`: The version of the room of the PDU.
+/// * `public_key_map`: The public keys of the servers that signed the PDU. They can be obtained
+///   with a [`KeyFetcher`](crate::key_fetcher::KeyFetcher).
+/// * `fetch_event`: A function to get an event by its ID. It is used to get the `auth_events` of
+///   the PDU, which must have been fetched and checked beforehand. It should return `None` for
+///   events that are unknown or were rejected.
+/// * `fetch_state_before`: A function to get the event with the given type and state key in the
+///   state of the room before the PDU, resolved from the state after its `prev_events`.
+/// * `fetch_current_state`: A function to get the event with the given type and state key in the
+///   current state of the room.
+pub fn check_incoming_pdu<E: Event>(
+    pdu: CanonicalJsonObject,
+    room_version: &RoomVersionId,
+    public_key_map: &PublicKeyMap,
+    fetch_event: impl Fn(&EventId) -> Option<E>,
+    fetch_state_before: impl Fn(&StateEventType, &str) -> Option<E>,
+    fetch_current_state: impl Fn(&StateEventType, &str) -> Option<E>,
+) -> IncomingPduOutcome {
+    let (pdu, rules) = match IncomingPdu::parse(pdu, room_version) {
+        Ok(pdu) => pdu,
+        Err(reason) => return IncomingPduOutcome::Dropped(reason),
+    };
+
+    let pdu = match verify_event(public_key_map, &pdu.json, room_version) {
+        Ok(Verified::All) => pdu,
+        Ok(Verified::Signatures) => {
+            debug!("content hash of {} doesn't match, redacting it", pdu.event_id);
+            match pdu.redact(room_version, rules.event_format) {
+                Ok(pdu) => pdu,
+                Err(reason) => return IncomingPduOutcome::Dropped(reason),
+            }
+        }
+        Err(error) => return IncomingPduOutcome::Dropped(DropReason::Signatures(error)),
+    };
+
+    let room_version = match RoomVersion::new(room_version) {
+        Ok(room_version) => room_version,
+        Err(_) => {
+            return IncomingPduOutcome::Dropped(DropReason::UnsupportedRoomVersion(
+                room_version.clone(),
+            ))
+        }
+    };
+
+    let auth_types = match auth_types_for_event(
+        pdu.event_type(),
+        pdu.sender(),
+        pdu.state_key(),
+        pdu.content(),
+    ) {
+        Ok(auth_types) => auth_types,
+        Err(error) => {
+            return IncomingPduOutcome::Rejected {
+                pdu,
+                reason: RejectionReason::AuthEvents(error.into()),
+            }
+        }
+    };
+
+    // Authorization based on the `auth_events` of the PDU.
+    let auth_events = match pdu.select_auth_events(&auth_types, fetch_event) {
+        Ok(auth_events) => auth_events,
+        Err(reason) => return IncomingPduOutcome::Rejected { pdu, reason },
+    };
+    if let Err(error) = check_auth(&room_version, &pdu, &auth_events) {
+        return IncomingPduOutcome::Rejected { pdu, reason: RejectionReason::AuthEvents(error) };
+    }
+
+    // Authorization based on the state before the PDU.
+    let state_before = select_state(&auth_types, fetch_state_before);
+    if let Err(error) = check_auth(&room_version, &pdu, &state_before) {
+        return IncomingPduOutcome::Rejected { pdu, reason: RejectionReason::StateBefore(error) };
+    }
+
+    // Authorization based on the current state of the room.
+    let current_state = select_state(&auth_types, fetch_current_state);
+    if let Err(error) = check_auth(&room_version, &pdu, &current_state) {
+        warn!("soft failing {}: {error}", pdu.event_id);
+        return IncomingPduOutcome::SoftFailed { pdu, reason: error };
+    }
+
+    IncomingPduOutcome::Accepted(pdu)
+}
+
+/// The outcome of [`check_incoming_pdu`].
+///
+/// A PDU that fails the hash checks is redacted and checked further, so whether it is accepted,
+/// rejected or soft failed, [`IncomingPdu::redacted`] must be checked to know if it was redacted.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+pub enum IncomingPduOutcome {
+    /// The PDU passed all the checks.
+    ///
+    /// It should be added to the room.
+    Accepted(IncomingPdu),
+
+    /// The PDU is not a valid event or has invalid signatures.
+    ///
+    /// It should be ignored.
+    Dropped(DropReason),
+
+    /// The PDU failed the authorization rules based on its `auth_events` or on the state before
+    /// it.
+    ///
+    /// It should be persisted as rejected, and it must not be referenced by new events.
+    Rejected {
+        /// The PDU.
+        pdu: IncomingPdu,
+
+        /// The reason why the PDU was rejected.
+        reason: RejectionReason,
+    },
+
+    /// The PDU passed the authorization rules based on its `auth_events` and on the state before
+    /// it, but not based on the current state of the room.
+    ///
+    /// It should be persisted, but it must not be sent to clients or referenced by new events.
+    SoftFailed {
+        /// The PDU.
+        pdu: IncomingPdu,
+
+        /// The reason why the PDU failed the authorization rules.
+        reason: ruma_state_res::Error,
+    },
+}
+
+/// The reason why a PDU was dropped.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DropReason {
+    /// The rules of the room version are unknown.
+    #[error("unsupported room version {0}")]
+    UnsupportedRoomVersion(RoomVersionId),
+
+    /// The PDU is larger than 65 536 bytes.
+    #[error("PDU is larger than {MAX_PDU_BYTES} bytes")]
+    TooLarge,
+
+    /// A field of the PDU is larger than 255 bytes.
+    #[error("{0} field is larger than {MAX_FIELD_BYTES} bytes")]
+    FieldTooLarge(&'static str),
+
+    /// The PDU doesn't have the format of the room version.
+    #[error("invalid PDU: {0}")]
+    InvalidFormat(serde_json::Error),
+
+    /// The signature checks failed.
+    #[error("invalid signatures: {0}")]
+    Signatures(ruma_signatures::Error),
+
+    /// The PDU failed the hash checks and could not be redacted.
+    #[error("redaction failed: {0}")]
+    Redaction(RedactionError),
+}
+
+/// The reason why a PDU was rejected.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RejectionReason {
+    /// An event of the `auth_events` is unknown or was rejected.
+    #[error("auth event {0} is missing or was rejected")]
+    MissingAuthEvent(OwnedEventId),
+
+    /// An event of the `auth_events` is not a state event of the same room.
+    #[error("auth event {0} is not a state event of the room")]
+    InvalidAuthEvent(OwnedEventId),
+
+    /// Several events of the `auth_events` have the same type and state key.
+    #[error("duplicate auth events for ({event_type}, {state_key:?})")]
+    DuplicateAuthEvent {
+        /// The type of the events.
+        event_type: StateEventType,
+
+        /// The state key of the events.
+        state_key: String,
+    },
+
+    /// An event of the `auth_events` is not needed to authorize the PDU.
+    #[error("auth event {0} is not needed to authorize the event")]
+    UnexpectedAuthEvent(OwnedEventId),
+
+    /// The PDU failed the authorization rules based on its `auth_events`.
+    #[error("auth check against the auth events failed: {0}")]
+    AuthEvents(ruma_state_res::Error),
+
+    /// The PDU failed the authorization rules based on the state before it.
+    #[error("auth check against the state before the event failed: {0}")]
+    StateBefore(ruma_state_res::Error),
+}
+
+/// A PDU received over federation that passed the format checks.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct IncomingPdu {
+    /// The ID of the event.
+    pub event_id: OwnedEventId,
+
+    /// The PDU.
+    pub pdu: Pdu,
+
+    /// The JSON of the PDU, redacted if it failed the hash checks.
+    pub json: CanonicalJsonObject,
+
+    /// Whether the PDU failed the hash checks and was redacted.
+    pub redacted: bool,
+}
+
+impl IncomingPdu {
+    /// Checks the format of the given PDU, and computes its event ID.
+    fn parse(
+        json: CanonicalJsonObject,
+        room_version: &RoomVersionId,
+    ) -> Result<(Self, RoomVersionRules), DropReason> {
+        let rules = room_version
+            .rules()
+            .ok_or_else(|| DropReason::UnsupportedRoomVersion(room_version.clone()))?;
+
+        let size = serde_json::to_vec(&json).map_err(DropReason::InvalidFormat)?.len();
+        if size > MAX_PDU_BYTES {
+            return Err(DropReason::TooLarge);
+        }
+
+        for field in ["event_id", "room_id", "sender", "type", "state_key"] {
+            if let Some(CanonicalJsonValue::String(value)) = json.get(field) {
+                if value.len() > MAX_FIELD_BYTES {
+                    return Err(DropReason::FieldTooLarge(field));
+                }
+            }
+        }
+
+        let pdu = parse_pdu(&json, rules.event_format)?;
+        let event_id = match &pdu {
+            Pdu::RoomV1Pdu(pdu) => pdu.event_id.clone(),
+            _ => {
+                let hash = reference_hash(&json, room_version).map_err(DropReason::Signatures)?;
+                EventId::parse(format!("${hash}")).expect("reference hashes are valid event IDs")
+            }
+        };
+
+        Ok((Self { event_id, pdu, json, redacted: false }, rules))
+    }
+
+    /// Redacts this PDU.
+    fn redact(
+        self,
+        room_version: &RoomVersionId,
+        event_format: EventFormatVersion,
+    ) -> Result<Self, DropReason> {
+        let json = redact(self.json, room_version, None).map_err(DropReason::Redaction)?;
+        let pdu = parse_pdu(&json, event_format)?;
+        Ok(Self { event_id: self.event_id, pdu, json, redacted: true })
+    }
+
+    /// Fetches the `auth_events` of this PDU, and checks that they are the ones selected by the
+    /// auth events selection algorithm.
+    fn select_auth_events<E: Event>(
+        &self,
+        auth_types: &[(StateEventType, String)],
+        fetch_event: impl Fn(&EventId) -> Option<E>,
+    ) -> Result<BTreeMap<(StateEventType, String), E>, RejectionReason> {
+        let mut auth_events = BTreeMap::new();
+
+        for event_id in self.auth_events() {
+            let event = fetch_event(event_id)
+                .ok_or_else(|| RejectionReason::MissingAuthEvent(event_id.clone()))?;
+
+            let state_key = match event.state_key() {
+                Some(state_key) if event.room_id() == self.room_id() => state_key.to_owned(),
+                _ => return Err(RejectionReason::InvalidAuthEvent(event_id.clone())),
+            };
+            let key = (event.event_type().to_string().into(), state_key);
+
+            if !auth_types.contains(&key) {
+                return Err(RejectionReason::UnexpectedAuthEvent(event_id.clone()));
+            }
+            if auth_events.contains_key(&key) {
+                let (event_type, state_key) = key;
+                return Err(RejectionReason::DuplicateAuthEvent { event_type, state_key });
+            }
+
+            auth_events.insert(key, event);
+        }
+
+        Ok(auth_events)
+    }
+}
+
+impl_event_for_pdu!(IncomingPdu);
+
+/// Parses the given JSON as a PDU with the given event format.
+fn parse_pdu(
+    json: &CanonicalJsonObject,
+    event_format: EventFormatVersion,
+) -> Result<Pdu, DropReason> {
+    let value = serde_json::Value::from(CanonicalJsonValue::Object(json.clone()));
+    let pdu = match event_format {
+        EventFormatVersion::V1 => serde_json::from_value::<RoomV1Pdu>(value).map(Pdu::RoomV1Pdu),
+        _ => serde_json::from_value::<RoomV3Pdu>(value).map(Pdu::RoomV3Pdu),
+    };
+
+    pdu.map_err(DropReason::InvalidFormat)
+}
+
+/// Gets the events with the given types and state keys in a state.
+fn select_state<E: Event>(
+    auth_types: &[(StateEventType, String)],
+    fetch_state: impl Fn(&StateEventType, &str) -> Option<E>,
+) -> BTreeMap<(StateEventType, String), E> {
+    auth_types
+        .iter()
+        .filter_map(|(event_type, state_key)| {
+            let event = fetch_state(event_type, state_key)?;
+            Some(((event_type.clone(), state_key.clone()), event))
+        })
+        .collect()
+}
+
+/// Checks the PDU against the authorization rules with the given auth events.
+fn check_auth<E: Event>(
+    room_version: &RoomVersion,
+    pdu: &IncomingPdu,
+    auth_events: &BTreeMap<(StateEventType, String), E>,
+) -> Result<(), ruma_state_res::Error> {
+    let third_party_invite = auth_events.iter().find_map(|((event_type, _), event)| {
+        (*event_type == StateEventType::RoomThirdPartyInvite).then_some(event)
+    });
+
+    auth_check(room_version, pdu, third_party_invite, |event_type, state_key| {
+        auth_events.get(&(event_type.clone(), state_key.to_owned()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ruma_common::{
+        events::StateEventType, serde::Base64, CanonicalJsonObject, CanonicalJsonValue,
+        RoomVersionId,
+    };
+    use ruma_signatures::{hash_and_sign_event, Ed25519KeyPair, PublicKeyMap};
+    use ruma_state_res::Event;
+    use serde_json::json;
+
+    use super::{check_incoming_pdu, DropReason, IncomingPdu, IncomingPduOutcome, RejectionReason};
+
+    const ROOM_VERSION: RoomVersionId = RoomVersionId::V10;
+
+    struct TestRoom {
+        key_pair: Ed25519KeyPair,
+        events: Vec<IncomingPdu>,
+    }
+
+    impl TestRoom {
+        /// Creates a room with an `m.room.create` event and the join of its creator.
+        fn new() -> Self {
+            let document = Ed25519KeyPair::generate().unwrap();
+            let key_pair = Ed25519KeyPair::from_der(&document, "1".to_owned()).unwrap();
+            let mut room = Self { key_pair, events: Vec::new() };
+
+            let create = room.pdu(
+                "m.room.create",
+                Some(""),
+                "@alice:example.org",
+                json!({ "creator": "@alice:example.org", "room_version": "10" }),
+                &[],
+            );
+            room.accept(create);
+
+            let join = room.pdu(
+                "m.room.member",
+                Some("@alice:example.org"),
+                "@alice:example.org",
+                json!({ "membership": "join" }),
+                &["m.room.create"],
+            );
+            room.accept(join);
+
+            room
+        }
+
+        fn public_key_map(&self) -> PublicKeyMap {
+            BTreeMap::from([(
+                "example.org".to_owned(),
+                BTreeMap::from([(
+                    "ed25519:1".to_owned(),
+                    Base64::new(self.key_pair.public_key().to_owned()),
+                )]),
+            )])
+        }
+
+        fn event(&self, event_type: &str) -> &IncomingPdu {
+            self.events
+                .iter()
+                .rev()
+                .find(|event| event.event_type().to_string() == event_type)
+                .unwrap()
+        }
+
+        /// Creates a signed PDU whose `auth_events` are the last events of the given types.
+        fn pdu(
+            &self,
+            event_type: &str,
+            state_key: Option<&str>,
+            sender: &str,
+            content: serde_json::Value,
+            auth_types: &[&str],
+        ) -> CanonicalJsonObject {
+            let auth_events: Vec<_> = auth_types
+                .iter()
+                .map(|event_type| self.event(event_type).event_id.clone())
+                .collect();
+            let prev_events: Vec<_> =
+                self.events.last().map(|event| event.event_id.clone()).into_iter().collect();
+
+            let mut pdu = json!({
+                "room_id": "!room:example.org",
+                "sender": sender,
+                "origin_server_ts": self.events.len() + 1,
+                "type": event_type,
+                "content": content,
+                "prev_events": prev_events,
+                "depth": self.events.len() + 1,
+                "auth_events": auth_events,
+                "hashes": {},
+                "signatures": {},
+            });
+            if let Some(state_key) = state_key {
+                pdu["state_key"] = state_key.into();
+            }
+
+            let mut pdu = serde_json::from_value(pdu).unwrap();
+            hash_and_sign_event("example.org", &self.key_pair, &mut pdu, &ROOM_VERSION).unwrap();
+            pdu
+        }
+
+        fn check(&self, pdu: CanonicalJsonObject) -> IncomingPduOutcome {
+            self.check_with_current_state(pdu, &self.events)
+        }
+
+        fn check_with_current_state(
+            &self,
+            pdu: CanonicalJsonObject,
+            current_state: &[IncomingPdu],
+        ) -> IncomingPduOutcome {
+            check_incoming_pdu(
+                pdu,
+                &ROOM_VERSION,
+                &self.public_key_map(),
+                |event_id| self.events.iter().find(|event| event.event_id == event_id),
+                |event_type, state_key| state_event(&self.events, event_type, state_key),
+                |event_type, state_key| state_event(current_state, event_type, state_key),
+            )
+        }
+
+        fn accept(&mut self, pdu: CanonicalJsonObject) {
+            match self.check(pdu) {
+                IncomingPduOutcome::Accepted(pdu) => self.events.push(pdu),
+                outcome => panic!("PDU was not accepted: {outcome:?}"),
+            }
+        }
+    }
+
+    fn state_event<'a>(
+        events: &'a [IncomingPdu],
+        event_type: &StateEventType,
+        state_key: &str,
+    ) -> Option<&'a IncomingPdu> {
+        events.iter().rev().find(|event| {
+            event.event_type().to_string() == event_type.to_string()
+                && event.state_key() == Some(state_key)
+        })
+    }
+
+    #[test]
+    fn accept_valid_pdus() {
+        let room = TestRoom::new();
+
+        let message = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create", "m.room.member"],
+        );
+
+        match room.check(message) {
+            IncomingPduOutcome::Accepted(pdu) => {
+                assert!(!pdu.redacted);
+                assert_eq!(pdu.prev_events().collect::<Vec<_>>(), [&room.events[1].event_id]);
+            }
+            outcome => panic!("PDU was not accepted: {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn drop_invalid_pdus() {
+        let room = TestRoom::new();
+
+        let mut large = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create", "m.room.member"],
+        );
+        let unsigned = json!({ "padding": "a".repeat(70_000) });
+        large.insert("unsigned".to_owned(), serde_json::from_value(unsigned).unwrap());
+        assert!(matches!(room.check(large), IncomingPduOutcome::Dropped(DropReason::TooLarge)));
+
+        let long_state_key = room.pdu(
+            "m.room.topic",
+            Some(&"a".repeat(256)),
+            "@alice:example.org",
+            json!({ "topic": "Test" }),
+            &["m.room.create", "m.room.member"],
+        );
+        assert!(matches!(
+            room.check(long_state_key),
+            IncomingPduOutcome::Dropped(DropReason::FieldTooLarge("state_key"))
+        ));
+
+        let mut invalid = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({}),
+            &["m.room.create", "m.room.member"],
+        );
+        invalid.remove("depth");
+        assert!(matches!(
+            room.check(invalid),
+            IncomingPduOutcome::Dropped(DropReason::InvalidFormat(_))
+        ));
+
+        let mut unsigned = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({}),
+            &["m.room.create", "m.room.member"],
+        );
+        unsigned.insert("signatures".to_owned(), CanonicalJsonValue::Object(BTreeMap::new()));
+        assert!(matches!(
+            room.check(unsigned),
+            IncomingPduOutcome::Dropped(DropReason::Signatures(_))
+        ));
+    }
+
+    #[test]
+    fn redact_pdus_with_invalid_hash() {
+        let room = TestRoom::new();
+
+        let mut message = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create", "m.room.member"],
+        );
+        let content = json!({ "msgtype": "m.text", "body": "Goodbye" });
+        message.insert("content".to_owned(), serde_json::from_value(content).unwrap());
+
+        match room.check(message) {
+            IncomingPduOutcome::Accepted(pdu) => {
+                assert!(pdu.redacted);
+                assert_eq!(pdu.content().get(), "{}");
+            }
+            outcome => panic!("PDU was not accepted: {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn reject_pdus_failing_auth() {
+        let mut room = TestRoom::new();
+
+        let missing_member = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create"],
+        );
+        assert!(matches!(
+            room.check(missing_member),
+            IncomingPduOutcome::Rejected { reason: RejectionReason::AuthEvents(_), .. }
+        ));
+
+        let duplicate = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create", "m.room.create", "m.room.member"],
+        );
+        assert!(matches!(
+            room.check(duplicate),
+            IncomingPduOutcome::Rejected { reason: RejectionReason::DuplicateAuthEvent { .. }, .. }
+        ));
+
+        let not_joined = room.pdu(
+            "m.room.message",
+            None,
+            "@bob:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create"],
+        );
+        assert!(matches!(
+            room.check(not_joined),
+            IncomingPduOutcome::Rejected { reason: RejectionReason::AuthEvents(_), .. }
+        ));
+
+        let unexpected = room.pdu(
+            "m.room.message",
+            None,
+            "@bob:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create", "m.room.member"],
+        );
+        match room.check(unexpected) {
+            IncomingPduOutcome::Rejected {
+                reason: RejectionReason::UnexpectedAuthEvent(event_id),
+                ..
+            } => assert_eq!(event_id, room.events[1].event_id),
+            outcome => panic!("PDU was not rejected: {outcome:?}"),
+        }
+
+        let missing = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create", "m.room.member"],
+        );
+        let member = room.events.pop().unwrap();
+        match room.check(missing) {
+            IncomingPduOutcome::Rejected {
+                reason: RejectionReason::MissingAuthEvent(event_id),
+                ..
+            } => assert_eq!(event_id, member.event_id),
+            outcome => panic!("PDU was not rejected: {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn soft_fail_pdus_failing_auth_against_current_state() {
+        let mut room = TestRoom::new();
+
+        let message = room.pdu(
+            "m.room.message",
+            None,
+            "@alice:example.org",
+            json!({ "msgtype": "m.text", "body": "Hello" }),
+            &["m.room.create", "m.room.member"],
+        );
+
+        let leave = room.pdu(
+            "m.room.member",
+            Some("@alice:example.org"),
+            "@alice:example.org",
+            json!({ "membership": "leave" }),
+            &["m.room.create", "m.room.member"],
+        );
+        let state_before = room.events.clone();
+        room.accept(leave);
+
+        // The state before the message is the one before the leave.
+        let current_state = room.events.clone();
+        room.events = state_before;
+        let outcome = room.check_with_current_state(message, &current_state);
+
+        assert!(matches!(outcome, IncomingPduOutcome::SoftFailed { .. }), "{outcome:?}");
+    }
+}
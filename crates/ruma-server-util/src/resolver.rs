@@ -0,0 +1,466 @@
+//! Resolution of server names to the address of their homeserver.
+//!
+//! This implements the [server discovery] process of the server-server API: IP literals and
+//! explicit ports, delegation through `/.well-known/matrix/server`, and `SRV` records.
+//!
+//! [server discovery]: https://spec.matrix.org/latest/server-server-api/#resolving-server-names
+
+use std::{
+    collections::BTreeMap,
+    error::Error as StdError,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use http::{header::CACHE_CONTROL, StatusCode};
+use ruma_client::HttpClient;
+use ruma_common::{
+    api::{IncomingResponse, MatrixVersion, OutgoingRequest, SendAccessToken},
+    OwnedServerName, ServerName,
+};
+use ruma_federation_api::discovery::discover_homeserver;
+use tracing::debug;
+
+/// The default port of the server-server API.
+const DEFAULT_PORT: u16 = 8448;
+
+/// How long a well-known response is cached if it doesn't have a cache period.
+const DEFAULT_WELL_KNOWN_CACHE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The maximum time a well-known response is cached.
+const MAX_WELL_KNOWN_CACHE: Duration = Duration::from_secs(48 * 60 * 60);
+
+/// How long the absence of a valid well-known response is cached.
+const WELL_KNOWN_ERROR_CACHE: Duration = Duration::from_secs(60 * 60);
+
+/// A DNS backend used by a [`Resolver`] to look up `SRV` records.
+///
+/// Address (`A`/`AAAA`) records are not looked up by the resolver, the HTTP client resolves the
+/// host of the [`ResolvedDestination`] when connecting.
+#[async_trait]
+pub trait DnsResolver: Send + Sync {
+    /// Look up the `SRV` records of the given name, e.g. `_matrix-fed._tcp.example.com`.
+    ///
+    /// Should return an empty list if the name has no `SRV` records.
+    async fn srv_lookup(
+        &self,
+        name: &str,
+    ) -> Result<Vec<SrvRecord>, Box<dyn StdError + Send + Sync>>;
+}
+
+/// A DNS `SRV` record.
+#[derive(Clone, Debug)]
+#[allow(clippy::exhaustive_structs)]
+pub struct SrvRecord {
+    /// The priority of the target, lower values are preferred.
+    pub priority: u16,
+
+    /// The relative weight of records with the same priority, higher values are preferred.
+    pub weight: u16,
+
+    /// The port of the service on the target.
+    pub port: u16,
+
+    /// The hostname of the target.
+    pub target: String,
+}
+
+/// The result of resolving a server name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ResolvedDestination {
+    /// The host to connect to.
+    ///
+    /// This is a hostname, an IPv4 address or an IPv6 address in square brackets.
+    pub host: String,
+
+    /// The port to connect to.
+    pub port: u16,
+
+    /// The value of the `Host` header of requests to the destination.
+    pub host_header: String,
+
+    /// The name the TLS certificate of the destination must be valid for.
+    ///
+    /// This should also be sent as the TLS Server Name Indication, unless it is an IP address.
+    pub tls_name: String,
+}
+
+impl ResolvedDestination {
+    /// The base URL for requests to the destination.
+    pub fn base_url(&self) -> String {
+        format!("https://{}:{}", self.host, self.port)
+    }
+
+    /// A destination that is reached directly at the host of the given server name.
+    fn direct(server_name: &ServerName) -> Self {
+        Self {
+            host: server_name.host().to_owned(),
+            port: server_name.port().unwrap_or(DEFAULT_PORT),
+            host_header: server_name.as_str().to_owned(),
+            tls_name: server_name.host().to_owned(),
+        }
+    }
+}
+
+/// A resolver for server names, as defined in the server-server API.
+///
+/// The results of `/.well-known/matrix/server` requests are cached according to their
+/// `Cache-Control` header.
+pub struct Resolver<C> {
+    http_client: C,
+    dns: Box<dyn DnsResolver>,
+    well_known_cache: Mutex<BTreeMap<String, WellKnownEntry>>,
+}
+
+#[derive(Clone)]
+struct WellKnownEntry {
+    server: Option<OwnedServerName>,
+    expires_at: Instant,
+}
+
+impl<C: HttpClient> Resolver<C> {
+    /// Creates a new `Resolver` using the given HTTP client to fetch well-known files and the given
+    /// DNS backend to look up `SRV` records.
+    pub fn new(http_client: C, dns: impl DnsResolver + 'static) -> Self {
+        Self { http_client, dns: Box::new(dns), well_known_cache: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// The HTTP client of this resolver.
+    pub fn http_client(&self) -> &C {
+        &self.http_client
+    }
+
+    /// Resolve the given server name to the destination requests should be sent to.
+    ///
+    /// Failures to fetch the well-known file or to look up `SRV` records are not errors, the
+    /// resolution falls back to the next step as specified instead.
+    pub async fn resolve(&self, server_name: &ServerName) -> ResolvedDestination {
+        if server_name.is_ip_literal() || server_name.port().is_some() {
+            return ResolvedDestination::direct(server_name);
+        }
+
+        let hostname = server_name.host();
+        if let Some(delegated) = self.well_known(hostname).await {
+            if delegated.is_ip_literal() || delegated.port().is_some() {
+                return ResolvedDestination::direct(&delegated);
+            }
+
+            return self.resolve_hostname(delegated.host()).await;
+        }
+
+        self.resolve_hostname(hostname).await
+    }
+
+    /// Resolve a hostname without port through its `SRV` records, or use the default port.
+    async fn resolve_hostname(&self, hostname: &str) -> ResolvedDestination {
+        for service in ["_matrix-fed._tcp", "_matrix._tcp"] {
+            let name = format!("{service}.{hostname}");
+            let records = match self.dns.srv_lookup(&name).await {
+                Ok(records) => records,
+                Err(error) => {
+                    debug!(name, %error, "SRV lookup failed");
+                    continue;
+                }
+            };
+
+            let record = records
+                .into_iter()
+                .filter(|record| record.target != ".")
+                .min_by_key(|record| (record.priority, u16::MAX - record.weight));
+
+            if let Some(record) = record {
+                return ResolvedDestination {
+                    host: record.target.trim_end_matches('.').to_owned(),
+                    port: record.port,
+                    host_header: hostname.to_owned(),
+                    tls_name: hostname.to_owned(),
+                };
+            }
+        }
+
+        ResolvedDestination {
+            host: hostname.to_owned(),
+            port: DEFAULT_PORT,
+            host_header: hostname.to_owned(),
+            tls_name: hostname.to_owned(),
+        }
+    }
+
+    /// Get the server the given hostname delegates to, from the cache or its well-known file.
+    async fn well_known(&self, hostname: &str) -> Option<OwnedServerName> {
+        let cached = self.well_known_cache.lock().unwrap().get(hostname).cloned();
+        if let Some(entry) = cached {
+            if entry.expires_at > Instant::now() {
+                return entry.server;
+            }
+        }
+
+        let (server, cache_duration) = match self.fetch_well_known(hostname).await {
+            Some((server, cache_duration)) => (Some(server), cache_duration),
+            None => (None, WELL_KNOWN_ERROR_CACHE),
+        };
+
+        let entry =
+            WellKnownEntry { server: server.clone(), expires_at: Instant::now() + cache_duration };
+        self.well_known_cache.lock().unwrap().insert(hostname.to_owned(), entry);
+
+        server
+    }
+
+    async fn fetch_well_known(&self, hostname: &str) -> Option<(OwnedServerName, Duration)> {
+        let request = discover_homeserver::Request::new()
+            .try_into_http_request(
+                &format!("https://{hostname}"),
+                SendAccessToken::None,
+                &[MatrixVersion::V1_0],
+            )
+            .ok()?;
+
+        let response = match self.http_client.send_http_request(request).await {
+            Ok(response) if response.status() == StatusCode::OK => response,
+            Ok(response) => {
+                debug!(hostname, status = %response.status(), "No well-known file");
+                return None;
+            }
+            Err(_) => {
+                debug!(hostname, "Failed to fetch the well-known file");
+                return None;
+            }
+        };
+
+        let cache_duration = response
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok())
+            .and_then(max_age)
+            .unwrap_or(DEFAULT_WELL_KNOWN_CACHE)
+            .min(MAX_WELL_KNOWN_CACHE);
+
+        match discover_homeserver::Response::try_from_http_response(response) {
+            Ok(response) => Some((response.server, cache_duration)),
+            Err(error) => {
+                debug!(hostname, %error, "Invalid well-known file");
+                None
+            }
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for Resolver<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Resolver").finish_non_exhaustive()
+    }
+}
+
+/// Get the `max-age` directive of a `Cache-Control` header.
+fn max_age(cache_control: &str) -> Option<Duration> {
+    cache_control.split(',').find_map(|directive| {
+        let seconds = directive.trim().strip_prefix("max-age=")?;
+        seconds.parse().ok().map(Duration::from_secs)
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        collections::BTreeMap,
+        error::Error as StdError,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use ruma_client::HttpClient;
+    use ruma_common::server_name;
+
+    use super::{DnsResolver, ResolvedDestination, Resolver, SrvRecord};
+
+    /// A DNS backend with fixed `SRV` records.
+    #[derive(Default)]
+    pub(crate) struct FakeDns(pub(crate) BTreeMap<&'static str, Vec<SrvRecord>>);
+
+    #[async_trait]
+    impl DnsResolver for FakeDns {
+        async fn srv_lookup(
+            &self,
+            name: &str,
+        ) -> Result<Vec<SrvRecord>, Box<dyn StdError + Send + Sync>> {
+            Ok(self.0.get(name).cloned().unwrap_or_default())
+        }
+    }
+
+    /// An HTTP client that answers with fixed responses by host, and records the requests it gets.
+    #[derive(Clone, Default)]
+    pub(crate) struct FakeHttp {
+        pub(crate) responses: BTreeMap<&'static str, (u16, &'static str)>,
+        pub(crate) requests: Arc<Mutex<Vec<http::Request<Vec<u8>>>>>,
+    }
+
+    #[async_trait]
+    impl HttpClient for FakeHttp {
+        type RequestBody = Vec<u8>;
+        type ResponseBody = Vec<u8>;
+        type Error = ();
+
+        async fn send_http_request(
+            &self,
+            req: http::Request<Vec<u8>>,
+        ) -> Result<http::Response<Vec<u8>>, ()> {
+            let host = req.uri().host().unwrap_or_default().to_owned();
+            self.requests.lock().unwrap().push(req);
+
+            let (status, body) = self.responses.get(host.as_str()).copied().unwrap_or((404, ""));
+            Ok(http::Response::builder()
+                .status(status)
+                .header(http::header::CACHE_CONTROL, "public, max-age=3600")
+                .body(body.into())
+                .unwrap())
+        }
+    }
+
+    fn srv(target: &str, port: u16) -> SrvRecord {
+        SrvRecord { priority: 10, weight: 0, port, target: target.to_owned() }
+    }
+
+    fn destination(host: &str, port: u16, host_header: &str) -> ResolvedDestination {
+        ResolvedDestination {
+            host: host.to_owned(),
+            port,
+            host_header: host_header.to_owned(),
+            tls_name: host_header.split(':').next().unwrap().to_owned(),
+        }
+    }
+
+    fn resolver() -> Resolver<FakeHttp> {
+        let http = FakeHttp {
+            responses: [
+                ("delegated-port.example", (200, r#"{ "m.server": "matrix.example:443" }"#)),
+                ("delegated-srv.example", (200, r#"{ "m.server": "matrix-srv.example" }"#)),
+                ("delegated-ip.example", (200, r#"{ "m.server": "10.0.0.1" }"#)),
+                ("invalid.example", (200, r#"{ "m.server": "not a server name" }"#)),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let dns = FakeDns(
+            [
+                ("_matrix-fed._tcp.matrix-srv.example", vec![srv("target.example.", 8000)]),
+                (
+                    "_matrix-fed._tcp.srv.example",
+                    vec![
+                        SrvRecord {
+                            priority: 20,
+                            weight: 0,
+                            port: 1,
+                            target: "low.example".into(),
+                        },
+                        SrvRecord {
+                            priority: 10,
+                            weight: 5,
+                            port: 2,
+                            target: "high.example".into(),
+                        },
+                    ],
+                ),
+                ("_matrix._tcp.legacy.example", vec![srv("legacy-target.example", 8001)]),
+            ]
+            .into(),
+        );
+
+        Resolver::new(http, dns)
+    }
+
+    #[tokio::test]
+    async fn ip_literals_and_explicit_ports() {
+        let resolver = resolver();
+
+        assert_eq!(
+            resolver.resolve(server_name!("1.2.3.4")).await,
+            destination("1.2.3.4", 8448, "1.2.3.4")
+        );
+        assert_eq!(
+            resolver.resolve(server_name!("[1234:5678::abcd]:1234")).await,
+            ResolvedDestination {
+                host: "[1234:5678::abcd]".to_owned(),
+                port: 1234,
+                host_header: "[1234:5678::abcd]:1234".to_owned(),
+                tls_name: "[1234:5678::abcd]".to_owned(),
+            }
+        );
+        assert_eq!(
+            resolver.resolve(server_name!("example.com:8000")).await,
+            destination("example.com", 8000, "example.com:8000")
+        );
+        assert!(resolver.http_client.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn well_known_delegation() {
+        let resolver = resolver();
+
+        assert_eq!(
+            resolver.resolve(server_name!("delegated-port.example")).await,
+            destination("matrix.example", 443, "matrix.example:443")
+        );
+        assert_eq!(
+            resolver.resolve(server_name!("delegated-ip.example")).await,
+            destination("10.0.0.1", 8448, "10.0.0.1")
+        );
+        assert_eq!(
+            resolver.resolve(server_name!("delegated-srv.example")).await,
+            ResolvedDestination {
+                host: "target.example".to_owned(),
+                port: 8000,
+                host_header: "matrix-srv.example".to_owned(),
+                tls_name: "matrix-srv.example".to_owned(),
+            }
+        );
+
+        let request = &resolver.http_client.requests.lock().unwrap()[0];
+        assert_eq!(
+            request.uri().to_string(),
+            "https://delegated-port.example/.well-known/matrix/server"
+        );
+    }
+
+    #[tokio::test]
+    async fn srv_and_fallback() {
+        let resolver = resolver();
+
+        assert_eq!(
+            resolver.resolve(server_name!("srv.example")).await,
+            ResolvedDestination {
+                host: "high.example".to_owned(),
+                port: 2,
+                host_header: "srv.example".to_owned(),
+                tls_name: "srv.example".to_owned(),
+            }
+        );
+        assert_eq!(
+            resolver.resolve(server_name!("legacy.example")).await,
+            ResolvedDestination {
+                host: "legacy-target.example".to_owned(),
+                port: 8001,
+                host_header: "legacy.example".to_owned(),
+                tls_name: "legacy.example".to_owned(),
+            }
+        );
+        assert_eq!(
+            resolver.resolve(server_name!("invalid.example")).await,
+            destination("invalid.example", 8448, "invalid.example")
+        );
+    }
+
+    #[tokio::test]
+    async fn well_known_is_cached() {
+        let resolver = resolver();
+
+        for _ in 0..2 {
+            resolver.resolve(server_name!("delegated-port.example")).await;
+            resolver.resolve(server_name!("nothing.example")).await;
+        }
+
+        assert_eq!(resolver.http_client.requests.lock().unwrap().len(), 2);
+    }
+}
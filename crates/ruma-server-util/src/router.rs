@@ -0,0 +1,395 @@
+//! A framework-agnostic router for Matrix API endpoints.
+//!
+//! A [`Router`] is built from a list of endpoint types implementing [`IncomingRequest`]. It matches
+//! `http::Request`s against the paths and methods from the endpoints' [`Metadata`], deserializes
+//! them into the endpoint's request type and dispatches them to a typed async handler. The
+//! handler's response or error is converted into an `http::Response`.
+//!
+//! [`Metadata`]: ruma_common::api::Metadata
+
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+use http::{Method, StatusCode};
+use percent_encoding::percent_decode_str;
+use ruma_common::api::{
+    error::{FromHttpRequestError, IntoHttpError},
+    IncomingRequest, OutgoingResponse,
+};
+use serde_json::json;
+use tracing::debug;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+type ResponseFuture = BoxFuture<Result<http::Response<Vec<u8>>, IntoHttpError>>;
+
+type HandlerResult = Result<ResponseFuture, FromHttpRequestError>;
+
+type ErasedHandler<S> = dyn Fn(S, http::Request<&[u8]>, &[String]) -> HandlerResult + Send + Sync;
+
+/// A router that dispatches incoming requests to typed handlers.
+///
+/// The state `S` is passed to every handler. Since it is given to
+/// [`dispatch`](Self::dispatch) for every request, it can contain per-request data computed by
+/// the web framework, like the authenticated user.
+///
+/// # Example
+///
+/// ```
+/// use ruma_client_api::profile::get_display_name;
+/// use ruma_server_util::router::Router;
+///
+/// let router =
+///     Router::new().route(|_state: (), request: get_display_name::v3::Request| async move {
+///         let displayname = request.user_id.localpart().to_owned();
+///         Ok(get_display_name::v3::Response::new(Some(displayname)))
+///     });
+///
+/// # async {
+/// let request =
+///     http::Request::get("/_matrix/client/v3/profile/%40alice%3Aexample.com/displayname")
+///         .body(Vec::new())?;
+/// let response = match router.dispatch((), request).await {
+///     Ok(response) => response,
+///     Err(error) => error.to_http_response(),
+/// };
+/// # Ok::<_, http::Error>(())
+/// # };
+/// ```
+pub struct Router<S> {
+    routes: Vec<Route<S>>,
+}
+
+struct Route<S> {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: Arc<ErasedHandler<S>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Segment {
+    Literal(&'static str),
+    Param,
+}
+
+impl<S> Router<S>
+where
+    S: Send + 'static,
+{
+    /// Creates a new `Router` without any routes.
+    pub fn new() -> Self {
+        Self { routes: Vec::new() }
+    }
+
+    /// Add a route for the endpoint `R`, handled by the given handler.
+    ///
+    /// The route matches all the stable and unstable paths of the endpoint. If the handler
+    /// returns an error, the error is converted into the response.
+    pub fn route<R, F, Fut>(mut self, handler: F) -> Self
+    where
+        R: IncomingRequest + Send + 'static,
+        F: Fn(S, R) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R::OutgoingResponse, R::EndpointError>> + Send + 'static,
+    {
+        let handler: Arc<ErasedHandler<S>> = Arc::new(move |state, request, path_args| {
+            let request = R::try_from_http_request(request, path_args)?;
+            let response = handler(state, request);
+
+            Ok(Box::pin(async move {
+                match response.await {
+                    Ok(response) => response.try_into_http_response(),
+                    Err(error) => error.try_into_http_response(),
+                }
+            }) as ResponseFuture)
+        });
+
+        for path in R::METADATA.history.all_paths() {
+            self.routes.push(Route {
+                method: R::METADATA.method.clone(),
+                segments: parse_path(path),
+                handler: handler.clone(),
+            });
+        }
+
+        self
+    }
+
+    /// Dispatch the given request to the handler of the matching route.
+    ///
+    /// Returns an error if no route matches the request, or if the request couldn't be
+    /// deserialized into the endpoint's request type.
+    pub async fn dispatch<B>(
+        &self,
+        state: S,
+        request: http::Request<B>,
+    ) -> Result<http::Response<Vec<u8>>, DispatchError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let (route, path_args) = self.find_route(request.method(), request.uri().path())?;
+
+        let (parts, body) = request.into_parts();
+        let request = http::Request::from_parts(parts, body.as_ref());
+        let response = (route.handler)(state, request, &path_args)?;
+
+        Ok(response.await?)
+    }
+
+    /// Find the route matching the given method and path, along with its percent-decoded path
+    /// arguments.
+    ///
+    /// If several routes match the path, the one whose first differing segment is a literal is
+    /// preferred.
+    fn find_route(
+        &self,
+        method: &Method,
+        path: &str,
+    ) -> Result<(&Route<S>, Vec<String>), DispatchError> {
+        let path_segments: Vec<_> = path.trim_start_matches('/').split('/').collect();
+
+        let mut method_not_allowed = false;
+        let mut best: Option<(&Route<S>, Vec<&str>)> = None;
+
+        for route in &self.routes {
+            let path_args = match match_segments(&route.segments, &path_segments) {
+                Some(path_args) => path_args,
+                None => continue,
+            };
+
+            if route.method != *method {
+                method_not_allowed = true;
+                continue;
+            }
+
+            if best
+                .as_ref()
+                .map_or(true, |(best, _)| is_more_specific(&route.segments, &best.segments))
+            {
+                best = Some((route, path_args));
+            }
+        }
+
+        match best {
+            Some((route, path_args)) => {
+                let path_args = path_args
+                    .into_iter()
+                    .map(|arg| Ok(percent_decode_str(arg).decode_utf8()?.into_owned()))
+                    .collect::<Result<_, FromHttpRequestError>>()?;
+                Ok((route, path_args))
+            }
+            None if method_not_allowed => Err(DispatchError::MethodNotAllowed),
+            None => {
+                debug!(%method, path, "No route matches the request");
+                Err(DispatchError::NotFound)
+            }
+        }
+    }
+}
+
+impl<S> Default for Router<S>
+where
+    S: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> fmt::Debug for Router<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router").field("routes", &self.routes.len()).finish()
+    }
+}
+
+fn parse_path(path: &'static str) -> Vec<Segment> {
+    path.trim_start_matches('/')
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(_) => Segment::Param,
+            None => Segment::Literal(segment),
+        })
+        .collect()
+}
+
+/// Match the segments of a route against the segments of a request path.
+///
+/// Returns the raw path arguments if the path matches.
+fn match_segments<'a>(route: &[Segment], path: &[&'a str]) -> Option<Vec<&'a str>> {
+    if route.len() != path.len() {
+        return None;
+    }
+
+    let mut path_args = Vec::new();
+    for (segment, value) in route.iter().zip(path) {
+        match segment {
+            Segment::Literal(literal) if literal == value => {}
+            Segment::Param if !value.is_empty() => path_args.push(*value),
+            _ => return None,
+        }
+    }
+
+    Some(path_args)
+}
+
+/// Whether a route with the given segments should be preferred over a route with the other
+/// segments, when both match the same path.
+fn is_more_specific(segments: &[Segment], other: &[Segment]) -> bool {
+    segments
+        .iter()
+        .zip(other)
+        .find(|(a, b)| a != b)
+        .map_or(false, |(a, _)| matches!(a, Segment::Literal(_)))
+}
+
+/// An error that can occur when dispatching a request with a [`Router`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DispatchError {
+    /// No route matches the path of the request.
+    #[error("no route matches the request path")]
+    NotFound,
+
+    /// A route matches the path of the request, but not its method.
+    #[error("the request method is not allowed for this path")]
+    MethodNotAllowed,
+
+    /// The request couldn't be converted to the endpoint's request type.
+    #[error("invalid request: {0}")]
+    FromHttpRequest(#[from] FromHttpRequestError),
+
+    /// The handler's response couldn't be converted to an HTTP response.
+    #[error("response serialization failed: {0}")]
+    IntoHttp(#[from] IntoHttpError),
+}
+
+impl DispatchError {
+    /// The HTTP status code that should be used in the response to the request.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::FromHttpRequest(_) => StatusCode::BAD_REQUEST,
+            Self::IntoHttp(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The Matrix error code that should be used in the response to the request.
+    pub fn errcode(&self) -> &'static str {
+        match self {
+            Self::NotFound | Self::MethodNotAllowed => "M_UNRECOGNIZED",
+            Self::FromHttpRequest(FromHttpRequestError::Deserialization(_)) => "M_BAD_JSON",
+            Self::FromHttpRequest(_) => "M_UNRECOGNIZED",
+            Self::IntoHttp(_) => "M_UNKNOWN",
+        }
+    }
+
+    /// Build a response with a standard Matrix error body for this error.
+    pub fn to_http_response(&self) -> http::Response<Vec<u8>> {
+        let body = json!({ "errcode": self.errcode(), "error": self.to_string() });
+
+        http::Response::builder()
+            .status(self.status_code())
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string().into_bytes())
+            .expect("response with valid status and header should be valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Method, StatusCode};
+    use ruma_client_api::profile::get_display_name;
+    use ruma_common::api::EndpointError;
+    use serde_json::{from_slice as from_json_slice, json, Value as JsonValue};
+
+    use super::{is_more_specific, match_segments, parse_path, DispatchError, Router};
+
+    fn router() -> Router<&'static str> {
+        Router::new().route(
+            |state: &'static str, request: get_display_name::v3::Request| async move {
+                if request.user_id.localpart() == "bob" {
+                    return Err(ruma_client_api::Error::from_http_response(
+                        http::Response::builder()
+                            .status(StatusCode::FORBIDDEN)
+                            .body(r#"{ "errcode": "M_FORBIDDEN", "error": "Nope" }"#)
+                            .unwrap(),
+                    ));
+                }
+
+                Ok(get_display_name::v3::Response::new(Some(format!(
+                    "{state} {}",
+                    request.user_id
+                ))))
+            },
+        )
+    }
+
+    async fn dispatch(
+        method: Method,
+        path: &str,
+    ) -> Result<http::Response<Vec<u8>>, DispatchError> {
+        let request = http::Request::builder().method(method).uri(path).body(b"").unwrap();
+        router().dispatch("state", request).await
+    }
+
+    fn body(response: &http::Response<Vec<u8>>) -> JsonValue {
+        from_json_slice(response.body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn dispatch_to_handler() {
+        for path in [
+            "/_matrix/client/r0/profile/%40alice%3Aexample.com/displayname",
+            "/_matrix/client/v3/profile/@alice:example.com/displayname",
+        ] {
+            let response = dispatch(Method::GET, path).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(body(&response), json!({ "displayname": "state @alice:example.com" }));
+        }
+    }
+
+    #[tokio::test]
+    async fn endpoint_error_becomes_response() {
+        let response =
+            dispatch(Method::GET, "/_matrix/client/v3/profile/@bob:example.com/displayname")
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(body(&response), json!({ "errcode": "M_FORBIDDEN", "error": "Nope" }));
+    }
+
+    #[tokio::test]
+    async fn routing_errors() {
+        let error = dispatch(Method::GET, "/_matrix/client/v3/unknown").await.unwrap_err();
+        assert!(matches!(error, DispatchError::NotFound));
+        let response = error.to_http_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body(&response)["errcode"], "M_UNRECOGNIZED");
+
+        let error =
+            dispatch(Method::PUT, "/_matrix/client/v3/profile/@alice:example.com/displayname")
+                .await
+                .unwrap_err();
+        assert!(matches!(error, DispatchError::MethodNotAllowed));
+        assert_eq!(error.status_code(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let error = dispatch(Method::GET, "/_matrix/client/v3/profile/alice/displayname")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, DispatchError::FromHttpRequest(_)));
+        assert_eq!(error.to_http_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn match_paths() {
+        let param = parse_path("/_matrix/client/v3/rooms/:room_id/state/:event_type");
+        let literal = parse_path("/_matrix/client/v3/rooms/:room_id/state/m.room.create");
+        let path =
+            ["_matrix", "client", "v3", "rooms", "!room:example.com", "state", "m.room.create"];
+
+        assert_eq!(match_segments(&param, &path).unwrap(), ["!room:example.com", "m.room.create"]);
+        assert_eq!(match_segments(&literal, &path).unwrap(), ["!room:example.com"]);
+        assert!(match_segments(&param, &path[..6]).is_none());
+        assert!(is_more_specific(&literal, &param));
+        assert!(!is_more_specific(&param, &literal));
+    }
+}
@@ -38,9 +38,20 @@ pub enum Error {
     #[error("signature uses an unsupported algorithm: {0}")]
     UnsupportedAlgorithm(String),
 
+    /// A [`KeyPair`](crate::KeyPair) failed to sign a message.
+    ///
+    /// This can be used by key pairs whose private key is held externally, for example by a
+    /// hardware security module or another process.
+    #[error("failed to sign message: {0}")]
+    Signing(Box<dyn std::error::Error + Send + Sync>),
+
     /// PDU was too large
     #[error("PDU is larger than maximum of 65535 bytes")]
     PduSize,
+
+    /// The rules of the room version are unknown.
+    #[error("room version {0} is not supported")]
+    UnsupportedRoomVersion(RoomVersionId),
 }
 
 impl From<RedactionError> for Error {
@@ -52,6 +63,9 @@ impl From<RedactionError> for Error {
             RedactionError::JsonFieldMissingFromObject(field) => {
                 JsonError::JsonFieldMissingFromObject(field).into()
             }
+            RedactionError::UnsupportedRoomVersion(version) => {
+                Error::UnsupportedRoomVersion(version)
+            }
             #[allow(unreachable_patterns)]
             _ => unreachable!(),
         }
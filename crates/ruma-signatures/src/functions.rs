@@ -9,17 +9,18 @@ use std::{
 use base64::{alphabet, Engine};
 use ruma_common::{
     canonical_json::{redact, JsonType},
+    room_version_rules::{EventFormatVersion, RoomVersionRules},
     serde::{base64::Standard, Base64},
     CanonicalJsonObject, CanonicalJsonValue, OwnedEventId, OwnedServerName, RoomVersionId, UserId,
 };
-use serde_json::{from_str as from_json_str, to_string as to_json_string};
+use serde_json::to_string as to_json_string;
 use sha2::{digest::Digest, Sha256};
 
 use crate::{
     keys::{KeyPair, PublicKeyMap},
     split_id,
-    verification::{Ed25519Verifier, Verified, Verifier},
-    Error, JsonError, ParseError, VerificationError,
+    verification::{is_supported, SignedMessage, Verified},
+    Algorithm, Error, JsonError, ParseError, VerificationError,
 };
 
 const MAX_PDU_BYTES: usize = 65_535;
@@ -95,7 +96,7 @@ pub fn sign_json<K>(
     object: &mut CanonicalJsonObject,
 ) -> Result<(), Error>
 where
-    K: KeyPair,
+    K: KeyPair + ?Sized,
 {
     let (signatures_key, mut signature_map) = match object.remove_entry("signatures") {
         Some((key, CanonicalJsonValue::Object(signatures))) => (Cow::Owned(key), signatures),
@@ -109,7 +110,7 @@ where
     let json = to_json_string(object).map_err(JsonError::Serde)?;
 
     // Sign the canonical JSON string.
-    let signature = key_pair.sign(json.as_bytes());
+    let signature = key_pair.sign(json.as_bytes())?;
 
     // Insert the new signature in the map we pulled out (or created) previously.
     let signature_set = signature_map
@@ -172,9 +173,11 @@ pub fn canonical_json(object: &CanonicalJsonObject) -> Result<String, Error> {
 /// "ed25519:1") then map to their respective public keys.
 /// * object: The JSON object that was signed.
 ///
+/// Signatures made with an algorithm that has no [`Verifier`](crate::Verifier) are skipped.
+///
 /// # Errors
 ///
-/// Returns an error if verification fails.
+/// Returns an error if verification fails, or if all the signatures of an entity were skipped.
 ///
 /// # Examples
 ///
@@ -209,26 +212,63 @@ pub fn verify_json(
     public_key_map: &PublicKeyMap,
     object: &CanonicalJsonObject,
 ) -> Result<(), Error> {
+    json_signatures(public_key_map, object)?.verify()
+}
+
+/// Uses a set of public keys to verify several signed JSON objects at once.
+///
+/// Each object is verified like with [`verify_json`], so a failure only affects the result of the
+/// object it happened in.
+///
+/// # Parameters
+///
+/// * public_key_map: A map from entity identifiers to a map from key identifiers to public keys,
+///   like for [`verify_json`]. It must contain the keys for all the objects.
+/// * objects: The JSON objects that were signed.
+///
+/// Returns the result of the verification of each object, in the same order as `objects`.
+pub fn verify_json_batch<'a>(
+    public_key_map: &PublicKeyMap,
+    objects: impl IntoIterator<Item = &'a CanonicalJsonObject>,
+) -> Vec<Result<(), Error>> {
+    objects.into_iter().map(|object| json_signatures(public_key_map, object)?.verify()).collect()
+}
+
+/// Collects the signatures to verify for a signed JSON object.
+fn json_signatures<'a>(
+    public_key_map: &'a PublicKeyMap,
+    object: &CanonicalJsonObject,
+) -> Result<SignedMessage<'a>, Error> {
     let signature_map = match object.get("signatures") {
-        Some(CanonicalJsonValue::Object(signatures)) => signatures.clone(),
+        Some(CanonicalJsonValue::Object(signatures)) => signatures,
         Some(_) => return Err(JsonError::not_of_type("signatures", JsonType::Object)),
         None => return Err(JsonError::field_missing_from_object("signatures")),
     };
 
+    let mut signatures = Vec::new();
+
     for (entity_id, signature_set) in signature_map {
         let signature_set = match signature_set {
             CanonicalJsonValue::Object(set) => set,
             _ => return Err(JsonError::not_multiples_of_type("signature sets", JsonType::Object)),
         };
 
-        let public_keys = match public_key_map.get(&entity_id) {
+        let public_keys = match public_key_map.get(entity_id) {
             Some(keys) => keys,
-            None => {
-                return Err(JsonError::key_missing("public_key_map", "public_keys", &entity_id))
-            }
+            None => return Err(JsonError::key_missing("public_key_map", "public_keys", entity_id)),
         };
 
-        for (key_id, signature) in &signature_set {
+        let mut skipped_algorithm = None;
+        let mut signature_count = 0;
+
+        for (key_id, signature) in signature_set {
+            let (algorithm, _) = split_id(key_id)?;
+
+            if !is_supported(&algorithm) {
+                skipped_algorithm = Some(algorithm);
+                continue;
+            }
+
             let signature = match signature {
                 CanonicalJsonValue::String(s) => s,
                 _ => return Err(JsonError::not_of_type("signature", JsonType::String)),
@@ -245,40 +285,16 @@ pub fn verify_json(
             let signature = Base64::<Standard>::parse(signature)
                 .map_err(|e| ParseError::base64("signature", signature, e))?;
 
-            verify_json_with(
-                &Ed25519Verifier,
-                public_key.as_bytes(),
-                signature.as_bytes(),
-                object,
-            )?;
+            signatures.push((algorithm, public_key, signature));
+            signature_count += 1;
         }
-    }
 
-    Ok(())
-}
+        if let (Some(algorithm), 0) = (skipped_algorithm, signature_count) {
+            return Err(Error::UnsupportedAlgorithm(algorithm.to_string()));
+        }
+    }
 
-/// Uses a public key to verify a signed JSON object.
-///
-/// # Parameters
-///
-/// * verifier: A `Verifier` appropriate for the digital signature algorithm that was used.
-/// * public_key: The raw bytes of the public key used to sign the JSON.
-/// * signature: The raw bytes of the signature.
-/// * object: The JSON object that was signed.
-///
-/// # Errors
-///
-/// Returns an error if verification fails.
-fn verify_json_with<V>(
-    verifier: &V,
-    public_key: &[u8],
-    signature: &[u8],
-    object: &CanonicalJsonObject,
-) -> Result<(), Error>
-where
-    V: Verifier,
-{
-    verifier.verify_json(public_key, signature, canonical_json(object)?.as_bytes())
+    Ok(SignedMessage::new(canonical_json(object)?, signatures))
 }
 
 /// Creates a *content hash* for an event.
@@ -333,9 +349,9 @@ pub fn reference_hash(
 
     let hash = Sha256::digest(json.as_bytes());
 
-    let base64_alphabet = match version {
-        RoomVersionId::V1 | RoomVersionId::V2 | RoomVersionId::V3 => alphabet::STANDARD,
-        // Room versions higher than version 3 are url safe base64 encoded
+    let base64_alphabet = match room_version_rules(version)?.event_format {
+        EventFormatVersion::V1 | EventFormatVersion::V2 => alphabet::STANDARD,
+        // Event IDs are url safe base64 encoded since room version 4
         _ => alphabet::URL_SAFE,
     };
     let base64_engine = base64::engine::GeneralPurpose::new(
@@ -448,7 +464,7 @@ pub fn hash_and_sign_event<K>(
     version: &RoomVersionId,
 ) -> Result<(), Error>
 where
-    K: KeyPair,
+    K: KeyPair + ?Sized,
 {
     let hash = content_hash(object)?;
 
@@ -477,7 +493,8 @@ where
 /// Some room versions may require signatures from multiple homeservers, so this function takes a
 /// map from servers to sets of public keys. Signatures are verified for each required homeserver.
 /// All known public keys for a homeserver should be provided. The first one found on the given
-/// event will be used.
+/// event will be used, skipping the keys of algorithms that have no
+/// [`Verifier`](crate::Verifier).
 ///
 /// If the `Ok` variant is returned by this function, it will contain a `Verified` value which
 /// distinguishes an event with valid signatures and a matching content hash with an event with
@@ -544,21 +561,42 @@ pub fn verify_event(
     object: &CanonicalJsonObject,
     version: &RoomVersionId,
 ) -> Result<Verified, Error> {
+    event_signatures(public_key_map, object, version)?.verify()?;
+    check_content_hash(object)
+}
+
+/// Verifies several signed events at once.
+///
+/// Each event is verified like with [`verify_event`], e.g. for the PDUs of a federation
+/// transaction, so a failure only affects the result of the event it happened in.
+///
+/// # Parameters
+///
+/// * public_key_map: A map from entity identifiers to a map from key identifiers to public keys,
+///   like for [`verify_event`]. It must contain the keys for all the events.
+/// * events: The JSON objects of the events that were signed, with the room version of each event.
+///
+/// Returns the result of the verification of each event, in the same order as `events`.
+pub fn verify_event_batch<'a>(
+    public_key_map: &PublicKeyMap,
+    events: impl IntoIterator<Item = (&'a CanonicalJsonObject, &'a RoomVersionId)>,
+) -> Vec<Result<Verified, Error>> {
+    events
+        .into_iter()
+        .map(|(object, version)| verify_event(public_key_map, object, version))
+        .collect()
+}
+
+/// Collects the signatures to verify for a signed event.
+fn event_signatures<'a>(
+    public_key_map: &'a PublicKeyMap,
+    object: &CanonicalJsonObject,
+    version: &RoomVersionId,
+) -> Result<SignedMessage<'a>, Error> {
     let redacted = redact(object.clone(), version, None)?;
 
-    let hash = match object.get("hashes") {
-        Some(hashes_value) => match hashes_value {
-            CanonicalJsonValue::Object(hashes) => match hashes.get("sha256") {
-                Some(hash_value) => match hash_value {
-                    CanonicalJsonValue::String(hash) => hash,
-                    _ => return Err(JsonError::not_of_type("sha256 hash", JsonType::String)),
-                },
-                None => return Err(JsonError::not_of_type("hashes", JsonType::Object)),
-            },
-            _ => return Err(JsonError::field_missing_from_object("sha256")),
-        },
-        None => return Err(JsonError::field_missing_from_object("hashes")),
-    };
+    // Check that the event has a hash before verifying the signatures.
+    event_hash(object)?;
 
     let signature_map = match object.get("signatures") {
         Some(CanonicalJsonValue::Object(signatures)) => signatures,
@@ -567,7 +605,7 @@ pub fn verify_event(
     };
 
     let servers_to_check = servers_to_check_signatures(object, version)?;
-    let canonical_json = from_json_str(&canonical_json(&redacted)?).map_err(JsonError::from)?;
+    let mut signatures = Vec::with_capacity(servers_to_check.len());
 
     for entity_id in servers_to_check {
         let signature_set = match signature_map.get(entity_id.as_str()) {
@@ -585,14 +623,14 @@ pub fn verify_event(
             .ok_or_else(|| VerificationError::public_key_not_found(entity_id))?;
 
         for (key_id, public_key) in public_keys {
-            // Since only ed25519 is supported right now, we don't actually need to check what the
-            // algorithm is. If it split successfully, it's ed25519.
-            if split_id(key_id).is_err() {
-                break;
-            }
+            let algorithm = match split_id(key_id) {
+                Ok((algorithm, _)) if is_supported(&algorithm) => algorithm,
+                _ => continue,
+            };
 
             if let Some(signature) = signature_set.get(key_id) {
-                maybe_signature_and_public_key = Some(SignatureAndPubkey { signature, public_key });
+                maybe_signature_and_public_key =
+                    Some(SignatureAndPubkey { algorithm, signature, public_key });
 
                 break;
             }
@@ -608,19 +646,39 @@ pub fn verify_event(
             _ => return Err(JsonError::not_of_type("signature", JsonType::String)),
         };
 
-        let public_key = signature_and_pubkey.public_key;
-
         let signature = Base64::<Standard>::parse(signature)
             .map_err(|e| ParseError::base64("signature", signature, e))?;
 
-        verify_json_with(
-            &Ed25519Verifier,
-            public_key.as_bytes(),
-            signature.as_bytes(),
-            &canonical_json,
-        )?;
+        signatures.push((
+            signature_and_pubkey.algorithm,
+            signature_and_pubkey.public_key,
+            signature,
+        ));
     }
 
+    Ok(SignedMessage::new(canonical_json(&redacted)?, signatures))
+}
+
+/// Get the content hash of the given event.
+fn event_hash(object: &CanonicalJsonObject) -> Result<&str, Error> {
+    match object.get("hashes") {
+        Some(hashes_value) => match hashes_value {
+            CanonicalJsonValue::Object(hashes) => match hashes.get("sha256") {
+                Some(hash_value) => match hash_value {
+                    CanonicalJsonValue::String(hash) => Ok(hash),
+                    _ => Err(JsonError::not_of_type("sha256 hash", JsonType::String)),
+                },
+                None => Err(JsonError::not_of_type("hashes", JsonType::Object)),
+            },
+            _ => Err(JsonError::field_missing_from_object("sha256")),
+        },
+        None => Err(JsonError::field_missing_from_object("hashes")),
+    }
+}
+
+/// Checks whether the content hash of the given event matches its content.
+fn check_content_hash(object: &CanonicalJsonObject) -> Result<Verified, Error> {
+    let hash = event_hash(object)?;
     let calculated_hash = content_hash(object)?;
 
     if let Ok(hash) = Base64::<Standard>::parse(hash) {
@@ -632,9 +690,10 @@ pub fn verify_event(
     Ok(Verified::Signatures)
 }
 
-struct SignatureAndPubkey<'a> {
-    signature: &'a CanonicalJsonValue,
-    public_key: &'a Base64,
+struct SignatureAndPubkey<'s, 'k> {
+    algorithm: Algorithm,
+    signature: &'s CanonicalJsonValue,
+    public_key: &'k Base64,
 }
 
 /// Internal implementation detail of the canonical JSON algorithm.
@@ -678,8 +737,10 @@ fn servers_to_check_signatures(
         };
     }
 
-    match version {
-        RoomVersionId::V1 | RoomVersionId::V2 => match object.get("event_id") {
+    let rules = room_version_rules(version)?;
+
+    if rules.event_format == EventFormatVersion::V1 {
+        match object.get("event_id") {
             Some(CanonicalJsonValue::String(raw_event_id)) => {
                 let event_id: OwnedEventId =
                     raw_event_id.parse().map_err(|e| Error::from(ParseError::EventId(e)))?;
@@ -694,34 +755,33 @@ fn servers_to_check_signatures(
             _ => {
                 return Err(JsonError::field_missing_from_object("event_id"));
             }
-        },
-        RoomVersionId::V3
-        | RoomVersionId::V4
-        | RoomVersionId::V5
-        | RoomVersionId::V6
-        | RoomVersionId::V7 => {}
-        // TODO: And for all future versions that have join_authorised_via_users_server
-        RoomVersionId::V8 | RoomVersionId::V9 | RoomVersionId::V10 => {
-            if let Some(authorized_user) = object
-                .get("content")
-                .and_then(|c| c.as_object())
-                .and_then(|c| c.get("join_authorised_via_users_server"))
-            {
-                let authorized_user = authorized_user.as_str().ok_or_else(|| {
-                    JsonError::not_of_type("join_authorised_via_users_server", JsonType::String)
-                })?;
-                let authorized_user = <&UserId>::try_from(authorized_user)
-                    .map_err(|e| Error::from(ParseError::UserId(e)))?;
+        }
+    }
 
-                servers_to_check.insert(authorized_user.server_name().to_owned());
-            }
+    if rules.signatures.check_join_authorised_via_users_server {
+        if let Some(authorized_user) = object
+            .get("content")
+            .and_then(|c| c.as_object())
+            .and_then(|c| c.get("join_authorised_via_users_server"))
+        {
+            let authorized_user = authorized_user.as_str().ok_or_else(|| {
+                JsonError::not_of_type("join_authorised_via_users_server", JsonType::String)
+            })?;
+            let authorized_user = <&UserId>::try_from(authorized_user)
+                .map_err(|e| Error::from(ParseError::UserId(e)))?;
+
+            servers_to_check.insert(authorized_user.server_name().to_owned());
         }
-        _ => unimplemented!(),
     }
 
     Ok(servers_to_check)
 }
 
+/// Get the rules of the given room version.
+fn room_version_rules(version: &RoomVersionId) -> Result<RoomVersionRules, Error> {
+    version.rules().ok_or_else(|| Error::UnsupportedRoomVersion(version.clone()))
+}
+
 /// Checks if `object` contains an event of type `m.room.third_party_invite`
 fn is_third_party_invite(object: &CanonicalJsonObject) -> Result<bool, Error> {
     match object.get("type") {
@@ -736,16 +796,53 @@ mod tests {
 
     use assert_matches::assert_matches;
     use ruma_common::{
-        serde::Base64, CanonicalJsonValue, RoomVersionId, ServerSigningKeyId, SigningKeyAlgorithm,
+        serde::Base64, CanonicalJsonObject, CanonicalJsonValue, RoomVersionId, ServerSigningKeyId,
+        SigningKeyAlgorithm,
     };
     use serde_json::json;
 
+    use sha2::{Digest, Sha256};
+
     use super::canonical_json;
     use crate::{
-        sign_json, verify_event, Ed25519KeyPair, Error, PublicKeyMap, PublicKeySet,
-        VerificationError, Verified,
+        hash_and_sign_event, register_verifier, sign_json, verify_event, verify_event_batch,
+        verify_json, verify_json_batch, Algorithm, Ed25519KeyPair, Error, KeyPair, PublicKeyMap,
+        PublicKeySet, Signature, VerificationError, Verified, Verifier,
     };
 
+    /// A toy signing algorithm where the signature is the SHA-256 hash of the key and the message.
+    const TEST_ALGORITHM: &str = "org.example.sha256";
+
+    struct TestKeyPair(Option<&'static [u8]>);
+
+    impl KeyPair for TestKeyPair {
+        fn sign(&self, message: &[u8]) -> Result<Signature, Error> {
+            let key = self.0.ok_or_else(|| Error::Signing("signer is unavailable".into()))?;
+            Signature::new(&format!("{TEST_ALGORITHM}:1"), &test_signature(key, message))
+        }
+    }
+
+    struct TestVerifier;
+
+    impl Verifier for TestVerifier {
+        fn verify_json(
+            &self,
+            public_key: &[u8],
+            signature: &[u8],
+            message: &[u8],
+        ) -> Result<(), Error> {
+            if signature == test_signature(public_key, message).as_slice() {
+                Ok(())
+            } else {
+                Err(VerificationError::Signature(ed25519_dalek::SignatureError::new()).into())
+            }
+        }
+    }
+
+    fn test_signature(key: &[u8], message: &[u8]) -> Vec<u8> {
+        Sha256::new().chain_update(key).chain_update(message).finalize().to_vec()
+    }
+
     #[test]
     fn canonical_json_complex() {
         let data = json!({
@@ -1011,6 +1108,234 @@ mod tests {
         assert!(format!("{error:?}").contains("Some(Verification equation was not satisfied)"));
     }
 
+    #[test]
+    fn verify_event_batch_reports_each_event() {
+        let key_pair_sender = generate_key_pair();
+        let other_key_pair = generate_key_pair();
+        let mut public_key_map = BTreeMap::new();
+        add_key_to_map(&mut public_key_map, "domain-sender", &key_pair_sender);
+
+        let event = |body: &str, key_pair: &Ed25519KeyPair| {
+            let mut event = serde_json::from_value(json!({
+                "auth_events": [],
+                "content": { "body": body },
+                "depth": 3,
+                "origin_server_ts": 1_000_000,
+                "prev_events": [],
+                "room_id": "!x:domain",
+                "sender": "@name:domain-sender",
+                "type": "X",
+            }))
+            .unwrap();
+            hash_and_sign_event("domain-sender", key_pair, &mut event, &RoomVersionId::V6).unwrap();
+            event
+        };
+
+        let valid = event("valid", &key_pair_sender);
+        let mut tampered = event("tampered", &key_pair_sender);
+        tampered.insert("content".to_owned(), json!({ "body": "other" }).try_into().unwrap());
+        let wrong_key = event("wrong key", &other_key_pair);
+        let mut unknown_server = event("unknown server", &key_pair_sender);
+        unknown_server
+            .insert("sender".to_owned(), CanonicalJsonValue::String("@name:unknown".to_owned()));
+
+        let events = [&valid, &tampered, &wrong_key, &unknown_server, &valid];
+        let results =
+            verify_event_batch(&public_key_map, events.map(|event| (event, &RoomVersionId::V6)));
+
+        assert_eq!(results.len(), 5);
+        assert_matches!(results[0], Ok(Verified::All));
+        assert_matches!(results[1], Ok(Verified::Signatures));
+        assert_matches!(results[2], Err(Error::Verification(VerificationError::Signature(_))));
+        assert_matches!(
+            &results[3],
+            Err(Error::Verification(VerificationError::SignatureNotFound(entity)))
+                if entity == "unknown"
+        );
+        assert_matches!(results[4], Ok(Verified::All));
+
+        // The results are the same as when verifying the events one at a time.
+        for (event, result) in events.into_iter().zip(results) {
+            assert_eq!(verify_event(&public_key_map, event, &RoomVersionId::V6).ok(), result.ok());
+        }
+    }
+
+    #[test]
+    fn verify_json_batch_reports_each_object() {
+        let key_pair = generate_key_pair();
+        let mut public_key_map = BTreeMap::new();
+        add_key_to_map(&mut public_key_map, "domain", &key_pair);
+
+        let mut first = serde_json::from_value(json!({ "id": 1 })).unwrap();
+        sign_json("domain", &key_pair, &mut first).unwrap();
+        let mut second: CanonicalJsonObject = serde_json::from_value(json!({ "id": 2 })).unwrap();
+        sign_json("domain", &key_pair, &mut second).unwrap();
+        let mut invalid = second.clone();
+        invalid.insert("id".to_owned(), CanonicalJsonValue::Integer(3_u8.into()));
+
+        assert!(verify_json_batch(&public_key_map, [&first, &second])
+            .into_iter()
+            .all(|result| result.is_ok()));
+
+        let results = verify_json_batch(&public_key_map, [&first, &invalid, &second]);
+        assert_matches!(results[0], Ok(()));
+        assert_matches!(results[1], Err(Error::Verification(VerificationError::Signature(_))));
+        assert_matches!(results[2], Ok(()));
+
+        assert!(verify_json_batch(&public_key_map, []).is_empty());
+    }
+
+    #[test]
+    fn small_order_signature_is_rejected() {
+        // The identity point as public key and as `R`, with `s = 0`, satisfies the verification
+        // equation for any message.
+        let mut identity = [0; 32];
+        identity[0] = 1;
+        let mut signature = [0; 64];
+        signature[0] = 1;
+        let signature: Base64 = Base64::new(signature.to_vec());
+
+        let mut public_key_map = BTreeMap::new();
+        public_key_map.insert(
+            "domain".to_owned(),
+            [("ed25519:1".to_owned(), Base64::new(identity.to_vec()))].into(),
+        );
+        let object = serde_json::from_value(json!({
+            "id": 1,
+            "signatures": {
+                "domain": { "ed25519:1": signature.encode() }
+            }
+        }))
+        .unwrap();
+
+        assert_matches!(
+            verify_json(&public_key_map, &object),
+            Err(Error::Verification(VerificationError::Signature(_)))
+        );
+        assert_matches!(
+            verify_json_batch(&public_key_map, [&object])[0],
+            Err(Error::Verification(VerificationError::Signature(_)))
+        );
+    }
+
+    #[test]
+    fn custom_algorithm_is_verified_with_registered_verifier() {
+        register_verifier(Algorithm::from(TEST_ALGORITHM), TestVerifier);
+        assert!(!register_verifier(Algorithm::Ed25519, TestVerifier));
+
+        let ed25519_key_pair = generate_key_pair();
+        let key_pairs: [&dyn KeyPair; 2] = [&ed25519_key_pair, &TestKeyPair(Some(b"secret"))];
+        let mut public_key_map = BTreeMap::new();
+        add_key_to_map(&mut public_key_map, "domain", &ed25519_key_pair);
+        public_key_map
+            .get_mut("domain")
+            .unwrap()
+            .insert(format!("{TEST_ALGORITHM}:1"), Base64::new(b"secret".to_vec()));
+
+        let mut object = serde_json::from_value(json!({ "id": 1 })).unwrap();
+        for key_pair in key_pairs {
+            sign_json("domain", key_pair, &mut object).unwrap();
+        }
+        verify_json(&public_key_map, &object).unwrap();
+
+        let mut custom_only = serde_json::from_value(json!({ "id": 2 })).unwrap();
+        sign_json("domain", &TestKeyPair(Some(b"secret")), &mut custom_only).unwrap();
+        let mut invalid = custom_only.clone();
+        invalid.insert("id".to_owned(), CanonicalJsonValue::Integer(3_u8.into()));
+
+        let results = verify_json_batch(&public_key_map, [&object, &custom_only, &invalid]);
+        assert_matches!(results[0], Ok(()));
+        assert_matches!(results[1], Ok(()));
+        assert_matches!(results[2], Err(Error::Verification(VerificationError::Signature(_))));
+
+        let mut event = serde_json::from_value(json!({
+            "auth_events": [],
+            "content": {},
+            "depth": 3,
+            "origin_server_ts": 1_000_000,
+            "prev_events": [],
+            "room_id": "!x:domain",
+            "sender": "@name:domain",
+            "type": "X",
+        }))
+        .unwrap();
+        let key_pair: Box<dyn KeyPair> = Box::new(TestKeyPair(Some(b"secret")));
+        hash_and_sign_event("domain", &key_pair, &mut event, &RoomVersionId::V6).unwrap();
+        assert_matches!(
+            verify_event(&public_key_map, &event, &RoomVersionId::V6),
+            Ok(Verified::All)
+        );
+    }
+
+    #[test]
+    fn signing_error_of_external_key_pair_is_returned() {
+        let mut object = serde_json::from_value(json!({ "id": 1 })).unwrap();
+        assert_matches!(
+            sign_json("domain", &TestKeyPair(None), &mut object),
+            Err(Error::Signing(_))
+        );
+    }
+
+    #[test]
+    fn signatures_with_unknown_algorithms_are_skipped() {
+        let key_pair = generate_key_pair();
+        let mut public_key_map = BTreeMap::new();
+        add_key_to_map(&mut public_key_map, "domain", &key_pair);
+        // Sorted before the ed25519 key, so it would be used first by `verify_event`.
+        public_key_map
+            .get_mut("domain")
+            .unwrap()
+            .insert("a.unknown:1".to_owned(), Base64::new(b"key".to_vec()));
+
+        let unknown_signature = |object: &mut CanonicalJsonObject| {
+            let signatures = match object.get_mut("signatures") {
+                Some(CanonicalJsonValue::Object(signatures)) => signatures,
+                _ => panic!("object is not signed"),
+            };
+            let domain_signatures = match signatures.get_mut("domain") {
+                Some(CanonicalJsonValue::Object(domain_signatures)) => domain_signatures,
+                _ => panic!("object is not signed by domain"),
+            };
+            domain_signatures.insert(
+                "a.unknown:1".to_owned(),
+                CanonicalJsonValue::String("not a signature".to_owned()),
+            );
+        };
+
+        let mut object = serde_json::from_value(json!({ "id": 1 })).unwrap();
+        sign_json("domain", &key_pair, &mut object).unwrap();
+        unknown_signature(&mut object);
+        verify_json(&public_key_map, &object).unwrap();
+
+        let mut unknown_only: CanonicalJsonObject = serde_json::from_value(json!({
+            "signatures": { "domain": {} },
+        }))
+        .unwrap();
+        unknown_signature(&mut unknown_only);
+        assert_matches!(
+            verify_json(&public_key_map, &unknown_only),
+            Err(Error::UnsupportedAlgorithm(algorithm)) if algorithm == "a.unknown"
+        );
+
+        let mut event = serde_json::from_value(json!({
+            "auth_events": [],
+            "content": {},
+            "depth": 3,
+            "origin_server_ts": 1_000_000,
+            "prev_events": [],
+            "room_id": "!x:domain",
+            "sender": "@name:domain",
+            "type": "X",
+        }))
+        .unwrap();
+        hash_and_sign_event("domain", &key_pair, &mut event, &RoomVersionId::V6).unwrap();
+        unknown_signature(&mut event);
+        assert_matches!(
+            verify_event(&public_key_map, &event, &RoomVersionId::V6),
+            Ok(Verified::All)
+        );
+    }
+
     fn generate_key_pair() -> Ed25519KeyPair {
         let key_content = Ed25519KeyPair::generate().unwrap();
         Ed25519KeyPair::from_der(&key_content, "1".to_owned())
@@ -3,6 +3,7 @@
 use std::{
     collections::BTreeMap,
     fmt::{Debug, Formatter, Result as FmtResult},
+    sync::Arc,
 };
 
 use ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey};
@@ -15,13 +16,40 @@ use crate::{signatures::Signature, Algorithm, Error, ParseError};
 pub mod compat;
 
 /// A cryptographic key pair for digitally signing data.
-pub trait KeyPair: Sized {
+///
+/// This trait is object safe, so key pairs of different algorithms, or whose private key is held
+/// outside of the process, can be used as `&dyn KeyPair` or `Box<dyn KeyPair>`. Such key pairs
+/// can create their signature with [`Signature::new`].
+pub trait KeyPair {
     /// Signs a JSON object.
     ///
     /// # Parameters
     ///
     /// * message: An arbitrary series of bytes to sign.
-    fn sign(&self, message: &[u8]) -> Signature;
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be signed, e.g. because the external signer of
+    /// the key pair is not available.
+    fn sign(&self, message: &[u8]) -> Result<Signature, Error>;
+}
+
+impl<K: KeyPair + ?Sized> KeyPair for &K {
+    fn sign(&self, message: &[u8]) -> Result<Signature, Error> {
+        (**self).sign(message)
+    }
+}
+
+impl<K: KeyPair + ?Sized> KeyPair for Box<K> {
+    fn sign(&self, message: &[u8]) -> Result<Signature, Error> {
+        (**self).sign(message)
+    }
+}
+
+impl<K: KeyPair + ?Sized> KeyPair for Arc<K> {
+    fn sign(&self, message: &[u8]) -> Result<Signature, Error> {
+        (**self).sign(message)
+    }
 }
 
 pub const ED25519_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
@@ -182,12 +210,12 @@ impl Ed25519KeyPair {
 }
 
 impl KeyPair for Ed25519KeyPair {
-    fn sign(&self, message: &[u8]) -> Signature {
-        Signature {
+    fn sign(&self, message: &[u8]) -> Result<Signature, Error> {
+        Ok(Signature {
             algorithm: Algorithm::Ed25519,
             signature: self.extended_privkey.sign(message, &self.pubkey).as_ref().to_vec(),
             version: self.version.clone(),
-        }
+        })
     }
 }
 
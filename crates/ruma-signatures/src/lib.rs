@@ -41,19 +41,24 @@
 //! To verify a signature on arbitrary JSON, use the `verify_json` function. To verify the
 //! signatures and hashes on an event, use the `verify_event` function. See the documentation for
 //! these respective functions for more details and full examples of use.
+//!
+//! To verify many objects or events at once, like the PDUs of a federation transaction, use the
+//! `verify_json_batch` and `verify_event_batch` functions, which report the result of each one.
 
 #![warn(missing_docs)]
 
-use ruma_common::serde::{AsRefStr, DisplayAsRefStr};
+use std::fmt;
+
+use ruma_common::serde::StringEnum;
 
 pub use error::{Error, JsonError, ParseError, VerificationError};
 pub use functions::{
     canonical_json, content_hash, hash_and_sign_event, reference_hash, sign_json, verify_event,
-    verify_json,
+    verify_event_batch, verify_json, verify_json_batch,
 };
 pub use keys::{Ed25519KeyPair, KeyPair, PublicKeyMap, PublicKeySet};
 pub use signatures::Signature;
-pub use verification::Verified;
+pub use verification::{register_verifier, Verified, Verifier};
 
 mod error;
 mod functions;
@@ -62,12 +67,31 @@ mod signatures;
 mod verification;
 
 /// The algorithm used for signing data.
-#[derive(Clone, Debug, Eq, Hash, PartialEq, AsRefStr, DisplayAsRefStr)]
+///
+/// Signatures made with algorithms other than the ones listed here can only be verified if a
+/// [`Verifier`] was registered for them with [`register_verifier`].
+#[derive(Clone, Eq, Hash, PartialEq, StringEnum)]
 #[cfg_attr(not(feature = "unstable-exhaustive-types"), non_exhaustive)]
 #[ruma_enum(rename_all = "snake_case")]
 pub enum Algorithm {
     /// The Ed25519 digital signature algorithm.
     Ed25519,
+
+    #[doc(hidden)]
+    _Custom(PrivOwnedStr),
+}
+
+// Wrapper around `Box<str>` that cannot be used in a meaningful way outside of
+// this crate. Used for string enums because their `_Custom` variant can't be
+// truly private (only `#[doc(hidden)]`).
+#[doc(hidden)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PrivOwnedStr(Box<str>);
+
+impl fmt::Debug for PrivOwnedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
 /// Extract the algorithm and version from a key identifier.
@@ -91,10 +115,11 @@ fn split_id(id: &str) -> Result<(Algorithm, String), Error> {
 
     let algorithm_input = signature_id[0];
 
-    let algorithm = match algorithm_input {
-        "ed25519" => Algorithm::Ed25519,
-        algorithm => return Err(Error::UnsupportedAlgorithm(algorithm.into())),
-    };
+    if algorithm_input.is_empty() {
+        return Err(Error::UnsupportedAlgorithm(algorithm_input.into()));
+    }
+
+    let algorithm = Algorithm::from(algorithm_input);
 
     Ok((algorithm, signature_id[1].to_owned()))
 }
@@ -38,7 +38,7 @@ impl Signature {
     ///
     /// Returns an error if:
     ///
-    /// * The key ID specifies an unknown algorithm.
+    /// * The key ID has an empty algorithm.
     /// * The key ID is malformed.
     /// * The key ID contains a version with invalid characters.
     pub fn new(id: &str, bytes: &[u8]) -> Result<Self, Error> {
@@ -98,8 +98,15 @@ mod tests {
         Signature::new("ed25519:abc!def", &[]).unwrap_err();
     }
 
+    #[test]
+    fn custom_key_id_algorithm() {
+        let signature = Signature::new("foobar:abcdef", &[]).unwrap();
+        assert_eq!(signature.algorithm().as_str(), "foobar");
+        assert_eq!(signature.id(), "foobar:abcdef");
+    }
+
     #[test]
     fn invalid_key_id_algorithm() {
-        Signature::new("foobar:abcdef", &[]).unwrap_err();
+        Signature::new(":abcdef", &[]).unwrap_err();
     }
 }
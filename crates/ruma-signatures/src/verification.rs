@@ -1,10 +1,64 @@
 //! Verification of digital signatures.
 
-use ed25519_dalek::{PublicKey, Verifier as _};
+use std::sync::{Arc, PoisonError, RwLock};
 
-use crate::{Error, ParseError, VerificationError};
+use ed25519_dalek::PublicKey;
+use ruma_common::serde::{base64::Standard, Base64};
+
+use crate::{Algorithm, Error, ParseError, VerificationError};
+
+/// The verifiers registered with [`register_verifier`].
+static CUSTOM_VERIFIERS: RwLock<Vec<(Algorithm, Arc<dyn Verifier + Send + Sync>)>> =
+    RwLock::new(Vec::new());
+
+/// Registers the verifier to use for signatures made with the given algorithm.
+///
+/// Signatures made with an algorithm that has no verifier are skipped during verification: they
+/// are ignored by [`verify_json`](crate::verify_json), and [`verify_event`](crate::verify_event)
+/// only uses the public keys of algorithms that have a verifier. Verification still fails if an
+/// entity has no signature that can be verified.
+///
+/// Registering a verifier for an algorithm that already has a registered verifier replaces it.
+///
+/// Returns `false` and doesn't register the verifier if the algorithm is built into this crate,
+/// like [`Algorithm::Ed25519`].
+pub fn register_verifier<V>(algorithm: Algorithm, verifier: V) -> bool
+where
+    V: Verifier + Send + Sync + 'static,
+{
+    if matches!(algorithm, Algorithm::Ed25519) {
+        return false;
+    }
+
+    let mut verifiers = CUSTOM_VERIFIERS.write().unwrap_or_else(PoisonError::into_inner);
+    let verifier = Arc::new(verifier);
+
+    match verifiers.iter_mut().find(|(a, _)| *a == algorithm) {
+        Some((_, existing)) => *existing = verifier,
+        None => verifiers.push((algorithm, verifier)),
+    }
+
+    true
+}
+
+/// Whether signatures made with the given algorithm can be verified.
+pub(crate) fn is_supported(algorithm: &Algorithm) -> bool {
+    matches!(algorithm, Algorithm::Ed25519) || custom_verifier(algorithm).is_some()
+}
+
+/// Get the verifier registered for the given algorithm.
+fn custom_verifier(algorithm: &Algorithm) -> Option<Arc<dyn Verifier + Send + Sync>> {
+    CUSTOM_VERIFIERS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|(a, _)| a == algorithm)
+        .map(|(_, verifier)| verifier.clone())
+}
 
 /// A digital signature verifier.
+///
+/// A verifier can be registered for a signing algorithm with [`register_verifier`].
 pub trait Verifier {
     /// Use a public key to verify a signature against the JSON object that was signed.
     ///
@@ -22,6 +76,9 @@ pub trait Verifier {
 }
 
 /// A verifier for Ed25519 digital signatures.
+///
+/// Signatures are verified with the strict rules of `ed25519_dalek`, which reject public keys and
+/// signatures of small order.
 #[derive(Debug, Default)]
 pub struct Ed25519Verifier;
 
@@ -34,12 +91,48 @@ impl Verifier for Ed25519Verifier {
     ) -> Result<(), Error> {
         PublicKey::from_bytes(public_key)
             .map_err(ParseError::PublicKey)?
-            .verify(message, &signature.try_into().map_err(ParseError::Signature)?)
+            .verify_strict(message, &signature.try_into().map_err(ParseError::Signature)?)
             .map_err(VerificationError::Signature)
             .map_err(Error::from)
     }
 }
 
+/// A message with the signatures to verify for it.
+pub(crate) struct SignedMessage<'a> {
+    /// The canonical JSON that was signed.
+    message: String,
+
+    /// The algorithms and public keys, and the signatures made with them.
+    signatures: Vec<(Algorithm, &'a Base64, Base64<Standard>)>,
+}
+
+impl<'a> SignedMessage<'a> {
+    pub(crate) fn new(
+        message: String,
+        signatures: Vec<(Algorithm, &'a Base64, Base64<Standard>)>,
+    ) -> Self {
+        Self { message, signatures }
+    }
+
+    /// Verifies the signatures one at a time.
+    pub(crate) fn verify(&self) -> Result<(), Error> {
+        for (algorithm, public_key, signature) in &self.signatures {
+            let (public_key, signature, message) =
+                (public_key.as_bytes(), signature.as_bytes(), self.message.as_bytes());
+
+            if let Algorithm::Ed25519 = algorithm {
+                Ed25519Verifier.verify_json(public_key, signature, message)?;
+            } else {
+                custom_verifier(algorithm)
+                    .ok_or_else(|| Error::UnsupportedAlgorithm(algorithm.to_string()))?
+                    .verify_json(public_key, signature, message)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// A value returned when an event is successfully verified.
 ///
 /// Event verification involves verifying both signatures and a content hash. It is possible for
@@ -32,7 +32,7 @@ use ruma_common::{
     room_id, user_id, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, RoomId, RoomVersionId,
     UserId,
 };
-use ruma_state_res::{self as state_res, Error, Event, Result, StateMap};
+use ruma_state_res::{self as state_res, AuthChainCache, Error, Event, Result, StateMap};
 use serde_json::{
     json,
     value::{to_raw_value as to_raw_json_value, RawValue as RawJsonValue},
@@ -143,11 +143,39 @@ fn resolve_deeper_event_set(c: &mut Criterion) {
     });
 }
 
+fn auth_chain_deeper_event_set(c: &mut Criterion) {
+    let mut inner = INITIAL_EVENTS();
+    inner.extend(BAN_STATE_SET());
+    let state_event_ids = ["CREATE", "IJR", "IMA", "IMB", "IMC", "MB", "IME", "PA"]
+        .into_iter()
+        .map(event_id)
+        .collect::<Vec<_>>();
+
+    c.bench_function("auth chain of 8 events without cache", |b| {
+        b.iter(|| {
+            let _ = state_res::auth_chain(state_event_ids.iter().cloned(), |id| {
+                inner.get(id).map(Arc::clone)
+            });
+        });
+    });
+
+    c.bench_function("auth chain of 8 events with warm cache", |b| {
+        let mut cache = AuthChainCache::new();
+        cache.auth_chain(state_event_ids.iter().cloned(), |id| inner.get(id).map(Arc::clone));
+
+        b.iter(|| {
+            let _ = cache
+                .auth_chain(state_event_ids.iter().cloned(), |id| inner.get(id).map(Arc::clone));
+        });
+    });
+}
+
 criterion_group!(
     benches,
     lexico_topo_sort,
     resolution_shallow_auth_chain,
-    resolve_deeper_event_set
+    resolve_deeper_event_set,
+    auth_chain_deeper_event_set
 );
 
 criterion_main!(benches);
@@ -529,6 +557,7 @@ impl EventTypeExt for &TimelineEventType {
 }
 
 mod event {
+    use js_int::UInt;
     use ruma_common::{
         events::{pdu::Pdu, TimelineEventType},
         MilliSecondsSinceUnixEpoch, OwnedEventId, RoomId, UserId,
@@ -598,6 +627,15 @@ mod event {
             }
         }
 
+        fn depth(&self) -> UInt {
+            match &self.rest {
+                Pdu::RoomV1Pdu(ev) => ev.depth,
+                Pdu::RoomV3Pdu(ev) => ev.depth,
+                #[cfg(not(feature = "unstable-exhaustive-types"))]
+                _ => unreachable!("new PDU version"),
+            }
+        }
+
         fn prev_events(&self) -> Box<dyn DoubleEndedIterator<Item = &Self::Id> + '_> {
             match &self.rest {
                 Pdu::RoomV1Pdu(ev) => Box::new(ev.prev_events.iter().map(|(id, _)| id)),
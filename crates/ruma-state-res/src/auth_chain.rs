@@ -0,0 +1,282 @@
+//! Computation of the auth chains of events.
+
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::Arc,
+};
+
+use ruma_common::EventId;
+use tracing::{debug, warn};
+
+use crate::Event;
+
+/// The auth chain of a list of events.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct AuthChain<Id> {
+    /// The IDs of all the events in the `auth_events` of the events, recursively.
+    ///
+    /// The events themselves are not part of their auth chain, unless they are referenced by
+    /// another event of the list.
+    pub event_ids: HashSet<Id>,
+
+    /// The IDs of the events that could not be fetched.
+    ///
+    /// If this is not empty, `event_ids` might be incomplete.
+    pub missing: HashSet<Id>,
+}
+
+impl<Id> Default for AuthChain<Id> {
+    fn default() -> Self {
+        Self { event_ids: HashSet::new(), missing: HashSet::new() }
+    }
+}
+
+/// A cache of the auth chains of events.
+///
+/// The auth chain of every event encountered while walking the `auth_events` is memoized, so
+/// events that share parts of their auth chain, like the events of the same room, only need to be
+/// fetched once across calls.
+///
+/// The auth chains that are incomplete because some of their events could not be fetched are not
+/// cached.
+///
+/// The auth chains of the state sets passed to [`resolve`](crate::resolve) can be computed with:
+///
+/// ```ignore
+/// let auth_chain_sets = state_sets
+///     .iter()
+///     .map(|state| cache.auth_chain(state.values().cloned(), &fetch_event).event_ids)
+///     .collect();
+/// ```
+#[derive(Clone, Debug)]
+pub struct AuthChainCache<Id> {
+    chains: HashMap<Id, Arc<HashSet<Id>>>,
+}
+
+impl<Id> AuthChainCache<Id>
+where
+    Id: Clone + Eq + Hash + Borrow<EventId>,
+{
+    /// Creates an empty `AuthChainCache`.
+    pub fn new() -> Self {
+        Self { chains: HashMap::new() }
+    }
+
+    /// The number of events whose auth chain is cached.
+    pub fn len(&self) -> usize {
+        self.chains.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// Removes all the auth chains from the cache.
+    pub fn clear(&mut self) {
+        self.chains.clear();
+    }
+
+    /// Computes the auth chain of the given events.
+    ///
+    /// `fetch_event` is only called for events whose auth chain is not in the cache.
+    pub fn auth_chain<E>(
+        &mut self,
+        event_ids: impl IntoIterator<Item = Id>,
+        fetch_event: impl Fn(&EventId) -> Option<E>,
+    ) -> AuthChain<Id>
+    where
+        E: Event<Id = Id>,
+    {
+        let mut auth_chain = AuthChain::default();
+        // The incomplete auth chains computed during this call.
+        let mut incomplete = HashMap::new();
+
+        for event_id in event_ids {
+            self.compute(&event_id, &fetch_event, &mut incomplete, &mut auth_chain.missing);
+
+            if let Some(chain) =
+                self.chains.get(event_id.borrow()).or_else(|| incomplete.get(event_id.borrow()))
+            {
+                auth_chain.event_ids.extend(chain.iter().cloned());
+            }
+        }
+
+        debug!(
+            "auth chain has {} events, {} are missing",
+            auth_chain.event_ids.len(),
+            auth_chain.missing.len()
+        );
+
+        auth_chain
+    }
+
+    /// Computes the auth chain of `event_id` and of all the events in its auth chain, with a
+    /// post-order walk of the `auth_events`.
+    ///
+    /// Complete auth chains are inserted in the cache, the others in `incomplete`.
+    fn compute<E>(
+        &mut self,
+        event_id: &Id,
+        fetch_event: impl Fn(&EventId) -> Option<E>,
+        incomplete: &mut HashMap<Id, Arc<HashSet<Id>>>,
+        missing: &mut HashSet<Id>,
+    ) where
+        E: Event<Id = Id>,
+    {
+        // The `auth_events` of an entry are `None` until the event is fetched.
+        let mut stack = vec![(event_id.clone(), None)];
+        // The events whose `auth_events` are being walked, used to detect cycles.
+        let mut visiting = HashSet::new();
+
+        while let Some((id, auth_events)) = stack.pop() {
+            let auth_events: Vec<Id> = match auth_events {
+                Some(auth_events) => auth_events,
+                None => {
+                    if self.chains.contains_key(id.borrow())
+                        || incomplete.contains_key(id.borrow())
+                        || missing.contains(id.borrow())
+                        || visiting.contains(id.borrow())
+                    {
+                        continue;
+                    }
+
+                    let auth_events = match fetch_event(id.borrow()) {
+                        Some(event) => event.auth_events().cloned().collect::<Vec<_>>(),
+                        None => {
+                            let event_id: &EventId = id.borrow();
+                            warn!("could not find event {event_id} while computing auth chain");
+                            missing.insert(id);
+                            continue;
+                        }
+                    };
+
+                    visiting.insert(id.clone());
+                    let children = auth_events
+                        .iter()
+                        .filter(|&aid| {
+                            !self.chains.contains_key(aid.borrow())
+                                && !visiting.contains(aid.borrow())
+                        })
+                        .map(|aid| (aid.clone(), None))
+                        .collect::<Vec<_>>();
+                    stack.push((id, Some(auth_events)));
+                    stack.extend(children);
+                    continue;
+                }
+            };
+
+            // All the auth events were walked, the auth chain of this event can be built.
+            visiting.remove(id.borrow());
+
+            let mut chain = HashSet::new();
+            let mut complete = true;
+            for aid in auth_events {
+                if let Some(sub_chain) = self.chains.get(aid.borrow()) {
+                    chain.extend(sub_chain.iter().cloned());
+                } else {
+                    // The auth event is missing, is part of a cycle or has an incomplete chain.
+                    complete = false;
+                    if let Some(sub_chain) = incomplete.get(aid.borrow()) {
+                        chain.extend(sub_chain.iter().cloned());
+                    }
+                }
+
+                chain.insert(aid);
+            }
+
+            if complete {
+                self.chains.insert(id, Arc::new(chain));
+            } else {
+                incomplete.insert(id, Arc::new(chain));
+            }
+        }
+    }
+}
+
+impl<Id> Default for AuthChainCache<Id>
+where
+    Id: Clone + Eq + Hash + Borrow<EventId>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the auth chain of the given events, without reusing any previous computation.
+///
+/// Use an [`AuthChainCache`] to share the computation of auth chains across calls.
+pub fn auth_chain<E: Event>(
+    event_ids: impl IntoIterator<Item = E::Id>,
+    fetch_event: impl Fn(&EventId) -> Option<E>,
+) -> AuthChain<E::Id> {
+    AuthChainCache::new().auth_chain(event_ids, fetch_event)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
+
+    use ruma_common::OwnedEventId;
+
+    use super::{auth_chain, AuthChainCache};
+    use crate::test_utils::{event_id, PduEvent, INITIAL_EVENTS};
+
+    fn event_ids(ids: &[&str]) -> HashSet<OwnedEventId> {
+        ids.iter().copied().map(event_id).collect()
+    }
+
+    #[test]
+    fn auth_chain_of_initial_events() {
+        let events = INITIAL_EVENTS();
+
+        let chain = auth_chain([event_id("IMB"), event_id("IMA")], |id| events.get(id).cloned());
+
+        assert_eq!(chain.event_ids, event_ids(&["CREATE", "IMA", "IPOWER", "IJR"]));
+        assert!(chain.missing.is_empty());
+    }
+
+    #[test]
+    fn cache_is_reused() {
+        let events = INITIAL_EVENTS();
+        let fetched = Cell::new(0);
+        let fetch_event = |id: &_| {
+            fetched.set(fetched.get() + 1);
+            events.get(id).cloned()
+        };
+
+        let mut cache = AuthChainCache::new();
+        let chain = cache.auth_chain([event_id("IJR")], fetch_event);
+        assert_eq!(chain.event_ids, event_ids(&["CREATE", "IMA", "IPOWER"]));
+        assert_eq!(fetched.get(), 4);
+        assert_eq!(cache.len(), 4);
+
+        fetched.set(0);
+        let chain = cache.auth_chain([event_id("IMB"), event_id("IMC")], fetch_event);
+        assert_eq!(chain.event_ids, event_ids(&["CREATE", "IMA", "IPOWER", "IJR"]));
+        // Only the new events were fetched.
+        assert_eq!(fetched.get(), 2);
+    }
+
+    #[test]
+    fn missing_events_are_reported() {
+        let mut events: HashMap<OwnedEventId, Arc<PduEvent>> = INITIAL_EVENTS();
+        events.remove(&event_id("IMA"));
+
+        let mut cache = AuthChainCache::new();
+        let chain =
+            cache.auth_chain([event_id("IJR"), event_id("UNKNOWN")], |id| events.get(id).cloned());
+
+        assert_eq!(chain.event_ids, event_ids(&["CREATE", "IMA", "IPOWER"]));
+        assert_eq!(chain.missing, event_ids(&["IMA", "UNKNOWN"]));
+        // The incomplete auth chains of IJR and IPOWER are not cached.
+        assert_eq!(cache.len(), 1);
+    }
+}
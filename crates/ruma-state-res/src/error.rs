@@ -1,3 +1,11 @@
+use js_int::Int;
+use ruma_common::{
+    events::{
+        room::{join_rules::JoinRule, member::MembershipState},
+        TimelineEventType,
+    },
+    OwnedEventId, OwnedRoomId, OwnedUserId,
+};
 use serde_json::Error as JsonError;
 use thiserror::Error;
 
@@ -24,6 +32,10 @@ pub enum Error {
     #[error("Invalid PDU: {0}")]
     InvalidPdu(String),
 
+    /// The event was rejected by the authorization rules.
+    #[error("Event rejected by the authorization rules: {0}")]
+    AuthRejected(#[from] AuthRejection),
+
     /// A custom error.
     #[error("{0}")]
     Custom(Box<dyn std::error::Error>),
@@ -34,3 +46,286 @@ impl Error {
         Self::Custom(Box::new(e))
     }
 }
+
+/// The reason why an event was rejected by the [authorization rules].
+///
+/// [authorization rules]: https://spec.matrix.org/latest/rooms/v10/#authorization-rules
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AuthRejection {
+    /// The `m.room.create` event has previous events.
+    #[error("m.room.create event has prev_events")]
+    CreateHasPrevEvents,
+
+    /// The server of the room ID of the `m.room.create` event is not the server of its sender.
+    #[error("m.room.create event for {room_id} was sent by {sender} from another server")]
+    CreateServerMismatch {
+        /// The ID of the room.
+        room_id: OwnedRoomId,
+
+        /// The sender of the event.
+        sender: OwnedUserId,
+    },
+
+    /// The `room_version` of the `m.room.create` event is not a known room version.
+    #[error("m.room.create event has an unknown room version")]
+    CreateUnknownRoomVersion,
+
+    /// The `m.room.create` event has no `creator`.
+    #[error("m.room.create event has no creator")]
+    CreateMissingCreator,
+
+    /// There is no `m.room.create` event in the state of the room.
+    #[error("no m.room.create event in the room state")]
+    MissingCreateEvent,
+
+    /// The `m.room.create` event of the room is not one of the auth events of the event.
+    #[error("m.room.create event {0} is not in the auth events")]
+    CreateNotInAuthEvents(OwnedEventId),
+
+    /// The room is not federated and the sender is not on the server of the room creator.
+    #[error("room is not federated and {sender} is not on the server of the creator")]
+    NotFederated {
+        /// The sender of the event.
+        sender: OwnedUserId,
+    },
+
+    /// The state key of the `m.room.aliases` event is not the server of its sender.
+    #[error("state key of m.room.aliases event doesn't match the server of {sender}")]
+    AliasesStateKeyMismatch {
+        /// The sender of the event.
+        sender: OwnedUserId,
+    },
+
+    /// The `m.room.member` event has no state key.
+    #[error("m.room.member event has no state key")]
+    MemberMissingStateKey,
+
+    /// The `m.room.member` event has no valid `membership`.
+    #[error("m.room.member event has no valid membership")]
+    InvalidMembership,
+
+    /// The sender tried to change the membership of another user to a membership that only the
+    /// user can set, like `join` or `knock`.
+    #[error("{sender} can't set the {membership} membership of {target}")]
+    MembershipForOtherUser {
+        /// The membership of the event.
+        membership: MembershipState,
+
+        /// The sender of the event.
+        sender: OwnedUserId,
+
+        /// The user whose membership would change.
+        target: OwnedUserId,
+    },
+
+    /// The user is banned from the room.
+    #[error("{user} is banned")]
+    UserBanned {
+        /// The banned user.
+        user: OwnedUserId,
+    },
+
+    /// The join rule of the room doesn't allow the user to join.
+    #[error("join rule {} doesn't allow joining", .join_rule.as_str())]
+    JoinNotAllowed {
+        /// The join rule of the room.
+        join_rule: JoinRule,
+    },
+
+    /// The user authorising a restricted join is missing, not joined or can't invite users.
+    #[error("invalid user authorising the restricted join: {authorising_user:?}")]
+    InvalidJoinAuthorisation {
+        /// The user set in `join_authorised_via_users_server`, if any.
+        authorising_user: Option<OwnedUserId>,
+    },
+
+    /// The join rule of the room doesn't allow knocking.
+    #[error("join rule {} doesn't allow knocking", .join_rule.as_str())]
+    KnockNotAllowed {
+        /// The join rule of the room.
+        join_rule: JoinRule,
+    },
+
+    /// The current membership of the target user doesn't allow the membership change.
+    #[error("membership change not allowed while {target} has the {membership} membership")]
+    InvalidTargetMembership {
+        /// The user whose membership would change.
+        target: OwnedUserId,
+
+        /// The current membership of the user.
+        membership: MembershipState,
+    },
+
+    /// The `mxid` of the third-party invite is not the state key of the event.
+    #[error("third-party invite is for {mxid}, not the target of the event")]
+    ThirdPartyInviteUserMismatch {
+        /// The user ID in the signed third-party invite.
+        mxid: OwnedUserId,
+    },
+
+    /// There is no `m.room.third_party_invite` event in the state for the token of the
+    /// third-party invite.
+    #[error("no m.room.third_party_invite event for token {token}")]
+    MissingThirdPartyInvite {
+        /// The token of the third-party invite.
+        token: String,
+    },
+
+    /// The sender of the event is not the sender of the `m.room.third_party_invite` event.
+    #[error("{sender} didn't send the m.room.third_party_invite event")]
+    ThirdPartyInviteSenderMismatch {
+        /// The sender of the event.
+        sender: OwnedUserId,
+    },
+
+    /// The signature of the third-party invite doesn't match any public key of the
+    /// `m.room.third_party_invite` event.
+    #[error("invalid signature for third-party invite with token {token}")]
+    InvalidThirdPartyInviteSignature {
+        /// The token of the third-party invite.
+        token: String,
+    },
+
+    /// The membership of the sender is not `join`.
+    #[error("{sender} is not joined, membership is {membership}")]
+    SenderNotJoined {
+        /// The sender of the event.
+        sender: OwnedUserId,
+
+        /// The current membership of the sender.
+        membership: MembershipState,
+    },
+
+    /// The power level of the sender is lower than the invite level.
+    #[error("{sender} has power level {level}, invite level is {required}")]
+    InsufficientInviteLevel {
+        /// The sender of the event.
+        sender: OwnedUserId,
+
+        /// The power level of the sender.
+        level: Int,
+
+        /// The invite level of the room.
+        required: Int,
+    },
+
+    /// The power level of the sender is lower than the kick level.
+    #[error("{sender} has power level {level}, kick level is {required}")]
+    InsufficientKickLevel {
+        /// The sender of the event.
+        sender: OwnedUserId,
+
+        /// The power level of the sender.
+        level: Int,
+
+        /// The kick level of the room.
+        required: Int,
+    },
+
+    /// The power level of the sender is lower than the ban level.
+    #[error("{sender} has power level {level}, ban level is {required}")]
+    InsufficientBanLevel {
+        /// The sender of the event.
+        sender: OwnedUserId,
+
+        /// The power level of the sender.
+        level: Int,
+
+        /// The ban level of the room.
+        required: Int,
+    },
+
+    /// The power level of the target user is not lower than the power level of the sender.
+    #[error("{target} has power level {target_level}, not lower than {sender_level}")]
+    TargetLevelNotLower {
+        /// The user whose membership would change.
+        target: OwnedUserId,
+
+        /// The power level of the target user.
+        target_level: Int,
+
+        /// The power level of the sender.
+        sender_level: Int,
+    },
+
+    /// The membership of the event is unknown or not supported by the room version.
+    #[error("unsupported membership {0}")]
+    UnsupportedMembership(MembershipState),
+
+    /// The power level of the sender is lower than the level required to send the event.
+    #[error("power level {level} is not enough to send {event_type}, {required} is required")]
+    InsufficientPowerLevel {
+        /// The type of the event.
+        event_type: TimelineEventType,
+
+        /// The power level of the sender.
+        level: Int,
+
+        /// The power level required to send events of this type.
+        required: Int,
+    },
+
+    /// The state key of the event is a user ID that is not the sender.
+    #[error("state key {state_key} is a user ID different from the sender")]
+    StateKeyNotSender {
+        /// The state key of the event.
+        state_key: String,
+    },
+
+    /// The `m.room.power_levels` event has a non-empty state key or invalid content.
+    #[error("invalid m.room.power_levels event")]
+    InvalidPowerLevels,
+
+    /// The sender tried to change the power level of a user without having a higher power level
+    /// than the old and new values.
+    #[error("power level {sender_level} is not enough to change the power level of {user}")]
+    UserPowerLevelChange {
+        /// The user whose power level would change.
+        user: OwnedUserId,
+
+        /// The power level of the sender.
+        sender_level: Int,
+    },
+
+    /// The sender tried to change the power level of an event type to or from a value higher
+    /// than their own power level.
+    #[error("power level {sender_level} is not enough to change the power level of {event_type}")]
+    EventPowerLevelChange {
+        /// The event type whose power level would change.
+        event_type: TimelineEventType,
+
+        /// The power level of the sender.
+        sender_level: Int,
+    },
+
+    /// The sender tried to change the power level of a notification to or from a value higher
+    /// than their own power level.
+    #[error("power level {sender_level} is not enough to change the @room notification level")]
+    NotificationPowerLevelChange {
+        /// The power level of the sender.
+        sender_level: Int,
+    },
+
+    /// The sender tried to change one of the top-level power levels, like `ban` or
+    /// `users_default`, to or from a value higher than their own power level.
+    #[error("power level {sender_level} is not enough to change {field}")]
+    PowerLevelChange {
+        /// The name of the power level field.
+        field: &'static str,
+
+        /// The power level of the sender.
+        sender_level: Int,
+    },
+
+    /// The power level of the sender is lower than the redact level, and the redacted event is
+    /// from another server.
+    #[error("power level {level} is not enough to redact events of other servers, {required} is required")]
+    InsufficientRedactLevel {
+        /// The power level of the sender.
+        level: Int,
+
+        /// The redact level of the room.
+        required: Int,
+    },
+}
@@ -1,5 +1,6 @@
-use std::{borrow::Borrow, collections::BTreeSet};
+use std::{borrow::Borrow, collections::BTreeSet, future::Future};
 
+use futures_util::future::join_all;
 use js_int::{int, Int};
 use ruma_common::{
     events::{
@@ -13,11 +14,11 @@ use ruma_common::{
         StateEventType, TimelineEventType,
     },
     serde::{Base64, Raw},
-    OwnedUserId, RoomVersionId, UserId,
+    EventId, OwnedEventId, OwnedUserId, RoomVersionId, UserId,
 };
 use serde::{de::IgnoredAny, Deserialize};
 use serde_json::{from_str as from_json_str, value::RawValue as RawJsonValue};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info};
 
 use crate::{
     power_levels::{
@@ -25,7 +26,7 @@ use crate::{
         deserialize_power_levels_content_invite, deserialize_power_levels_content_redact,
     },
     room_version::RoomVersion,
-    Error, Event, Result,
+    AuthRejection, Error, Event, EventTypeExt, Result, StateMap,
 };
 
 // FIXME: field extracting could be bundled for `content`
@@ -121,12 +122,18 @@ pub fn auth_types_for_event(
 ///
 /// The `fetch_state` closure should gather state from a state snapshot. We need to know if the
 /// event passes auth against some state not a recursive collection of auth_events fields.
+///
+/// # Errors
+///
+/// Returns [`Error::AuthRejected`] with the reason of the rejection if the event doesn't pass the
+/// authorization rules. Other errors mean that the event couldn't be checked, e.g. because the
+/// content of an event of the state couldn't be deserialized.
 pub fn auth_check<E: Event>(
     room_version: &RoomVersion,
     incoming_event: impl Event,
     current_third_party_invite: Option<impl Event>,
     fetch_state: impl Fn(&StateEventType, &str) -> Option<E>,
-) -> Result<bool> {
+) -> Result<()> {
     info!(
         "auth_check beginning for {} ({})",
         incoming_event.event_id(),
@@ -157,32 +164,32 @@ pub fn auth_check<E: Event>(
 
         // If it has any previous events, reject
         if incoming_event.prev_events().next().is_some() {
-            warn!("the room creation event had previous events");
-            return Ok(false);
+            return Err(AuthRejection::CreateHasPrevEvents.into());
         }
 
         // If the domain of the room_id does not match the domain of the sender, reject
         if incoming_event.room_id().server_name() != sender.server_name() {
-            warn!("creation events server does not match sender");
-            return Ok(false); // creation events room id does not match senders
+            return Err(AuthRejection::CreateServerMismatch {
+                room_id: incoming_event.room_id().to_owned(),
+                sender: sender.to_owned(),
+            }
+            .into());
         }
 
         let content: RoomCreateContentFields = from_json_str(incoming_event.content().get())?;
 
         // If content.room_version is present and is not a recognized version, reject
         if content.room_version.map(|v| v.deserialize().is_err()).unwrap_or(false) {
-            warn!("invalid room version found in m.room.create event");
-            return Ok(false);
+            return Err(AuthRejection::CreateUnknownRoomVersion.into());
         }
 
-        // If content has no creator field, reject
-        if content.creator.is_none() {
-            warn!("no creator field found in m.room.create content");
-            return Ok(false);
+        // Before room version 11, if content has no creator field, reject
+        if !room_version.use_room_create_sender && content.creator.is_none() {
+            return Err(AuthRejection::CreateMissingCreator.into());
         }
 
         info!("m.room.create event was allowed");
-        return Ok(true);
+        return Ok(());
     }
 
     /*
@@ -211,18 +218,14 @@ pub fn auth_check<E: Event>(
     */
 
     let room_create_event = match fetch_state(&StateEventType::RoomCreate, "") {
-        None => {
-            warn!("no m.room.create event in auth chain");
-            return Ok(false);
-        }
+        None => return Err(AuthRejection::MissingCreateEvent.into()),
         Some(e) => e,
     };
 
     // 3. If event does not have m.room.create in auth_events reject
-    if !incoming_event.auth_events().any(|id| id.borrow() == room_create_event.event_id().borrow())
-    {
-        warn!("no m.room.create event in auth events");
-        return Ok(false);
+    let room_create_event_id: &EventId = room_create_event.event_id().borrow();
+    if !incoming_event.auth_events().any(|id| id.borrow() == room_create_event_id) {
+        return Err(AuthRejection::CreateNotInAuthEvents(room_create_event_id.to_owned()).into());
     }
 
     // If the create event content has the field m.federate set to false and the sender domain of
@@ -237,8 +240,7 @@ pub fn auth_check<E: Event>(
     if !room_create_content.federate
         && room_create_event.sender().server_name() != incoming_event.sender().server_name()
     {
-        warn!("room is not federated and event's sender domain does not match create event's sender domain");
-        return Ok(false);
+        return Err(AuthRejection::NotFederated { sender: sender.to_owned() }.into());
     }
 
     // Only in some room versions 6 and below
@@ -249,12 +251,13 @@ pub fn auth_check<E: Event>(
 
             // If sender's domain doesn't matches state_key, reject
             if incoming_event.state_key() != Some(sender.server_name().as_str()) {
-                warn!("state_key does not match sender");
-                return Ok(false);
+                return Err(
+                    AuthRejection::AliasesStateKeyMismatch { sender: sender.to_owned() }.into()
+                );
             }
 
             info!("m.room.aliases event was allowed");
-            return Ok(true);
+            return Ok(());
         }
     }
 
@@ -265,17 +268,13 @@ pub fn auth_check<E: Event>(
     if *incoming_event.event_type() == TimelineEventType::RoomMember {
         info!("starting m.room.member check");
         let state_key = match incoming_event.state_key() {
-            None => {
-                warn!("no statekey in member event");
-                return Ok(false);
-            }
+            None => return Err(AuthRejection::MemberMissingStateKey.into()),
             Some(s) => s,
         };
 
         let content: RoomMemberContentFields = from_json_str(incoming_event.content().get())?;
         if content.membership.as_ref().and_then(|m| m.deserialize().ok()).is_none() {
-            warn!("no valid membership field found for m.room.member event content");
-            return Ok(false);
+            return Err(AuthRejection::InvalidMembership.into());
         }
 
         let target_user =
@@ -291,7 +290,7 @@ pub fn auth_check<E: Event>(
             .map(|mem| mem.membership)
             .unwrap_or(MembershipState::Leave);
 
-        if !valid_membership_change(
+        valid_membership_change(
             room_version,
             target_user,
             fetch_state(&StateEventType::RoomMember, target_user.as_str()).as_ref(),
@@ -304,20 +303,21 @@ pub fn auth_check<E: Event>(
             user_for_join_auth.as_deref(),
             &user_for_join_auth_membership,
             room_create_event,
-        )? {
-            return Ok(false);
-        }
+        )?;
 
         info!("m.room.member event was allowed");
-        return Ok(true);
+        return Ok(());
     }
 
     // If the sender's current membership state is not join, reject
     let sender_member_event = match sender_member_event {
         Some(mem) => mem,
         None => {
-            warn!("sender not found in room");
-            return Ok(false);
+            return Err(AuthRejection::SenderNotJoined {
+                sender: sender.to_owned(),
+                membership: MembershipState::Leave,
+            }
+            .into());
         }
     };
 
@@ -329,8 +329,11 @@ pub fn auth_check<E: Event>(
         .deserialize()?;
 
     if !matches!(membership_state, MembershipState::Join) {
-        warn!("sender's membership is not join");
-        return Ok(false);
+        return Err(AuthRejection::SenderNotJoined {
+            sender: sender.to_owned(),
+            membership: membership_state,
+        }
+        .into());
     }
 
     // If type is m.room.third_party_invite
@@ -343,10 +346,11 @@ pub fn auth_check<E: Event>(
         }
     } else {
         // If no power level event found the creator gets 100 everyone else gets 0
-        from_json_str::<RoomCreateEventContent>(room_create_event.content().get())
-            .ok()
-            .and_then(|create| (create.creator == *sender).then(|| int!(100)))
-            .unwrap_or_default()
+        if is_room_creator(room_version, &room_create_event, sender) {
+            int!(100)
+        } else {
+            int!(0)
+        }
     };
 
     // Allow if and only if sender's current power level is greater than
@@ -361,39 +365,33 @@ pub fn auth_check<E: Event>(
         };
 
         if sender_power_level < invite_level {
-            warn!("sender's cannot send invites in this room");
-            return Ok(false);
+            return Err(AuthRejection::InsufficientInviteLevel {
+                sender: sender.to_owned(),
+                level: sender_power_level,
+                required: invite_level,
+            }
+            .into());
         }
 
         info!("m.room.third_party_invite event was allowed");
-        return Ok(true);
+        return Ok(());
     }
 
     // If the event type's required power level is greater than the sender's power level, reject
     // If the event has a state_key that starts with an @ and does not match the sender, reject.
-    if !can_send_event(&incoming_event, power_levels_event.as_ref(), sender_power_level) {
-        warn!("user cannot send event");
-        return Ok(false);
-    }
+    can_send_event(&incoming_event, power_levels_event.as_ref(), sender_power_level)?;
 
     // If type is m.room.power_levels
     if *incoming_event.event_type() == TimelineEventType::RoomPowerLevels {
         info!("starting m.room.power_levels check");
 
-        if let Some(required_pwr_lvl) = check_power_levels(
+        check_power_levels(
             room_version,
             &incoming_event,
             power_levels_event.as_ref(),
             sender_power_level,
-        ) {
-            if !required_pwr_lvl {
-                warn!("power level was not allowed");
-                return Ok(false);
-            }
-        } else {
-            warn!("power level was not allowed");
-            return Ok(false);
-        }
+        )?;
+
         info!("power levels event allowed");
     }
 
@@ -414,13 +412,51 @@ pub fn auth_check<E: Event>(
             None => int!(50),
         };
 
-        if !check_redaction(room_version, incoming_event, sender_power_level, redact_level)? {
-            return Ok(false);
-        }
+        check_redaction(room_version, incoming_event, sender_power_level, redact_level)?;
     }
 
     info!("allowing event passed all checks");
-    Ok(true)
+    Ok(())
+}
+
+/// Authenticate the incoming `event`, like [`auth_check`], with a `fetch_state` closure that is
+/// asynchronous.
+///
+/// The state events needed by the authorization rules, as returned by [`auth_types_for_event`], are
+/// all fetched concurrently before the checks start.
+///
+/// # Errors
+///
+/// Returns the same errors as [`auth_check`], or an error if the content of `incoming_event` is not
+/// a JSON object.
+pub async fn auth_check_async<E, F, Fut>(
+    room_version: &RoomVersion,
+    incoming_event: impl Event,
+    current_third_party_invite: Option<impl Event>,
+    fetch_state: F,
+) -> Result<()>
+where
+    E: Event,
+    F: Fn(&StateEventType, &str) -> Fut,
+    Fut: Future<Output = Option<E>>,
+{
+    let auth_types = auth_types_for_event(
+        incoming_event.event_type(),
+        incoming_event.sender(),
+        incoming_event.state_key(),
+        incoming_event.content(),
+    )?;
+
+    let state_events = join_all(auth_types.iter().map(|(ty, key)| fetch_state(ty, key))).await;
+    let state = auth_types
+        .into_iter()
+        .zip(state_events)
+        .filter_map(|(key, event)| Some((key, event?)))
+        .collect::<StateMap<_>>();
+
+    auth_check(room_version, incoming_event, current_third_party_invite, |ty, key| {
+        state.get(&ty.with_state_key(key))
+    })
 }
 
 // TODO deserializing the member, power, join_rules event contents is done in conduit
@@ -446,7 +482,7 @@ fn valid_membership_change(
     user_for_join_auth: Option<&UserId>,
     user_for_join_auth_membership: &MembershipState,
     create_room: impl Event,
-) -> Result<bool> {
+) -> Result<()> {
     #[derive(Deserialize)]
     struct GetThirdPartyInvite {
         third_party_invite: Option<Raw<ThirdPartyInvite>>,
@@ -487,11 +523,6 @@ fn valid_membership_change(
         join_rules = from_json_str::<RoomJoinRulesEventContent>(jr.content().get())?.join_rule;
     }
 
-    let power_levels_event_id = power_levels_event.as_ref().map(|e| e.event_id());
-    let sender_membership_event_id = sender_membership_event.as_ref().map(|e| e.event_id());
-    let target_user_membership_event_id =
-        target_user_membership_event.as_ref().map(|e| e.event_id());
-
     let user_for_join_auth_is_valid = if let Some(user_for_join_auth) = user_for_join_auth {
         // Is the authorised user allowed to invite users into this room
         let (auth_user_pl, invite_level) = if let Some(pl) = &power_levels_event {
@@ -517,7 +548,29 @@ fn valid_membership_change(
         false
     };
 
-    Ok(match target_membership {
+    let sender_not_joined = || AuthRejection::SenderNotJoined {
+        sender: sender.to_owned(),
+        membership: sender_membership.clone(),
+    };
+    let invalid_target_membership = || AuthRejection::InvalidTargetMembership {
+        target: target_user.to_owned(),
+        membership: target_user_current_membership.clone(),
+    };
+    let sender_level = sender_power.copied().unwrap_or_default();
+    // The target must have a lower power level than the sender to be kicked or banned.
+    let check_target_level = || {
+        if target_power < sender_power {
+            Ok(())
+        } else {
+            Err(AuthRejection::TargetLevelNotLower {
+                target: target_user.to_owned(),
+                target_level: target_power.copied().unwrap_or_default(),
+                sender_level,
+            })
+        }
+    };
+
+    match target_membership {
         MembershipState::Join => {
             // 1. If the only previous event is an m.room.create and the state_key is the creator,
             // allow
@@ -529,141 +582,129 @@ fn valid_membership_change(
                 .unwrap_or(false);
             let no_more_prev_events = prev_events.next().is_none();
 
-            if prev_event_is_create_event && no_more_prev_events {
-                let create_content =
-                    from_json_str::<RoomCreateEventContent>(create_room.content().get())?;
-
-                if create_content.creator == sender && create_content.creator == target_user {
-                    return Ok(true);
-                }
+            if prev_event_is_create_event
+                && no_more_prev_events
+                && sender == target_user
+                && is_room_creator(room_version, &create_room, sender)
+            {
+                return Ok(());
             }
 
             if sender != target_user {
                 // If the sender does not match state_key, reject.
-                warn!("Can't make other user join");
-                false
+                Err(AuthRejection::MembershipForOtherUser {
+                    membership: target_membership,
+                    sender: sender.to_owned(),
+                    target: target_user.to_owned(),
+                })
             } else if let MembershipState::Ban = target_user_current_membership {
                 // If the sender is banned, reject.
-                warn!(?target_user_membership_event_id, "Banned user can't join");
-                false
+                Err(AuthRejection::UserBanned { user: target_user.to_owned() })
             } else if (join_rules == JoinRule::Invite
                     || room_version.allow_knocking && join_rules == JoinRule::Knock)
                 // If the join_rule is invite then allow if membership state is invite or join
                     && (target_user_current_membership == MembershipState::Join
                         || target_user_current_membership == MembershipState::Invite)
             {
-                true
+                Ok(())
             } else if room_version.restricted_join_rules
                 && matches!(join_rules, JoinRule::Restricted(_))
                 || room_version.knock_restricted_join_rule
                     && matches!(join_rules, JoinRule::KnockRestricted(_))
             {
-                // If the join_rule is restricted or knock_restricted
+                // If the join_rule is restricted or knock_restricted:
+                // - If membership state is join or invite, allow.
+                // - If the join_authorised_via_users_server key in content is not a user with
+                //   sufficient permission to invite other users, reject.
+                // - Otherwise, allow.
                 if matches!(
                     target_user_current_membership,
                     MembershipState::Invite | MembershipState::Join
-                ) {
-                    // If membership state is join or invite, allow.
-                    true
+                ) || user_for_join_auth_is_valid
+                {
+                    Ok(())
                 } else {
-                    // If the join_authorised_via_users_server key in content is not a user with
-                    // sufficient permission to invite other users, reject.
-                    // Otherwise, allow.
-                    user_for_join_auth_is_valid
+                    Err(AuthRejection::InvalidJoinAuthorisation {
+                        authorising_user: user_for_join_auth.map(ToOwned::to_owned),
+                    })
                 }
-            } else {
+            } else if join_rules == JoinRule::Public {
                 // If the join_rule is public, allow.
+                Ok(())
+            } else {
                 // Otherwise, reject.
-                join_rules == JoinRule::Public
+                Err(AuthRejection::JoinNotAllowed { join_rule: join_rules })
             }
         }
         MembershipState::Invite => {
             // If content has third_party_invite key
             if let Some(tp_id) = third_party_invite.and_then(|i| i.deserialize().ok()) {
                 if target_user_current_membership == MembershipState::Ban {
-                    warn!(?target_user_membership_event_id, "Can't invite banned user");
-                    false
+                    Err(AuthRejection::UserBanned { user: target_user.to_owned() })
                 } else {
-                    let allow = verify_third_party_invite(
+                    verify_third_party_invite(
                         Some(target_user),
                         sender,
                         &tp_id,
                         current_third_party_invite,
-                    );
-                    if !allow {
-                        warn!("Third party invite invalid");
-                    }
-                    allow
+                    )
                 }
-            } else if !sender_is_joined
-                || target_user_current_membership == MembershipState::Join
+            } else if !sender_is_joined {
+                Err(sender_not_joined())
+            } else if target_user_current_membership == MembershipState::Join
                 || target_user_current_membership == MembershipState::Ban
             {
-                warn!(
-                    ?target_user_membership_event_id,
-                    ?sender_membership_event_id,
-                    "Can't invite user if sender not joined or the user is currently joined or \
-                     banned",
-                );
-                false
+                Err(invalid_target_membership())
+            } else if sender_power.filter(|&p| p >= &power_levels.invite).is_none() {
+                Err(AuthRejection::InsufficientInviteLevel {
+                    sender: sender.to_owned(),
+                    level: sender_level,
+                    required: power_levels.invite,
+                })
             } else {
-                let allow = sender_power.filter(|&p| p >= &power_levels.invite).is_some();
-                if !allow {
-                    warn!(
-                        ?target_user_membership_event_id,
-                        ?power_levels_event_id,
-                        "User does not have enough power to invite",
-                    );
-                }
-                allow
+                Ok(())
             }
         }
         MembershipState::Leave => {
             if sender == target_user {
-                let allow = target_user_current_membership == MembershipState::Join
-                    || target_user_current_membership == MembershipState::Invite;
-                if !allow {
-                    warn!(?target_user_membership_event_id, "Can't leave if not invited or joined");
+                if target_user_current_membership == MembershipState::Join
+                    || target_user_current_membership == MembershipState::Invite
+                {
+                    Ok(())
+                } else {
+                    Err(invalid_target_membership())
                 }
-                allow
-            } else if !sender_is_joined
-                || target_user_current_membership == MembershipState::Ban
-                    && sender_power.filter(|&p| p < &power_levels.ban).is_some()
+            } else if !sender_is_joined {
+                Err(sender_not_joined())
+            } else if target_user_current_membership == MembershipState::Ban
+                && sender_power.filter(|&p| p < &power_levels.ban).is_some()
             {
-                warn!(
-                    ?target_user_membership_event_id,
-                    ?sender_membership_event_id,
-                    "Can't kick if sender not joined or user is already banned",
-                );
-                false
+                Err(AuthRejection::InsufficientBanLevel {
+                    sender: sender.to_owned(),
+                    level: sender_level,
+                    required: power_levels.ban,
+                })
+            } else if sender_power.filter(|&p| p >= &power_levels.kick).is_none() {
+                Err(AuthRejection::InsufficientKickLevel {
+                    sender: sender.to_owned(),
+                    level: sender_level,
+                    required: power_levels.kick,
+                })
             } else {
-                let allow = sender_power.filter(|&p| p >= &power_levels.kick).is_some()
-                    && target_power < sender_power;
-                if !allow {
-                    warn!(
-                        ?target_user_membership_event_id,
-                        ?power_levels_event_id,
-                        "User does not have enough power to kick",
-                    );
-                }
-                allow
+                check_target_level()
             }
         }
         MembershipState::Ban => {
             if !sender_is_joined {
-                warn!(?sender_membership_event_id, "Can't ban user if sender is not joined");
-                false
+                Err(sender_not_joined())
+            } else if sender_power.filter(|&p| p >= &power_levels.ban).is_none() {
+                Err(AuthRejection::InsufficientBanLevel {
+                    sender: sender.to_owned(),
+                    level: sender_level,
+                    required: power_levels.ban,
+                })
             } else {
-                let allow = sender_power.filter(|&p| p >= &power_levels.ban).is_some()
-                    && target_power < sender_power;
-                if !allow {
-                    warn!(
-                        ?target_user_membership_event_id,
-                        ?power_levels_event_id,
-                        "User does not have enough power to ban",
-                    );
-                }
-                allow
+                check_target_level()
             }
         }
         MembershipState::Knock if room_version.allow_knocking => {
@@ -672,57 +713,58 @@ fn valid_membership_change(
                 || room_version.knock_restricted_join_rule
                     && matches!(join_rules, JoinRule::KnockRestricted(_))
             {
-                warn!("Join rule is not set to knock or knock_restricted, knocking is not allowed");
-                false
-            } else {
+                Err(AuthRejection::KnockNotAllowed { join_rule: join_rules })
+            } else if sender != target_user {
                 // 2. If `sender` does not match `state_key`, reject.
+                Err(AuthRejection::MembershipForOtherUser {
+                    membership: target_membership,
+                    sender: sender.to_owned(),
+                    target: target_user.to_owned(),
+                })
+            } else if matches!(sender_membership, MembershipState::Ban | MembershipState::Join) {
                 // 3. If the `sender`'s current membership is not `ban` or `join`, allow.
                 // 4. Otherwise, reject.
-                if sender != target_user {
-                    warn!(
-                        ?sender,
-                        ?target_user,
-                        "Can't make another user join, sender did not match target"
-                    );
-                    false
-                } else if matches!(sender_membership, MembershipState::Ban | MembershipState::Join)
-                {
-                    warn!(
-                        ?target_user_membership_event_id,
-                        "Membership state of ban or join are invalid",
-                    );
-                    false
-                } else {
-                    true
-                }
+                Err(AuthRejection::InvalidTargetMembership {
+                    target: target_user.to_owned(),
+                    membership: sender_membership.clone(),
+                })
+            } else {
+                Ok(())
             }
         }
-        _ => {
-            warn!("Unknown membership transition");
-            false
-        }
-    })
+        _ => Err(AuthRejection::UnsupportedMembership(target_membership)),
+    }
+    .map_err(Into::into)
 }
 
 /// Is the user allowed to send a specific event based on the rooms power levels.
 ///
 /// Does the event have the correct userId as its state_key if it's not the "" state_key.
-fn can_send_event(event: impl Event, ple: Option<impl Event>, user_level: Int) -> bool {
+fn can_send_event(
+    event: impl Event,
+    ple: Option<impl Event>,
+    user_level: Int,
+) -> std::result::Result<(), AuthRejection> {
     let event_type_power_level = get_send_level(event.event_type(), event.state_key(), ple);
 
     debug!("{} ev_type {event_type_power_level} usr {user_level}", event.event_id());
 
     if user_level < event_type_power_level {
-        return false;
+        return Err(AuthRejection::InsufficientPowerLevel {
+            event_type: event.event_type().clone(),
+            level: user_level,
+            required: event_type_power_level,
+        });
     }
 
-    if event.state_key().map_or(false, |k| k.starts_with('@'))
-        && event.state_key() != Some(event.sender().as_str())
-    {
-        return false; // permission required to post in this room
+    if let Some(state_key) = event.state_key() {
+        if state_key.starts_with('@') && state_key != event.sender().as_str() {
+            // permission required to post in this room
+            return Err(AuthRejection::StateKeyNotSender { state_key: state_key.to_owned() });
+        }
     }
 
-    true
+    Ok(())
 }
 
 /// Confirm that the event sender has the required power levels.
@@ -731,16 +773,16 @@ fn check_power_levels(
     power_event: impl Event,
     previous_power_event: Option<impl Event>,
     user_level: Int,
-) -> Option<bool> {
+) -> std::result::Result<(), AuthRejection> {
     match power_event.state_key() {
         Some("") => {}
         Some(key) => {
             error!("m.room.power_levels event has non-empty state key: {key}");
-            return None;
+            return Err(AuthRejection::InvalidPowerLevels);
         }
         None => {
             error!("check_power_levels requires an m.room.power_levels *state* event argument");
-            return None;
+            return Err(AuthRejection::InvalidPowerLevels);
         }
     }
 
@@ -751,7 +793,8 @@ fn check_power_levels(
     // - If users key in content is not a dictionary with keys that are valid user IDs with values
     //   that are integers, reject.
     let user_content: RoomPowerLevelsEventContent =
-        deserialize_power_levels(power_event.content().get(), room_version)?;
+        deserialize_power_levels(power_event.content().get(), room_version)
+            .ok_or(AuthRejection::InvalidPowerLevels)?;
 
     // Validation of users is done in Ruma, synapse for loops validating user_ids and integers here
     info!("validation of power event finished");
@@ -759,11 +802,12 @@ fn check_power_levels(
     let current_state = match previous_power_event {
         Some(current_state) => current_state,
         // If there is no previous m.room.power_levels event in the room, allow
-        None => return Some(true),
+        None => return Ok(()),
     };
 
     let current_content: RoomPowerLevelsEventContent =
-        deserialize_power_levels(current_state.content().get(), room_version)?;
+        deserialize_power_levels(current_state.content().get(), room_version)
+            .ok_or(AuthRejection::InvalidPowerLevels)?;
 
     let mut user_levels_to_check = BTreeSet::new();
     let old_list = &current_content.users;
@@ -799,18 +843,16 @@ fn check_power_levels(
         }
 
         // If the current value is equal to the sender's current power level, reject
-        if user != power_event.sender() && old_level == Some(&user_level) {
-            warn!("m.room.power_level cannot remove ops == to own");
-            return Some(false); // cannot remove ops level == to own
-        }
-
         // If the current value is higher than the sender's current power level, reject
         // If the new value is higher than the sender's current power level, reject
+        let removes_own_level = user != power_event.sender() && old_level == Some(&user_level);
         let old_level_too_big = old_level > Some(&user_level);
         let new_level_too_big = new_level > Some(&user_level);
-        if old_level_too_big || new_level_too_big {
-            warn!("m.room.power_level failed to add ops > than own");
-            return Some(false); // cannot add ops greater than own
+        if removes_own_level || old_level_too_big || new_level_too_big {
+            return Err(AuthRejection::UserPowerLevelChange {
+                user: user.to_owned(),
+                sender_level: user_level,
+            });
         }
     }
 
@@ -827,8 +869,10 @@ fn check_power_levels(
         let old_level_too_big = old_level > Some(&user_level);
         let new_level_too_big = new_level > Some(&user_level);
         if old_level_too_big || new_level_too_big {
-            warn!("m.room.power_level failed to add ops > than own");
-            return Some(false); // cannot add ops greater than own
+            return Err(AuthRejection::EventPowerLevelChange {
+                event_type: ev_type.clone(),
+                sender_level: user_level,
+            });
         }
     }
 
@@ -842,8 +886,9 @@ fn check_power_levels(
             let old_level_too_big = old_level > user_level;
             let new_level_too_big = new_level > user_level;
             if old_level_too_big || new_level_too_big {
-                warn!("m.room.power_level failed to add ops > than own");
-                return Some(false); // cannot add ops greater than own
+                return Err(AuthRejection::NotificationPowerLevelChange {
+                    sender_level: user_level,
+                });
             }
         }
     }
@@ -852,19 +897,21 @@ fn check_power_levels(
         ["users_default", "events_default", "state_default", "ban", "redact", "kick", "invite"];
     let old_state = serde_json::to_value(old_state).unwrap();
     let new_state = serde_json::to_value(new_state).unwrap();
-    for lvl_name in &levels {
+    for lvl_name in levels {
         if let Some((old_lvl, new_lvl)) = get_deserialize_levels(&old_state, &new_state, lvl_name) {
             let old_level_too_big = old_lvl > user_level;
             let new_level_too_big = new_lvl > user_level;
 
             if old_level_too_big || new_level_too_big {
-                warn!("cannot add ops > than own");
-                return Some(false);
+                return Err(AuthRejection::PowerLevelChange {
+                    field: lvl_name,
+                    sender_level: user_level,
+                });
             }
         }
     }
 
-    Some(true)
+    Ok(())
 }
 
 fn get_deserialize_levels(
@@ -878,28 +925,56 @@ fn get_deserialize_levels(
     ))
 }
 
+/// Whether the given user is the creator of the room.
+fn is_room_creator(
+    room_version: &RoomVersion,
+    room_create_event: impl Event,
+    user: &UserId,
+) -> bool {
+    if room_version.use_room_create_sender {
+        return room_create_event.sender() == user;
+    }
+
+    from_json_str::<RoomCreateEventContent>(room_create_event.content().get())
+        .ok()
+        .and_then(|create| create.creator)
+        .map_or(false, |creator| creator == user)
+}
+
 /// Does the event redacting come from a user with enough power to redact the given event.
 fn check_redaction(
-    _room_version: &RoomVersion,
+    room_version: &RoomVersion,
     redaction_event: impl Event,
     user_level: Int,
     redact_level: Int,
-) -> Result<bool> {
+) -> std::result::Result<(), AuthRejection> {
     if user_level >= redact_level {
         info!("redaction allowed via power levels");
-        return Ok(true);
+        return Ok(());
     }
 
     // If the domain of the event_id of the event being redacted is the same as the
     // domain of the event_id of the m.room.redaction, allow
-    if redaction_event.event_id().borrow().server_name()
-        == redaction_event.redacts().as_ref().and_then(|&id| id.borrow().server_name())
-    {
+    let redacts_server_name = if room_version.content_field_redacts {
+        #[derive(Deserialize)]
+        struct RedactsContentField {
+            redacts: Option<OwnedEventId>,
+        }
+
+        from_json_str::<RedactsContentField>(redaction_event.content().get())
+            .ok()
+            .and_then(|content| content.redacts)
+            .and_then(|redacts| redacts.server_name().map(ToOwned::to_owned))
+    } else {
+        redaction_event.redacts().and_then(|id| id.borrow().server_name().map(ToOwned::to_owned))
+    };
+
+    if redaction_event.event_id().borrow().server_name() == redacts_server_name.as_deref() {
         info!("redaction event allowed via room version 1 rules");
-        return Ok(true);
+        return Ok(());
     }
 
-    Ok(false)
+    Err(AuthRejection::InsufficientRedactLevel { level: user_level, required: redact_level })
 }
 
 /// Helper function to fetch the power level needed to send an event of type
@@ -931,53 +1006,62 @@ fn verify_third_party_invite(
     sender: &UserId,
     tp_id: &ThirdPartyInvite,
     current_third_party_invite: Option<impl Event>,
-) -> bool {
+) -> std::result::Result<(), AuthRejection> {
     // 1. Check for user being banned happens before this is called
     // checking for mxid and token keys is done by ruma when deserializing
 
     // The state key must match the invitee
     if target_user != Some(&tp_id.signed.mxid) {
-        return false;
+        return Err(AuthRejection::ThirdPartyInviteUserMismatch {
+            mxid: tp_id.signed.mxid.clone(),
+        });
     }
 
     // If there is no m.room.third_party_invite event in the current room state with state_key
     // matching token, reject
+    let missing_third_party_invite =
+        || AuthRejection::MissingThirdPartyInvite { token: tp_id.signed.token.clone() };
     let current_tpid = match current_third_party_invite {
         Some(id) => id,
-        None => return false,
+        None => return Err(missing_third_party_invite()),
     };
 
     if current_tpid.state_key() != Some(&tp_id.signed.token) {
-        return false;
+        return Err(missing_third_party_invite());
     }
 
     if sender != current_tpid.sender() {
-        return false;
+        return Err(AuthRejection::ThirdPartyInviteSenderMismatch { sender: sender.to_owned() });
     }
 
     // If any signature in signed matches any public key in the m.room.third_party_invite event,
     // allow
+    let invalid_signature =
+        || AuthRejection::InvalidThirdPartyInviteSignature { token: tp_id.signed.token.clone() };
     let tpid_ev =
         match from_json_str::<RoomThirdPartyInviteEventContent>(current_tpid.content().get()) {
             Ok(ev) => ev,
-            Err(_) => return false,
+            Err(_) => return Err(missing_third_party_invite()),
         };
 
     let decoded_invite_token = match Base64::parse(&tp_id.signed.token) {
         Ok(tok) => tok,
-        // FIXME: Log a warning?
-        Err(_) => return false,
+        Err(_) => return Err(invalid_signature()),
     };
 
     // A list of public keys in the public_keys field
     for key in tpid_ev.public_keys.unwrap_or_default() {
         if key.public_key == decoded_invite_token {
-            return true;
+            return Ok(());
         }
     }
 
     // A single public key in the public_key field
-    tpid_ev.public_key == decoded_invite_token
+    if tpid_ev.public_key == decoded_invite_token {
+        Ok(())
+    } else {
+        Err(invalid_signature())
+    }
 }
 
 #[cfg(test)]
@@ -993,17 +1077,85 @@ mod tests {
         },
         StateEventType, TimelineEventType,
     };
-    use serde_json::value::to_raw_value as to_raw_json_value;
+    use serde_json::{json, value::to_raw_value as to_raw_json_value};
 
     use crate::{
-        event_auth::valid_membership_change,
+        event_auth::{auth_check, auth_check_async, valid_membership_change},
         test_utils::{
             alice, charlie, ella, event_id, member_content_ban, member_content_join, room_id,
             to_pdu_event, PduEvent, INITIAL_EVENTS, INITIAL_EVENTS_CREATE_ROOM,
         },
-        Event, EventTypeExt, RoomVersion, StateMap,
+        AuthRejection, Error, Event, EventTypeExt, RoomVersion, StateMap,
     };
 
+    #[test]
+    fn room_create_without_creator() {
+        let create = to_pdu_event::<&str>(
+            "CREATE",
+            alice(),
+            TimelineEventType::RoomCreate,
+            Some(""),
+            to_raw_json_value(&json!({ "room_version": "11" })).unwrap(),
+            &[],
+            &[],
+        );
+
+        auth_check(&RoomVersion::V11, &create, None::<PduEvent>, |_, _| None::<PduEvent>).unwrap();
+
+        let error =
+            auth_check(&RoomVersion::V10, &create, None::<PduEvent>, |_, _| None::<PduEvent>)
+                .unwrap_err();
+        assert!(matches!(error, Error::AuthRejected(AuthRejection::CreateMissingCreator)));
+    }
+
+    #[test]
+    fn room_create_sender_is_creator() {
+        let create = to_pdu_event::<&str>(
+            "CREATE",
+            alice(),
+            TimelineEventType::RoomCreate,
+            Some(""),
+            to_raw_json_value(&json!({ "room_version": "11" })).unwrap(),
+            &[],
+            &[],
+        );
+        let join = to_pdu_event(
+            "IMA",
+            alice(),
+            TimelineEventType::RoomMember,
+            Some(alice().as_str()),
+            member_content_join(),
+            &["CREATE"],
+            &["CREATE"],
+        );
+
+        // The first join of the creator is allowed.
+        let auth_events =
+            StateMap::from_iter([(StateEventType::RoomCreate.with_state_key(""), create.clone())]);
+        let fetch_state =
+            |ty: &StateEventType, key: &str| auth_events.get(&ty.with_state_key(key)).cloned();
+        auth_check(&RoomVersion::V11, &join, None::<PduEvent>, fetch_state).unwrap();
+
+        // Without power levels, the creator can send state events.
+        let topic = to_pdu_event(
+            "T1",
+            alice(),
+            TimelineEventType::RoomTopic,
+            Some(""),
+            to_raw_json_value(&json!({ "topic": "Rust" })).unwrap(),
+            &["CREATE", "IMA"],
+            &["IMA"],
+        );
+        let auth_events = StateMap::from_iter([
+            (StateEventType::RoomCreate.with_state_key(""), create),
+            (StateEventType::RoomMember.with_state_key(alice().as_str()), join),
+        ]);
+        let fetch_state =
+            |ty: &StateEventType, key: &str| auth_events.get(&ty.with_state_key(key)).cloned();
+        auth_check(&RoomVersion::V11, &topic, None::<PduEvent>, fetch_state).unwrap();
+        assert!(auth_check(&RoomVersion::V10, &topic, None::<PduEvent>, fetch_state).is_err());
+    }
+
     #[test]
     fn test_ban_pass() {
         let _ =
@@ -1043,7 +1195,51 @@ mod tests {
             &MembershipState::Leave,
             fetch_state(StateEventType::RoomCreate, "".to_owned()).unwrap(),
         )
-        .unwrap());
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn auth_check_async_fetches_state() {
+        let events = INITIAL_EVENTS();
+
+        let auth_events = events
+            .values()
+            .map(|ev| (ev.event_type().with_state_key(ev.state_key().unwrap()), Arc::clone(ev)))
+            .collect::<StateMap<_>>();
+        let fetch_state = |ty: &StateEventType, key: &str| {
+            let event = auth_events.get(&ty.with_state_key(key)).cloned();
+            async move {
+                tokio::task::yield_now().await;
+                event
+            }
+        };
+
+        let ban_charlie = to_pdu_event(
+            "HELLO",
+            alice(),
+            TimelineEventType::RoomMember,
+            Some(charlie().as_str()),
+            member_content_ban(),
+            &["CREATE", "IMA", "IPOWER"],
+            &["IMC"],
+        );
+        auth_check_async(&RoomVersion::V6, &ban_charlie, None::<PduEvent>, fetch_state)
+            .await
+            .unwrap();
+
+        let ban_alice = to_pdu_event(
+            "HELLO",
+            charlie(),
+            TimelineEventType::RoomMember,
+            Some(alice().as_str()),
+            member_content_ban(),
+            &["CREATE", "IMC", "IPOWER"],
+            &["IMC"],
+        );
+        let error = auth_check_async(&RoomVersion::V6, &ban_alice, None::<PduEvent>, fetch_state)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::AuthRejected(AuthRejection::InsufficientBanLevel { .. })));
     }
 
     #[test]
@@ -1071,7 +1267,7 @@ mod tests {
         let target_user = charlie();
         let sender = charlie();
 
-        assert!(!valid_membership_change(
+        let error = valid_membership_change(
             &RoomVersion::V6,
             target_user,
             fetch_state(StateEventType::RoomMember, target_user.to_string()),
@@ -1085,7 +1281,14 @@ mod tests {
             &MembershipState::Leave,
             fetch_state(StateEventType::RoomCreate, "".to_owned()).unwrap(),
         )
-        .unwrap());
+        .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::AuthRejected(AuthRejection::JoinNotAllowed { join_rule: JoinRule::Invite })
+            ),
+            "{error}"
+        );
     }
 
     #[test]
@@ -1127,7 +1330,7 @@ mod tests {
             &MembershipState::Leave,
             fetch_state(StateEventType::RoomCreate, "".to_owned()).unwrap(),
         )
-        .unwrap());
+        .is_ok());
     }
 
     #[test]
@@ -1155,7 +1358,7 @@ mod tests {
         let target_user = alice();
         let sender = charlie();
 
-        assert!(!valid_membership_change(
+        let error = valid_membership_change(
             &RoomVersion::V6,
             target_user,
             fetch_state(StateEventType::RoomMember, target_user.to_string()),
@@ -1169,7 +1372,11 @@ mod tests {
             &MembershipState::Leave,
             fetch_state(StateEventType::RoomCreate, "".to_owned()).unwrap(),
         )
-        .unwrap());
+        .unwrap_err();
+        assert!(
+            matches!(error, Error::AuthRejected(AuthRejection::InsufficientBanLevel { .. })),
+            "{error}"
+        );
     }
 
     #[test]
@@ -1228,9 +1435,9 @@ mod tests {
             &MembershipState::Join,
             fetch_state(StateEventType::RoomCreate, "".to_owned()).unwrap(),
         )
-        .unwrap());
+        .is_ok());
 
-        assert!(!valid_membership_change(
+        let error = valid_membership_change(
             &RoomVersion::V9,
             target_user,
             fetch_state(StateEventType::RoomMember, target_user.to_string()),
@@ -1244,7 +1451,16 @@ mod tests {
             &MembershipState::Leave,
             fetch_state(StateEventType::RoomCreate, "".to_owned()).unwrap(),
         )
-        .unwrap());
+        .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::AuthRejected(AuthRejection::InvalidJoinAuthorisation {
+                    authorising_user: Some(_)
+                })
+            ),
+            "{error}"
+        );
     }
 
     #[test]
@@ -1295,6 +1511,6 @@ mod tests {
             &MembershipState::Leave,
             fetch_state(StateEventType::RoomCreate, "".to_owned()).unwrap(),
         )
-        .unwrap());
+        .is_ok());
     }
 }
@@ -0,0 +1,102 @@
+use std::future::{ready, Future, Ready};
+
+use async_trait::async_trait;
+use futures_util::{future::BoxFuture, FutureExt};
+use ruma_common::{EventId, OwnedEventId};
+
+use crate::Event;
+
+/// An asynchronous source of events, used by the async variants of the state resolution
+/// functions.
+#[async_trait]
+pub trait EventStore: Sync {
+    /// The type of the events of this store.
+    type Event: Event + Send;
+
+    /// Fetch the event with the given ID.
+    ///
+    /// Returns `None` if the event is unknown.
+    async fn fetch_event(&self, event_id: &EventId) -> Option<Self::Event>;
+
+    /// Fetch the events with the given IDs.
+    ///
+    /// The unknown events are omitted from the returned list, which can be in any order.
+    ///
+    /// The default implementation calls [`EventStore::fetch_event()`] for each event. Stores that
+    /// can load several events at once, like databases, should override it.
+    async fn fetch_events(&self, event_ids: &[&EventId]) -> Vec<Self::Event> {
+        let mut events = Vec::with_capacity(event_ids.len());
+        for event_id in event_ids {
+            events.extend(self.fetch_event(event_id).await);
+        }
+        events
+    }
+}
+
+/// Abstraction over the synchronous and asynchronous ways to fetch events, so that the state
+/// resolution algorithms only need to be implemented once.
+pub(crate) trait FetchEvent<E: Event> {
+    type Future: Future<Output = Option<E>>;
+    type BatchFuture: Future<Output = Vec<E>>;
+
+    fn fetch_event(&self, event_id: &EventId) -> Self::Future;
+
+    fn fetch_events<'i>(
+        &self,
+        event_ids: impl IntoIterator<Item = &'i EventId>,
+    ) -> Self::BatchFuture;
+}
+
+/// Fetch events with a closure, the returned futures are always ready.
+pub(crate) struct SyncFetch<F>(pub(crate) F);
+
+impl<E, F> FetchEvent<E> for SyncFetch<F>
+where
+    E: Event,
+    F: Fn(&EventId) -> Option<E>,
+{
+    type Future = Ready<Option<E>>;
+    type BatchFuture = Ready<Vec<E>>;
+
+    fn fetch_event(&self, event_id: &EventId) -> Self::Future {
+        ready((self.0)(event_id))
+    }
+
+    fn fetch_events<'i>(
+        &self,
+        event_ids: impl IntoIterator<Item = &'i EventId>,
+    ) -> Self::BatchFuture {
+        ready(event_ids.into_iter().filter_map(|event_id| (self.0)(event_id)).collect())
+    }
+}
+
+/// Run a future that only fetches events with a [`SyncFetch`].
+pub(crate) fn run_sync<T>(future: impl Future<Output = T>) -> T {
+    future.now_or_never().expect("futures of synchronous fetches are always ready")
+}
+
+/// Fetch events with an [`EventStore`].
+pub(crate) struct StoreFetch<'a, S>(pub(crate) &'a S);
+
+impl<'a, S: EventStore> FetchEvent<S::Event> for StoreFetch<'a, S> {
+    type Future = BoxFuture<'a, Option<S::Event>>;
+    type BatchFuture = BoxFuture<'a, Vec<S::Event>>;
+
+    fn fetch_event(&self, event_id: &EventId) -> Self::Future {
+        let store = self.0;
+        let event_id = event_id.to_owned();
+        Box::pin(async move { store.fetch_event(&event_id).await })
+    }
+
+    fn fetch_events<'i>(
+        &self,
+        event_ids: impl IntoIterator<Item = &'i EventId>,
+    ) -> Self::BatchFuture {
+        let store = self.0;
+        let event_ids = event_ids.into_iter().map(ToOwned::to_owned).collect::<Vec<OwnedEventId>>();
+        Box::pin(async move {
+            let event_ids = event_ids.iter().map(|event_id| &**event_id).collect::<Vec<_>>();
+            store.fetch_events(&event_ids).await
+        })
+    }
+}
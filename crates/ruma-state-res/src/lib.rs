@@ -2,9 +2,11 @@ use std::{
     borrow::Borrow,
     cmp::Reverse,
     collections::{BinaryHeap, HashMap, HashSet},
+    future::Future,
     hash::Hash,
 };
 
+use futures_util::future::join_all;
 use itertools::Itertools;
 use js_int::{int, Int};
 use ruma_common::{
@@ -17,18 +19,25 @@ use ruma_common::{
 use serde_json::from_str as from_json_str;
 use tracing::{debug, info, trace, warn};
 
+mod auth_chain;
 mod error;
 pub mod event_auth;
+mod event_store;
 mod power_levels;
 pub mod room_version;
 mod state_event;
 #[cfg(test)]
 mod test_utils;
+mod v1;
 
-pub use error::{Error, Result};
-pub use event_auth::{auth_check, auth_types_for_event};
+pub use auth_chain::{auth_chain, AuthChain, AuthChainCache};
+pub use error::{AuthRejection, Error, Result};
+pub use event_auth::{auth_check, auth_check_async, auth_types_for_event};
+pub use event_store::EventStore;
+use event_store::{run_sync, FetchEvent, StoreFetch, SyncFetch};
 use power_levels::PowerLevelsContentFields;
 pub use room_version::RoomVersion;
+use room_version::StateResolutionVersion;
 pub use state_event::Event;
 
 /// A mapping of event type and state_key to some value `T`, usually an `EventId`.
@@ -36,6 +45,9 @@ pub type StateMap<T> = HashMap<(StateEventType, String), T>;
 
 /// Resolve sets of state events as they come in.
 ///
+/// The state resolution algorithm is selected according to the [`RoomVersion::state_res`] of
+/// `room_version`.
+///
 /// Internally `StateResolution` builds a graph and an auth chain to allow for state conflict
 /// resolution.
 ///
@@ -45,7 +57,8 @@ pub type StateMap<T> = HashMap<(StateEventType, String), T>;
 ///   the state of a room.
 ///
 /// * `auth_chain_sets` - The full recursive set of `auth_events` for each event in the
-///   `state_sets`.
+///   `state_sets`, that can be computed with an [`AuthChainCache`]. It is not used by the state
+///   resolution algorithm of room version 1.
 ///
 /// * `fetch_event` - Any event not found in the `event_map` will defer to this closure to find the
 ///   event.
@@ -65,54 +78,150 @@ where
     E::Id: 'a,
     SetIter: Iterator<Item = &'a StateMap<E::Id>> + Clone,
 {
+    resolve_with_rejections(room_version, state_sets, auth_chain_sets, fetch_event)
+        .map(|resolution| resolution.state)
+}
+
+/// Resolve sets of state events, like [`resolve`], with the events fetched asynchronously from
+/// `store`.
+pub async fn resolve_async<'a, E, S, SetIter>(
+    room_version: &RoomVersionId,
+    state_sets: impl IntoIterator<IntoIter = SetIter>,
+    auth_chain_sets: Vec<HashSet<E::Id>>,
+    store: &S,
+) -> Result<StateMap<E::Id>>
+where
+    E: Event + Clone,
+    E::Id: 'a,
+    S: EventStore<Event = E>,
+    SetIter: Iterator<Item = &'a StateMap<E::Id>> + Clone,
+{
+    resolve_with_rejections_async(room_version, state_sets, auth_chain_sets, store)
+        .await
+        .map(|resolution| resolution.state)
+}
+
+/// The result of [`resolve_with_rejections`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Resolution<Id> {
+    /// The resolved state.
+    pub state: StateMap<Id>,
+
+    /// The conflicted events that were dropped from the resolved state because they didn't pass
+    /// the authorization rules, with the reason of their rejection.
+    pub rejected: HashMap<Id, AuthRejection>,
+}
+
+/// Resolve sets of state events, like [`resolve`], and also return the events that were rejected
+/// by the authorization rules during the resolution.
+pub fn resolve_with_rejections<'a, E, SetIter>(
+    room_version: &RoomVersionId,
+    state_sets: impl IntoIterator<IntoIter = SetIter>,
+    auth_chain_sets: Vec<HashSet<E::Id>>,
+    fetch_event: impl Fn(&EventId) -> Option<E>,
+) -> Result<Resolution<E::Id>>
+where
+    E: Event + Clone,
+    E::Id: 'a,
+    SetIter: Iterator<Item = &'a StateMap<E::Id>> + Clone,
+{
+    run_sync(resolve_inner(
+        room_version,
+        state_sets.into_iter(),
+        auth_chain_sets,
+        &SyncFetch(fetch_event),
+    ))
+}
+
+/// Resolve sets of state events, like [`resolve_with_rejections`], with the events fetched
+/// asynchronously from `store`.
+pub async fn resolve_with_rejections_async<'a, E, S, SetIter>(
+    room_version: &RoomVersionId,
+    state_sets: impl IntoIterator<IntoIter = SetIter>,
+    auth_chain_sets: Vec<HashSet<E::Id>>,
+    store: &S,
+) -> Result<Resolution<E::Id>>
+where
+    E: Event + Clone,
+    E::Id: 'a,
+    S: EventStore<Event = E>,
+    SetIter: Iterator<Item = &'a StateMap<E::Id>> + Clone,
+{
+    resolve_inner(room_version, state_sets.into_iter(), auth_chain_sets, &StoreFetch(store)).await
+}
+
+async fn resolve_inner<'a, E, SetIter>(
+    room_version: &RoomVersionId,
+    state_sets: SetIter,
+    auth_chain_sets: Vec<HashSet<E::Id>>,
+    fetch: &impl FetchEvent<E>,
+) -> Result<Resolution<E::Id>>
+where
+    E: Event + Clone,
+    E::Id: 'a,
+    SetIter: Iterator<Item = &'a StateMap<E::Id>> + Clone,
+{
+    let room_version = RoomVersion::new(room_version)?;
+    if let StateResolutionVersion::V1 = room_version.state_res {
+        return v1::resolve(&room_version, state_sets, fetch).await;
+    }
+
     info!("State resolution starting");
 
     // Split non-conflicting and conflicting state
-    let (clean, conflicting) = separate(state_sets.into_iter());
+    let (clean, conflicting) = separate(state_sets);
 
     info!("non conflicting events: {}", clean.len());
     trace!("{clean:?}");
 
     if conflicting.is_empty() {
         info!("no conflicting state found");
-        return Ok(clean);
+        return Ok(Resolution { state: clean, rejected: HashMap::new() });
     }
 
     info!("conflicting events: {}", conflicting.len());
     debug!("{conflicting:?}");
 
-    // `all_conflicted` contains unique items
+    // `full_conflicted_set` contains unique items
+    let full_conflicted_set: HashSet<_> =
+        get_auth_chain_diff(auth_chain_sets).chain(conflicting.into_values().flatten()).collect();
+
+    // Don't honor events we cannot "verify"
     // synapse says `full_set = {eid for eid in full_conflicted_set if eid in event_map}`
-    let all_conflicted: HashSet<_> = get_auth_chain_diff(auth_chain_sets)
-        .chain(conflicting.into_values().flatten())
-        // Don't honor events we cannot "verify"
-        .filter(|id| fetch_event(id.borrow()).is_some())
-        .collect();
+    let future = fetch.fetch_events(full_conflicted_set.iter().map(Borrow::borrow));
+    let conflicted_events = events_by_id(future.await);
 
-    info!("full conflicted set: {}", all_conflicted.len());
-    debug!("{all_conflicted:?}");
+    info!("full conflicted set: {}", conflicted_events.len());
+    debug!("{:?}", conflicted_events.keys().collect::<Vec<_>>());
 
     // We used to check that all events are events from the correct room
     // this is now a check the caller of `resolve` must make.
 
     // Get only the control events with a state_key: "" or ban/kick event (sender != state_key)
-    let control_events = all_conflicted
-        .iter()
-        .filter(|&id| is_power_event_id(id.borrow(), &fetch_event))
-        .cloned()
+    let control_events = conflicted_events
+        .values()
+        .filter(|&event| is_power_event(event))
+        .map(|event| event.event_id().clone())
         .collect::<Vec<_>>();
 
     // Sort the control events based on power_level/clock/event_id and outgoing/incoming edges
     let sorted_control_levels =
-        reverse_topological_power_sort(control_events, &all_conflicted, &fetch_event)?;
+        reverse_topological_power_sort(control_events, &conflicted_events, fetch).await?;
 
     debug!("sorted control events: {}", sorted_control_levels.len());
     trace!("{sorted_control_levels:?}");
 
-    let room_version = RoomVersion::new(room_version)?;
+    let mut rejected = HashMap::new();
     // Sequentially auth check each control event.
-    let resolved_control =
-        iterative_auth_check(&room_version, &sorted_control_levels, clean.clone(), &fetch_event)?;
+    let resolved_control = iterative_auth_check(
+        &room_version,
+        &sorted_control_levels,
+        clean.clone(),
+        &mut rejected,
+        fetch,
+    )
+    .await?;
 
     debug!("resolved control events: {}", resolved_control.len());
     trace!("{resolved_control:?}");
@@ -123,8 +232,8 @@ where
 
     // This removes the control events that passed auth and more importantly those that failed
     // auth
-    let events_to_resolve = all_conflicted
-        .iter()
+    let events_to_resolve = conflicted_events
+        .keys()
         .filter(|&id| !deduped_power_ev.contains(id.borrow()))
         .cloned()
         .collect::<Vec<_>>();
@@ -137,7 +246,8 @@ where
 
     debug!("power event: {power_event:?}");
 
-    let sorted_left_events = mainline_sort(&events_to_resolve, power_event.cloned(), &fetch_event)?;
+    let sorted_left_events =
+        mainline_sort(&events_to_resolve, power_event.cloned(), &conflicted_events, fetch).await?;
 
     trace!("events left, sorted: {sorted_left_events:?}");
 
@@ -145,13 +255,15 @@ where
         &room_version,
         &sorted_left_events,
         resolved_control, // The control events are added to the final resolved state
-        &fetch_event,
-    )?;
+        &mut rejected,
+        fetch,
+    )
+    .await?;
 
     // Add unconflicted state to the resolved state
     // We priorities the unconflicting state
     resolved_state.extend(clean);
-    Ok(resolved_state)
+    Ok(Resolution { state: resolved_state, rejected })
 }
 
 /// Split the events that have no conflicts from those that are conflicting.
@@ -211,37 +323,40 @@ where
 ///
 /// The power level is negative because a higher power level is equated to an earlier (further back
 /// in time) origin server timestamp.
-fn reverse_topological_power_sort<E: Event>(
+async fn reverse_topological_power_sort<E: Event>(
     events_to_sort: Vec<E::Id>,
-    auth_diff: &HashSet<E::Id>,
-    fetch_event: impl Fn(&EventId) -> Option<E>,
+    conflicted_events: &HashMap<E::Id, E>,
+    fetch: &impl FetchEvent<E>,
 ) -> Result<Vec<E::Id>> {
     debug!("reverse topological sort of power events");
 
     let mut graph = HashMap::new();
     for event_id in events_to_sort {
-        add_event_and_auth_chain_to_graph(&mut graph, event_id, auth_diff, &fetch_event);
-
-        // TODO: if these functions are ever made async here
-        // is a good place to yield every once in a while so other
-        // tasks can make progress
+        add_event_and_auth_chain_to_graph(&mut graph, event_id, conflicted_events);
     }
 
+    // The power levels of the senders are found in the auth events of the events in the graph.
+    let auth_event_ids = graph
+        .keys()
+        .filter_map(|event_id| conflicted_events.get(event_id.borrow()))
+        .flat_map(|event| event.auth_events())
+        .map(Borrow::borrow)
+        .collect::<HashSet<&EventId>>();
+    let future = fetch.fetch_events(auth_event_ids);
+    let auth_events = events_by_id(future.await);
+
     // This is used in the `key_fn` passed to the lexico_topo_sort fn
     let mut event_to_pl = HashMap::new();
     for event_id in graph.keys() {
-        let pl = get_power_level_for_sender(event_id.borrow(), &fetch_event)?;
+        let event = conflicted_events.get(event_id.borrow());
+        let pl = get_power_level_for_sender(event_id.borrow(), event, &auth_events)?;
         info!("{event_id} power level {pl}");
 
         event_to_pl.insert(event_id.clone(), pl);
-
-        // TODO: if these functions are ever made async here
-        // is a good place to yield every once in a while so other
-        // tasks can make progress
     }
 
     lexicographical_topological_sort(&graph, |event_id| {
-        let ev = fetch_event(event_id).ok_or_else(|| Error::NotFound("".into()))?;
+        let ev = conflicted_events.get(event_id).ok_or_else(|| Error::NotFound("".into()))?;
         let pl = *event_to_pl.get(event_id).ok_or_else(|| Error::NotFound("".into()))?;
         Ok((pl, ev.origin_server_ts()))
     })
@@ -336,6 +451,34 @@ where
     Ok(sorted)
 }
 
+/// Sorts the event graph like [`lexicographical_topological_sort`], with a `key_fn` that is
+/// asynchronous.
+///
+/// `key_fn` is called concurrently for all the events of the graph before sorting.
+pub async fn lexicographical_topological_sort_async<Id, F, Fut>(
+    graph: &HashMap<Id, HashSet<Id>>,
+    key_fn: F,
+) -> Result<Vec<Id>>
+where
+    F: Fn(&EventId) -> Fut,
+    Fut: Future<Output = Result<(Int, MilliSecondsSinceUnixEpoch)>>,
+    Id: Clone + Eq + Ord + Hash + Borrow<EventId>,
+{
+    let keys = join_all(graph.keys().map(|event_id| key_fn(event_id.borrow()))).await;
+    let keys = graph
+        .keys()
+        .map(Borrow::borrow)
+        .zip(keys)
+        .map(|(event_id, key)| Ok((event_id, key?)))
+        .collect::<Result<HashMap<&EventId, _>>>()?;
+
+    lexicographical_topological_sort(graph, |event_id| {
+        keys.get(event_id)
+            .copied()
+            .ok_or_else(|| Error::NotFound(format!("Failed to find {event_id}")))
+    })
+}
+
 /// Find the power level for the sender of `event_id` or return a default value of zero.
 ///
 /// Do NOT use this any where but topological sort, we find the power level for the eventId
@@ -343,16 +486,16 @@ where
 /// event).
 fn get_power_level_for_sender<E: Event>(
     event_id: &EventId,
-    fetch_event: impl Fn(&EventId) -> Option<E>,
+    event: Option<&E>,
+    auth_events: &HashMap<E::Id, E>,
 ) -> serde_json::Result<Int> {
     info!("fetch event ({event_id}) senders power level");
 
-    let event = fetch_event(event_id);
     let mut pl = None;
 
-    for aid in event.as_ref().map(|pdu| pdu.auth_events()).into_iter().flatten() {
-        if let Some(aev) = fetch_event(aid.borrow()) {
-            if is_type_and_key(&aev, &TimelineEventType::RoomPowerLevels, "") {
+    for aid in event.map(|pdu| pdu.auth_events()).into_iter().flatten() {
+        if let Some(aev) = auth_events.get(aid.borrow()) {
+            if is_type_and_key(aev, &TimelineEventType::RoomPowerLevels, "") {
                 pl = Some(aev);
                 break;
             }
@@ -379,24 +522,38 @@ fn get_power_level_for_sender<E: Event>(
 /// ## Returns
 ///
 /// The `unconflicted_state` combined with the newly auth'ed events. So any event that fails the
-/// `event_auth::auth_check` will be excluded from the returned state map.
+/// `event_auth::auth_check` will be excluded from the returned state map, and added to `rejected`
+/// with the reason of its rejection.
 ///
-/// For each `events_to_check` event we gather the events needed to auth it from the the
-/// `fetch_event` closure and verify each event using the `event_auth::auth_check` function.
-fn iterative_auth_check<E: Event + Clone>(
+/// For each `events_to_check` event we gather the events needed to auth it from `fetch` and verify
+/// each event using the `event_auth::auth_check` function.
+async fn iterative_auth_check<E: Event + Clone>(
     room_version: &RoomVersion,
     events_to_check: &[E::Id],
     unconflicted_state: StateMap<E::Id>,
-    fetch_event: impl Fn(&EventId) -> Option<E>,
+    rejected: &mut HashMap<E::Id, AuthRejection>,
+    fetch: &impl FetchEvent<E>,
 ) -> Result<StateMap<E::Id>> {
     info!("starting iterative auth check");
 
     debug!("performing auth checks on {events_to_check:?}");
 
+    let future = fetch.fetch_events(events_to_check.iter().map(Borrow::borrow));
+    let events = events_by_id(future.await);
+
+    let auth_event_ids = events
+        .values()
+        .flat_map(|event| event.auth_events())
+        .map(Borrow::borrow)
+        .collect::<HashSet<&EventId>>();
+    let future = fetch.fetch_events(auth_event_ids);
+    let events_auth_events = events_by_id(future.await);
+
     let mut resolved_state = unconflicted_state;
 
     for event_id in events_to_check {
-        let event = fetch_event(event_id.borrow())
+        let event = events
+            .get(event_id.borrow())
             .ok_or_else(|| Error::NotFound(format!("Failed to find {event_id}")))?;
         let state_key = event
             .state_key()
@@ -404,31 +561,35 @@ fn iterative_auth_check<E: Event + Clone>(
 
         let mut auth_events = StateMap::new();
         for aid in event.auth_events() {
-            if let Some(ev) = fetch_event(aid.borrow()) {
+            if let Some(ev) = events_auth_events.get(aid.borrow()) {
                 // TODO synapse check "rejected_reason" which is most likely
                 // related to soft-failing
                 auth_events.insert(
                     ev.event_type().with_state_key(ev.state_key().ok_or_else(|| {
                         Error::InvalidPdu("State event had no state key".to_owned())
                     })?),
-                    ev,
+                    ev.clone(),
                 );
             } else {
                 warn!("auth event id for {aid} is missing {event_id}");
             }
         }
 
-        for key in auth_types_for_event(
+        let state_keys = auth_types_for_event(
             event.event_type(),
             event.sender(),
             Some(state_key),
             event.content(),
-        )? {
-            if let Some(ev_id) = resolved_state.get(&key) {
-                if let Some(event) = fetch_event(ev_id.borrow()) {
-                    // TODO synapse checks `rejected_reason` is None here
-                    auth_events.insert(key.to_owned(), event);
-                }
+        )?
+        .into_iter()
+        .filter_map(|key| resolved_state.get(&key).map(|ev_id| (ev_id.clone(), key)))
+        .collect::<HashMap<_, _>>();
+
+        let future = fetch.fetch_events(state_keys.keys().map(Borrow::borrow));
+        for state_event in future.await {
+            if let Some(key) = state_keys.get(state_event.event_id().borrow()) {
+                // TODO synapse checks `rejected_reason` is None here
+                auth_events.insert(key.to_owned(), state_event);
             }
         }
 
@@ -440,19 +601,21 @@ fn iterative_auth_check<E: Event + Clone>(
             (*pdu.event_type() == TimelineEventType::RoomThirdPartyInvite).then_some(pdu)
         });
 
-        if auth_check(room_version, &event, current_third_party, |ty, key| {
+        match auth_check(room_version, event, current_third_party, |ty, key| {
             auth_events.get(&ty.with_state_key(key))
-        })? {
-            // add event to resolved state map
-            resolved_state.insert(event.event_type().with_state_key(state_key), event_id.clone());
-        } else {
-            // synapse passes here on AuthError. We do not add this event to resolved_state.
-            warn!("event {event_id} failed the authentication check");
+        }) {
+            Ok(()) => {
+                // add event to resolved state map
+                resolved_state
+                    .insert(event.event_type().with_state_key(state_key), event_id.clone());
+            }
+            Err(Error::AuthRejected(rejection)) => {
+                // synapse passes here on AuthError. We do not add this event to resolved_state.
+                warn!("event {event_id} failed the authentication check: {rejection}");
+                rejected.insert(event_id.clone(), rejection);
+            }
+            Err(error) => return Err(error),
         }
-
-        // TODO: if these functions are ever made async here
-        // is a good place to yield every once in a while so other
-        // tasks can make progress
     }
     Ok(resolved_state)
 }
@@ -464,10 +627,11 @@ fn iterative_auth_check<E: Event + Clone>(
 /// power_level event. If there have been two power events the after the most recent are depth 0,
 /// the events before (with the first power level as a parent) will be marked as depth 1. depth 1 is
 /// "older" than depth 0.
-fn mainline_sort<E: Event>(
+async fn mainline_sort<E: Event + Clone>(
     to_sort: &[E::Id],
     resolved_power_level: Option<E::Id>,
-    fetch_event: impl Fn(&EventId) -> Option<E>,
+    conflicted_events: &HashMap<E::Id, E>,
+    fetch: &impl FetchEvent<E>,
 ) -> Result<Vec<E::Id>> {
     debug!("mainline sort of events");
 
@@ -481,20 +645,23 @@ fn mainline_sort<E: Event>(
     while let Some(p) = pl {
         mainline.push(p.clone());
 
-        let event = fetch_event(p.borrow())
+        let event = fetch
+            .fetch_event(p.borrow())
+            .await
             .ok_or_else(|| Error::NotFound(format!("Failed to find {p}")))?;
         pl = None;
+
+        let future = fetch.fetch_events(event.auth_events().map(Borrow::borrow));
+        let auth_events = events_by_id(future.await);
         for aid in event.auth_events() {
-            let ev = fetch_event(aid.borrow())
+            let ev = auth_events
+                .get(aid.borrow())
                 .ok_or_else(|| Error::NotFound(format!("Failed to find {aid}")))?;
-            if is_type_and_key(&ev, &TimelineEventType::RoomPowerLevels, "") {
+            if is_type_and_key(ev, &TimelineEventType::RoomPowerLevels, "") {
                 pl = Some(aid.to_owned());
                 break;
             }
         }
-        // TODO: if these functions are ever made async here
-        // is a good place to yield every once in a while so other
-        // tasks can make progress
     }
 
     let mainline_map = mainline
@@ -504,36 +671,27 @@ fn mainline_sort<E: Event>(
         .map(|(idx, eid)| ((*eid).clone(), idx))
         .collect::<HashMap<_, _>>();
 
-    let mut order_map = HashMap::new();
+    let mut order = Vec::new();
     for ev_id in to_sort.iter() {
-        if let Some(event) = fetch_event(ev_id.borrow()) {
-            if let Ok(depth) = get_mainline_depth(Some(event), &mainline_map, &fetch_event) {
-                order_map.insert(
-                    ev_id,
-                    (depth, fetch_event(ev_id.borrow()).map(|ev| ev.origin_server_ts()), ev_id),
-                );
+        if let Some(event) = conflicted_events.get(ev_id.borrow()) {
+            if let Ok(depth) = get_mainline_depth(Some(event.clone()), &mainline_map, fetch).await {
+                order.push((depth, event.origin_server_ts(), ev_id.clone()));
             }
         }
-
-        // TODO: if these functions are ever made async here
-        // is a good place to yield every once in a while so other
-        // tasks can make progress
     }
 
     // Sort the event_ids by their depth, timestamp and EventId
-    // unwrap is OK order map and sort_event_ids are from to_sort (the same Vec)
-    let mut sort_event_ids = order_map.keys().map(|&k| k.clone()).collect::<Vec<_>>();
-    sort_event_ids.sort_by_key(|sort_id| order_map.get(sort_id).unwrap());
+    order.sort();
 
-    Ok(sort_event_ids)
+    Ok(order.into_iter().map(|(_, _, ev_id)| ev_id).collect())
 }
 
 /// Get the mainline depth from the `mainline_map` or finds a power_level event that has an
 /// associated mainline depth.
-fn get_mainline_depth<E: Event>(
+async fn get_mainline_depth<E: Event>(
     mut event: Option<E>,
     mainline_map: &HashMap<E::Id, usize>,
-    fetch_event: impl Fn(&EventId) -> Option<E>,
+    fetch: &impl FetchEvent<E>,
 ) -> Result<usize> {
     while let Some(sort_ev) = event {
         debug!("mainline event_id {}", sort_ev.event_id());
@@ -543,8 +701,12 @@ fn get_mainline_depth<E: Event>(
         }
 
         event = None;
+
+        let future = fetch.fetch_events(sort_ev.auth_events().map(Borrow::borrow));
+        let mut auth_events = events_by_id(future.await);
         for aid in sort_ev.auth_events() {
-            let aev = fetch_event(aid.borrow())
+            let aev = auth_events
+                .remove(aid.borrow())
                 .ok_or_else(|| Error::NotFound(format!("Failed to find {aid}")))?;
             if is_type_and_key(&aev, &TimelineEventType::RoomPowerLevels, "") {
                 event = Some(aev);
@@ -559,17 +721,16 @@ fn get_mainline_depth<E: Event>(
 fn add_event_and_auth_chain_to_graph<E: Event>(
     graph: &mut HashMap<E::Id, HashSet<E::Id>>,
     event_id: E::Id,
-    auth_diff: &HashSet<E::Id>,
-    fetch_event: impl Fn(&EventId) -> Option<E>,
+    conflicted_events: &HashMap<E::Id, E>,
 ) {
     let mut state = vec![event_id];
     while let Some(eid) = state.pop() {
         graph.entry(eid.clone()).or_default();
         // Prefer the store to event as the store filters dedups the events
         for aid in
-            fetch_event(eid.borrow()).as_ref().map(|ev| ev.auth_events()).into_iter().flatten()
+            conflicted_events.get(eid.borrow()).map(|ev| ev.auth_events()).into_iter().flatten()
         {
-            if auth_diff.contains(aid.borrow()) {
+            if conflicted_events.contains_key(aid.borrow()) {
                 if !graph.contains_key(aid.borrow()) {
                     state.push(aid.to_owned());
                 }
@@ -581,11 +742,9 @@ fn add_event_and_auth_chain_to_graph<E: Event>(
     }
 }
 
-fn is_power_event_id<E: Event>(event_id: &EventId, fetch: impl Fn(&EventId) -> Option<E>) -> bool {
-    match fetch(event_id).as_ref() {
-        Some(state) => is_power_event(state),
-        _ => false,
-    }
+/// Index the given events by their ID.
+fn events_by_id<E: Event>(events: Vec<E>) -> HashMap<E::Id, E> {
+    events.into_iter().map(|event| (event.event_id().clone(), event)).collect()
 }
 
 fn is_type_and_key(ev: impl Event, ev_type: &TimelineEventType, state_key: &str) -> bool {
@@ -639,8 +798,8 @@ where
 #[cfg(test)]
 mod tests {
     use std::{
-        collections::{HashMap, HashSet},
-        sync::Arc,
+        collections::HashMap,
+        sync::{atomic::Ordering, Arc},
     };
 
     use js_int::{int, uint};
@@ -651,19 +810,21 @@ mod tests {
             room::join_rules::{JoinRule, RoomJoinRulesEventContent},
             StateEventType, TimelineEventType,
         },
-        MilliSecondsSinceUnixEpoch, OwnedEventId, RoomVersionId,
+        EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, RoomVersionId,
     };
     use serde_json::{json, value::to_raw_value as to_raw_json_value};
     use tracing::debug;
 
     use crate::{
+        event_store::{run_sync, SyncFetch},
         is_power_event,
         room_version::RoomVersion,
         test_utils::{
             alice, bob, charlie, do_check, ella, event_id, member_content_ban, member_content_join,
-            room_id, to_init_pdu_event, to_pdu_event, zara, PduEvent, TestStore, INITIAL_EVENTS,
+            room_id, to_init_pdu_event, to_pdu_event, zara, PduEvent, TestEventStore, TestStore,
+            INITIAL_EVENTS,
         },
-        Event, EventTypeExt, StateMap,
+        AuthRejection, Event, EventTypeExt, StateMap,
     };
 
     fn test_event_sort() {
@@ -676,26 +837,28 @@ mod tests {
             .map(|ev| (ev.event_type().with_state_key(ev.state_key().unwrap()), ev.clone()))
             .collect::<StateMap<_>>();
 
-        let auth_chain: HashSet<OwnedEventId> = HashSet::new();
+        let fetch = SyncFetch(|id: &EventId| events.get(id).map(Arc::clone));
 
         let power_events = event_map
             .values()
             .filter(|&pdu| is_power_event(&**pdu))
-            .map(|pdu| pdu.event_id.clone())
-            .collect::<Vec<_>>();
-
-        let sorted_power_events =
-            crate::reverse_topological_power_sort(power_events, &auth_chain, |id| {
-                events.get(id).map(Arc::clone)
-            })
-            .unwrap();
+            .map(|pdu| (pdu.event_id.clone(), pdu.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let sorted_power_events = run_sync(crate::reverse_topological_power_sort(
+            power_events.keys().cloned().collect(),
+            &power_events,
+            &fetch,
+        ))
+        .unwrap();
 
-        let resolved_power = crate::iterative_auth_check(
+        let resolved_power = run_sync(crate::iterative_auth_check(
             &RoomVersion::V6,
             &sorted_power_events,
             HashMap::new(), // unconflicted events
-            |id| events.get(id).map(Arc::clone),
-        )
+            &mut HashMap::new(),
+            &fetch,
+        ))
         .expect("iterative auth check failed on resolved events");
 
         // don't remove any events so we know it sorts them all correctly
@@ -707,8 +870,7 @@ mod tests {
             resolved_power.get(&(StateEventType::RoomPowerLevels, "".to_owned())).cloned();
 
         let sorted_event_ids =
-            crate::mainline_sort(&events_to_sort, power_level, |id| events.get(id).map(Arc::clone))
-                .unwrap();
+            run_sync(crate::mainline_sort(&events_to_sort, power_level, &events, &fetch)).unwrap();
 
         assert_eq!(
             vec![
@@ -778,7 +940,7 @@ mod tests {
         let expected_state_ids =
             vec!["PA", "MA", "MB"].into_iter().map(event_id).collect::<Vec<_>>();
 
-        do_check(events, edges, expected_state_ids);
+        do_check(&RoomVersionId::V6, events, edges, expected_state_ids);
     }
 
     #[test]
@@ -839,7 +1001,7 @@ mod tests {
 
         let expected_state_ids = vec!["PA2", "T2"].into_iter().map(event_id).collect::<Vec<_>>();
 
-        do_check(events, edges, expected_state_ids);
+        do_check(&RoomVersionId::V6, events, edges, expected_state_ids);
     }
 
     #[test]
@@ -886,7 +1048,7 @@ mod tests {
         let expected_state_ids =
             vec!["T1", "MB", "PA"].into_iter().map(event_id).collect::<Vec<_>>();
 
-        do_check(events, edges, expected_state_ids);
+        do_check(&RoomVersionId::V6, events, edges, expected_state_ids);
     }
 
     #[test]
@@ -918,7 +1080,7 @@ mod tests {
 
         let expected_state_ids = vec![event_id("JR")];
 
-        do_check(events, edges, expected_state_ids);
+        do_check(&RoomVersionId::V6, events, edges, expected_state_ids);
     }
 
     #[test]
@@ -959,7 +1121,7 @@ mod tests {
 
         let expected_state_ids = vec!["PC"].into_iter().map(event_id).collect::<Vec<_>>();
 
-        do_check(events, edges, expected_state_ids);
+        do_check(&RoomVersionId::V6, events, edges, expected_state_ids);
     }
 
     #[test]
@@ -1036,7 +1198,7 @@ mod tests {
 
         let expected_state_ids = vec!["T4", "PA2"].into_iter().map(event_id).collect::<Vec<_>>();
 
-        do_check(events, edges, expected_state_ids);
+        do_check(&RoomVersionId::V6, events, edges, expected_state_ids);
     }
 
     #[test]
@@ -1109,7 +1271,12 @@ mod tests {
 
         let expected_state_ids = vec!["PA", "MB"].into_iter().map(event_id).collect::<Vec<_>>();
 
-        do_check(&ban.values().cloned().collect::<Vec<_>>(), edges, expected_state_ids);
+        do_check(
+            &RoomVersionId::V6,
+            &ban.values().cloned().collect::<Vec<_>>(),
+            edges,
+            expected_state_ids,
+        );
     }
 
     #[test]
@@ -1191,6 +1358,101 @@ mod tests {
         assert_eq!(expected.len(), resolved.len());
     }
 
+    #[tokio::test]
+    async fn resolve_async_matches_resolve() {
+        let mut inner = INITIAL_EVENTS();
+        inner.extend(BAN_STATE_SET());
+        let store = TestStore(inner.clone());
+
+        let state_sets = [
+            ["CREATE", "IJR", "IMA", "IMB", "IMC", "MB", "PA"],
+            ["CREATE", "IJR", "IMA", "IMB", "IMC", "IME", "PA"],
+        ]
+        .map(|ids| {
+            ids.into_iter()
+                .map(|id| {
+                    let ev = &inner[&event_id(id)];
+                    (ev.event_type().with_state_key(ev.state_key().unwrap()), ev.event_id.clone())
+                })
+                .collect::<StateMap<_>>()
+        });
+        let auth_chain_sets = || {
+            state_sets
+                .iter()
+                .map(|map| {
+                    store.auth_event_ids(room_id(), map.values().cloned().collect()).unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let resolved = crate::resolve(&RoomVersionId::V6, &state_sets, auth_chain_sets(), |id| {
+            inner.get(id).map(Arc::clone)
+        })
+        .unwrap();
+
+        let event_store = TestEventStore::new(inner.clone());
+        let resolved_async =
+            crate::resolve_async(&RoomVersionId::V6, &state_sets, auth_chain_sets(), &event_store)
+                .await
+                .unwrap();
+
+        assert_eq!(resolved_async, resolved);
+        assert!(event_store.batches.load(Ordering::SeqCst) > 0);
+    }
+
+    #[test]
+    fn rejected_events_are_recorded() {
+        let _ =
+            tracing::subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+        let mut events = INITIAL_EVENTS();
+        // Charlie doesn't have the power level to change the topic.
+        let topic = to_pdu_event(
+            "T1",
+            charlie(),
+            TimelineEventType::RoomTopic,
+            Some(""),
+            to_raw_json_value(&json!({ "topic": "T1" })).unwrap(),
+            &["CREATE", "IMC", "IPOWER"],
+            &["IMC"],
+        );
+        events.insert(topic.event_id.clone(), topic);
+        let store = TestStore(events.clone());
+
+        let state_set_a = events
+            .values()
+            .map(|ev| {
+                (ev.event_type().with_state_key(ev.state_key().unwrap()), ev.event_id.clone())
+            })
+            .collect::<StateMap<_>>();
+        let mut state_set_b = state_set_a.clone();
+        state_set_b.remove(&(StateEventType::RoomTopic, "".to_owned()));
+
+        let state_sets = [state_set_a, state_set_b];
+        let resolution = crate::resolve_with_rejections(
+            &RoomVersionId::V6,
+            &state_sets,
+            state_sets
+                .iter()
+                .map(|map| {
+                    store.auth_event_ids(room_id(), map.values().cloned().collect()).unwrap()
+                })
+                .collect(),
+            |id| events.get(id).map(Arc::clone),
+        )
+        .unwrap();
+
+        assert!(!resolution.state.contains_key(&(StateEventType::RoomTopic, "".to_owned())));
+        assert_eq!(resolution.rejected.len(), 1);
+        assert_eq!(
+            resolution.rejected[&event_id("T1")],
+            AuthRejection::InsufficientPowerLevel {
+                event_type: TimelineEventType::RoomTopic,
+                level: int!(0),
+                required: int!(50),
+            }
+        );
+    }
+
     #[test]
     fn join_rule_with_auth_chain() {
         let join_rule = JOIN_RULE();
@@ -1202,7 +1464,12 @@ mod tests {
 
         let expected_state_ids = vec!["JR"].into_iter().map(event_id).collect::<Vec<_>>();
 
-        do_check(&join_rule.values().cloned().collect::<Vec<_>>(), edges, expected_state_ids);
+        do_check(
+            &RoomVersionId::V6,
+            &join_rule.values().cloned().collect::<Vec<_>>(),
+            edges,
+            expected_state_ids,
+        );
     }
 
     #[allow(non_snake_case)]
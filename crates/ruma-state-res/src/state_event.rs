@@ -5,6 +5,7 @@ use std::{
     sync::Arc,
 };
 
+use js_int::UInt;
 use ruma_common::{events::TimelineEventType, EventId, MilliSecondsSinceUnixEpoch, RoomId, UserId};
 use serde_json::value::RawValue as RawJsonValue;
 
@@ -33,6 +34,9 @@ pub trait Event {
     /// The state key for this event.
     fn state_key(&self) -> Option<&str>;
 
+    /// The depth of this event in the room's event graph.
+    fn depth(&self) -> UInt;
+
     /// The events before this event.
     // Requires GATs to avoid boxing (and TAIT for making it convenient).
     fn prev_events(&self) -> Box<dyn DoubleEndedIterator<Item = &Self::Id> + '_>;
@@ -76,6 +80,10 @@ impl<T: Event> Event for &T {
         (*self).state_key()
     }
 
+    fn depth(&self) -> UInt {
+        (*self).depth()
+    }
+
     fn prev_events(&self) -> Box<dyn DoubleEndedIterator<Item = &Self::Id> + '_> {
         (*self).prev_events()
     }
@@ -120,6 +128,10 @@ impl<T: Event> Event for Arc<T> {
         (**self).state_key()
     }
 
+    fn depth(&self) -> UInt {
+        (**self).depth()
+    }
+
     fn prev_events(&self) -> Box<dyn DoubleEndedIterator<Item = &Self::Id> + '_> {
         (**self).prev_events()
     }
@@ -7,6 +7,7 @@ use std::{
     },
 };
 
+use async_trait::async_trait;
 use js_int::{int, uint};
 use ruma_common::{
     event_id,
@@ -27,13 +28,16 @@ use serde_json::{
 };
 use tracing::info;
 
-use crate::{auth_types_for_event, Error, Event, EventTypeExt, Result, StateMap};
+use crate::{
+    auth_types_for_event, AuthChainCache, Error, Event, EventStore, EventTypeExt, Result, StateMap,
+};
 
 pub use event::PduEvent;
 
 static SERVER_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
 
 pub fn do_check(
+    room_version: &RoomVersionId,
     events: &[Arc<PduEvent>],
     edges: Vec<Vec<OwnedEventId>>,
     expected_state_ids: Vec<OwnedEventId>,
@@ -80,6 +84,7 @@ pub fn do_check(
     let mut event_map: HashMap<OwnedEventId, Arc<PduEvent>> = HashMap::new();
     // event_id -> StateMap<OwnedEventId>
     let mut state_at_event: HashMap<OwnedEventId, StateMap<OwnedEventId>> = HashMap::new();
+    let mut auth_chain_cache = AuthChainCache::new();
 
     // Resolve the current state and add it to the state_at_event map then continue
     // on in "time"
@@ -115,11 +120,13 @@ pub fn do_check(
             let auth_chain_sets = state_sets
                 .iter()
                 .map(|map| {
-                    store.auth_event_ids(room_id(), map.values().cloned().collect()).unwrap()
+                    auth_chain_cache
+                        .auth_chain(map.values().cloned(), |id| store.0.get(id).map(Arc::clone))
+                        .event_ids
                 })
                 .collect();
 
-            let resolved = crate::resolve(&RoomVersionId::V6, state_sets, auth_chain_sets, |id| {
+            let resolved = crate::resolve(room_version, state_sets, auth_chain_sets, |id| {
                 event_map.get(id).map(Arc::clone)
             });
             match resolved {
@@ -153,7 +160,7 @@ pub fn do_check(
         // the `to_pdu_event` was split into `init` and the fn below, could be better
         let e = fake_event;
         let ev_id = e.event_id();
-        let event = to_pdu_event(
+        let mut event = to_pdu_event(
             e.event_id().as_str(),
             e.sender(),
             e.event_type().clone(),
@@ -162,6 +169,11 @@ pub fn do_check(
             &auth_events,
             &prev_events.iter().cloned().collect::<Vec<_>>(),
         );
+        // The depth is used by the state resolution algorithm of room version 1.
+        let depth = prev_events.iter().map(|id| event_map[id].depth() + uint!(1)).max();
+        if let Pdu::RoomV3Pdu(pdu) = &mut Arc::make_mut(&mut event).rest {
+            pdu.depth = depth.unwrap_or_default();
+        }
 
         // We have to update our store, an actual user of this lib would
         // be giving us state from a DB.
@@ -242,6 +254,36 @@ impl<E: Event> TestStore<E> {
     }
 }
 
+/// An `EventStore` that yields to the runtime before returning events.
+#[allow(clippy::exhaustive_structs)]
+pub struct TestEventStore {
+    pub events: HashMap<OwnedEventId, Arc<PduEvent>>,
+    /// The number of calls to `fetch_events`.
+    pub batches: AtomicU64,
+}
+
+impl TestEventStore {
+    pub fn new(events: HashMap<OwnedEventId, Arc<PduEvent>>) -> Self {
+        Self { events, batches: AtomicU64::new(0) }
+    }
+}
+
+#[async_trait]
+impl EventStore for TestEventStore {
+    type Event = Arc<PduEvent>;
+
+    async fn fetch_event(&self, event_id: &EventId) -> Option<Self::Event> {
+        tokio::task::yield_now().await;
+        self.events.get(event_id).cloned()
+    }
+
+    async fn fetch_events(&self, event_ids: &[&EventId]) -> Vec<Self::Event> {
+        tokio::task::yield_now().await;
+        self.batches.fetch_add(1, SeqCst);
+        event_ids.iter().filter_map(|&event_id| self.events.get(event_id).cloned()).collect()
+    }
+}
+
 // A StateStore implementation for testing
 #[allow(clippy::type_complexity)]
 impl TestStore<PduEvent> {
@@ -548,6 +590,7 @@ pub fn INITIAL_EDGES() -> Vec<OwnedEventId> {
 }
 
 pub mod event {
+    use js_int::UInt;
     use ruma_common::{
         events::{pdu::Pdu, TimelineEventType},
         MilliSecondsSinceUnixEpoch, OwnedEventId, RoomId, UserId,
@@ -618,6 +661,15 @@ pub mod event {
             }
         }
 
+        fn depth(&self) -> UInt {
+            match &self.rest {
+                Pdu::RoomV1Pdu(ev) => ev.depth,
+                Pdu::RoomV3Pdu(ev) => ev.depth,
+                #[allow(unreachable_patterns)]
+                _ => unreachable!("new PDU version"),
+            }
+        }
+
         fn prev_events(&self) -> Box<dyn DoubleEndedIterator<Item = &Self::Id> + '_> {
             match &self.rest {
                 Pdu::RoomV1Pdu(ev) => Box::new(ev.prev_events.iter().map(|(id, _)| id)),
@@ -0,0 +1,524 @@
+//! The original state resolution algorithm, used by room version 1.
+//!
+//! See the [specification](https://spec.matrix.org/latest/rooms/v1/#state-resolution) for more
+//! details.
+
+use std::{
+    borrow::Borrow,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+use ruma_common::{
+    events::{StateEventType, TimelineEventType},
+    EventId,
+};
+use sha1::{Digest, Sha1};
+use tracing::{debug, info, trace, warn};
+
+use crate::{
+    auth_check, auth_types_for_event, event_store::FetchEvent, AuthRejection, Error, Event,
+    EventTypeExt, Resolution, Result, RoomVersion, StateMap,
+};
+
+/// Resolve sets of state events with the state resolution algorithm of room version 1.
+///
+/// Contrary to the newer algorithm, only the conflicted events themselves are considered, and the
+/// events used to authorize them are taken from the unconflicted state.
+pub(crate) async fn resolve<'a, E, SetIter>(
+    room_version: &RoomVersion,
+    state_sets: SetIter,
+    fetch: &impl FetchEvent<E>,
+) -> Result<Resolution<E::Id>>
+where
+    E: Event + Clone,
+    E::Id: 'a,
+    SetIter: Iterator<Item = &'a StateMap<E::Id>>,
+{
+    info!("State resolution v1 starting");
+
+    let (mut unconflicted, conflicted_ids) = separate(state_sets);
+
+    info!("non conflicting events: {}", unconflicted.len());
+    trace!("{unconflicted:?}");
+
+    if conflicted_ids.is_empty() {
+        info!("no conflicting state found");
+        return Ok(Resolution { state: unconflicted, rejected: HashMap::new() });
+    }
+
+    info!("conflicting events: {}", conflicted_ids.len());
+    debug!("{conflicted_ids:?}");
+
+    // Don't honor events we cannot "verify"
+    let future = fetch.fetch_events(conflicted_ids.values().flatten().map(Borrow::borrow));
+    let mut events = future
+        .await
+        .into_iter()
+        .map(|event| (event.event_id().clone(), event))
+        .collect::<HashMap<_, _>>();
+    let conflicted_events = conflicted_ids
+        .into_iter()
+        .map(|(key, ids)| {
+            (key, ids.iter().filter_map(|id| events.remove(id.borrow())).collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+
+    // The events used to authorize the conflicted events are only picked from the unconflicted
+    // state.
+    let mut auth_event_keys = HashMap::new();
+    for event in conflicted_events.iter().flat_map(|(_, events)| events) {
+        for key in auth_types_for_event(
+            event.event_type(),
+            event.sender(),
+            event.state_key(),
+            event.content(),
+        )? {
+            if let Some(id) = unconflicted.get(&key) {
+                auth_event_keys.insert(id.clone(), key);
+            }
+        }
+    }
+
+    let future = fetch.fetch_events(auth_event_keys.keys().map(Borrow::borrow));
+    let mut auth_events = StateMap::new();
+    for auth_event in future.await {
+        if let Some(key) = auth_event_keys.remove(auth_event.event_id().borrow()) {
+            auth_events.insert(key, auth_event);
+        }
+    }
+
+    let mut conflicted = StateMap::new();
+    for (key, mut events) in conflicted_events {
+        match events.len() {
+            0 => {}
+            // There is no conflict anymore if we only know one of the events.
+            1 => {
+                let event = events.pop().expect("events has one element");
+                unconflicted.insert(key, event.event_id().clone());
+            }
+            _ => {
+                conflicted.insert(key, events);
+            }
+        }
+    }
+
+    let mut rejected = HashMap::new();
+    let resolved = resolve_conflicted(room_version, &conflicted, auth_events, &mut rejected)?;
+
+    debug!("resolved conflicted events: {}", resolved.len());
+
+    let mut resolved_state = unconflicted;
+    resolved_state.extend(resolved.into_iter().map(|(key, event)| (key, event.event_id().clone())));
+    Ok(Resolution { state: resolved_state, rejected })
+}
+
+/// Split the events that have no conflicts from those that are conflicting.
+///
+/// The return tuple looks like `(unconflicted, conflicted)`.
+///
+/// Unlike the newer algorithm, state is only conflicting if there are different event IDs for the
+/// same (StateEventType, StateKey). A key that is missing from some of the state sets is not
+/// conflicting.
+fn separate<'a, Id>(
+    mut state_sets: impl Iterator<Item = &'a StateMap<Id>>,
+) -> (StateMap<Id>, StateMap<Vec<Id>>)
+where
+    Id: Clone + Eq + 'a,
+{
+    let mut unconflicted_state = state_sets.next().cloned().unwrap_or_default();
+    let mut conflicted_state: StateMap<Vec<Id>> = StateMap::new();
+
+    for state_set in state_sets {
+        for (key, id) in state_set {
+            if let Some(ids) = conflicted_state.get_mut(key) {
+                if !ids.contains(id) {
+                    ids.push(id.clone());
+                }
+                continue;
+            }
+
+            match unconflicted_state.get(key) {
+                None => {
+                    unconflicted_state.insert(key.clone(), id.clone());
+                }
+                Some(existing) if existing == id => {}
+                Some(_) => {
+                    let existing = unconflicted_state.remove(key).expect("key is in the map");
+                    conflicted_state.insert(key.clone(), vec![existing, id.clone()]);
+                }
+            }
+        }
+    }
+
+    (unconflicted_state, conflicted_state)
+}
+
+/// Pick an event for each conflicted (StateEventType, StateKey).
+///
+/// The conflicts are resolved in the following order, the resolved events of each step being used
+/// to authorize the events of the next steps:
+///
+/// 1. The power levels.
+/// 2. The join rules.
+/// 3. The memberships.
+/// 4. Any other event.
+fn resolve_conflicted<E: Event + Clone>(
+    room_version: &RoomVersion,
+    conflicted: &StateMap<Vec<E>>,
+    mut auth_events: StateMap<E>,
+    rejected: &mut HashMap<E::Id, AuthRejection>,
+) -> Result<StateMap<E>> {
+    let mut resolved_state = StateMap::new();
+
+    let power_levels_key = (StateEventType::RoomPowerLevels, "".to_owned());
+    if let Some(events) = conflicted.get(&power_levels_key) {
+        let event = resolve_auth_events(room_version, events, &auth_events, rejected)?;
+        resolved_state.insert(power_levels_key, event);
+    }
+
+    auth_events.extend(resolved_state.clone());
+
+    for event_type in [StateEventType::RoomJoinRules, StateEventType::RoomMember] {
+        let mut resolved = StateMap::new();
+        for (key, events) in conflicted.iter().filter(|((ty, _), _)| *ty == event_type) {
+            let event = resolve_auth_events(room_version, events, &auth_events, rejected)?;
+            resolved.insert(key.clone(), event);
+        }
+
+        auth_events.extend(resolved.clone());
+        resolved_state.extend(resolved);
+    }
+
+    for (key, events) in conflicted {
+        if !resolved_state.contains_key(key) {
+            let event = resolve_normal_events(room_version, events, &auth_events, rejected)?;
+            resolved_state.insert(key.clone(), event);
+        }
+    }
+
+    Ok(resolved_state)
+}
+
+/// Resolve a conflict between events that are used in the authorization rules.
+///
+/// Starting with the oldest event, each event is authorized against the previous one. The last
+/// event that passes the authorization rules before one fails wins.
+fn resolve_auth_events<E: Event + Clone>(
+    room_version: &RoomVersion,
+    events: &[E],
+    auth_events: &StateMap<E>,
+    rejected: &mut HashMap<E::Id, AuthRejection>,
+) -> Result<E> {
+    let mut auth_keys = HashSet::new();
+    for event in events {
+        auth_keys.extend(auth_types_for_event(
+            event.event_type(),
+            event.sender(),
+            event.state_key(),
+            event.content(),
+        )?);
+    }
+
+    let mut auth_events = auth_keys
+        .into_iter()
+        .filter_map(|key| auth_events.get(&key).map(|event| (key, event.clone())))
+        .collect::<StateMap<_>>();
+
+    let mut events = ordered_events(events).into_iter().rev();
+    let mut prev_event = events.next().expect("conflicted state contains at least two events");
+
+    for event in events {
+        let state_key = prev_event
+            .state_key()
+            .ok_or_else(|| Error::InvalidPdu("State event had no state key".to_owned()))?;
+        auth_events.insert(prev_event.event_type().with_state_key(state_key), prev_event.clone());
+
+        match check_event(room_version, &event, &auth_events) {
+            Ok(()) => prev_event = event,
+            Err(Error::AuthRejected(rejection)) => {
+                warn!("event {} failed the authentication check: {rejection}", event.event_id());
+                rejected.insert(event.event_id().clone(), rejection);
+                break;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(prev_event)
+}
+
+/// Resolve a conflict between events that are not used in the authorization rules.
+///
+/// The most recent event that passes the authorization rules wins. If none of them pass, the
+/// oldest event is used.
+fn resolve_normal_events<E: Event + Clone>(
+    room_version: &RoomVersion,
+    events: &[E],
+    auth_events: &StateMap<E>,
+    rejected: &mut HashMap<E::Id, AuthRejection>,
+) -> Result<E> {
+    let mut events = ordered_events(events);
+    let mut rejections = Vec::new();
+
+    for event in &events {
+        match check_event(room_version, event, auth_events) {
+            Ok(()) => {
+                rejected.extend(rejections);
+                return Ok(event.clone());
+            }
+            Err(Error::AuthRejected(rejection)) => {
+                warn!("event {} failed the authentication check: {rejection}", event.event_id());
+                rejections.push((event.event_id().clone(), rejection));
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    // The oldest event is kept in the state even though it failed the authorization rules.
+    rejections.pop();
+    rejected.extend(rejections);
+    Ok(events.pop().expect("conflicted state contains at least two events"))
+}
+
+/// Check `event` against the authorization rules with the given `auth_events`.
+fn check_event<E: Event>(
+    room_version: &RoomVersion,
+    event: &E,
+    auth_events: &StateMap<E>,
+) -> Result<()> {
+    let current_third_party_invite = auth_events
+        .values()
+        .find(|event| *event.event_type() == TimelineEventType::RoomThirdPartyInvite);
+
+    auth_check(room_version, event, current_third_party_invite, |ty, key| {
+        auth_events.get(&ty.with_state_key(key))
+    })
+}
+
+/// Sort the events from the most recent to the oldest.
+///
+/// They are compared using their depth, and in case of a tie with the SHA-1 hash of their event
+/// ID.
+fn ordered_events<E: Event + Clone>(events: &[E]) -> Vec<E> {
+    let mut events = events.to_vec();
+    events.sort_by_cached_key(|event| {
+        let event_id: &EventId = event.event_id().borrow();
+        // Comparing the bytes gives the same order as comparing the hexadecimal representation.
+        (Reverse(event.depth()), Sha1::digest(event_id.as_bytes()))
+    });
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use maplit::hashmap;
+    use ruma_common::{
+        events::{
+            room::join_rules::{JoinRule, RoomJoinRulesEventContent},
+            StateEventType, TimelineEventType,
+        },
+        RoomVersionId,
+    };
+    use serde_json::{json, value::to_raw_value as to_raw_json_value};
+
+    use super::separate;
+    use crate::{
+        test_utils::{
+            alice, bob, charlie, do_check, ella, event_id, member_content_join, to_init_pdu_event,
+            PduEvent, TestStore,
+        },
+        StateMap,
+    };
+
+    #[test]
+    fn separate_keeps_missing_keys_unconflicted() {
+        let state_a: StateMap<_> = hashmap! {
+            (StateEventType::RoomCreate, "".to_owned()) => event_id("CREATE"),
+            (StateEventType::RoomJoinRules, "".to_owned()) => event_id("IJR"),
+        };
+        let state_b: StateMap<_> = hashmap! {
+            (StateEventType::RoomCreate, "".to_owned()) => event_id("CREATE"),
+            (StateEventType::RoomJoinRules, "".to_owned()) => event_id("JR"),
+            (StateEventType::RoomMember, ella().to_string()) => event_id("ME"),
+        };
+
+        let (unconflicted, conflicted) = separate([&state_a, &state_b].into_iter());
+
+        assert_eq!(
+            unconflicted,
+            hashmap! {
+                (StateEventType::RoomCreate, "".to_owned()) => event_id("CREATE"),
+                (StateEventType::RoomMember, ella().to_string()) => event_id("ME"),
+            }
+        );
+        assert_eq!(
+            conflicted,
+            hashmap! {
+                (StateEventType::RoomJoinRules, "".to_owned()) => vec![event_id("IJR"), event_id("JR")],
+            }
+        );
+    }
+
+    #[test]
+    fn resolve_forked_joins() {
+        let _ =
+            tracing::subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let mut store = TestStore::<PduEvent>(HashMap::new());
+
+        // build up the DAG
+        let (state_at_bob, state_at_charlie, expected) = store.set_up();
+
+        let ev_map = store.0.clone();
+        let resolved = crate::resolve(
+            &RoomVersionId::V1,
+            &[state_at_bob, state_at_charlie],
+            Vec::new(),
+            |id| ev_map.get(id).map(Arc::clone),
+        )
+        .unwrap();
+
+        assert_eq!(expected, resolved);
+    }
+
+    #[test]
+    fn topic_basic() {
+        let _ =
+            tracing::subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let events = &[
+            to_init_pdu_event(
+                "T1",
+                alice(),
+                TimelineEventType::RoomTopic,
+                Some(""),
+                to_raw_json_value(&json!({})).unwrap(),
+            ),
+            to_init_pdu_event(
+                "PA1",
+                alice(),
+                TimelineEventType::RoomPowerLevels,
+                Some(""),
+                to_raw_json_value(&json!({ "users": { alice(): 100, bob(): 50 } })).unwrap(),
+            ),
+            to_init_pdu_event(
+                "T2",
+                alice(),
+                TimelineEventType::RoomTopic,
+                Some(""),
+                to_raw_json_value(&json!({})).unwrap(),
+            ),
+            to_init_pdu_event(
+                "PA2",
+                alice(),
+                TimelineEventType::RoomPowerLevels,
+                Some(""),
+                to_raw_json_value(&json!({ "users": { alice(): 100, bob(): 0 } })).unwrap(),
+            ),
+            to_init_pdu_event(
+                "PB",
+                bob(),
+                TimelineEventType::RoomPowerLevels,
+                Some(""),
+                to_raw_json_value(&json!({ "users": { alice(): 100, bob(): 50 } })).unwrap(),
+            ),
+            to_init_pdu_event(
+                "T3",
+                bob(),
+                TimelineEventType::RoomTopic,
+                Some(""),
+                to_raw_json_value(&json!({})).unwrap(),
+            ),
+        ];
+
+        let edges =
+            vec![vec!["END", "PA2", "T2", "PA1", "T1", "START"], vec!["END", "T3", "PB", "PA1"]]
+                .into_iter()
+                .map(|list| list.into_iter().map(event_id).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+
+        let expected_state_ids = vec!["PA2", "T2"].into_iter().map(event_id).collect::<Vec<_>>();
+
+        do_check(&RoomVersionId::V1, events, edges, expected_state_ids);
+    }
+
+    #[test]
+    fn offtopic_power_level() {
+        let _ =
+            tracing::subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let events = &[
+            to_init_pdu_event(
+                "PA",
+                alice(),
+                TimelineEventType::RoomPowerLevels,
+                Some(""),
+                to_raw_json_value(&json!({ "users": { alice(): 100, bob(): 50 } })).unwrap(),
+            ),
+            to_init_pdu_event(
+                "PB",
+                bob(),
+                TimelineEventType::RoomPowerLevels,
+                Some(""),
+                to_raw_json_value(&json!({ "users": { alice(): 100, bob(): 50, charlie(): 50 } }))
+                    .unwrap(),
+            ),
+            to_init_pdu_event(
+                "PC",
+                charlie(),
+                TimelineEventType::RoomPowerLevels,
+                Some(""),
+                to_raw_json_value(&json!({ "users": { alice(): 100, bob(): 50, charlie(): 0 } }))
+                    .unwrap(),
+            ),
+        ];
+
+        let edges = vec![vec!["END", "PC", "PB", "PA", "START"], vec!["END", "PA"]]
+            .into_iter()
+            .map(|list| list.into_iter().map(event_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        // Only the conflicted events are checked against each other, PB which allowed charlie to
+        // send PC is ignored so PC is rejected.
+        let expected_state_ids = vec!["PA"].into_iter().map(event_id).collect::<Vec<_>>();
+
+        do_check(&RoomVersionId::V1, events, edges, expected_state_ids);
+    }
+
+    #[test]
+    fn join_rule_evasion() {
+        let _ =
+            tracing::subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let events = &[
+            to_init_pdu_event(
+                "JR",
+                alice(),
+                TimelineEventType::RoomJoinRules,
+                Some(""),
+                to_raw_json_value(&RoomJoinRulesEventContent::new(JoinRule::Private)).unwrap(),
+            ),
+            to_init_pdu_event(
+                "ME",
+                ella(),
+                TimelineEventType::RoomMember,
+                Some(ella().to_string().as_str()),
+                member_content_join(),
+            ),
+        ];
+
+        let edges = vec![vec!["END", "JR", "START"], vec!["END", "ME", "START"]]
+            .into_iter()
+            .map(|list| list.into_iter().map(event_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        // The membership of ella only appears in one of the forks so it is not conflicted, and it
+        // is not checked against the new join rules.
+        let expected_state_ids = vec!["JR", "ME"].into_iter().map(event_id).collect::<Vec<_>>();
+
+        do_check(&RoomVersionId::V1, events, edges, expected_state_ids);
+    }
+}
@@ -1,23 +1,18 @@
-use std::{error::Error, io, process::exit, time::Duration};
+use std::{error::Error, io, process::exit};
 
-use futures_util::future::{join, join_all};
 use ruma::{
     api::client::{
         filter::FilterDefinition, membership::join_room_by_id, message::send_message_event,
-        sync::sync_events,
     },
-    assign, client,
-    events::{
-        room::message::{MessageType, RoomMessageEventContent},
-        AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent,
+    client::{self, EventHandlers, SyncSettings},
+    events::room::{
+        member::{MembershipState, StrippedRoomMemberEvent},
+        message::{MessageType, OriginalSyncRoomMessageEvent, RoomMessageEventContent},
     },
-    presence::PresenceState,
-    serde::Raw,
     OwnedRoomId, OwnedUserId, TransactionId, UserId,
 };
 use serde_json::Value as JsonValue;
 use tokio::fs;
-use tokio_stream::StreamExt as _;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -60,13 +55,7 @@ async fn run() -> Result<(), Box<dyn Error>> {
         return Err("No previous session found and no credentials stored in config".into());
     };
 
-    let filter = FilterDefinition::ignore_all().into();
-    let initial_sync_response = matrix_client
-        .send_request(assign!(sync_events::v3::Request::new(), {
-            filter: Some(filter),
-        }))
-        .await?;
-    let user_id = &config.username;
+    let user_id = config.username;
     let not_senders = vec![user_id.clone()];
     let filter = {
         let mut filter = FilterDefinition::empty();
@@ -75,41 +64,53 @@ async fn run() -> Result<(), Box<dyn Error>> {
     }
     .into();
 
-    let mut sync_stream = Box::pin(matrix_client.sync(
-        Some(filter),
-        initial_sync_response.next_batch,
-        PresenceState::Online,
-        Some(Duration::from_secs(30)),
-    ));
-
-    // Prevent the clients being moved by `async move` blocks
-    let http_client = &http_client;
-    let matrix_client = &matrix_client;
-
-    println!("Listening...");
-    while let Some(response) = sync_stream.try_next().await? {
-        let message_futures = response.rooms.join.iter().map(|(room_id, room_info)| async move {
-            // Use a regular for loop for the messages within one room to handle them sequentially
-            for e in &room_info.timeline.events {
+    let mut handlers = EventHandlers::new();
+    {
+        let http_client = http_client.clone();
+        let matrix_client = matrix_client.clone();
+        let user_id = user_id.clone();
+        handlers.on_message_like(move |event: OriginalSyncRoomMessageEvent, room_id| {
+            let http_client = http_client.clone();
+            let matrix_client = matrix_client.clone();
+            let user_id = user_id.clone();
+            async move {
                 if let Err(err) =
-                    handle_message(http_client, matrix_client, e, room_id.to_owned(), user_id).await
+                    handle_message(&http_client, &matrix_client, event, room_id, &user_id).await
                 {
                     eprintln!("failed to respond to message: {err}");
                 }
             }
         });
+    }
+    {
+        let matrix_client = matrix_client.clone();
+        handlers.on_stripped_state(move |event: StrippedRoomMemberEvent, room_id| {
+            let http_client = http_client.clone();
+            let matrix_client = matrix_client.clone();
+            let is_bot_invite =
+                event.state_key == user_id && event.content.membership == MembershipState::Invite;
+            async move {
+                if !is_bot_invite {
+                    return;
+                }
 
-        let invite_futures = response.rooms.invite.into_keys().map(|room_id| async move {
-            if let Err(err) = handle_invitations(http_client, matrix_client, room_id.clone()).await
-            {
-                eprintln!("failed to accept invitation for room {room_id}: {err}");
+                if let Err(err) =
+                    handle_invitations(&http_client, &matrix_client, room_id.clone()).await
+                {
+                    eprintln!("failed to accept invitation for room {room_id}: {err}");
+                }
             }
         });
-
-        // Handle messages from different rooms as well as invites concurrently
-        join(join_all(message_futures), join_all(invite_futures)).await;
     }
 
+    println!("Listening...");
+    matrix_client
+        .sync_with_handlers(
+            &handlers,
+            SyncSettings::new().filter(filter).skip_initial_backlog(true),
+        )
+        .await?;
+
     Ok(())
 }
 
@@ -146,37 +147,28 @@ async fn create_matrix_session(
 async fn handle_message(
     http_client: &HttpClient,
     matrix_client: &MatrixClient,
-    e: &Raw<AnySyncTimelineEvent>,
+    m: OriginalSyncRoomMessageEvent,
     room_id: OwnedRoomId,
     bot_user_id: &UserId,
 ) -> Result<(), Box<dyn Error>> {
-    if let Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
-        SyncMessageLikeEvent::Original(m),
-    ))) = e.deserialize()
-    {
-        // workaround because Conduit does not implement filtering.
-        if m.sender == bot_user_id {
-            return Ok(());
-        }
+    // workaround because Conduit does not implement filtering.
+    if m.sender == bot_user_id {
+        return Ok(());
+    }
 
-        if let MessageType::Text(t) = m.content.msgtype {
-            println!("{}:\t{}", m.sender, t.body);
-            if t.body.to_ascii_lowercase().contains("joke") {
-                let joke = match get_joke(http_client).await {
-                    Ok(joke) => joke,
-                    Err(_) => "I thought of a joke... but I just forgot it.".to_owned(),
-                };
-                let joke_content = RoomMessageEventContent::text_plain(joke);
+    if let MessageType::Text(t) = m.content.msgtype {
+        println!("{}:\t{}", m.sender, t.body);
+        if t.body.to_ascii_lowercase().contains("joke") {
+            let joke = match get_joke(http_client).await {
+                Ok(joke) => joke,
+                Err(_) => "I thought of a joke... but I just forgot it.".to_owned(),
+            };
+            let joke_content = RoomMessageEventContent::text_plain(joke);
 
-                let txn_id = TransactionId::new();
-                let req = send_message_event::v3::Request::new(
-                    room_id.to_owned(),
-                    txn_id,
-                    &joke_content,
-                )?;
-                // Do nothing if we can't send the message.
-                let _ = matrix_client.send_request(req).await;
-            }
+            let txn_id = TransactionId::new();
+            let req = send_message_event::v3::Request::new(room_id, txn_id, &joke_content)?;
+            // Do nothing if we can't send the message.
+            let _ = matrix_client.send_request(req).await;
         }
     }
 
@@ -1,16 +1,9 @@
-use std::{env, process::exit, time::Duration};
+use std::{env, process::exit};
 
 use ruma::{
-    api::client::{filter::FilterDefinition, sync::sync_events},
-    assign,
-    events::{
-        room::message::{MessageType, RoomMessageEventContent, TextMessageEventContent},
-        AnySyncMessageLikeEvent, AnySyncTimelineEvent, OriginalSyncMessageLikeEvent,
-        SyncMessageLikeEvent,
-    },
-    presence::PresenceState,
+    client::{EventHandlers, SyncSettings},
+    events::room::message::{MessageType, OriginalSyncRoomMessageEvent, TextMessageEventContent},
 };
-use tokio_stream::StreamExt as _;
 
 type HttpClient = ruma::client::http_client::HyperNativeTls;
 
@@ -24,45 +17,17 @@ async fn log_messages(
 
     client.log_in(username, password, None, None).await?;
 
-    let filter = FilterDefinition::ignore_all().into();
-    let initial_sync_response = client
-        .send_request(assign!(sync_events::v3::Request::new(), {
-            filter: Some(filter),
-        }))
-        .await?;
-
-    let mut sync_stream = Box::pin(client.sync(
-        None,
-        initial_sync_response.next_batch,
-        PresenceState::Online,
-        Some(Duration::from_secs(30)),
-    ));
-
-    while let Some(res) = sync_stream.try_next().await? {
-        // Only look at rooms the user hasn't left yet
-        for (room_id, room) in res.rooms.join {
-            for event in room.timeline.events.into_iter().flat_map(|r| r.deserialize()) {
-                // Filter out the text messages
-                if let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
-                    SyncMessageLikeEvent::Original(OriginalSyncMessageLikeEvent {
-                        content:
-                            RoomMessageEventContent {
-                                msgtype:
-                                    MessageType::Text(TextMessageEventContent {
-                                        body: msg_body, ..
-                                    }),
-                                ..
-                            },
-                        sender,
-                        ..
-                    }),
-                )) = event
-                {
-                    println!("{sender} in {room_id}: {msg_body}");
-                }
-            }
+    let mut handlers = EventHandlers::new();
+    handlers.on_message_like(|event: OriginalSyncRoomMessageEvent, room_id| async move {
+        // Filter out the text messages
+        if let MessageType::Text(TextMessageEventContent { body: msg_body, .. }) =
+            event.content.msgtype
+        {
+            println!("{} in {room_id}: {msg_body}", event.sender);
         }
-    }
+    });
+
+    client.sync_with_handlers(&handlers, SyncSettings::new().skip_initial_backlog(true)).await?;
 
     Ok(())
 }